@@ -198,7 +198,7 @@ fn benchmark_graphql_dynamodb(c: &mut Criterion) {
                     "name": "DynamoDB Benchmark Order",
                     "status": "pending",
                     "number": "ORD-DDB-001",
-                    "amount": 999.99,
+                    "currency": "EUR",
                     "customer_name": "DynamoDB Customer",
                     "notes": "Benchmark test order with DynamoDB"
                 }
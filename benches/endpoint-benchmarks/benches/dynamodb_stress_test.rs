@@ -57,7 +57,7 @@ async fn dynamodb_stress_test(
                                 "name": format!("Stress Test Order {}", i),
                                 "status": "pending",
                                 "number": format!("STR-{:05}", i),
-                                "amount": 100.0 + (i as f64),
+                                "currency": "EUR",
                                 "customer_name": "Stress Test Customer",
                                 "notes": "Generated by stress test"
                             }
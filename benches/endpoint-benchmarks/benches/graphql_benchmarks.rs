@@ -98,7 +98,7 @@ fn benchmark_graphql_in_memory(c: &mut Criterion) {
                         "name": "Benchmark Order",
                         "status": "pending",
                         "number": "ORD-BENCH-001",
-                        "amount": 999.99,
+                        "currency": "EUR",
                         "customer_name": "Test Customer",
                         "notes": "Benchmark test order"
                     }
@@ -114,7 +114,7 @@ fn benchmark_graphql_in_memory(c: &mut Criterion) {
                         "name": "Benchmark Invoice",
                         "status": "draft",
                         "number": "INV-BENCH-001",
-                        "amount": 1999.50,
+                        "amount": "1999.50 EUR",
                         "due_date": "2024-12-31"
                     }
                 }
@@ -129,7 +129,7 @@ fn benchmark_graphql_in_memory(c: &mut Criterion) {
                         "name": "Benchmark Payment",
                         "status": "pending",
                         "number": "PAY-BENCH-001",
-                        "amount": 999.99,
+                        "amount": "999.99 EUR",
                         "method": "credit_card",
                         "transaction_id": "txn_bench_001"
                     }
@@ -308,7 +308,7 @@ fn benchmark_graphql_load_test(c: &mut Criterion) {
                             "name": format!("Concurrent Order {}", i),
                             "status": "pending",
                             "number": format!("ORD-CONC-{:03}", i),
-                            "amount": 100.0 + i as f64,
+                            "currency": "EUR",
                             "customer_name": "Concurrent Customer",
                             "notes": "Load test order"
                         }
@@ -360,7 +360,7 @@ fn benchmark_graphql_response_parsing(c: &mut Criterion) {
                         "name": "Parse Test Invoice",
                         "status": "draft",
                         "number": "INV-PARSE-001",
-                        "amount": 1500.00,
+                        "amount": "1500.00 EUR",
                         "due_date": "2024-12-31"
                     }
                 }
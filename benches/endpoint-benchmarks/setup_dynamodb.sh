@@ -80,6 +80,14 @@ create_tables() {
         --billing-mode PAY_PER_REQUEST \
         --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_orders existe déjà"
     
+    # Table order_lines
+    aws dynamodb create-table \
+        --table-name bench_order_lines \
+        --attribute-definitions AttributeName=id,AttributeType=S \
+        --key-schema AttributeName=id,KeyType=HASH \
+        --billing-mode PAY_PER_REQUEST \
+        --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_order_lines existe déjà"
+    
     # Table invoices
     aws dynamodb create-table \
         --table-name bench_invoices \
@@ -88,6 +96,14 @@ create_tables() {
         --billing-mode PAY_PER_REQUEST \
         --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_invoices existe déjà"
     
+    # Table invoice_lines
+    aws dynamodb create-table \
+        --table-name bench_invoice_lines \
+        --attribute-definitions AttributeName=id,AttributeType=S \
+        --key-schema AttributeName=id,KeyType=HASH \
+        --billing-mode PAY_PER_REQUEST \
+        --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_invoice_lines existe déjà"
+    
     # Table payments
     aws dynamodb create-table \
         --table-name bench_payments \
@@ -96,6 +112,14 @@ create_tables() {
         --billing-mode PAY_PER_REQUEST \
         --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_payments existe déjà"
     
+    # Table sequences
+    aws dynamodb create-table \
+        --table-name bench_sequences \
+        --attribute-definitions AttributeName=id,AttributeType=S \
+        --key-schema AttributeName=id,KeyType=HASH \
+        --billing-mode PAY_PER_REQUEST \
+        --endpoint-url "$AWS_ENDPOINT_URL" > /dev/null 2>&1 || echo "Table bench_sequences existe déjà"
+    
     # Table links
     aws dynamodb create-table \
         --table-name bench_links \
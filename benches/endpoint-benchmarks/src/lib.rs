@@ -135,8 +135,11 @@ pub async fn create_rest_server_dynamodb() -> Result<Router> {
     let stores = BillingStores::new_dynamodb(
         client,
         "bench_orders".to_string(),
+        "bench_order_lines".to_string(),
         "bench_invoices".to_string(),
+        "bench_invoice_lines".to_string(),
         "bench_payments".to_string(),
+        "bench_sequences".to_string(),
     );
 
     let billing_module = BillingModule::new(stores);
@@ -174,8 +177,11 @@ pub async fn create_graphql_server_dynamodb() -> Result<Router> {
     let stores = BillingStores::new_dynamodb(
         client,
         "bench_orders".to_string(),
+        "bench_order_lines".to_string(),
         "bench_invoices".to_string(),
+        "bench_invoice_lines".to_string(),
         "bench_payments".to_string(),
+        "bench_sequences".to_string(),
     );
 
     let billing_module = BillingModule::new(stores);
@@ -220,7 +226,7 @@ pub mod data {
             "name": "Sample Order",
             "status": "pending",
             "number": format!("ORD-{}", Uuid::new_v4().to_string().split('-').next().unwrap().to_uppercase()),
-            "amount": 1299.99,
+            "currency": "EUR",
             "customer_name": "John Doe",
             "notes": "Sample order for benchmarking"
         })
@@ -231,7 +237,7 @@ pub mod data {
             "name": "Sample Invoice",
             "status": "draft",
             "number": format!("INV-{}", Uuid::new_v4().to_string().split('-').next().unwrap().to_uppercase()),
-            "amount": 2599.50,
+            "amount": "2599.50 EUR",
             "due_date": "2024-12-31",
             "paid_at": null
         })
@@ -242,7 +248,7 @@ pub mod data {
             "name": "Sample Payment",
             "status": "pending",
             "number": format!("PAY-{}", Uuid::new_v4().to_string().split('-').next().unwrap().to_uppercase()),
-            "amount": 1299.99,
+            "amount": "1299.99 EUR",
             "method": "credit_card",
             "transaction_id": format!("txn_{}", Uuid::new_v4().to_string().replace('-', "")[..16].to_string())
         })
@@ -263,7 +269,7 @@ pub mod data {
                     "name": order["name"],
                     "status": order["status"],
                     "number": order["number"],
-                    "amount": order["amount"],
+                    "currency": order["currency"],
                     "customer_name": order["customer_name"],
                     "notes": order["notes"]
                 }
@@ -1,4 +1,5 @@
 use billing::entities::{invoice::Invoice, order::Order, payment::Payment};
+use common::{Currency, Money, Version};
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
 use this::prelude::*;
 
@@ -9,9 +10,11 @@ fn benchmark_invoice_creation(c: &mut Criterion) {
                 black_box("Invoice Benchmark".into()),
                 black_box("draft".into()),
                 black_box("INV-2024-001".into()),
-                black_box(1500.50),
+                black_box(Money::from_minor(150_050, Currency::EUR)),
                 black_box(Some("2024-12-31".into())),
                 black_box(None),
+                None,
+                Version::default(),
             );
             black_box(invoice)
         })
@@ -25,9 +28,11 @@ fn benchmark_invoice_validation(c: &mut Criterion) {
                 black_box("Valid Invoice".into()),
                 black_box("sent".into()),
                 black_box("INV-2024-002".into()),
-                black_box(2500.75),
+                black_box(Money::from_minor(250_075, Currency::EUR)),
                 black_box(Some("2024-11-30".into())),
                 black_box(None),
+                None,
+                Version::default(),
             );
             black_box(invoice)
         })
@@ -39,9 +44,11 @@ fn benchmark_invoice_validation(c: &mut Criterion) {
                 black_box("Paid Invoice".into()),
                 black_box("paid".into()),
                 black_box("INV-2024-003".into()),
-                black_box(1200.00),
+                black_box(Money::from_minor(120_000, Currency::EUR)),
                 black_box(Some("2024-11-30".into())),
                 black_box(Some("2024-11-15".into())),
+                None,
+                Version::default(),
             );
             black_box(invoice)
         })
@@ -55,9 +62,11 @@ fn benchmark_order_creation(c: &mut Criterion) {
                 black_box("Order Benchmark".into()),
                 black_box("pending".into()),
                 black_box("ORD-2024-001".into()),
-                black_box(3500.25),
+                black_box(Money::from_minor(350_025, Currency::EUR)),
                 black_box(Some("John Doe".into())),
                 black_box(Some("Special delivery instructions".into())),
+                None,
+                Version::default(),
             );
             black_box(order)
         })
@@ -72,9 +81,11 @@ fn benchmark_order_update(c: &mut Criterion) {
                 black_box("Order Update Test".into()),
                 black_box("pending".into()),
                 black_box("ORD-2024-002".into()),
-                black_box(1200.00),
+                black_box(Money::from_minor(120_000, Currency::EUR)),
                 black_box(Some("Customer".into())),
                 black_box(None),
+                None,
+                Version::default(),
             );
             // Clone it to simulate update operations
             let cloned_order = order.clone();
@@ -90,9 +101,11 @@ fn benchmark_payment_creation(c: &mut Criterion) {
                 black_box("Payment Benchmark".into()),
                 black_box("pending".into()),
                 black_box("PAY-2024-001".into()),
-                black_box(750.00),
+                black_box(Money::from_minor(75_000, Currency::EUR)),
                 black_box("credit_card".into()),
                 black_box(Some("txn_bench_001".into())),
+                None,
+                Version::default(),
             );
             black_box(payment)
         })
@@ -108,9 +121,11 @@ fn benchmark_bulk_operations(c: &mut Criterion) {
                     black_box(format!("Bulk Invoice {}", i)),
                     black_box("draft".into()),
                     black_box(format!("INV-BULK-{:03}", i)),
-                    black_box((i as f64) * 10.5),
+                    black_box(Money::from_minor(i * 1_050, Currency::EUR)),
                     black_box(Some("2024-12-31".into())),
                     black_box(None),
+                    None,
+                    Version::default(),
                 );
                 results.push(invoice);
             }
@@ -124,9 +139,11 @@ fn benchmark_serialization(c: &mut Criterion) {
         "Serialization Test".into(),
         "paid".into(),
         "INV-SER-001".into(),
-        1000.00,
+        Money::from_minor(100_000, Currency::EUR),
         Some("2024-11-15".into()),
         Some("2024-11-10".into()),
+        None,
+        Version::default(),
     );
 
     c.bench_function("invoice_to_json", |b| {
@@ -150,9 +167,11 @@ fn benchmark_field_access(c: &mut Criterion) {
         "Field Access Test".into(),
         "sent".into(),
         "INV-ACCESS-001".into(),
-        1500.00,
+        Money::from_minor(150_000, Currency::EUR),
         Some("2024-12-01".into()),
         None,
+        None,
+        Version::default(),
     );
 
     c.bench_function("invoice_field_access", |b| {
@@ -1,17 +1,20 @@
 use axum::routing::get;
 use std::sync::Arc;
+use this::core::events::EventBus;
 use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
 
 use super::InvoiceStore;
 use super::handlers::{
-    InvoiceState, create_invoice, delete_invoice, get_invoice, list_invoices, update_invoice,
+    InvoiceState, create_invoice, delete_invoice, get_invoice, list_invoices, patch_invoice,
+    update_invoice,
 };
 
 #[derive(Clone)]
 pub struct InvoiceDescriptor {
     store: Arc<dyn InvoiceStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    event_bus: Option<EventBus>,
 }
 
 impl InvoiceDescriptor {
@@ -26,8 +29,15 @@ impl InvoiceDescriptor {
         Self {
             store,
             entity_creator,
+            event_bus: None,
         }
     }
+
+    /// Publish invoice mutations made through the REST routes on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Option<EventBus>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
 }
 
 impl EntityDescriptor for InvoiceDescriptor {
@@ -41,12 +51,16 @@ impl EntityDescriptor for InvoiceDescriptor {
         let state = InvoiceState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            event_bus: self.event_bus.clone(),
         };
         Router::new()
             .route("/invoices", get(list_invoices).post(create_invoice))
             .route(
                 "/invoices/{id}",
-                get(get_invoice).put(update_invoice).delete(delete_invoice),
+                get(get_invoice)
+                    .put(update_invoice)
+                    .patch(patch_invoice)
+                    .delete(delete_invoice),
             )
             .with_state(state)
     }
@@ -1,66 +1,127 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
 use this::prelude::*;
 
-use super::{Invoice, InvoiceStore};
+use super::{Invoice, InvoiceStore, InvoiceStoreError};
 
 #[derive(Clone)]
 pub struct InvoiceState {
     pub store: Arc<dyn InvoiceStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub event_bus: Option<EventBus>,
 }
 
-pub async fn list_invoices(State(state): State<InvoiceState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List invoices error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list invoices",
-                "details": e.to_string()
-            }))
+impl InvoiceState {
+    /// Publish a invoice event so that REST mutations reach the same subscribers
+    /// as the GraphQL and gRPC exposures
+    fn publish(&self, event: EntityEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(event));
         }
     }
 }
 
+pub async fn list_invoices(
+    State(state): State<InvoiceState>,
+    query: ListQuery,
+) -> Result<Json<Page<Invoice>>, Problem> {
+    query.check::<Invoice>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List invoices error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
 pub async fn get_invoice(
     State(state): State<InvoiceState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Invoice>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Invoice>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_invoice(
     State(state): State<InvoiceState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating invoice with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Invoice created successfully: {:?}", created);
-            Json(created)
+            if let Some(entity_id) = created["id"].as_str().and_then(|id| id.parse().ok()) {
+                state.publish(EntityEvent::Created {
+                    entity_type: "invoice".to_string(),
+                    entity_id,
+                    data: created.clone(),
+                });
+            }
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create invoice error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create invoice",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<InvoiceStoreError>(e))
         }
     }
 }
 
 pub async fn update_invoice(
     State(state): State<InvoiceState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(invoice): Json<Invoice>,
-) -> Json<Option<Invoice>> {
-    let updated = state.store.update(invoice).await.ok();
-    Json(updated)
+) -> Result<Tagged<Invoice>, Problem> {
+    check_id(id, invoice.id)?;
+    let mut invoice = validate_entity(&Invoice::validation_rules("update"), invoice)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    invoice.version = current.version;
+    invoice.owned_by = current.owned_by;
+    let updated = state.store.update(invoice).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "invoice".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
 }
 
-pub async fn delete_invoice(State(state): State<InvoiceState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing invoice
+pub async fn patch_invoice(
+    State(state): State<InvoiceState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Invoice>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut invoice = patch_entity(&Invoice::validation_rules("update"), current, patch)?;
+    invoice.touch();
+    let updated = state.store.update(invoice).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "invoice".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_invoice(
+    State(state): State<InvoiceState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    state.publish(EntityEvent::Deleted {
+        entity_type: "invoice".to_string(),
+        entity_id: id,
+    });
+    Ok(StatusCode::NO_CONTENT)
 }
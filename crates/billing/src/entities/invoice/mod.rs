@@ -22,3 +22,5 @@ pub use store::InvoiceNeo4jStore;
 pub use store::InvoicePostgresStore;
 #[cfg(feature = "scylladb")]
 pub use store::InvoiceScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::InvoiceSqliteStore;
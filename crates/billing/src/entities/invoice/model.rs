@@ -1,3 +1,8 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use common::money::{self, Money};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -6,19 +11,21 @@ impl_data_entity_validated!(
     ["name", "number"],
     {
         number: String,
-        amount: f64,
+        amount: Money,
         due_date: Option<String>,
         paid_at: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
             number: [required string_length(3, 50)],
-            amount: [required positive max_value(1_000_000.0)],
+            amount: [required],
             status: [required in_list("draft", "sent", "paid", "cancelled")],
             due_date: [optional date_format("%Y-%m-%d")],
         },
         update: {
-            amount: [optional positive max_value(1_000_000.0)],
+            amount: [optional],
             status: [optional in_list("draft", "sent", "paid", "cancelled")],
             due_date: [optional date_format("%Y-%m-%d")],
         },
@@ -27,11 +34,23 @@ impl_data_entity_validated!(
         create: {
             number: [trim uppercase],
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
         update: {
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
     }
 );
+
+common::impl_versioned!(Invoice);
+
+impl Invoice {
+    /// Rules for `operation`: the ones declared above plus the money rules on
+    /// `amount`, which the macro's numeric validators and filters cannot check
+    pub fn validation_rules(operation: &str) -> EntityValidationConfig {
+        let mut config = <Self as ValidatableEntity>::validation_config(operation);
+        config.add_filter("amount", money::filters::normalize());
+        config.add_validator("amount", money::validators::positive());
+        config.add_validator("amount", money::validators::max_amount(1_000_000));
+        config
+    }
+}
@@ -0,0 +1,66 @@
+use axum::routing::get;
+use std::sync::Arc;
+use this::core::events::EventBus;
+use this::prelude::Router;
+use this::server::entity_registry::EntityDescriptor;
+
+use super::InvoiceLineStore;
+use super::handlers::{
+    InvoiceLineState, create_invoice_line, delete_invoice_line, get_invoice_line,
+    list_invoice_lines, patch_invoice_line, update_invoice_line,
+};
+
+#[derive(Clone)]
+pub struct InvoiceLineDescriptor {
+    store: Arc<dyn InvoiceLineStore + Send + Sync>,
+    entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    event_bus: Option<EventBus>,
+}
+
+impl InvoiceLineDescriptor {
+    pub fn new_with_creator(
+        store: Arc<dyn InvoiceLineStore + Send + Sync>,
+        entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    ) -> Self {
+        Self {
+            store,
+            entity_creator,
+            event_bus: None,
+        }
+    }
+
+    /// Publish invoice line mutations made through the REST routes on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Option<EventBus>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
+}
+
+impl EntityDescriptor for InvoiceLineDescriptor {
+    fn entity_type(&self) -> &str {
+        "invoice_line"
+    }
+    fn plural(&self) -> &str {
+        "invoice_lines"
+    }
+    fn build_routes(&self) -> Router {
+        let state = InvoiceLineState {
+            store: self.store.clone(),
+            entity_creator: self.entity_creator.clone(),
+            event_bus: self.event_bus.clone(),
+        };
+        Router::new()
+            .route(
+                "/invoice_lines",
+                get(list_invoice_lines).post(create_invoice_line),
+            )
+            .route(
+                "/invoice_lines/{id}",
+                get(get_invoice_line)
+                    .put(update_invoice_line)
+                    .patch(patch_invoice_line)
+                    .delete(delete_invoice_line),
+            )
+            .with_state(state)
+    }
+}
@@ -0,0 +1,127 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
+use std::sync::Arc;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
+use this::prelude::*;
+
+use super::{InvoiceLine, InvoiceLineStore, InvoiceLineStoreError};
+
+#[derive(Clone)]
+pub struct InvoiceLineState {
+    pub store: Arc<dyn InvoiceLineStore>,
+    pub entity_creator: Arc<dyn EntityCreator>,
+    pub event_bus: Option<EventBus>,
+}
+
+impl InvoiceLineState {
+    /// Publish an invoice line event so that REST mutations reach the same subscribers
+    /// as the GraphQL and gRPC exposures
+    fn publish(&self, event: EntityEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(event));
+        }
+    }
+}
+
+pub async fn list_invoice_lines(
+    State(state): State<InvoiceLineState>,
+    query: ListQuery,
+) -> Result<Json<Page<InvoiceLine>>, Problem> {
+    query.check::<InvoiceLine>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List invoice lines error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
+pub async fn get_invoice_line(
+    State(state): State<InvoiceLineState>,
+    Path(id): Path<Uuid>,
+) -> Result<Tagged<InvoiceLine>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
+}
+
+pub async fn create_invoice_line(
+    State(state): State<InvoiceLineState>,
+    Json(entity_data): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
+    eprintln!("Creating invoice line with data: {:?}", entity_data);
+    match state.entity_creator.create_from_json(entity_data).await {
+        Ok(created) => {
+            eprintln!("Invoice line created successfully: {:?}", created);
+            if let Some(entity_id) = created["id"].as_str().and_then(|id| id.parse().ok()) {
+                state.publish(EntityEvent::Created {
+                    entity_type: "invoice_line".to_string(),
+                    entity_id,
+                    data: created.clone(),
+                });
+            }
+            Ok((StatusCode::CREATED, Json(created)))
+        }
+        Err(e) => {
+            eprintln!("Create invoice line error: {:?}", e);
+            Err(Problem::from_creator::<InvoiceLineStoreError>(e))
+        }
+    }
+}
+
+pub async fn update_invoice_line(
+    State(state): State<InvoiceLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(invoice_line): Json<InvoiceLine>,
+) -> Result<Tagged<InvoiceLine>, Problem> {
+    check_id(id, invoice_line.id)?;
+    let mut invoice_line = validate_entity(&InvoiceLine::validation_rules("update"), invoice_line)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    invoice_line.version = current.version;
+    invoice_line.owned_by = current.owned_by;
+    let updated = state.store.update(invoice_line).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "invoice_line".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing invoice line
+pub async fn patch_invoice_line(
+    State(state): State<InvoiceLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<InvoiceLine>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut invoice_line = patch_entity(&InvoiceLine::validation_rules("update"), current, patch)?;
+    invoice_line.touch();
+    let updated = state.store.update(invoice_line).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "invoice_line".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_invoice_line(
+    State(state): State<InvoiceLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    state.publish(EntityEvent::Deleted {
+        entity_type: "invoice_line".to_string(),
+        entity_id: id,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
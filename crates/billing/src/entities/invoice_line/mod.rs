@@ -0,0 +1,26 @@
+pub mod descriptor;
+pub mod handlers;
+pub mod model;
+pub mod store;
+
+pub use model::*;
+pub use store::*;
+
+// Export stores for use in BillingStores
+pub use store::InMemoryInvoiceLineStore;
+#[cfg(feature = "dynamodb")]
+pub use store::InvoiceLineDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::InvoiceLineLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::InvoiceLineMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::InvoiceLineMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::InvoiceLineNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::InvoiceLinePostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::InvoiceLineScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::InvoiceLineSqliteStore;
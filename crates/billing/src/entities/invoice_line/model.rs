@@ -0,0 +1,80 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use common::money::{self, Money, MoneyError};
+use this::core::validation::extractor::ValidatableEntity;
+use this::prelude::*;
+
+impl_data_entity_validated!(
+    InvoiceLine,
+    "invoice_line",
+    ["name", "code"],
+    {
+        code: String,
+        quantity: f64,
+        unit: Option<String>,
+        unit_price: Money,
+        line_total: Money,
+        owned_by: Option<Uuid>,
+        version: Version,
+    },
+    validate: {
+        create: {
+            code: [required string_length(1, 50)],
+            quantity: [required positive],
+            status: [required in_list("active", "cancelled")],
+        },
+        update: {
+            quantity: [optional positive],
+            status: [optional in_list("active", "cancelled")],
+        },
+    },
+    filters: {
+        create: {
+            code: [trim lowercase],
+            status: [trim lowercase],
+        },
+        update: {
+            status: [trim lowercase],
+        },
+    }
+);
+
+common::impl_versioned!(InvoiceLine);
+
+impl InvoiceLine {
+    /// A line billing `quantity` of `code` at `unit_price`
+    ///
+    /// Unlike order lines, the quantity may be fractional (hours, kilograms,
+    /// ...); the total is rounded to the nearest minor unit.
+    pub fn priced(
+        name: String,
+        code: String,
+        quantity: f64,
+        unit: Option<String>,
+        unit_price: Money,
+    ) -> Result<Self, MoneyError> {
+        let line_total = unit_price.checked_mul_rounded(quantity)?;
+        Ok(Self::new(
+            name,
+            "active".to_string(),
+            code,
+            quantity,
+            unit,
+            unit_price,
+            line_total,
+            None,
+            Version::default(),
+        ))
+    }
+
+    /// Declared rules for `operation` plus the money rules on the unit price;
+    /// `line_total` is computed and never validated from input
+    pub fn validation_rules(operation: &str) -> EntityValidationConfig {
+        let mut config = <Self as ValidatableEntity>::validation_config(operation);
+        config.add_filter("unit_price", money::filters::normalize());
+        config.add_validator("unit_price", money::validators::positive());
+        config.add_validator("unit_price", money::validators::max_amount(1_000_000));
+        config
+    }
+}
@@ -0,0 +1,62 @@
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{Payload, ValidationError};
+
+use super::InvoiceLine;
+
+pub type InvoiceLineStoreError = StoreError;
+
+/// Store of invoice lines
+pub trait InvoiceLineStore: EntityStore<InvoiceLine> {}
+
+impl<S: EntityStore<InvoiceLine> + ?Sized> InvoiceLineStore for S {}
+
+impl StoredEntity for InvoiceLine {
+    type Error = InvoiceLineStoreError;
+    const NAME: &'static str = "InvoiceLine";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name", "status", "code"];
+
+    /// Build a new invoice line from a create payload
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&InvoiceLine::validation_rules("create"), entity_data);
+        let name = payload.required_string("name");
+        let status = payload.required_string("status");
+        let code = payload.required_string("code");
+        let quantity = payload.required_f64("quantity");
+        let unit = payload.string("unit");
+        let unit_price = payload.required_money("unit_price");
+        let owned_by = payload.uuid("owned_by");
+        payload.finish()?;
+
+        let mut invoice_line = InvoiceLine::priced(name, code, quantity, unit, unit_price)
+            .map_err(|e| ValidationError::field("unit_price", e.to_string()))?;
+        invoice_line.status = status;
+        invoice_line.owned_by = owned_by;
+        Ok(invoice_line)
+    }
+}
+
+pub type InMemoryInvoiceLineStore = InMemoryEntityStore<InvoiceLine>;
+
+#[cfg(feature = "dynamodb")]
+pub type InvoiceLineDynamoDBStore = common::store::DynamoDBStore<InvoiceLine>;
+
+#[cfg(feature = "postgres")]
+pub type InvoiceLinePostgresStore = common::store::PostgresStore<InvoiceLine>;
+
+#[cfg(feature = "mongodb_backend")]
+pub type InvoiceLineMongoStore = common::store::MongoStore<InvoiceLine>;
+
+#[cfg(feature = "neo4j")]
+pub type InvoiceLineNeo4jStore = common::store::Neo4jStore<InvoiceLine>;
+
+#[cfg(feature = "scylladb")]
+pub type InvoiceLineScyllaStore = common::store::ScyllaStore<InvoiceLine>;
+
+#[cfg(feature = "mysql")]
+pub type InvoiceLineMysqlStore = common::store::MysqlStore<InvoiceLine>;
+
+#[cfg(feature = "lmdb")]
+pub type InvoiceLineLmdbStore = common::store::LmdbStore<InvoiceLine>;
+
+#[cfg(feature = "sqlite")]
+pub type InvoiceLineSqliteStore = common::store::SqliteStore<InvoiceLine>;
@@ -1,3 +1,5 @@
 pub mod invoice;
+pub mod invoice_line;
 pub mod order;
+pub mod order_line;
 pub mod payment;
@@ -1,17 +1,20 @@
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
+use this::core::events::EventBus;
 use this::prelude::{EntityCreator, Router};
 use this::server::entity_registry::EntityDescriptor;
 
 use super::OrderStore;
 use super::handlers::{
-    OrderState, create_order, delete_order, get_order, list_orders, update_order,
+    OrderState, cancel_order, confirm_order, create_order, delete_order, get_order, list_orders,
+    patch_order, update_order,
 };
 
 #[derive(Clone)]
 pub struct OrderDescriptor {
     store: Arc<dyn OrderStore + Send + Sync>,
     entity_creator: Arc<dyn EntityCreator + Send + Sync>,
+    event_bus: Option<EventBus>,
 }
 
 impl OrderDescriptor {
@@ -28,8 +31,15 @@ impl OrderDescriptor {
         Self {
             store,
             entity_creator,
+            event_bus: None,
         }
     }
+
+    /// Publish order mutations made through the REST routes on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Option<EventBus>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
 }
 
 impl EntityDescriptor for OrderDescriptor {
@@ -43,13 +53,19 @@ impl EntityDescriptor for OrderDescriptor {
         let state = OrderState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            event_bus: self.event_bus.clone(),
         };
         Router::new()
             .route("/orders", get(list_orders).post(create_order))
             .route(
                 "/orders/{id}",
-                get(get_order).put(update_order).delete(delete_order),
+                get(get_order)
+                    .put(update_order)
+                    .patch(patch_order)
+                    .delete(delete_order),
             )
+            .route("/orders/{id}/confirm", post(confirm_order))
+            .route("/orders/{id}/cancel", post(cancel_order))
             .with_state(state)
     }
 }
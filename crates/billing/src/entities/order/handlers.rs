@@ -1,66 +1,168 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Order, OrderStore};
+use super::store::check_edit;
+use super::{Order, OrderStatus, OrderStore, OrderStoreError};
 
 #[derive(Clone)]
 pub struct OrderState {
     pub store: Arc<dyn OrderStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub event_bus: Option<EventBus>,
 }
 
-pub async fn list_orders(State(state): State<OrderState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List orders error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list orders",
-                "details": e.to_string()
-            }))
+impl OrderState {
+    /// Publish an order event so that REST mutations reach the same subscribers
+    /// as the GraphQL and gRPC exposures
+    fn publish(&self, event: EntityEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(event));
+        }
+    }
+
+    fn publish_updated(&self, order: &Order) {
+        if let Ok(data) = serde_json::to_value(order) {
+            self.publish(EntityEvent::Updated {
+                entity_type: "order".to_string(),
+                entity_id: order.id,
+                data,
+            });
         }
     }
 }
 
+pub async fn list_orders(
+    State(state): State<OrderState>,
+    query: ListQuery,
+) -> Result<Json<Page<Order>>, Problem> {
+    query.check::<Order>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List orders error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
 pub async fn get_order(
     State(state): State<OrderState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Order>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Order>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_order(
     State(state): State<OrderState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating order with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Order created successfully: {:?}", created);
-            Json(created)
+            if let Some(entity_id) = created["id"].as_str().and_then(|id| id.parse().ok()) {
+                state.publish(EntityEvent::Created {
+                    entity_type: "order".to_string(),
+                    entity_id,
+                    data: created.clone(),
+                });
+            }
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create order error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create order",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<OrderStoreError>(e))
         }
     }
 }
 
+/// The amount is computed from the order lines and kept as stored, and the
+/// status only changes through the transition routes (see `check_edit`)
 pub async fn update_order(
     State(state): State<OrderState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(order): Json<Order>,
-) -> Json<Option<Order>> {
-    let updated = state.store.update(order).await.ok();
-    Json(updated)
+) -> Result<Tagged<Order>, Problem> {
+    check_id(id, order.id)?;
+    let order = validate_entity(&Order::validation_config("update"), order)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    check_edit(&current, &order)?;
+    let updated = state
+        .store
+        .update(Order {
+            amount: current.amount,
+            version: current.version,
+            owned_by: current.owned_by,
+            ..order
+        })
+        .await?;
+    state.publish_updated(&updated);
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing order
+///
+/// As with `PUT`, the amount follows the order lines and is never patched,
+/// and the status is not edited.
+pub async fn patch_order(
+    State(state): State<OrderState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Order>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut order = patch_entity(&Order::validation_config("update"), current.clone(), patch)?;
+    check_edit(&current, &order)?;
+    order.amount = current.amount;
+    order.touch();
+    let updated = state.store.update(order).await?;
+    state.publish_updated(&updated);
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_order(
+    State(state): State<OrderState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    state.publish(EntityEvent::Deleted {
+        entity_type: "order".to_string(),
+        entity_id: id,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn confirm_order(
+    State(state): State<OrderState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Order>, Problem> {
+    transition_order(&state, &id, OrderStatus::Confirmed).await
+}
+
+pub async fn cancel_order(
+    State(state): State<OrderState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Order>, Problem> {
+    transition_order(&state, &id, OrderStatus::Cancelled).await
 }
 
-pub async fn delete_order(State(state): State<OrderState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+async fn transition_order(
+    state: &OrderState,
+    id: &Uuid,
+    to: OrderStatus,
+) -> Result<Json<Order>, Problem> {
+    let order = state.store.transition(id, to).await.map_err(|e| {
+        eprintln!("Transition order {} to {} error: {:?}", id, to, e);
+        e
+    })?;
+    state.publish_updated(&order);
+    Ok(Json(order))
 }
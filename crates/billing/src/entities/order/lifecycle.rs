@@ -0,0 +1,98 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Lifecycle states of an [`Order`](super::Order)
+///
+/// ```text
+/// pending ──▶ confirmed ──▶ paid
+///    │            │
+///    └────────────┴──▶ cancelled
+/// ```
+///
+/// `paid` and `cancelled` are terminal: no transition leaves them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    Pending,
+    Confirmed,
+    Paid,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 4] = [
+        OrderStatus::Pending,
+        OrderStatus::Confirmed,
+        OrderStatus::Paid,
+        OrderStatus::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, OrderStatus::Paid | OrderStatus::Cancelled)
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (OrderStatus::Pending, OrderStatus::Confirmed)
+                | (OrderStatus::Confirmed, OrderStatus::Paid)
+                | (OrderStatus::Pending, OrderStatus::Cancelled)
+                | (OrderStatus::Confirmed, OrderStatus::Cancelled)
+        )
+    }
+
+    /// Validate a transition, returning the rejected pair on failure
+    pub fn transition_to(&self, next: OrderStatus) -> Result<OrderStatus, OrderTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(OrderTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OrderStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown order status '{}'", s))
+    }
+}
+
+/// A transition rejected by the order state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderTransition {
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+}
+
+impl fmt::Display for OrderTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "order cannot transition from '{}' to '{}'",
+            self.from, self.to
+        )
+    }
+}
@@ -1,8 +1,10 @@
 pub mod descriptor;
 pub mod handlers;
+pub mod lifecycle;
 pub mod model;
 pub mod store;
 
+pub use lifecycle::{OrderStatus, OrderTransition};
 pub use model::*;
 pub use store::*;
 
@@ -22,3 +24,5 @@ pub use store::OrderNeo4jStore;
 pub use store::OrderPostgresStore;
 #[cfg(feature = "scylladb")]
 pub use store::OrderScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::OrderSqliteStore;
@@ -1,3 +1,6 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::{Money, Version};
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -6,18 +9,18 @@ impl_data_entity_validated!(
     ["name", "number"],
     {
         number: String,
-        amount: f64,
+        amount: Money,
         customer_name: Option<String>,
         notes: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
             number: [required string_length(3, 50)],
-            amount: [required positive max_value(2_000_000.0)],
-            status: [required in_list("pending", "confirmed", "cancelled", "paid")],
+            status: [optional in_list("pending", "confirmed", "cancelled", "paid")],
         },
         update: {
-            amount: [optional positive max_value(2_000_000.0)],
             status: [optional in_list("pending", "confirmed", "cancelled", "paid")],
         },
     },
@@ -25,11 +28,11 @@ impl_data_entity_validated!(
         create: {
             number: [trim uppercase],
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
         update: {
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
     }
 );
+
+common::impl_versioned!(Order);
@@ -1,517 +1,234 @@
 use async_trait::async_trait;
-use std::sync::Arc;
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{Currency, Money, Version, VersionMismatch};
+use common::{ErrorKind, HttpError, Payload, ValidationError};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
 use super::Order;
+use super::lifecycle::{OrderStatus, OrderTransition};
 
 #[derive(Debug, thiserror::Error)]
 pub enum OrderStoreError {
     #[error("not found: {0}")]
     NotFound(String),
-    #[error("conflict: {0}")]
-    Conflict(String),
+    /// `transition` is the lifecycle transition that was rejected, if any
+    #[error("conflict: {message}")]
+    Conflict {
+        message: String,
+        transition: Option<OrderTransition>,
+    },
     #[error("validation: {0}")]
     Validation(String),
     #[error(transparent)]
+    VersionMismatch(#[from] VersionMismatch),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
-#[async_trait]
-pub trait OrderStore: Send + Sync {
-    async fn create(&self, order: Order) -> Result<Order, OrderStoreError>;
-    async fn get(&self, id: &Uuid) -> Result<Order, OrderStoreError>;
-    async fn update(&self, order: Order) -> Result<Order, OrderStoreError>;
-    async fn delete(&self, id: &Uuid) -> Result<(), OrderStoreError>;
-    async fn list(&self) -> Result<Vec<Order>, OrderStoreError>;
-}
-
-// ============================================================================
-// InMemory Store Implementation
-// ============================================================================
-
-use tokio::sync::RwLock;
-
-#[derive(Clone, Default)]
-pub struct InMemoryOrderStore {
-    inner: Arc<RwLock<Vec<Order>>>,
-}
-
-#[async_trait::async_trait]
-impl EntityFetcher for InMemoryOrderStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let order = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Order not found: {}", entity_id))?;
-        Ok(serde_json::to_value(order)?)
-    }
-
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_orders = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let orders: Vec<Order> = all_orders.into_iter().skip(offset).take(limit).collect();
-        orders
-            .into_iter()
-            .map(|order| serde_json::to_value(order).map_err(Into::into))
-            .collect()
-    }
-}
-
-#[async_trait::async_trait]
-impl EntityCreator for InMemoryOrderStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
-        let order = Order::new(
-            entity_data["name"].as_str().unwrap_or("Order").to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("pending")
-                .to_string(),
-            entity_data["number"]
-                .as_str()
-                .unwrap_or("ORD-000")
-                .to_string(),
-            entity_data["amount"].as_f64().unwrap_or(0.0),
-            entity_data["customer_name"].as_str().map(String::from),
-            entity_data["notes"].as_str().map(String::from),
-        );
-
-        self.create(order.clone()).await?;
-        Ok(serde_json::to_value(order)?)
-    }
-}
-
-#[async_trait::async_trait]
-impl OrderStore for InMemoryOrderStore {
-    async fn create(&self, order: Order) -> Result<Order, OrderStoreError> {
-        let mut g = self.inner.write().await;
-        if g.iter().any(|o| o.id == order.id) {
-            return Err(OrderStoreError::Conflict(order.id.to_string()));
+impl HttpError for OrderStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            OrderStoreError::NotFound(_) => ErrorKind::NotFound,
+            OrderStoreError::Conflict { .. } => ErrorKind::Conflict,
+            OrderStoreError::Validation(_) => ErrorKind::Validation,
+            OrderStoreError::VersionMismatch(e) => e.kind(),
+            OrderStoreError::Other(_) => ErrorKind::Other,
         }
-        g.push(order.clone());
-        Ok(order)
-    }
-
-    async fn get(&self, id: &Uuid) -> Result<Order, OrderStoreError> {
-        let g = self.inner.read().await;
-        g.iter()
-            .find(|o| &o.id == id)
-            .cloned()
-            .ok_or_else(|| OrderStoreError::NotFound(id.to_string()))
     }
 
-    async fn update(&self, order: Order) -> Result<Order, OrderStoreError> {
-        let mut g = self.inner.write().await;
-        if let Some(x) = g.iter_mut().find(|o| o.id == order.id) {
-            *x = order.clone();
-            Ok(order)
-        } else {
-            Err(OrderStoreError::NotFound(order.id.to_string()))
-        }
-    }
-
-    async fn delete(&self, id: &Uuid) -> Result<(), OrderStoreError> {
-        let mut g = self.inner.write().await;
-        let before = g.len();
-        g.retain(|o| &o.id != id);
-        if g.len() == before {
-            return Err(OrderStoreError::NotFound(id.to_string()));
+    fn extensions(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut extensions = serde_json::Map::new();
+        match self {
+            OrderStoreError::Conflict {
+                transition: Some(transition),
+                ..
+            } => {
+                extensions.insert("from".to_string(), transition.from.as_str().into());
+                extensions.insert("to".to_string(), transition.to.as_str().into());
+            }
+            OrderStoreError::VersionMismatch(e) => extensions = e.extensions(),
+            _ => {}
         }
-        Ok(())
+        extensions
     }
-
-    async fn list(&self) -> Result<Vec<Order>, OrderStoreError> {
-        Ok(self.inner.read().await.clone())
-    }
-}
-
-// ============================================================================
-// DynamoDB Store Implementation
-// ============================================================================
-
-#[cfg(feature = "dynamodb")]
-use aws_sdk_dynamodb::Client as DynamoDBClient;
-#[cfg(feature = "dynamodb")]
-use this::storage::DynamoDBDataService;
-
-#[cfg(feature = "dynamodb")]
-/// DynamoDB store for Order entities
-#[derive(Clone)]
-pub struct OrderDynamoDBStore {
-    service: Arc<DynamoDBDataService<Order>>,
 }
 
-#[cfg(feature = "dynamodb")]
-impl OrderDynamoDBStore {
-    pub fn new(client: DynamoDBClient, table_name: String) -> Self {
-        Self {
-            service: Arc::new(DynamoDBDataService::new(client, table_name)),
+impl From<OrderTransition> for OrderStoreError {
+    fn from(transition: OrderTransition) -> Self {
+        OrderStoreError::Conflict {
+            message: transition.to_string(),
+            transition: Some(transition),
         }
     }
 }
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityFetcher for OrderDynamoDBStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let order = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Order not found: {}", entity_id))?;
-        Ok(serde_json::to_value(order)?)
-    }
-
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_orders = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let orders: Vec<Order> = all_orders.into_iter().skip(offset).take(limit).collect();
-        orders
-            .into_iter()
-            .map(|order| serde_json::to_value(order).map_err(Into::into))
-            .collect()
+impl From<StoreError> for OrderStoreError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::NotFound(id) => OrderStoreError::NotFound(id),
+            StoreError::Conflict(message) => OrderStoreError::Conflict {
+                message,
+                transition: None,
+            },
+            StoreError::Validation(message) => OrderStoreError::Validation(message),
+            StoreError::VersionMismatch(e) => OrderStoreError::VersionMismatch(e),
+            StoreError::Other(e) => OrderStoreError::Other(e),
+        }
     }
 }
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityCreator for OrderDynamoDBStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
+#[async_trait]
+pub trait OrderStore: EntityStore<Order> {
+    /// Move an order to `to`, rejecting transitions the lifecycle forbids
+    async fn transition(&self, id: &Uuid, to: OrderStatus) -> Result<Order, OrderStoreError> {
+        let mut order = self.get(id).await?;
+        parse_status(&order.status)?.transition_to(to)?;
+        order.set_status(to.to_string());
+        self.update(order).await
+    }
+}
+
+impl<S: EntityStore<Order> + ?Sized> OrderStore for S {}
+
+impl StoredEntity for Order {
+    type Error = OrderStoreError;
+    const NAME: &'static str = "Order";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name", "status", "number", "customer_name"];
+
+    /// Build a new order from a create payload
+    ///
+    /// Orders start `pending`: any other `status` is rejected, the lifecycle
+    /// being walked through the transition routes. The amount is never taken
+    /// from the client: it starts at zero in the optional `currency`, EUR by
+    /// default, and then follows the order lines (see
+    /// `billing::services::TotaledOrderStore`).
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&Order::validation_config("create"), entity_data);
+        let currency = match payload.string("currency") {
+            Some(code) => Currency::new(&code).unwrap_or_else(|e| {
+                payload.reject("currency", format!("'currency' {}", e));
+                Currency::EUR
+            }),
+            None => Currency::EUR,
+        };
+        let status = payload.string("status");
+        if status
+            .as_deref()
+            .is_some_and(|s| s != OrderStatus::Pending.as_str())
+        {
+            payload.reject(
+                "status",
+                format!("'status' of a new order must be '{}'", OrderStatus::Pending),
+            );
+        }
         let order = Order::new(
-            entity_data["name"].as_str().unwrap_or("Order").to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("pending")
-                .to_string(),
-            entity_data["number"]
-                .as_str()
-                .unwrap_or("ORD-000")
-                .to_string(),
-            entity_data["amount"].as_f64().unwrap_or(0.0),
-            entity_data["customer_name"].as_str().map(String::from),
-            entity_data["notes"].as_str().map(String::from),
+            payload.required_string("name"),
+            OrderStatus::Pending.to_string(),
+            payload.required_string("number"),
+            Money::zero(currency),
+            payload.string("customer_name"),
+            payload.string("notes"),
+            payload.uuid("owned_by"),
+            Version::default(),
         );
-
-        self.create(order.clone()).await?;
-        Ok(serde_json::to_value(order)?)
-    }
-}
-
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl OrderStore for OrderDynamoDBStore {
-    async fn create(&self, order: Order) -> Result<Order, OrderStoreError> {
-        self.service
-            .create(order.clone())
-            .await
-            .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
-    }
-
-    async fn get(&self, id: &Uuid) -> Result<Order, OrderStoreError> {
-        self.service
-            .get(id)
-            .await
-            .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))?
-            .ok_or_else(|| OrderStoreError::NotFound(id.to_string()))
-    }
-
-    async fn update(&self, order: Order) -> Result<Order, OrderStoreError> {
-        self.service
-            .update(&order.id, order.clone())
-            .await
-            .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
+        payload.finish()?;
+        Ok(order)
     }
 
-    async fn delete(&self, id: &Uuid) -> Result<(), OrderStoreError> {
-        self.service
-            .delete(id)
-            .await
-            .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
+    fn merge_json(self, entity_data: serde_json::Value) -> anyhow::Result<Self> {
+        merge_order_json(self, entity_data)
     }
 
-    async fn list(&self) -> Result<Vec<Order>, OrderStoreError> {
-        self.service
-            .list()
-            .await
-            .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
+    fn check_update(current: &Self, next: &Self) -> Result<(), OrderStoreError> {
+        check_transition(current, next)
     }
 }
 
-// ============================================================================
-// Macro for backend store implementations
-// ============================================================================
-
-/// Generates EntityFetcher, EntityCreator, and OrderStore implementations
-/// for a backend store type that wraps a DataService.
-macro_rules! impl_order_backend_store {
-    ($store:ident) => {
-        #[async_trait::async_trait]
-        impl EntityFetcher for $store {
-            async fn fetch_as_json(
-                &self,
-                entity_id: &Uuid,
-            ) -> Result<serde_json::Value, anyhow::Error> {
-                let order = self
-                    .get(entity_id)
-                    .await
-                    .map_err(|_| anyhow::anyhow!("Order not found: {}", entity_id))?;
-                Ok(serde_json::to_value(order)?)
-            }
-
-            async fn list_as_json(
-                &self,
-                limit: Option<i32>,
-                offset: Option<i32>,
-            ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-                let all_orders = self.list().await?;
-                let offset = offset.unwrap_or(0) as usize;
-                let limit = limit.unwrap_or(20) as usize;
-                let orders: Vec<Order> = all_orders.into_iter().skip(offset).take(limit).collect();
-                orders
-                    .into_iter()
-                    .map(|o| serde_json::to_value(o).map_err(Into::into))
-                    .collect()
-            }
-        }
-
-        #[async_trait::async_trait]
-        impl EntityCreator for $store {
-            async fn create_from_json(
-                &self,
-                entity_data: serde_json::Value,
-            ) -> Result<serde_json::Value, anyhow::Error> {
-                let order = Order::new(
-                    entity_data["name"].as_str().unwrap_or("Order").to_string(),
-                    entity_data["status"]
-                        .as_str()
-                        .unwrap_or("pending")
-                        .to_string(),
-                    entity_data["number"]
-                        .as_str()
-                        .unwrap_or("ORD-000")
-                        .to_string(),
-                    entity_data["amount"].as_f64().unwrap_or(0.0),
-                    entity_data["customer_name"].as_str().map(String::from),
-                    entity_data["notes"].as_str().map(String::from),
-                );
-                self.create(order.clone()).await?;
-                Ok(serde_json::to_value(order)?)
-            }
-        }
-
-        #[async_trait::async_trait]
-        impl OrderStore for $store {
-            async fn create(&self, order: Order) -> Result<Order, OrderStoreError> {
-                self.service
-                    .create(order.clone())
-                    .await
-                    .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
-            }
-
-            async fn get(&self, id: &Uuid) -> Result<Order, OrderStoreError> {
-                self.service
-                    .get(id)
-                    .await
-                    .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))?
-                    .ok_or_else(|| OrderStoreError::NotFound(id.to_string()))
-            }
-
-            async fn update(&self, order: Order) -> Result<Order, OrderStoreError> {
-                self.service
-                    .update(&order.id, order.clone())
-                    .await
-                    .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
-            }
-
-            async fn delete(&self, id: &Uuid) -> Result<(), OrderStoreError> {
-                self.service
-                    .delete(id)
-                    .await
-                    .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
-            }
-
-            async fn list(&self) -> Result<Vec<Order>, OrderStoreError> {
-                self.service
-                    .list()
-                    .await
-                    .map_err(|e| OrderStoreError::Other(anyhow::anyhow!(e)))
-            }
-        }
-    };
-}
-
-// ============================================================================
-// PostgreSQL Store Implementation
-// ============================================================================
-
-#[cfg(feature = "postgres")]
-use sqlx::PgPool;
-#[cfg(feature = "postgres")]
-use this::storage::PostgresDataService;
-
-#[cfg(feature = "postgres")]
-#[derive(Clone)]
-pub struct OrderPostgresStore {
-    service: Arc<PostgresDataService<Order>>,
+fn parse_status(status: &str) -> Result<OrderStatus, OrderStoreError> {
+    status.parse().map_err(OrderStoreError::Validation)
 }
 
-#[cfg(feature = "postgres")]
-impl OrderPostgresStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self {
-            service: Arc::new(PostgresDataService::new(pool)),
-        }
+/// Reject updates whose status change is not a lifecycle transition
+fn check_transition(current: &Order, next: &Order) -> Result<(), OrderStoreError> {
+    if current.status == next.status {
+        return Ok(());
     }
+    parse_status(&current.status)?
+        .transition_to(parse_status(&next.status)?)
+        .map(|_| ())
+        .map_err(Into::into)
 }
 
-#[cfg(feature = "postgres")]
-impl_order_backend_store!(OrderPostgresStore);
-
-// ============================================================================
-// MongoDB Store Implementation
-// ============================================================================
-
-#[cfg(feature = "mongodb_backend")]
-use mongodb::Database as MongoDatabase;
-#[cfg(feature = "mongodb_backend")]
-use this::storage::MongoDataService;
-
-#[cfg(feature = "mongodb_backend")]
-#[derive(Clone)]
-pub struct OrderMongoStore {
-    service: Arc<MongoDataService<Order>>,
-}
-
-#[cfg(feature = "mongodb_backend")]
-impl OrderMongoStore {
-    pub fn new(database: MongoDatabase) -> Self {
-        Self {
-            service: Arc::new(MongoDataService::new(database)),
-        }
+/// Reject an edit of an order that changes its status
+///
+/// Orders are confirmed and cancelled through their transition routes and
+/// paid by `billing::services::PaymentReconciler` once their payments cover
+/// them, never by writing `status` with `PUT`, `PATCH` or `update_from_json`.
+pub(super) fn check_edit(current: &Order, next: &Order) -> Result<(), OrderStoreError> {
+    if current.status == next.status {
+        return Ok(());
     }
-}
-
-#[cfg(feature = "mongodb_backend")]
-impl_order_backend_store!(OrderMongoStore);
-
-// ============================================================================
-// Neo4j Store Implementation
-// ============================================================================
-
-#[cfg(feature = "neo4j")]
-use neo4rs::Graph;
-#[cfg(feature = "neo4j")]
-use this::storage::Neo4jDataService;
-
-#[cfg(feature = "neo4j")]
-#[derive(Clone)]
-pub struct OrderNeo4jStore {
-    service: Arc<Neo4jDataService<Order>>,
-}
-
-#[cfg(feature = "neo4j")]
-impl OrderNeo4jStore {
-    pub fn new(graph: Graph) -> Self {
-        Self {
-            service: Arc::new(Neo4jDataService::new(graph)),
+    let transition = match (parse_status(&current.status), parse_status(&next.status)) {
+        (Ok(from), Ok(to)) => Some(OrderTransition { from, to }),
+        _ => None,
+    };
+    Err(OrderStoreError::Conflict {
+        message: format!(
+            "the status of an order cannot be edited from '{}' to '{}': use \
+             POST /orders/{{id}}/confirm or /cancel, orders become 'paid' with their payments",
+            current.status, next.status
+        ),
+        transition,
+    })
+}
+
+/// Merge a partial JSON payload into an existing order, keeping its identity,
+/// its status and its computed amount
+fn merge_order_json(order: Order, entity_data: serde_json::Value) -> anyhow::Result<Order> {
+    let changes =
+        Payload::validate(&Order::validation_config("update"), entity_data).into_values()?;
+    let current = order.clone();
+    let mut merged = serde_json::to_value(order)?;
+    if let Some(fields) = merged.as_object_mut() {
+        for (key, value) in changes {
+            if !matches!(
+                key.as_str(),
+                "id" | "type" | "created_at" | "amount" | "version" | "owned_by"
+            ) {
+                fields.insert(key, value);
+            }
         }
     }
+    let mut order: Order =
+        serde_json::from_value(merged).map_err(|e| ValidationError::field("$", e.to_string()))?;
+    check_edit(&current, &order)?;
+    order.touch();
+    Ok(order)
 }
 
-#[cfg(feature = "neo4j")]
-impl_order_backend_store!(OrderNeo4jStore);
+pub type InMemoryOrderStore = InMemoryEntityStore<Order>;
 
-// ============================================================================
-// ScyllaDB Store Implementation
-// ============================================================================
+#[cfg(feature = "dynamodb")]
+pub type OrderDynamoDBStore = common::store::DynamoDBStore<Order>;
 
-#[cfg(feature = "scylladb")]
-use scylla::client::session::Session;
-#[cfg(feature = "scylladb")]
-use this::storage::ScyllaDataService;
+#[cfg(feature = "postgres")]
+pub type OrderPostgresStore = common::store::PostgresStore<Order>;
 
-#[cfg(feature = "scylladb")]
-#[derive(Clone)]
-pub struct OrderScyllaStore {
-    service: Arc<ScyllaDataService<Order>>,
-}
+#[cfg(feature = "mongodb_backend")]
+pub type OrderMongoStore = common::store::MongoStore<Order>;
 
-#[cfg(feature = "scylladb")]
-impl OrderScyllaStore {
-    pub fn new(session: Arc<Session>, keyspace: impl Into<String>) -> Self {
-        Self {
-            service: Arc::new(ScyllaDataService::new(session, keyspace)),
-        }
-    }
-}
+#[cfg(feature = "neo4j")]
+pub type OrderNeo4jStore = common::store::Neo4jStore<Order>;
 
 #[cfg(feature = "scylladb")]
-impl_order_backend_store!(OrderScyllaStore);
+pub type OrderScyllaStore = common::store::ScyllaStore<Order>;
 
-// ============================================================================
-// MySQL Store Implementation
-// ============================================================================
-
-#[cfg(feature = "mysql")]
-use sqlx::MySqlPool;
 #[cfg(feature = "mysql")]
-use this::storage::MysqlDataService;
-
-#[cfg(feature = "mysql")]
-#[derive(Clone)]
-pub struct OrderMysqlStore {
-    service: Arc<MysqlDataService<Order>>,
-}
-
-#[cfg(feature = "mysql")]
-impl OrderMysqlStore {
-    pub fn new(pool: MySqlPool) -> Self {
-        Self {
-            service: Arc::new(MysqlDataService::new(pool)),
-        }
-    }
-}
-
-#[cfg(feature = "mysql")]
-impl_order_backend_store!(OrderMysqlStore);
-
-// ============================================================================
-// LMDB Store Implementation
-// ============================================================================
+pub type OrderMysqlStore = common::store::MysqlStore<Order>;
 
 #[cfg(feature = "lmdb")]
-use this::storage::LmdbDataService;
+pub type OrderLmdbStore = common::store::LmdbStore<Order>;
 
-#[cfg(feature = "lmdb")]
-#[derive(Clone)]
-pub struct OrderLmdbStore {
-    service: Arc<LmdbDataService<Order>>,
-}
-
-#[cfg(feature = "lmdb")]
-impl OrderLmdbStore {
-    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
-        Ok(Self {
-            service: Arc::new(LmdbDataService::open(path)?),
-        })
-    }
-}
-
-#[cfg(feature = "lmdb")]
-impl_order_backend_store!(OrderLmdbStore);
+#[cfg(feature = "sqlite")]
+pub type OrderSqliteStore = common::store::SqliteStore<Order>;
@@ -0,0 +1,132 @@
+use common::ValidationError;
+use common::store::StoreError;
+use std::sync::Arc;
+use this::prelude::*;
+
+use crate::module::EntityStore;
+
+/// Order line entity store that snapshots products from the catalog
+///
+/// `Product` lives in the `catalog` module, so billing only sees it through
+/// the product [`EntityFetcher`] registered by that module. On create, the
+/// `product_id` of the payload is looked up and its `sku`, `price` and `name`
+/// are copied into the line, replacing anything the client sent. Only
+/// `active` products can be ordered. The line is then linked to its product
+/// with the cross-module `priced_from` link.
+pub struct CatalogOrderLineStore {
+    inner: Arc<dyn EntityStore>,
+    products: Arc<dyn EntityFetcher>,
+    link_service: Arc<dyn LinkService>,
+}
+
+impl CatalogOrderLineStore {
+    pub fn new(
+        inner: Arc<dyn EntityStore>,
+        products: Arc<dyn EntityFetcher>,
+        link_service: Arc<dyn LinkService>,
+    ) -> Self {
+        Self {
+            inner,
+            products,
+            link_service,
+        }
+    }
+
+    async fn snapshot(
+        &self,
+        mut entity_data: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let product_id: Uuid = entity_data["product_id"]
+            .as_str()
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| ValidationError::field("product_id", "'product_id' must be a UUID"))?;
+        let product = match self.products.fetch_as_json(&product_id).await {
+            Ok(product) => product,
+            Err(e) if matches!(e.downcast_ref(), Some(StoreError::NotFound(_))) => {
+                return Err(ValidationError::field(
+                    "product_id",
+                    format!("product {} not found", product_id),
+                )
+                .into());
+            }
+            Err(e) => return Err(e.context(format!("looking up product {}", product_id))),
+        };
+        if product["status"] != "active" {
+            return Err(ValidationError::field(
+                "product_id",
+                format!(
+                    "product {} is {} and cannot be ordered",
+                    product_id,
+                    product["status"].as_str().unwrap_or("unavailable")
+                ),
+            )
+            .into());
+        }
+
+        if let Some(fields) = entity_data.as_object_mut() {
+            fields.insert("sku".to_string(), product["sku"].clone());
+            fields.insert("unit_price".to_string(), product["price"].clone());
+            fields.insert("name".to_string(), product["name"].clone());
+        }
+        Ok(entity_data)
+    }
+}
+
+#[async_trait::async_trait]
+impl EntityFetcher for CatalogOrderLineStore {
+    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
+        self.inner.fetch_as_json(entity_id).await
+    }
+
+    async fn get_sample_entity(&self) -> Result<serde_json::Value, anyhow::Error> {
+        self.inner.get_sample_entity().await
+    }
+
+    async fn list_as_json(
+        &self,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.inner.list_as_json(limit, offset).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EntityCreator for CatalogOrderLineStore {
+    async fn create_from_json(
+        &self,
+        entity_data: serde_json::Value,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        let entity_data = self.snapshot(entity_data).await?;
+        let created = self.inner.create_from_json(entity_data).await?;
+
+        let (Some(line_id), Some(product_id)) = (
+            created["id"].as_str().and_then(|id| id.parse().ok()),
+            created["product_id"]
+                .as_str()
+                .and_then(|id| id.parse().ok()),
+        ) else {
+            anyhow::bail!("created order line has no id or product_id");
+        };
+        let link = LinkEntity::new("priced_from", line_id, product_id, None);
+        if let Err(e) = self.link_service.create(link).await {
+            if let Err(rollback) = self.inner.delete(&line_id).await {
+                eprintln!("Failed to roll back order line {}: {:?}", line_id, rollback);
+            }
+            return Err(e);
+        }
+        Ok(created)
+    }
+
+    async fn update_from_json(
+        &self,
+        entity_id: &Uuid,
+        entity_data: serde_json::Value,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        self.inner.update_from_json(entity_id, entity_data).await
+    }
+
+    async fn delete(&self, entity_id: &Uuid) -> Result<(), anyhow::Error> {
+        self.inner.delete(entity_id).await
+    }
+}
@@ -0,0 +1,66 @@
+use axum::routing::get;
+use std::sync::Arc;
+use this::core::events::EventBus;
+use this::prelude::Router;
+use this::server::entity_registry::EntityDescriptor;
+
+use super::OrderLineStore;
+use super::handlers::{
+    OrderLineState, create_order_line, delete_order_line, get_order_line, list_order_lines,
+    patch_order_line, update_order_line,
+};
+
+#[derive(Clone)]
+pub struct OrderLineDescriptor {
+    store: Arc<dyn OrderLineStore + Send + Sync>,
+    entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    event_bus: Option<EventBus>,
+}
+
+impl OrderLineDescriptor {
+    pub fn new_with_creator(
+        store: Arc<dyn OrderLineStore + Send + Sync>,
+        entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    ) -> Self {
+        Self {
+            store,
+            entity_creator,
+            event_bus: None,
+        }
+    }
+
+    /// Publish order line mutations made through the REST routes on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Option<EventBus>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
+}
+
+impl EntityDescriptor for OrderLineDescriptor {
+    fn entity_type(&self) -> &str {
+        "order_line"
+    }
+    fn plural(&self) -> &str {
+        "order_lines"
+    }
+    fn build_routes(&self) -> Router {
+        let state = OrderLineState {
+            store: self.store.clone(),
+            entity_creator: self.entity_creator.clone(),
+            event_bus: self.event_bus.clone(),
+        };
+        Router::new()
+            .route(
+                "/order_lines",
+                get(list_order_lines).post(create_order_line),
+            )
+            .route(
+                "/order_lines/{id}",
+                get(get_order_line)
+                    .put(update_order_line)
+                    .patch(patch_order_line)
+                    .delete(delete_order_line),
+            )
+            .with_state(state)
+    }
+}
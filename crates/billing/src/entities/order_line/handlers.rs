@@ -0,0 +1,149 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{ErrorKind, IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity};
+use std::sync::Arc;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
+use this::prelude::*;
+
+use super::{OrderLine, OrderLineStore, OrderLineStoreError};
+
+#[derive(Clone)]
+pub struct OrderLineState {
+    pub store: Arc<dyn OrderLineStore>,
+    pub entity_creator: Arc<dyn EntityCreator>,
+    pub event_bus: Option<EventBus>,
+}
+
+impl OrderLineState {
+    /// Publish an order line event so that REST mutations reach the same subscribers
+    /// as the GraphQL and gRPC exposures
+    fn publish(&self, event: EntityEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(event));
+        }
+    }
+}
+
+pub async fn list_order_lines(
+    State(state): State<OrderLineState>,
+    query: ListQuery,
+) -> Result<Json<Page<OrderLine>>, Problem> {
+    query.check::<OrderLine>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List order lines error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
+pub async fn get_order_line(
+    State(state): State<OrderLineState>,
+    Path(id): Path<Uuid>,
+) -> Result<Tagged<OrderLine>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
+}
+
+pub async fn create_order_line(
+    State(state): State<OrderLineState>,
+    Json(entity_data): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
+    eprintln!("Creating order line with data: {:?}", entity_data);
+    match state.entity_creator.create_from_json(entity_data).await {
+        Ok(created) => {
+            eprintln!("Order line created successfully: {:?}", created);
+            if let Some(entity_id) = created["id"].as_str().and_then(|id| id.parse().ok()) {
+                state.publish(EntityEvent::Created {
+                    entity_type: "order_line".to_string(),
+                    entity_id,
+                    data: created.clone(),
+                });
+            }
+            Ok((StatusCode::CREATED, Json(created)))
+        }
+        Err(e) => {
+            eprintln!("Create order line error: {:?}", e);
+            Err(Problem::from_creator::<OrderLineStoreError>(e))
+        }
+    }
+}
+
+/// Only the quantity, status and name of a line can change; the product
+/// snapshot is kept and the line total recomputed
+pub async fn update_order_line(
+    State(state): State<OrderLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(order_line): Json<OrderLine>,
+) -> Result<Tagged<OrderLine>, Problem> {
+    check_id(id, order_line.id)?;
+    if_match.check(state.store.get(&id).await?.version)?;
+    let entity_data = serde_json::to_value(&order_line)
+        .map_err(|e| Problem::new(ErrorKind::Validation, e.to_string()))?;
+    let updated = state
+        .entity_creator
+        .update_from_json(&id, entity_data)
+        .await
+        .map_err(Problem::from_creator::<OrderLineStoreError>)?;
+    let updated = serde_json::from_value::<OrderLine>(updated)
+        .map_err(|e| Problem::new(ErrorKind::Other, e.to_string()))?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "order_line".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing order line
+///
+/// Like `PUT`, only the quantity, status and name change; the product
+/// snapshot is kept and `line_total` recomputed.
+pub async fn patch_order_line(
+    State(state): State<OrderLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<OrderLine>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let patched = patch_entity(
+        &OrderLine::validation_rules("update"),
+        current.clone(),
+        patch,
+    )?;
+    let mut order_line = OrderLine {
+        name: patched.name,
+        status: patched.status,
+        quantity: patched.quantity,
+        ..current
+    };
+    order_line
+        .reprice()
+        .map_err(|e| Problem::new(ErrorKind::Validation, e.to_string()))?;
+    order_line.touch();
+    let updated = state.store.update(order_line).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "order_line".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_order_line(
+    State(state): State<OrderLineState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    state.publish(EntityEvent::Deleted {
+        entity_type: "order_line".to_string(),
+        entity_id: id,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,28 @@
+pub mod catalog;
+pub mod descriptor;
+pub mod handlers;
+pub mod model;
+pub mod store;
+
+pub use catalog::CatalogOrderLineStore;
+pub use model::*;
+pub use store::*;
+
+// Export stores for use in BillingStores
+pub use store::InMemoryOrderLineStore;
+#[cfg(feature = "dynamodb")]
+pub use store::OrderLineDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::OrderLineLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::OrderLineMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::OrderLineMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::OrderLineNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::OrderLinePostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::OrderLineScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::OrderLineSqliteStore;
@@ -0,0 +1,86 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use common::money::{self, Money, MoneyError};
+use this::core::validation::extractor::ValidatableEntity;
+use this::prelude::*;
+
+impl_data_entity_validated!(
+    OrderLine,
+    "order_line",
+    ["name", "sku"],
+    {
+        product_id: Uuid,
+        sku: String,
+        unit_price: Money,
+        quantity: i32,
+        line_total: Money,
+        owned_by: Option<Uuid>,
+        version: Version,
+    },
+    validate: {
+        create: {
+            product_id: [required],
+            quantity: [required positive max_value(10_000.0)],
+            status: [required in_list("active", "cancelled")],
+        },
+        update: {
+            quantity: [optional positive max_value(10_000.0)],
+            status: [optional in_list("active", "cancelled")],
+        },
+    },
+    filters: {
+        create: {
+            sku: [trim uppercase],
+            status: [trim lowercase],
+        },
+        update: {
+            status: [trim lowercase],
+        },
+    }
+);
+
+common::impl_versioned!(OrderLine);
+
+impl OrderLine {
+    /// A line for `quantity` units of a product, priced from its snapshot
+    ///
+    /// `name`, `sku` and `unit_price` are copied from the product at the time
+    /// the line is created; later catalog changes do not affect the line.
+    pub fn for_product(
+        name: String,
+        product_id: Uuid,
+        sku: String,
+        unit_price: Money,
+        quantity: i32,
+    ) -> Result<Self, MoneyError> {
+        let line_total = unit_price.checked_mul(quantity as i64)?;
+        Ok(Self::new(
+            name,
+            "active".to_string(),
+            product_id,
+            sku,
+            unit_price,
+            quantity,
+            line_total,
+            None,
+            Version::default(),
+        ))
+    }
+
+    /// Recompute `line_total` after a quantity change
+    pub fn reprice(&mut self) -> Result<(), MoneyError> {
+        self.line_total = self.unit_price.checked_mul(self.quantity as i64)?;
+        Ok(())
+    }
+
+    /// Declared rules for `operation` plus the money rules on the price
+    /// snapshot; `line_total` is computed and never validated from input
+    pub fn validation_rules(operation: &str) -> EntityValidationConfig {
+        let mut config = <Self as ValidatableEntity>::validation_config(operation);
+        config.add_filter("unit_price", money::filters::normalize());
+        config.add_validator("unit_price", money::validators::positive());
+        config.add_validator("unit_price", money::validators::max_amount(1_000_000));
+        config
+    }
+}
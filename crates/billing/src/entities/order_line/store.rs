@@ -0,0 +1,95 @@
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{Payload, ValidationError};
+
+use super::OrderLine;
+
+pub type OrderLineStoreError = StoreError;
+
+/// Store of order lines
+pub trait OrderLineStore: EntityStore<OrderLine> {}
+
+impl<S: EntityStore<OrderLine> + ?Sized> OrderLineStore for S {}
+
+impl StoredEntity for OrderLine {
+    type Error = OrderLineStoreError;
+    const NAME: &'static str = "OrderLine";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name", "status", "sku", "product_id"];
+
+    /// Build a new order line from a create payload
+    ///
+    /// The line is named after its SKU unless the payload names it.
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&OrderLine::validation_rules("create"), entity_data);
+        let status = payload.required_string("status");
+        let product_id = payload.required_uuid("product_id");
+        let sku = payload.required_string("sku");
+        let name = payload.string("name").unwrap_or_else(|| sku.clone());
+        let unit_price = payload.required_money("unit_price");
+        let quantity = payload.required_i32("quantity");
+        let owned_by = payload.uuid("owned_by");
+        payload.finish()?;
+
+        let mut order_line = OrderLine::for_product(name, product_id, sku, unit_price, quantity)
+            .map_err(|e| ValidationError::field("unit_price", e.to_string()))?;
+        order_line.status = status;
+        order_line.owned_by = owned_by;
+        Ok(order_line)
+    }
+
+    fn merge_json(self, entity_data: serde_json::Value) -> anyhow::Result<Self> {
+        merge_order_line_json(self, entity_data)
+    }
+}
+
+/// Merge a partial JSON payload into an existing line
+///
+/// The product snapshot is kept and `line_total` recomputed, so only the
+/// quantity, status and name of a line can change.
+fn merge_order_line_json(
+    order_line: OrderLine,
+    entity_data: serde_json::Value,
+) -> anyhow::Result<OrderLine> {
+    let changes =
+        Payload::validate(&OrderLine::validation_rules("update"), entity_data).into_values()?;
+    let mut merged = serde_json::to_value(order_line)?;
+    if let Some(fields) = merged.as_object_mut() {
+        for (key, value) in changes {
+            if matches!(key.as_str(), "name" | "status" | "quantity") {
+                fields.insert(key, value);
+            }
+        }
+    }
+    let mut order_line: OrderLine =
+        serde_json::from_value(merged).map_err(|e| ValidationError::field("$", e.to_string()))?;
+    order_line
+        .reprice()
+        .map_err(|e| ValidationError::field("quantity", e.to_string()))?;
+    order_line.touch();
+    Ok(order_line)
+}
+
+pub type InMemoryOrderLineStore = InMemoryEntityStore<OrderLine>;
+
+#[cfg(feature = "dynamodb")]
+pub type OrderLineDynamoDBStore = common::store::DynamoDBStore<OrderLine>;
+
+#[cfg(feature = "postgres")]
+pub type OrderLinePostgresStore = common::store::PostgresStore<OrderLine>;
+
+#[cfg(feature = "mongodb_backend")]
+pub type OrderLineMongoStore = common::store::MongoStore<OrderLine>;
+
+#[cfg(feature = "neo4j")]
+pub type OrderLineNeo4jStore = common::store::Neo4jStore<OrderLine>;
+
+#[cfg(feature = "scylladb")]
+pub type OrderLineScyllaStore = common::store::ScyllaStore<OrderLine>;
+
+#[cfg(feature = "mysql")]
+pub type OrderLineMysqlStore = common::store::MysqlStore<OrderLine>;
+
+#[cfg(feature = "lmdb")]
+pub type OrderLineLmdbStore = common::store::LmdbStore<OrderLine>;
+
+#[cfg(feature = "sqlite")]
+pub type OrderLineSqliteStore = common::store::SqliteStore<OrderLine>;
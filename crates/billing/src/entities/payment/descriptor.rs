@@ -1,17 +1,20 @@
 use axum::routing::get;
 use std::sync::Arc;
+use this::core::events::EventBus;
 use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
 
 use super::PaymentStore;
 use super::handlers::{
-    PaymentState, create_payment, delete_payment, get_payment, list_payments, update_payment,
+    PaymentState, create_payment, delete_payment, get_payment, list_payments, patch_payment,
+    update_payment,
 };
 
 #[derive(Clone)]
 pub struct PaymentDescriptor {
     store: Arc<dyn PaymentStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    event_bus: Option<EventBus>,
 }
 
 impl PaymentDescriptor {
@@ -26,8 +29,15 @@ impl PaymentDescriptor {
         Self {
             store,
             entity_creator,
+            event_bus: None,
         }
     }
+
+    /// Publish payment mutations made through the REST routes on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Option<EventBus>) -> Self {
+        self.event_bus = event_bus;
+        self
+    }
 }
 
 impl EntityDescriptor for PaymentDescriptor {
@@ -41,12 +51,16 @@ impl EntityDescriptor for PaymentDescriptor {
         let state = PaymentState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            event_bus: self.event_bus.clone(),
         };
         Router::new()
             .route("/payments", get(list_payments).post(create_payment))
             .route(
                 "/payments/{id}",
-                get(get_payment).put(update_payment).delete(delete_payment),
+                get(get_payment)
+                    .put(update_payment)
+                    .patch(patch_payment)
+                    .delete(delete_payment),
             )
             .with_state(state)
     }
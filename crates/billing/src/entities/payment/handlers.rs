@@ -1,66 +1,127 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
 use this::prelude::*;
 
-use super::{Payment, PaymentStore};
+use super::{Payment, PaymentStore, PaymentStoreError};
 
 #[derive(Clone)]
 pub struct PaymentState {
     pub store: Arc<dyn PaymentStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub event_bus: Option<EventBus>,
 }
 
-pub async fn list_payments(State(state): State<PaymentState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List payments error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list payments",
-                "details": e.to_string()
-            }))
+impl PaymentState {
+    /// Publish a payment event so that REST mutations reach the same subscribers
+    /// as the GraphQL and gRPC exposures
+    fn publish(&self, event: EntityEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(event));
         }
     }
 }
 
+pub async fn list_payments(
+    State(state): State<PaymentState>,
+    query: ListQuery,
+) -> Result<Json<Page<Payment>>, Problem> {
+    query.check::<Payment>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List payments error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
 pub async fn get_payment(
     State(state): State<PaymentState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Payment>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Payment>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_payment(
     State(state): State<PaymentState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating payment with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Payment created successfully: {:?}", created);
-            Json(created)
+            if let Some(entity_id) = created["id"].as_str().and_then(|id| id.parse().ok()) {
+                state.publish(EntityEvent::Created {
+                    entity_type: "payment".to_string(),
+                    entity_id,
+                    data: created.clone(),
+                });
+            }
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create payment error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create payment",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<PaymentStoreError>(e))
         }
     }
 }
 
 pub async fn update_payment(
     State(state): State<PaymentState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(payment): Json<Payment>,
-) -> Json<Option<Payment>> {
-    let updated = state.store.update(payment).await.ok();
-    Json(updated)
+) -> Result<Tagged<Payment>, Problem> {
+    check_id(id, payment.id)?;
+    let mut payment = validate_entity(&Payment::validation_rules("update"), payment)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    payment.version = current.version;
+    payment.owned_by = current.owned_by;
+    let updated = state.store.update(payment).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "payment".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
 }
 
-pub async fn delete_payment(State(state): State<PaymentState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing payment
+pub async fn patch_payment(
+    State(state): State<PaymentState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Payment>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut payment = patch_entity(&Payment::validation_rules("update"), current, patch)?;
+    payment.touch();
+    let updated = state.store.update(payment).await?;
+    if let Ok(data) = serde_json::to_value(&updated) {
+        state.publish(EntityEvent::Updated {
+            entity_type: "payment".to_string(),
+            entity_id: updated.id,
+            data,
+        });
+    }
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_payment(
+    State(state): State<PaymentState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    state.publish(EntityEvent::Deleted {
+        entity_type: "payment".to_string(),
+        entity_id: id,
+    });
+    Ok(StatusCode::NO_CONTENT)
 }
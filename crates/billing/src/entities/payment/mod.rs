@@ -22,3 +22,5 @@ pub use store::PaymentNeo4jStore;
 pub use store::PaymentPostgresStore;
 #[cfg(feature = "scylladb")]
 pub use store::PaymentScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::PaymentSqliteStore;
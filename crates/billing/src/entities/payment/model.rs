@@ -1,3 +1,8 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use common::money::{self, Money};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -6,19 +11,21 @@ impl_data_entity_validated!(
     ["name", "number"],
     {
         number: String,
-        amount: f64,
+        amount: Money,
         method: String,
         transaction_id: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
             number: [required string_length(3, 50)],
-            amount: [required positive max_value(2_000_000.0)],
+            amount: [required],
             method: [required in_list("credit_card", "bank_transfer", "cash")],
             status: [required in_list("pending", "completed", "failed")],
         },
         update: {
-            amount: [optional positive max_value(2_000_000.0)],
+            amount: [optional],
             method: [optional in_list("credit_card", "bank_transfer", "cash")],
             status: [optional in_list("pending", "completed", "failed")],
         },
@@ -28,12 +35,24 @@ impl_data_entity_validated!(
             number: [trim uppercase],
             method: [trim lowercase],
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
         update: {
             method: [trim lowercase],
             status: [trim lowercase],
-            amount: [round_decimals(2)],
         },
     }
 );
+
+common::impl_versioned!(Payment);
+
+impl Payment {
+    /// Rules for `operation`: the ones declared above plus the money rules on
+    /// `amount`, which the macro's numeric validators and filters cannot check
+    pub fn validation_rules(operation: &str) -> EntityValidationConfig {
+        let mut config = <Self as ValidatableEntity>::validation_config(operation);
+        config.add_filter("amount", money::filters::normalize());
+        config.add_validator("amount", money::validators::positive());
+        config.add_validator("amount", money::validators::max_amount(2_000_000));
+        config
+    }
+}
@@ -1,5 +1,6 @@
 pub mod entities;
 pub mod module;
+pub mod services;
 pub mod stores;
 
 // Re-export for convenience
@@ -1,15 +1,26 @@
+use common::links::LinkRules;
 use std::sync::Arc;
+use this::core::events::EventBus;
 use this::core::module::Module;
 use this::prelude::LinksConfig;
-use this::prelude::{EntityCreator, EntityFetcher};
+use this::prelude::{EntityCreator, EntityFetcher, LinkService};
 use this::server::entity_registry::EntityRegistry;
 
 use crate::entities::invoice::InvoiceStore;
 use crate::entities::invoice::descriptor::InvoiceDescriptor;
+use crate::entities::invoice_line::InvoiceLineStore;
+use crate::entities::invoice_line::descriptor::InvoiceLineDescriptor;
 use crate::entities::order::OrderStore;
 use crate::entities::order::descriptor::OrderDescriptor;
+use crate::entities::order_line::descriptor::OrderLineDescriptor;
+use crate::entities::order_line::{CatalogOrderLineStore, OrderLineStore};
 use crate::entities::payment::PaymentStore;
 use crate::entities::payment::descriptor::PaymentDescriptor;
+use crate::services::TotaledOrderStore;
+use crate::services::sequence::Sequence;
+
+/// The `links.yaml` of the module
+const LINKS_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
 
 // Combined trait for entity stores
 pub trait EntityStore: EntityFetcher + EntityCreator + Send + Sync {}
@@ -17,22 +28,79 @@ pub trait EntityStore: EntityFetcher + EntityCreator + Send + Sync {}
 // Blanket implementation for any type that implements both traits
 impl<T> EntityStore for T where T: EntityFetcher + EntityCreator + Send + Sync {}
 
+#[derive(Clone)]
 pub struct BillingStores {
     pub orders_store: Arc<dyn OrderStore>,
     pub orders_entity: Arc<dyn EntityStore>,
+    pub order_lines_store: Arc<dyn OrderLineStore>,
+    pub order_lines_entity: Arc<dyn EntityStore>,
     pub invoices_store: Arc<dyn InvoiceStore>,
     pub invoices_entity: Arc<dyn EntityStore>,
+    pub invoice_lines_store: Arc<dyn InvoiceLineStore>,
+    pub invoice_lines_entity: Arc<dyn EntityStore>,
     pub payments_store: Arc<dyn PaymentStore>,
     pub payments_entity: Arc<dyn EntityStore>,
+    /// Numbering of the invoices, see `services::sequence`
+    pub sequences_store: Arc<dyn common::store::EntityStore<Sequence>>,
 }
 
 pub struct BillingModule {
     pub stores: BillingStores,
+    pub event_bus: Option<EventBus>,
 }
 
 impl BillingModule {
     pub fn new(stores: BillingStores) -> Self {
-        Self { stores }
+        Self {
+            stores,
+            event_bus: None,
+        }
+    }
+
+    /// Publish mutations made through the module's REST routes on `event_bus`
+    ///
+    /// Pass a clone of the bus later attached to the host with
+    /// `ServerHost::with_event_bus` so that REST, GraphQL and gRPC mutations
+    /// all reach the same subscribers.
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    /// Snapshot catalog products into new order lines
+    ///
+    /// `products` is the product fetcher of the catalog module
+    /// (`CatalogStores::products_entity`) and `link_service` the one the host
+    /// is built with. Without a catalog, order lines must carry their own
+    /// `sku` and `unit_price`.
+    pub fn with_product_catalog(
+        mut self,
+        products: Arc<dyn EntityFetcher>,
+        link_service: Arc<dyn LinkService>,
+    ) -> Self {
+        self.stores.order_lines_entity = Arc::new(CatalogOrderLineStore::new(
+            self.stores.order_lines_entity.clone(),
+            products,
+            link_service,
+        ));
+        self
+    }
+
+    /// Settle the amount of pending orders from their lines on every update,
+    /// confirmations included, see `billing::services::TotaledOrderStore`
+    ///
+    /// `link_service` is the one the host is built with.
+    pub fn with_order_totals(mut self, link_service: Arc<dyn LinkService>) -> Self {
+        let orders = Arc::new(TotaledOrderStore::new(&self.stores, link_service));
+        self.stores.orders_store = orders.clone();
+        self.stores.orders_entity = orders;
+        self
+    }
+
+    /// The `cardinality` and `on_delete` rules of the module's links, see
+    /// `common::links`
+    pub fn link_rules(&self) -> Result<LinkRules, anyhow::Error> {
+        LinkRules::from_yaml_file(LINKS_CONFIG)
     }
 }
 
@@ -45,19 +113,20 @@ impl Module for BillingModule {
     }
 
     fn entity_types(&self) -> Vec<&str> {
-        vec!["order", "invoice", "payment"]
+        vec!["order", "order_line", "invoice", "invoice_line", "payment"]
     }
 
     fn links_config(&self) -> Result<LinksConfig, anyhow::Error> {
         // Load configuration from YAML file
-        let config_path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
-        LinksConfig::from_yaml_file(config_path)
+        LinksConfig::from_yaml_file(LINKS_CONFIG)
     }
 
     fn get_entity_fetcher(&self, entity_type: &str) -> Option<Arc<dyn EntityFetcher>> {
         match entity_type {
             "order" => Some(self.stores.orders_entity.clone()),
+            "order_line" => Some(self.stores.order_lines_entity.clone()),
             "invoice" => Some(self.stores.invoices_entity.clone()),
+            "invoice_line" => Some(self.stores.invoice_lines_entity.clone()),
             "payment" => Some(self.stores.payments_entity.clone()),
             _ => None,
         }
@@ -66,24 +135,49 @@ impl Module for BillingModule {
     fn get_entity_creator(&self, entity_type: &str) -> Option<Arc<dyn EntityCreator>> {
         match entity_type {
             "order" => Some(self.stores.orders_entity.clone()),
+            "order_line" => Some(self.stores.order_lines_entity.clone()),
             "invoice" => Some(self.stores.invoices_entity.clone()),
+            "invoice_line" => Some(self.stores.invoice_lines_entity.clone()),
             "payment" => Some(self.stores.payments_entity.clone()),
             _ => None,
         }
     }
 
     fn register_entities(&self, registry: &mut EntityRegistry) {
-        registry.register(Box::new(OrderDescriptor::new_with_creator(
-            self.stores.orders_store.clone(),
-            self.stores.orders_entity.clone(),
-        )));
-        registry.register(Box::new(InvoiceDescriptor::new_with_creator(
-            self.stores.invoices_store.clone(),
-            self.stores.invoices_entity.clone(),
-        )));
-        registry.register(Box::new(PaymentDescriptor::new_with_creator(
-            self.stores.payments_store.clone(),
-            self.stores.payments_entity.clone(),
-        )));
+        registry.register(Box::new(
+            OrderDescriptor::new_with_creator(
+                self.stores.orders_store.clone(),
+                self.stores.orders_entity.clone(),
+            )
+            .with_event_bus(self.event_bus.clone()),
+        ));
+        registry.register(Box::new(
+            OrderLineDescriptor::new_with_creator(
+                self.stores.order_lines_store.clone(),
+                self.stores.order_lines_entity.clone(),
+            )
+            .with_event_bus(self.event_bus.clone()),
+        ));
+        registry.register(Box::new(
+            InvoiceDescriptor::new_with_creator(
+                self.stores.invoices_store.clone(),
+                self.stores.invoices_entity.clone(),
+            )
+            .with_event_bus(self.event_bus.clone()),
+        ));
+        registry.register(Box::new(
+            InvoiceLineDescriptor::new_with_creator(
+                self.stores.invoice_lines_store.clone(),
+                self.stores.invoice_lines_entity.clone(),
+            )
+            .with_event_bus(self.event_bus.clone()),
+        ));
+        registry.register(Box::new(
+            PaymentDescriptor::new_with_creator(
+                self.stores.payments_store.clone(),
+                self.stores.payments_entity.clone(),
+            )
+            .with_event_bus(self.event_bus.clone()),
+        ));
     }
 }
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use common::store::EntityStore;
+use common::{ErrorKind, HttpError, Version};
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent, LinkEvent};
+use this::prelude::{LinkEntity, LinkService, Uuid};
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::entities::invoice::{Invoice, InvoiceStore};
+use crate::entities::order::{Order, OrderStatus, OrderStore};
+use crate::module::BillingStores;
+use crate::services::sequence::{self, Sequence};
+
+/// Settings for automatically generated invoices
+#[derive(Debug, Clone)]
+pub struct InvoicingConfig {
+    /// Prefix of the invoice number sequence (`INV` gives `INV-000001`, ...),
+    /// also the name of the sequence in the store
+    pub number_prefix: String,
+    /// Days between invoice creation and its `due_date`
+    pub payment_term_days: i64,
+}
+
+impl Default for InvoicingConfig {
+    fn default() -> Self {
+        Self {
+            number_prefix: "INV".to_string(),
+            payment_term_days: 30,
+        }
+    }
+}
+
+/// An invoice created for an order together with its `has_invoice` link
+#[derive(Debug, Clone)]
+pub struct GeneratedInvoice {
+    pub invoice: Invoice,
+    pub link: LinkEntity,
+}
+
+/// Creates an invoice for every order that reaches `confirmed`
+///
+/// The generator listens on the host event bus, so it reacts to confirmations
+/// made through any exposure (REST, GraphQL, gRPC). It works against whatever
+/// backend the [`BillingStores`] were built with.
+///
+/// Invoicing an order is idempotent: the invoice and its `has_invoice` link
+/// have ids derived from the order, so an interrupted or repeated run picks
+/// up the invoice already created instead of making another. Numbers come
+/// from a sequence of the store (see [`sequence::next_value`]), distinct
+/// across the processes sharing the backend; an invoice created by another
+/// process first leaves a gap.
+pub struct InvoiceGenerator {
+    orders: Arc<dyn OrderStore>,
+    invoices: Arc<dyn InvoiceStore>,
+    sequences: Arc<dyn EntityStore<Sequence>>,
+    link_service: Arc<dyn LinkService>,
+    config: InvoicingConfig,
+    // Keeps concurrent events for the same order from both linking its
+    // invoice
+    lock: Mutex<()>,
+}
+
+impl InvoiceGenerator {
+    pub fn new(
+        stores: &BillingStores,
+        link_service: Arc<dyn LinkService>,
+        config: InvoicingConfig,
+    ) -> Self {
+        Self {
+            orders: stores.orders_store.clone(),
+            invoices: stores.invoices_store.clone(),
+            sequences: stores.sequences_store.clone(),
+            link_service,
+            config,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Invoice a confirmed order
+    ///
+    /// Returns `None` when the order is not `confirmed` or already has an
+    /// invoice. An invoice left without its link, by a run interrupted in
+    /// between, is linked rather than created again.
+    pub async fn invoice_order(&self, order_id: &Uuid) -> anyhow::Result<Option<GeneratedInvoice>> {
+        let _guard = self.lock.lock().await;
+
+        let order = self.orders.get(order_id).await?;
+        if order.status != OrderStatus::Confirmed.as_str() {
+            return Ok(None);
+        }
+
+        let existing = self
+            .link_service
+            .find_by_source(order_id, Some("has_invoice"), Some("invoice"))
+            .await?;
+        if !existing.is_empty() {
+            return Ok(None);
+        }
+
+        let invoice = self.invoice_of(&order).await?;
+        let mut link = LinkEntity::new(
+            "has_invoice",
+            order.id,
+            invoice.id,
+            Some(serde_json::json!({
+                "created_by": "billing.invoicing",
+                "invoice_type": "standard"
+            })),
+        );
+        link.id = Uuid::new_v5(&order.id, b"has_invoice");
+        let link = self.link_service.create(link).await?;
+
+        Ok(Some(GeneratedInvoice { invoice, link }))
+    }
+
+    /// Invoice the confirmed orders that have no invoice yet, e.g. because
+    /// the process stopped before linking one
+    pub async fn recover(&self) -> anyhow::Result<Vec<GeneratedInvoice>> {
+        let mut generated = Vec::new();
+        for order in self.orders.list().await? {
+            if order.status == OrderStatus::Confirmed.as_str()
+                && let Some(invoice) = self.invoice_order(&order.id).await?
+            {
+                generated.push(invoice);
+            }
+        }
+        Ok(generated)
+    }
+
+    /// The invoice of `order`, created unless an earlier run did
+    async fn invoice_of(&self, order: &Order) -> anyhow::Result<Invoice> {
+        let id = invoice_id(&order.id);
+        match self.invoices.get(&id).await {
+            Ok(invoice) => return Ok(invoice),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut invoice = self.build_invoice(order).await?;
+        invoice.id = id;
+        match self.invoices.create(invoice).await {
+            Ok(invoice) => Ok(invoice),
+            // Created by another process in the meantime
+            Err(e) if e.kind() == ErrorKind::Conflict => Ok(self.invoices.get(&id).await?),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Listen for order confirmations on `event_bus` and invoice them
+    ///
+    /// The orders confirmed but left without an invoice are invoiced first
+    /// (see [`InvoiceGenerator::recover`]). The created invoices and links are
+    /// published back on the bus.
+    pub fn spawn(self: Arc<Self>, event_bus: EventBus) -> JoinHandle<()> {
+        let mut events = event_bus.subscribe();
+        tokio::spawn(async move {
+            match self.recover().await {
+                Ok(generated) => generated
+                    .iter()
+                    .for_each(|generated| publish(&event_bus, generated)),
+                Err(e) => eprintln!("Failed to invoice the orders left without one: {:?}", e),
+            }
+            loop {
+                let envelope = match events.recv().await {
+                    Ok(envelope) => envelope,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Invoicing skipped {} events (bus lagged)", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let Some(order_id) = confirmed_order_id(&envelope.event) else {
+                    continue;
+                };
+
+                match self.invoice_order(&order_id).await {
+                    Ok(Some(generated)) => publish(&event_bus, &generated),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Failed to invoice order {}: {:?}", order_id, e),
+                }
+            }
+        })
+    }
+
+    async fn build_invoice(&self, order: &Order) -> anyhow::Result<Invoice> {
+        let number = self.next_number().await?;
+        let due_date = (Utc::now() + Duration::days(self.config.payment_term_days))
+            .format("%Y-%m-%d")
+            .to_string();
+
+        Ok(Invoice::new(
+            format!("Invoice for {}", order.number),
+            "sent".to_string(),
+            number,
+            order.amount,
+            Some(due_date),
+            None,
+            order.owned_by,
+            Version::default(),
+        ))
+    }
+
+    /// Next number in the `{prefix}-NNNNNN` sequence
+    async fn next_number(&self) -> anyhow::Result<String> {
+        let prefix = format!("{}-", self.config.number_prefix);
+        let value = sequence::next_value(
+            self.sequences.as_ref(),
+            &self.config.number_prefix,
+            self.last_listed_number(&prefix),
+        )
+        .await?;
+        Ok(format!("{}{:06}", prefix, value))
+    }
+
+    /// The highest number of the invoices, for a sequence started on a
+    /// backend that already has some
+    async fn last_listed_number(&self, prefix: &str) -> anyhow::Result<u64> {
+        Ok(self
+            .invoices
+            .list()
+            .await?
+            .iter()
+            .filter_map(|invoice| invoice.number.strip_prefix(prefix)?.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0))
+    }
+}
+
+/// The id of the standard invoice of an order
+fn invoice_id(order_id: &Uuid) -> Uuid {
+    Uuid::new_v5(order_id, b"invoice")
+}
+
+/// Id of the order an event moved into `confirmed`, if any
+fn confirmed_order_id(event: &FrameworkEvent) -> Option<Uuid> {
+    match event {
+        FrameworkEvent::Entity(
+            EntityEvent::Created {
+                entity_type,
+                entity_id,
+                data,
+            }
+            | EntityEvent::Updated {
+                entity_type,
+                entity_id,
+                data,
+            },
+        ) if entity_type == "order" && data["status"] == OrderStatus::Confirmed.as_str() => {
+            Some(*entity_id)
+        }
+        _ => None,
+    }
+}
+
+fn publish(event_bus: &EventBus, generated: &GeneratedInvoice) {
+    if let Ok(data) = serde_json::to_value(&generated.invoice) {
+        event_bus.publish(FrameworkEvent::Entity(EntityEvent::Created {
+            entity_type: "invoice".to_string(),
+            entity_id: generated.invoice.id,
+            data,
+        }));
+    }
+    event_bus.publish(FrameworkEvent::Link(LinkEvent::Created {
+        link_type: generated.link.link_type.clone(),
+        link_id: generated.link.id,
+        source_id: generated.link.source_id,
+        target_id: generated.link.target_id,
+        metadata: generated.link.metadata.clone(),
+    }));
+}
@@ -0,0 +1,8 @@
+pub mod invoicing;
+pub mod order_totals;
+pub mod reconciliation;
+pub mod sequence;
+
+pub use invoicing::{GeneratedInvoice, InvoiceGenerator, InvoicingConfig};
+pub use order_totals::{OrderTotals, TotaledOrderStore};
+pub use reconciliation::{InvoiceSettlement, PaymentCoverage, PaymentReconciler, SETTLEMENT_EVENT};
@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::store::EntityStore;
+use common::{ListQuery, Money, Page, Version};
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent, LinkEvent};
+use this::prelude::{LinkService, Uuid};
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::entities::order::{Order, OrderStatus, OrderStore, OrderStoreError};
+use crate::entities::order_line::{OrderLineStore, OrderLineStoreError};
+use crate::module::BillingStores;
+
+/// Keeps the `amount` of pending orders equal to the sum of their lines
+///
+/// Lines are attached to an order with the `has_line` link, typically through
+/// `POST /orders/{id}/lines`. Once an order leaves `pending` its amount is
+/// frozen, since invoices are generated from it. All lines of an order must be
+/// in the same currency.
+///
+/// Every update of a pending order, its confirmation included, settles the
+/// amount in the same write through [`TotaledOrderStore`] (see
+/// `BillingModule::with_order_totals`). [`OrderTotals::spawn`] also follows
+/// line changes on the event bus, so that pending orders show their current
+/// amount between updates.
+pub struct OrderTotals {
+    orders: Arc<dyn OrderStore>,
+    order_lines: Arc<dyn OrderLineStore>,
+    link_service: Arc<dyn LinkService>,
+    lock: Mutex<()>,
+}
+
+impl OrderTotals {
+    pub fn new(stores: &BillingStores, link_service: Arc<dyn LinkService>) -> Self {
+        Self {
+            orders: stores.orders_store.clone(),
+            order_lines: stores.order_lines_store.clone(),
+            link_service,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Recompute the amount of a pending order from its `active` lines
+    ///
+    /// Returns the order when its amount changed.
+    pub async fn recompute(&self, order_id: &Uuid) -> anyhow::Result<Option<Order>> {
+        let _guard = self.lock.lock().await;
+
+        let mut order = self.orders.get(order_id).await?;
+        if order.status != OrderStatus::Pending.as_str() {
+            return Ok(None);
+        }
+
+        let amount = self.total(&order).await?;
+        if amount == order.amount {
+            return Ok(None);
+        }
+
+        order.amount = amount;
+        order.touch();
+        Ok(Some(self.orders.update(order).await?))
+    }
+
+    /// Sum of the `active` lines of `order`
+    pub async fn total(&self, order: &Order) -> anyhow::Result<Money> {
+        let mut totals = Vec::new();
+        for link in self
+            .link_service
+            .find_by_source(&order.id, Some("has_line"), Some("order_line"))
+            .await?
+        {
+            match self.order_lines.get(&link.target_id).await {
+                Ok(line) if line.status == "active" => totals.push(line.line_total),
+                Ok(_) | Err(OrderLineStoreError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // An order without lines keeps its currency
+        let currency = totals
+            .first()
+            .map(Money::currency)
+            .unwrap_or(order.amount.currency());
+        Ok(Money::sum(currency, totals)?)
+    }
+
+    /// Listen for `has_line` links and line changes on `event_bus` and
+    /// recompute the affected orders
+    ///
+    /// Orders whose amount changed are published back on the bus.
+    pub fn spawn(self: Arc<Self>, event_bus: EventBus) -> JoinHandle<()> {
+        let mut events = event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let envelope = match events.recv().await {
+                    Ok(envelope) => envelope,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Order totals skipped {} events (bus lagged)", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let order_ids = match self.affected_orders(&envelope.event).await {
+                    Ok(ids) => ids,
+                    Err(e) => {
+                        eprintln!("Failed to resolve orders for line event: {:?}", e);
+                        continue;
+                    }
+                };
+
+                for order_id in order_ids {
+                    match self.recompute(&order_id).await {
+                        Ok(Some(order)) => publish(&event_bus, &order),
+                        Ok(None) => {}
+                        Err(e) => eprintln!("Failed to total order {}: {:?}", order_id, e),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Orders whose total may have changed because of `event`
+    async fn affected_orders(&self, event: &FrameworkEvent) -> anyhow::Result<Vec<Uuid>> {
+        match event {
+            FrameworkEvent::Link(
+                LinkEvent::Created {
+                    link_type,
+                    source_id,
+                    ..
+                }
+                | LinkEvent::Deleted {
+                    link_type,
+                    source_id,
+                    ..
+                },
+            ) if link_type == "has_line" => Ok(vec![*source_id]),
+            FrameworkEvent::Entity(
+                EntityEvent::Updated {
+                    entity_type,
+                    entity_id,
+                    ..
+                }
+                | EntityEvent::Deleted {
+                    entity_type,
+                    entity_id,
+                },
+            ) if entity_type == "order_line" => Ok(self
+                .link_service
+                .find_by_target(entity_id, Some("has_line"), Some("order"))
+                .await?
+                .into_iter()
+                .map(|link| link.source_id)
+                .collect()),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+/// Order store settling the amount of pending orders from their lines as
+/// they are updated
+///
+/// The amount is written together with the update, so an order is confirmed
+/// with the total of the lines it has at that moment, whether or not
+/// [`OrderTotals::spawn`] runs.
+pub struct TotaledOrderStore {
+    inner: Arc<dyn OrderStore>,
+    totals: OrderTotals,
+}
+
+impl TotaledOrderStore {
+    pub fn new(stores: &BillingStores, link_service: Arc<dyn LinkService>) -> Self {
+        Self {
+            inner: stores.orders_store.clone(),
+            totals: OrderTotals::new(stores, link_service),
+        }
+    }
+}
+
+#[async_trait]
+impl EntityStore<Order> for TotaledOrderStore {
+    async fn create(&self, order: Order) -> Result<Order, OrderStoreError> {
+        self.inner.create(order).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Order, OrderStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, mut order: Order) -> Result<Order, OrderStoreError> {
+        let current = self.inner.get(&order.id).await?;
+        if current.status == OrderStatus::Pending.as_str() {
+            order.amount = self.totals.total(&current).await?;
+        }
+        self.inner.update(order).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), OrderStoreError> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), OrderStoreError> {
+        self.inner.delete_at(id, expected).await
+    }
+
+    async fn list(&self) -> Result<Vec<Order>, OrderStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<Order>, OrderStoreError> {
+        self.inner.query(query).await
+    }
+}
+
+common::impl_entity_json!(TotaledOrderStore, Order);
+
+fn publish(event_bus: &EventBus, order: &Order) {
+    if let Ok(data) = serde_json::to_value(order) {
+        event_bus.publish(FrameworkEvent::Entity(EntityEvent::Updated {
+            entity_type: "order".to_string(),
+            entity_id: order.id,
+            data,
+        }));
+    }
+}
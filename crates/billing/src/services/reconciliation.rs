@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use common::{Currency, Money};
+use serde::Serialize;
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent, LinkEvent};
+use this::prelude::{LinkService, Uuid};
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::entities::invoice::{Invoice, InvoiceStore};
+use crate::entities::order::{Order, OrderStatus, OrderStore};
+use crate::entities::payment::PaymentStore;
+use crate::module::BillingStores;
+
+/// Entity type of the events published for the invoices that the completed
+/// payments cover only partially or exceed, with an [`InvoiceSettlement`] as
+/// data
+pub const SETTLEMENT_EVENT: &str = "invoice_settlement";
+
+/// How the completed payments of an invoice compare to its amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentCoverage {
+    Unpaid,
+    Partial,
+    Exact,
+    Overpaid,
+}
+
+impl PaymentCoverage {
+    pub fn is_settled(&self) -> bool {
+        matches!(self, PaymentCoverage::Exact | PaymentCoverage::Overpaid)
+    }
+}
+
+/// Outcome of reconciling one invoice against its `payment` links
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceSettlement {
+    pub invoice: Invoice,
+    /// Sum of the linked payments whose status is `completed`
+    pub paid_amount: Money,
+    /// `invoice.amount - paid_amount`; negative when overpaid
+    pub outstanding: Money,
+    pub coverage: PaymentCoverage,
+    /// Whether this run moved the invoice to `paid`
+    pub marked_paid: bool,
+    /// Orders this run moved to `paid` because all their invoices are settled
+    pub paid_orders: Vec<Order>,
+}
+
+/// Settles invoices and orders from completed payments
+///
+/// An invoice becomes `paid` (with `paid_at` set) once the completed payments
+/// linked to it through `payment` cover its amount. An order becomes `paid`
+/// once every invoice linked through `has_invoice` is paid. Partial payments
+/// and overpayments are returned in the [`InvoiceSettlement`], which the event
+/// listener publishes as a [`SETTLEMENT_EVENT`]. Amounts are summed exactly; a completed payment in another
+/// currency than its invoice makes reconciliation fail for that invoice.
+pub struct PaymentReconciler {
+    orders: Arc<dyn OrderStore>,
+    invoices: Arc<dyn InvoiceStore>,
+    payments: Arc<dyn PaymentStore>,
+    link_service: Arc<dyn LinkService>,
+    lock: Mutex<()>,
+}
+
+impl PaymentReconciler {
+    pub fn new(stores: &BillingStores, link_service: Arc<dyn LinkService>) -> Self {
+        Self {
+            orders: stores.orders_store.clone(),
+            invoices: stores.invoices_store.clone(),
+            payments: stores.payments_store.clone(),
+            link_service,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Recompute the payment coverage of an invoice and settle it if covered
+    pub async fn reconcile_invoice(&self, invoice_id: &Uuid) -> anyhow::Result<InvoiceSettlement> {
+        let _guard = self.lock.lock().await;
+
+        let mut invoice = self.invoices.get(invoice_id).await?;
+        let paid_amount = self
+            .completed_payments_total(invoice_id, invoice.amount.currency())
+            .await?;
+        let coverage = coverage(invoice.amount, paid_amount);
+        let outstanding = invoice.amount.checked_sub(paid_amount)?;
+
+        let mut marked_paid = false;
+        let mut paid_orders = Vec::new();
+        if coverage.is_settled() && !matches!(invoice.status.as_str(), "paid" | "cancelled") {
+            invoice.status = "paid".to_string();
+            invoice.paid_at = Some(Utc::now().format("%Y-%m-%d").to_string());
+            invoice.touch();
+            invoice = self.invoices.update(invoice).await?;
+            marked_paid = true;
+
+            for link in self
+                .link_service
+                .find_by_target(invoice_id, Some("has_invoice"), Some("order"))
+                .await?
+            {
+                if let Some(order) = self.settle_order(&link.source_id).await? {
+                    paid_orders.push(order);
+                }
+            }
+        }
+
+        Ok(InvoiceSettlement {
+            outstanding,
+            invoice,
+            paid_amount,
+            coverage,
+            marked_paid,
+            paid_orders,
+        })
+    }
+
+    /// Listen for payments and `payment` links on `event_bus` and reconcile
+    /// the invoices they belong to, as well as new invoices
+    ///
+    /// Invoices and orders settled by the listener are published back on the
+    /// bus, and so are the partially paid and overpaid invoices.
+    pub fn spawn(self: Arc<Self>, event_bus: EventBus) -> JoinHandle<()> {
+        let mut events = event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let envelope = match events.recv().await {
+                    Ok(envelope) => envelope,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Reconciliation skipped {} events (bus lagged)", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let invoice_ids = match self.affected_invoices(&envelope.event).await {
+                    Ok(ids) => ids,
+                    Err(e) => {
+                        eprintln!("Failed to resolve invoices for payment event: {:?}", e);
+                        continue;
+                    }
+                };
+
+                for invoice_id in invoice_ids {
+                    match self.reconcile_invoice(&invoice_id).await {
+                        Ok(settlement) => publish(&event_bus, &settlement),
+                        Err(e) => eprintln!("Failed to reconcile invoice {}: {:?}", invoice_id, e),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Move an order to `paid` once all of its invoices are paid
+    async fn settle_order(&self, order_id: &Uuid) -> anyhow::Result<Option<Order>> {
+        let order = self.orders.get(order_id).await?;
+        if order.status != OrderStatus::Confirmed.as_str() {
+            return Ok(None);
+        }
+
+        for link in self
+            .link_service
+            .find_by_source(order_id, Some("has_invoice"), Some("invoice"))
+            .await?
+        {
+            if self.invoices.get(&link.target_id).await?.status != "paid" {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(
+            self.orders.transition(order_id, OrderStatus::Paid).await?,
+        ))
+    }
+
+    async fn completed_payments_total(
+        &self,
+        invoice_id: &Uuid,
+        currency: Currency,
+    ) -> anyhow::Result<Money> {
+        let mut total = Money::zero(currency);
+        for link in self
+            .link_service
+            .find_by_source(invoice_id, Some("payment"), Some("payment"))
+            .await?
+        {
+            let payment = self.payments.get(&link.target_id).await?;
+            if payment.status == "completed" {
+                total = total.checked_add(payment.amount)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Invoices whose coverage may have changed because of `event`
+    async fn affected_invoices(&self, event: &FrameworkEvent) -> anyhow::Result<Vec<Uuid>> {
+        match event {
+            FrameworkEvent::Link(LinkEvent::Created {
+                link_type,
+                source_id,
+                ..
+            }) if link_type == "payment" => Ok(vec![*source_id]),
+            // Settles the invoices with nothing to pay
+            FrameworkEvent::Entity(EntityEvent::Created {
+                entity_type,
+                entity_id,
+                ..
+            }) if entity_type == "invoice" => Ok(vec![*entity_id]),
+            FrameworkEvent::Entity(
+                EntityEvent::Created {
+                    entity_type,
+                    entity_id,
+                    data,
+                }
+                | EntityEvent::Updated {
+                    entity_type,
+                    entity_id,
+                    data,
+                },
+            ) if entity_type == "payment" && data["status"] == "completed" => Ok(self
+                .link_service
+                .find_by_target(entity_id, Some("payment"), Some("invoice"))
+                .await?
+                .into_iter()
+                .map(|link| link.source_id)
+                .collect()),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+/// Both amounts are in the invoice currency
+fn coverage(amount: Money, paid: Money) -> PaymentCoverage {
+    let (amount, paid) = (amount.minor_units(), paid.minor_units());
+    if paid > amount {
+        PaymentCoverage::Overpaid
+    } else if paid == amount {
+        PaymentCoverage::Exact
+    } else if paid <= 0 {
+        PaymentCoverage::Unpaid
+    } else {
+        PaymentCoverage::Partial
+    }
+}
+
+fn publish(event_bus: &EventBus, settlement: &InvoiceSettlement) {
+    if matches!(
+        settlement.coverage,
+        PaymentCoverage::Partial | PaymentCoverage::Overpaid
+    ) && let Ok(data) = serde_json::to_value(settlement)
+    {
+        event_bus.publish(FrameworkEvent::Entity(EntityEvent::Updated {
+            entity_type: SETTLEMENT_EVENT.to_string(),
+            entity_id: settlement.invoice.id,
+            data,
+        }));
+    }
+    if settlement.marked_paid
+        && let Ok(data) = serde_json::to_value(&settlement.invoice)
+    {
+        event_bus.publish(FrameworkEvent::Entity(EntityEvent::Updated {
+            entity_type: "invoice".to_string(),
+            entity_id: settlement.invoice.id,
+            data,
+        }));
+    }
+    for order in &settlement.paid_orders {
+        if let Ok(data) = serde_json::to_value(order) {
+            event_bus.publish(FrameworkEvent::Entity(EntityEvent::Updated {
+                entity_type: "order".to_string(),
+                entity_id: order.id,
+                data,
+            }));
+        }
+    }
+}
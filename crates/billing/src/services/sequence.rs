@@ -0,0 +1,91 @@
+use std::future::Future;
+
+use chrono::Utc;
+use common::store::{EntityStore, StoreError, StoredEntity};
+use common::{ValidationError, Version};
+use this::prelude::*;
+
+/// How many times [`next_value`] reads and tries again when another process
+/// took a number in between
+const ATTEMPTS: usize = 10;
+
+/// Namespace of the ids of the sequences, derived from their names
+const NAMESPACE: Uuid = Uuid::from_u128(0x5d1c_61f3_4a4e_4d2b_9a0e_2b51_8c3f_7e10);
+
+impl_data_entity!(
+    Sequence,
+    "sequence",
+    ["name"],
+    {
+        value: u64,
+        version: Version,
+    }
+);
+
+common::impl_versioned!(Sequence);
+
+/// Numbers are taken with [`next_value`], never created through the API
+impl StoredEntity for Sequence {
+    type Error = StoreError;
+    const NAME: &'static str = "Sequence";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name"];
+
+    fn from_json(_entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        Err(ValidationError::field(
+            "$",
+            "sequences are not created through the API",
+        ))
+    }
+}
+
+/// Take the next value of the sequence `name`
+///
+/// The value is written with the conditional update of the store, so that
+/// every process sharing the backend takes distinct values. The first call
+/// on a backend creates the sequence after the value `last` resolves to,
+/// e.g. the highest number already handed out before the sequence existed.
+pub async fn next_value(
+    store: &dyn EntityStore<Sequence>,
+    name: &str,
+    last: impl Future<Output = anyhow::Result<u64>>,
+) -> anyhow::Result<u64> {
+    let id = Uuid::new_v5(&NAMESPACE, name.as_bytes());
+    let mut last = Some(last);
+    for _ in 0..ATTEMPTS {
+        let mut sequence = match store.get(&id).await {
+            Ok(sequence) => sequence,
+            Err(StoreError::NotFound(_)) => {
+                let start = match last.take() {
+                    Some(last) => last.await? + 1,
+                    None => 1,
+                };
+                let mut sequence = Sequence::new(
+                    name.to_string(),
+                    "active".to_string(),
+                    start,
+                    Version::default(),
+                );
+                sequence.id = id;
+                match store.create(sequence).await {
+                    Ok(_) => return Ok(start),
+                    // Another process created it first
+                    Err(StoreError::Conflict(_)) => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+        sequence.value += 1;
+        sequence.updated_at = Utc::now();
+        match store.update(sequence).await {
+            Ok(sequence) => return Ok(sequence.value),
+            Err(StoreError::VersionMismatch(_)) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(StoreError::Conflict(format!(
+        "sequence '{}' kept changing, gave up after {} attempts",
+        name, ATTEMPTS
+    ))
+    .into())
+}
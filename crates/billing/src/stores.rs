@@ -1,72 +1,131 @@
 use std::sync::Arc;
 
 use crate::module::BillingStores;
+use crate::services::sequence::Sequence;
+use common::store::InMemoryEntityStore;
 
 // Import stores from entity modules
 use crate::entities::invoice::InMemoryInvoiceStore;
+use crate::entities::invoice_line::InMemoryInvoiceLineStore;
 use crate::entities::order::InMemoryOrderStore;
+use crate::entities::order_line::InMemoryOrderLineStore;
 use crate::entities::payment::InMemoryPaymentStore;
 
 #[cfg(feature = "dynamodb")]
 use crate::entities::invoice::InvoiceDynamoDBStore;
 #[cfg(feature = "dynamodb")]
+use crate::entities::invoice_line::InvoiceLineDynamoDBStore;
+#[cfg(feature = "dynamodb")]
 use crate::entities::order::OrderDynamoDBStore;
 #[cfg(feature = "dynamodb")]
+use crate::entities::order_line::OrderLineDynamoDBStore;
+#[cfg(feature = "dynamodb")]
 use crate::entities::payment::PaymentDynamoDBStore;
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::Client as DynamoDBClient;
+#[cfg(feature = "dynamodb")]
+use common::store::DynamoDBStore;
 
 #[cfg(feature = "postgres")]
 use crate::entities::invoice::InvoicePostgresStore;
 #[cfg(feature = "postgres")]
+use crate::entities::invoice_line::InvoiceLinePostgresStore;
+#[cfg(feature = "postgres")]
 use crate::entities::order::OrderPostgresStore;
 #[cfg(feature = "postgres")]
+use crate::entities::order_line::OrderLinePostgresStore;
+#[cfg(feature = "postgres")]
 use crate::entities::payment::PaymentPostgresStore;
 #[cfg(feature = "postgres")]
+use common::store::PostgresStore;
+#[cfg(feature = "postgres")]
 use sqlx::PgPool;
 
 #[cfg(feature = "mongodb_backend")]
 use crate::entities::invoice::InvoiceMongoStore;
 #[cfg(feature = "mongodb_backend")]
+use crate::entities::invoice_line::InvoiceLineMongoStore;
+#[cfg(feature = "mongodb_backend")]
 use crate::entities::order::OrderMongoStore;
 #[cfg(feature = "mongodb_backend")]
+use crate::entities::order_line::OrderLineMongoStore;
+#[cfg(feature = "mongodb_backend")]
 use crate::entities::payment::PaymentMongoStore;
 #[cfg(feature = "mongodb_backend")]
+use common::store::MongoStore;
+#[cfg(feature = "mongodb_backend")]
 use mongodb::Database as MongoDatabase;
 
 #[cfg(feature = "neo4j")]
 use crate::entities::invoice::InvoiceNeo4jStore;
 #[cfg(feature = "neo4j")]
+use crate::entities::invoice_line::InvoiceLineNeo4jStore;
+#[cfg(feature = "neo4j")]
 use crate::entities::order::OrderNeo4jStore;
 #[cfg(feature = "neo4j")]
+use crate::entities::order_line::OrderLineNeo4jStore;
+#[cfg(feature = "neo4j")]
 use crate::entities::payment::PaymentNeo4jStore;
 #[cfg(feature = "neo4j")]
+use common::store::Neo4jStore;
+#[cfg(feature = "neo4j")]
 use neo4rs::Graph;
 
 #[cfg(feature = "scylladb")]
 use crate::entities::invoice::InvoiceScyllaStore;
 #[cfg(feature = "scylladb")]
+use crate::entities::invoice_line::InvoiceLineScyllaStore;
+#[cfg(feature = "scylladb")]
 use crate::entities::order::OrderScyllaStore;
 #[cfg(feature = "scylladb")]
+use crate::entities::order_line::OrderLineScyllaStore;
+#[cfg(feature = "scylladb")]
 use crate::entities::payment::PaymentScyllaStore;
 #[cfg(feature = "scylladb")]
+use common::store::ScyllaStore;
+#[cfg(feature = "scylladb")]
 use scylla::client::session::Session;
 
 #[cfg(feature = "mysql")]
 use crate::entities::invoice::InvoiceMysqlStore;
 #[cfg(feature = "mysql")]
+use crate::entities::invoice_line::InvoiceLineMysqlStore;
+#[cfg(feature = "mysql")]
 use crate::entities::order::OrderMysqlStore;
 #[cfg(feature = "mysql")]
+use crate::entities::order_line::OrderLineMysqlStore;
+#[cfg(feature = "mysql")]
 use crate::entities::payment::PaymentMysqlStore;
 #[cfg(feature = "mysql")]
+use common::store::MysqlStore;
+#[cfg(feature = "mysql")]
 use sqlx::MySqlPool;
 
 #[cfg(feature = "lmdb")]
 use crate::entities::invoice::InvoiceLmdbStore;
 #[cfg(feature = "lmdb")]
+use crate::entities::invoice_line::InvoiceLineLmdbStore;
+#[cfg(feature = "lmdb")]
 use crate::entities::order::OrderLmdbStore;
 #[cfg(feature = "lmdb")]
+use crate::entities::order_line::OrderLineLmdbStore;
+#[cfg(feature = "lmdb")]
 use crate::entities::payment::PaymentLmdbStore;
+#[cfg(feature = "lmdb")]
+use common::store::LmdbStore;
+
+#[cfg(feature = "sqlite")]
+use crate::entities::invoice::InvoiceSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::invoice_line::InvoiceLineSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::order::OrderSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::order_line::OrderLineSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::payment::PaymentSqliteStore;
+#[cfg(feature = "sqlite")]
+use common::store::SqliteStore;
 
 // ============================================================================
 // Store Factories
@@ -76,38 +135,89 @@ impl BillingStores {
     /// Create stores with in-memory implementations
     pub fn new_in_memory() -> Self {
         let orders = Arc::new(InMemoryOrderStore::default());
+        let order_lines = Arc::new(InMemoryOrderLineStore::default());
         let invoices = Arc::new(InMemoryInvoiceStore::default());
+        let invoice_lines = Arc::new(InMemoryInvoiceLineStore::default());
         let payments = Arc::new(InMemoryPaymentStore::default());
+        let sequences = Arc::new(InMemoryEntityStore::<Sequence>::default());
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
+    /// Create in-memory stores journaled in `dir`, recovering what they held
+    /// when last stopped (see `common::journal`)
+    pub fn new_journal(dir: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let orders = Arc::new(InMemoryOrderStore::open(dir)?);
+        let order_lines = Arc::new(InMemoryOrderLineStore::open(dir)?);
+        let invoices = Arc::new(InMemoryInvoiceStore::open(dir)?);
+        let invoice_lines = Arc::new(InMemoryInvoiceLineStore::open(dir)?);
+        let payments = Arc::new(InMemoryPaymentStore::open(dir)?);
+        let sequences = Arc::new(InMemoryEntityStore::<Sequence>::open(dir)?);
+
+        Ok(Self {
+            orders_store: orders.clone(),
+            orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
+            invoices_store: invoices.clone(),
+            invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
+            payments_store: payments.clone(),
+            payments_entity: payments,
+            sequences_store: sequences,
+        })
+    }
+
     #[cfg(feature = "dynamodb")]
     /// Create stores with DynamoDB implementations
     pub fn new_dynamodb(
         client: DynamoDBClient,
         orders_table: String,
+        order_lines_table: String,
         invoices_table: String,
+        invoice_lines_table: String,
         payments_table: String,
+        sequences_table: String,
     ) -> Self {
         let orders = Arc::new(OrderDynamoDBStore::new(client.clone(), orders_table));
+        let order_lines = Arc::new(OrderLineDynamoDBStore::new(
+            client.clone(),
+            order_lines_table,
+        ));
         let invoices = Arc::new(InvoiceDynamoDBStore::new(client.clone(), invoices_table));
-        let payments = Arc::new(PaymentDynamoDBStore::new(client, payments_table));
+        let invoice_lines = Arc::new(InvoiceLineDynamoDBStore::new(
+            client.clone(),
+            invoice_lines_table,
+        ));
+        let payments = Arc::new(PaymentDynamoDBStore::new(client.clone(), payments_table));
+        let sequences = Arc::new(DynamoDBStore::<Sequence>::new(client, sequences_table));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -115,16 +225,24 @@ impl BillingStores {
     /// Create stores with PostgreSQL implementations
     pub fn new_postgres(pool: PgPool) -> Self {
         let orders = Arc::new(OrderPostgresStore::new(pool.clone()));
+        let order_lines = Arc::new(OrderLinePostgresStore::new(pool.clone()));
         let invoices = Arc::new(InvoicePostgresStore::new(pool.clone()));
-        let payments = Arc::new(PaymentPostgresStore::new(pool));
+        let invoice_lines = Arc::new(InvoiceLinePostgresStore::new(pool.clone()));
+        let payments = Arc::new(PaymentPostgresStore::new(pool.clone()));
+        let sequences = Arc::new(PostgresStore::<Sequence>::new(pool));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -132,16 +250,24 @@ impl BillingStores {
     /// Create stores with MongoDB implementations
     pub fn new_mongodb(database: MongoDatabase) -> Self {
         let orders = Arc::new(OrderMongoStore::new(database.clone()));
+        let order_lines = Arc::new(OrderLineMongoStore::new(database.clone()));
         let invoices = Arc::new(InvoiceMongoStore::new(database.clone()));
-        let payments = Arc::new(PaymentMongoStore::new(database));
+        let invoice_lines = Arc::new(InvoiceLineMongoStore::new(database.clone()));
+        let payments = Arc::new(PaymentMongoStore::new(database.clone()));
+        let sequences = Arc::new(MongoStore::<Sequence>::new(database));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -149,16 +275,24 @@ impl BillingStores {
     /// Create stores with Neo4j implementations
     pub fn new_neo4j(graph: Graph) -> Self {
         let orders = Arc::new(OrderNeo4jStore::new(graph.clone()));
+        let order_lines = Arc::new(OrderLineNeo4jStore::new(graph.clone()));
         let invoices = Arc::new(InvoiceNeo4jStore::new(graph.clone()));
-        let payments = Arc::new(PaymentNeo4jStore::new(graph));
+        let invoice_lines = Arc::new(InvoiceLineNeo4jStore::new(graph.clone()));
+        let payments = Arc::new(PaymentNeo4jStore::new(graph.clone()));
+        let sequences = Arc::new(Neo4jStore::<Sequence>::new(graph));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -167,16 +301,24 @@ impl BillingStores {
     pub fn new_scylladb(session: Arc<Session>, keyspace: impl Into<String>) -> Self {
         let ks: String = keyspace.into();
         let orders = Arc::new(OrderScyllaStore::new(session.clone(), ks.clone()));
+        let order_lines = Arc::new(OrderLineScyllaStore::new(session.clone(), ks.clone()));
         let invoices = Arc::new(InvoiceScyllaStore::new(session.clone(), ks.clone()));
-        let payments = Arc::new(PaymentScyllaStore::new(session, ks));
+        let invoice_lines = Arc::new(InvoiceLineScyllaStore::new(session.clone(), ks.clone()));
+        let payments = Arc::new(PaymentScyllaStore::new(session.clone(), ks.clone()));
+        let sequences = Arc::new(ScyllaStore::<Sequence>::new(session, ks));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -184,16 +326,24 @@ impl BillingStores {
     /// Create stores with MySQL implementations
     pub fn new_mysql(pool: MySqlPool) -> Self {
         let orders = Arc::new(OrderMysqlStore::new(pool.clone()));
+        let order_lines = Arc::new(OrderLineMysqlStore::new(pool.clone()));
         let invoices = Arc::new(InvoiceMysqlStore::new(pool.clone()));
-        let payments = Arc::new(PaymentMysqlStore::new(pool));
+        let invoice_lines = Arc::new(InvoiceLineMysqlStore::new(pool.clone()));
+        let payments = Arc::new(PaymentMysqlStore::new(pool.clone()));
+        let sequences = Arc::new(MysqlStore::<Sequence>::new(pool));
 
         Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         }
     }
 
@@ -201,16 +351,51 @@ impl BillingStores {
     /// Create stores with LMDB implementations
     pub fn new_lmdb(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
         let orders = Arc::new(OrderLmdbStore::open(path.as_ref())?);
+        let order_lines = Arc::new(OrderLineLmdbStore::open(path.as_ref())?);
         let invoices = Arc::new(InvoiceLmdbStore::open(path.as_ref())?);
+        let invoice_lines = Arc::new(InvoiceLineLmdbStore::open(path.as_ref())?);
         let payments = Arc::new(PaymentLmdbStore::open(path.as_ref())?);
+        let sequences = Arc::new(LmdbStore::<Sequence>::open(path.as_ref())?);
+
+        Ok(Self {
+            orders_store: orders.clone(),
+            orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
+            invoices_store: invoices.clone(),
+            invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
+            payments_store: payments.clone(),
+            payments_entity: payments,
+            sequences_store: sequences,
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    /// Create stores with SQLite implementations, in the database file at
+    /// `path`
+    pub async fn new_sqlite(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let pool = common::sqlite::connect(path).await?;
+        let orders = Arc::new(OrderSqliteStore::new(pool.clone()));
+        let order_lines = Arc::new(OrderLineSqliteStore::new(pool.clone()));
+        let invoices = Arc::new(InvoiceSqliteStore::new(pool.clone()));
+        let invoice_lines = Arc::new(InvoiceLineSqliteStore::new(pool.clone()));
+        let payments = Arc::new(PaymentSqliteStore::new(pool.clone()));
+        let sequences = Arc::new(SqliteStore::<Sequence>::new(pool));
 
         Ok(Self {
             orders_store: orders.clone(),
             orders_entity: orders,
+            order_lines_store: order_lines.clone(),
+            order_lines_entity: order_lines,
             invoices_store: invoices.clone(),
             invoices_entity: invoices,
+            invoice_lines_store: invoice_lines.clone(),
+            invoice_lines_entity: invoice_lines,
             payments_store: payments.clone(),
             payments_entity: payments,
+            sequences_store: sequences,
         })
     }
 }
@@ -0,0 +1,143 @@
+//! Invoices generated for confirmed orders, on in-memory stores
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use billing::entities::invoice::Invoice;
+use billing::entities::order::Order;
+use billing::module::BillingStores;
+use billing::services::{InvoiceGenerator, InvoicingConfig};
+use common::{Currency, Money, Version};
+use this::prelude::LinkService;
+use this::storage::InMemoryLinkService;
+use uuid::Uuid;
+
+fn generator(stores: &BillingStores, links: &Arc<InMemoryLinkService>) -> InvoiceGenerator {
+    InvoiceGenerator::new(stores, links.clone(), InvoicingConfig::default())
+}
+
+async fn confirmed(stores: &BillingStores, name: &str) -> Order {
+    let order = Order::new(
+        name.into(),
+        "confirmed".into(),
+        format!("ORD-{}", Uuid::new_v4().simple()),
+        Money::from_minor(12_000, Currency::EUR),
+        None,
+        None,
+        None,
+        Version::default(),
+    );
+    stores
+        .orders_store
+        .create(order)
+        .await
+        .expect("create order")
+}
+
+#[tokio::test]
+async fn an_order_is_invoiced_once() {
+    let stores = BillingStores::new_in_memory();
+    let links = Arc::new(InMemoryLinkService::new());
+    let order = confirmed(&stores, "Once").await;
+
+    let generated = generator(&stores, &links)
+        .invoice_order(&order.id)
+        .await
+        .unwrap()
+        .expect("an invoice");
+    let again = generator(&stores, &links)
+        .invoice_order(&order.id)
+        .await
+        .unwrap();
+
+    assert!(again.is_none());
+    assert_eq!(generated.invoice.number, "INV-000001");
+    assert_eq!(generated.invoice.amount, order.amount);
+    assert_eq!(generated.link.source_id, order.id);
+    assert_eq!(generated.link.target_id, generated.invoice.id);
+    assert_eq!(stores.invoices_store.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn generators_sharing_a_backend_take_distinct_numbers() {
+    let stores = BillingStores::new_in_memory();
+    let links = Arc::new(InMemoryLinkService::new());
+    let mut orders = Vec::new();
+    for i in 0..20 {
+        orders.push(confirmed(&stores, &format!("Order {}", i)).await);
+    }
+
+    // One generator per order stands for as many processes: they share the
+    // stores but not the lock of a generator
+    let tasks = orders.iter().map(|order| {
+        let generator = generator(&stores, &links);
+        let id = order.id;
+        tokio::spawn(async move { generator.invoice_order(&id).await })
+    });
+    let mut numbers = HashSet::new();
+    for task in tasks {
+        let generated = task.await.unwrap().unwrap().expect("an invoice");
+        numbers.insert(generated.invoice.number);
+    }
+
+    assert_eq!(numbers.len(), orders.len());
+}
+
+#[tokio::test]
+async fn numbering_continues_after_the_existing_invoices() {
+    let stores = BillingStores::new_in_memory();
+    let links = Arc::new(InMemoryLinkService::new());
+    let existing = Invoice::new(
+        "Imported".into(),
+        "sent".into(),
+        "INV-000041".into(),
+        Money::from_minor(100, Currency::EUR),
+        None,
+        None,
+        None,
+        Version::default(),
+    );
+    stores.invoices_store.create(existing).await.unwrap();
+    let order = confirmed(&stores, "Next").await;
+
+    let generated = generator(&stores, &links)
+        .invoice_order(&order.id)
+        .await
+        .unwrap()
+        .expect("an invoice");
+
+    assert_eq!(generated.invoice.number, "INV-000042");
+}
+
+#[tokio::test]
+async fn recovery_links_an_invoice_left_without_its_link() {
+    let stores = BillingStores::new_in_memory();
+    let links = Arc::new(InMemoryLinkService::new());
+    let order = confirmed(&stores, "Interrupted").await;
+    let first = generator(&stores, &links)
+        .invoice_order(&order.id)
+        .await
+        .unwrap()
+        .expect("an invoice");
+    // As if the process had stopped right after creating the invoice
+    links.delete(&first.link.id).await.unwrap();
+
+    let recovered = generator(&stores, &links).recover().await.unwrap();
+
+    assert_eq!(recovered.len(), 1);
+    assert_eq!(recovered[0].invoice.id, first.invoice.id);
+    assert_eq!(recovered[0].invoice.number, first.invoice.number);
+    assert_eq!(stores.invoices_store.list().await.unwrap().len(), 1);
+    let linked = links
+        .find_by_source(&order.id, Some("has_invoice"), Some("invoice"))
+        .await
+        .unwrap();
+    assert_eq!(linked.len(), 1);
+    assert!(
+        generator(&stores, &links)
+            .recover()
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,189 @@
+//! The REST routes of orders, on an in-memory store
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use billing::entities::order::descriptor::OrderDescriptor;
+use billing::entities::order::{InMemoryOrderStore, Order};
+use common::store::EntityStore;
+use common::{Currency, Money, Version};
+use serde_json::{Value, json};
+use this::server::entity_registry::EntityDescriptor;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn routes(store: &Arc<InMemoryOrderStore>) -> Router {
+    OrderDescriptor::new_with_creator(store.clone(), store.clone()).build_routes()
+}
+
+async fn created(store: &InMemoryOrderStore, name: &str) -> Order {
+    let order = Order::new(
+        name.into(),
+        "pending".into(),
+        format!("ORD-{}", Uuid::new_v4().simple()),
+        Money::zero(Currency::EUR),
+        None,
+        None,
+        None,
+        Version::default(),
+    );
+    store.create(order).await.expect("create order")
+}
+
+async fn send(routes: Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = routes.oneshot(request).await.expect("response");
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn put(id: Uuid, if_match: &str, body: &Value) -> Request<Body> {
+    Request::put(format!("/orders/{}", id))
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::IF_MATCH, if_match)
+        .body(Body::from(body.to_string()))
+        .expect("request")
+}
+
+#[tokio::test]
+async fn put_writes_the_order_of_the_url() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Before").await;
+    let mut body = serde_json::to_value(&order).unwrap();
+    body["name"] = json!("After");
+
+    let (status, updated) = send(routes(&store), put(order.id, "\"0\"", &body)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["name"], "After");
+    assert_eq!(store.get(&order.id).await.unwrap().version, Version::new(1));
+}
+
+#[tokio::test]
+async fn put_with_another_id_in_the_body_is_rejected() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let url_order = created(&store, "At the URL").await;
+    let body_order = created(&store, "In the body").await;
+    let mut body = serde_json::to_value(&body_order).unwrap();
+    body["name"] = json!("Overwritten");
+
+    // The tag is current for the order of the URL, and would pass for the
+    // order of the body too
+    let (status, problem) = send(routes(&store), put(url_order.id, "\"0\"", &body)).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(problem["errors"]["id"].is_array(), "{}", problem);
+    for order in [&url_order, &body_order] {
+        let stored = store.get(&order.id).await.unwrap();
+        assert_eq!(stored.name, order.name);
+        assert_eq!(stored.version, Version::default());
+    }
+}
+
+#[tokio::test]
+async fn put_with_a_stale_tag_is_rejected() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Before").await;
+    let body = serde_json::to_value(&order).unwrap();
+
+    let (status, problem) = send(routes(&store), put(order.id, "\"7\"", &body)).await;
+
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(problem["current"], 0);
+}
+
+fn post(path: String) -> Request<Body> {
+    Request::post(path).body(Body::empty()).expect("request")
+}
+
+fn patch(id: Uuid, body: &Value) -> Request<Body> {
+    Request::patch(format!("/orders/{}", id))
+        .header(header::CONTENT_TYPE, "application/merge-patch+json")
+        .body(Body::from(body.to_string()))
+        .expect("request")
+}
+
+#[tokio::test]
+async fn orders_move_through_the_transition_routes() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Lifecycle").await;
+
+    let (status, confirmed) = send(
+        routes(&store),
+        post(format!("/orders/{}/confirm", order.id)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(confirmed["status"], "confirmed");
+
+    let (status, cancelled) =
+        send(routes(&store), post(format!("/orders/{}/cancel", order.id))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cancelled["status"], "cancelled");
+
+    let (status, problem) = send(
+        routes(&store),
+        post(format!("/orders/{}/confirm", order.id)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(problem["from"], "cancelled");
+    assert_eq!(problem["to"], "confirmed");
+}
+
+#[tokio::test]
+async fn patch_cannot_mark_an_order_paid() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Unpaid").await;
+    send(
+        routes(&store),
+        post(format!("/orders/{}/confirm", order.id)),
+    )
+    .await;
+
+    let (status, problem) = send(
+        routes(&store),
+        patch(order.id, &json!({ "status": "paid" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{}", problem);
+    assert_eq!(store.get(&order.id).await.unwrap().status, "confirmed");
+}
+
+#[tokio::test]
+async fn put_cannot_change_the_status() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Pending").await;
+    let mut body = serde_json::to_value(&order).unwrap();
+    body["status"] = json!("confirmed");
+
+    let (status, _) = send(routes(&store), put(order.id, "*", &body)).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(store.get(&order.id).await.unwrap().status, "pending");
+}
+
+#[tokio::test]
+async fn patch_keeps_the_status_and_amount() {
+    let store = Arc::new(InMemoryOrderStore::default());
+    let order = created(&store, "Patched").await;
+
+    let (status, patched) = send(
+        routes(&store),
+        patch(
+            order.id,
+            &json!({ "notes": "Ring twice", "amount": "99.00 EUR" }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["notes"], "Ring twice");
+    assert_eq!(patched["status"], "pending");
+    assert_eq!(patched["amount"], "0.00 EUR");
+}
@@ -0,0 +1,253 @@
+//! Conformance suite of the `OrderStore` contract
+//!
+//! Every check takes a store, which it may share with other runs: it only
+//! looks at the orders it creates itself.
+
+use billing::entities::order::{Order, OrderStatus, OrderStore, OrderStoreError};
+use chrono::{DateTime, Duration, Utc};
+use common::{Currency, ErrorKind, HttpError, Money, Version};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Generate a `#[tokio::test]` per check, running it against the store the
+/// `$factory` future gives, or skipping it when that is `None`: the factory
+/// fails instead when the backend is required
+macro_rules! order_store_conformance {
+    ($factory:expr) => {
+        order_store_conformance!(@checks $factory;
+            create_then_get,
+            round_trips_empty_options,
+            round_trips_timestamps,
+            duplicate_id_conflicts,
+            get_missing_is_not_found,
+            update_bumps_version,
+            stale_update_is_rejected,
+            update_missing_is_not_found,
+            delete_then_get_is_not_found,
+            delete_missing_is_not_found,
+            stale_delete_is_rejected,
+            delete_at_missing_is_not_found,
+            list_contains_created,
+            transition_follows_lifecycle,
+        );
+    };
+    (@checks $factory:expr; $($check:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $check() {
+                let Some(backend) = $factory.await else {
+                    return;
+                };
+                $crate::order_store::$check(backend.store.as_ref()).await;
+            }
+        )*
+    };
+}
+
+/// Now, to the microsecond, as precise as the SQL backends keep timestamps
+fn now() -> DateTime<Utc> {
+    DateTime::from_timestamp_micros(Utc::now().timestamp_micros()).expect("valid timestamp")
+}
+
+fn order(customer_name: Option<&str>, notes: Option<&str>, owned_by: Option<Uuid>) -> Order {
+    let mut order = Order::new(
+        "Conformance order".into(),
+        "pending".into(),
+        format!("ORD-{}", Uuid::new_v4().simple()),
+        Money::parse("123.45", Currency::EUR).expect("valid amount"),
+        customer_name.map(str::to_string),
+        notes.map(str::to_string),
+        owned_by,
+        Version::default(),
+    );
+    order.created_at = now();
+    order.updated_at = order.created_at;
+    order
+}
+
+fn full_order() -> Order {
+    order(Some("Ada"), Some("Leave at the door"), Some(Uuid::new_v4()))
+}
+
+fn json(order: &Order) -> Value {
+    serde_json::to_value(order).expect("orders serialize")
+}
+
+#[track_caller]
+fn assert_kind(result: Result<impl std::fmt::Debug, OrderStoreError>, kind: ErrorKind) {
+    match result {
+        Ok(value) => panic!("expected a {:?} error, got {:?}", kind, value),
+        Err(e) => assert_eq!(e.kind(), kind, "unexpected error: {}", e),
+    }
+}
+
+pub async fn create_then_get(store: &dyn OrderStore) {
+    let order = full_order();
+    let created = store.create(order.clone()).await.expect("create");
+    assert_eq!(json(&created), json(&order));
+
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(json(&stored), json(&order));
+}
+
+pub async fn round_trips_empty_options(store: &dyn OrderStore) {
+    let order = order(None, None, None);
+    store.create(order.clone()).await.expect("create");
+
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(stored.customer_name, None);
+    assert_eq!(stored.notes, None);
+    assert_eq!(stored.owned_by, None);
+    assert_eq!(stored.deleted_at, None);
+    assert_eq!(json(&stored), json(&order));
+}
+
+pub async fn round_trips_timestamps(store: &dyn OrderStore) {
+    let mut order = full_order();
+    order.created_at -= Duration::days(3);
+    order.updated_at -= Duration::hours(5);
+    order.deleted_at = Some(order.updated_at + Duration::minutes(1));
+    store.create(order.clone()).await.expect("create");
+
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(stored.created_at, order.created_at);
+    assert_eq!(stored.updated_at, order.updated_at);
+    assert_eq!(stored.deleted_at, order.deleted_at);
+}
+
+pub async fn duplicate_id_conflicts(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+
+    let mut duplicate = order.clone();
+    duplicate.name = "Duplicate".into();
+    assert_kind(store.create(duplicate).await, ErrorKind::Conflict);
+    // The first one stays
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(stored.name, order.name);
+}
+
+pub async fn get_missing_is_not_found(store: &dyn OrderStore) {
+    assert_kind(store.get(&Uuid::new_v4()).await, ErrorKind::NotFound);
+}
+
+pub async fn update_bumps_version(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+
+    let mut changed = order.clone();
+    changed.notes = None;
+    changed.customer_name = Some("Grace".into());
+    let updated = store.update(changed).await.expect("update");
+    assert_eq!(updated.version, order.version.next());
+
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(json(&stored), json(&updated));
+    assert_eq!(stored.notes, None);
+    assert_eq!(stored.customer_name.as_deref(), Some("Grace"));
+}
+
+pub async fn stale_update_is_rejected(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+    let updated = store.update(order.clone()).await.expect("update");
+
+    // Still at the version the first update was made against
+    let mut stale = order.clone();
+    stale.name = "Stale".into();
+    assert_kind(store.update(stale).await, ErrorKind::PreconditionFailed);
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(json(&stored), json(&updated));
+}
+
+pub async fn update_missing_is_not_found(store: &dyn OrderStore) {
+    assert_kind(store.update(full_order()).await, ErrorKind::NotFound);
+}
+
+pub async fn delete_then_get_is_not_found(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+
+    store.delete(&order.id).await.expect("delete");
+    assert_kind(store.get(&order.id).await, ErrorKind::NotFound);
+    assert!(
+        !store
+            .list()
+            .await
+            .expect("list")
+            .iter()
+            .any(|o| o.id == order.id)
+    );
+}
+
+pub async fn delete_missing_is_not_found(store: &dyn OrderStore) {
+    assert_kind(store.delete(&Uuid::new_v4()).await, ErrorKind::NotFound);
+}
+
+pub async fn stale_delete_is_rejected(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+    let updated = store.update(order.clone()).await.expect("update");
+
+    // Deleting at the version before the update leaves the order in place
+    assert_kind(
+        store.delete_at(&order.id, order.version).await,
+        ErrorKind::PreconditionFailed,
+    );
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(json(&stored), json(&updated));
+
+    store
+        .delete_at(&order.id, updated.version)
+        .await
+        .expect("delete at the current version");
+    assert_kind(store.get(&order.id).await, ErrorKind::NotFound);
+}
+
+pub async fn delete_at_missing_is_not_found(store: &dyn OrderStore) {
+    assert_kind(
+        store.delete_at(&Uuid::new_v4(), Version::default()).await,
+        ErrorKind::NotFound,
+    );
+}
+
+pub async fn list_contains_created(store: &dyn OrderStore) {
+    let orders = [full_order(), order(None, None, None)];
+    for order in &orders {
+        store.create(order.clone()).await.expect("create");
+    }
+
+    let listed = store.list().await.expect("list");
+    for order in &orders {
+        let found = listed
+            .iter()
+            .find(|o| o.id == order.id)
+            .unwrap_or_else(|| panic!("order {} not listed", order.id));
+        assert_eq!(json(found), json(order));
+    }
+}
+
+pub async fn transition_follows_lifecycle(store: &dyn OrderStore) {
+    let order = full_order();
+    store.create(order.clone()).await.expect("create");
+
+    let confirmed = store
+        .transition(&order.id, OrderStatus::Confirmed)
+        .await
+        .expect("pending -> confirmed");
+    assert_eq!(confirmed.status, "confirmed");
+    assert_eq!(confirmed.version, order.version.next());
+
+    assert_kind(
+        store.transition(&order.id, OrderStatus::Pending).await,
+        ErrorKind::Conflict,
+    );
+    assert_kind(
+        store
+            .transition(&Uuid::new_v4(), OrderStatus::Confirmed)
+            .await,
+        ErrorKind::NotFound,
+    );
+    let stored = store.get(&order.id).await.expect("get");
+    assert_eq!(stored.status, "confirmed");
+}
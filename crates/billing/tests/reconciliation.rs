@@ -0,0 +1,241 @@
+//! Invoices and orders settled from completed payments, on in-memory stores
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use billing::entities::invoice::Invoice;
+use billing::entities::order::Order;
+use billing::entities::payment::Payment;
+use billing::module::BillingStores;
+use billing::services::{PaymentCoverage, PaymentReconciler, SETTLEMENT_EVENT};
+use common::{Currency, Money, Version};
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent, LinkEvent};
+use this::prelude::{LinkEntity, LinkService};
+use this::storage::InMemoryLinkService;
+use uuid::Uuid;
+
+fn eur(minor_units: i64) -> Money {
+    Money::from_minor(minor_units, Currency::EUR)
+}
+
+struct Fixture {
+    stores: BillingStores,
+    links: Arc<InMemoryLinkService>,
+    order: Order,
+    invoice: Invoice,
+}
+
+impl Fixture {
+    /// A confirmed order with one invoice of `amount`
+    async fn new(amount: i64) -> Self {
+        let stores = BillingStores::new_in_memory();
+        let links = Arc::new(InMemoryLinkService::new());
+        let order = Order::new(
+            "Order".into(),
+            "confirmed".into(),
+            format!("ORD-{}", Uuid::new_v4().simple()),
+            eur(amount),
+            None,
+            None,
+            None,
+            Version::default(),
+        );
+        let order = stores.orders_store.create(order).await.unwrap();
+        let invoice = Invoice::new(
+            "Invoice".into(),
+            "sent".into(),
+            format!("INV-{}", Uuid::new_v4().simple()),
+            eur(amount),
+            None,
+            None,
+            None,
+            Version::default(),
+        );
+        let invoice = stores.invoices_store.create(invoice).await.unwrap();
+        links
+            .create(LinkEntity::new("has_invoice", order.id, invoice.id, None))
+            .await
+            .unwrap();
+        Self {
+            stores,
+            links,
+            order,
+            invoice,
+        }
+    }
+
+    fn reconciler(&self) -> PaymentReconciler {
+        PaymentReconciler::new(&self.stores, self.links.clone())
+    }
+
+    async fn pay(&self, amount: i64, status: &str) -> LinkEntity {
+        let payment = Payment::new(
+            "Payment".into(),
+            status.into(),
+            format!("PAY-{}", Uuid::new_v4().simple()),
+            eur(amount),
+            "bank_transfer".into(),
+            None,
+            None,
+            Version::default(),
+        );
+        let payment = self.stores.payments_store.create(payment).await.unwrap();
+        self.links
+            .create(LinkEntity::new(
+                "payment",
+                self.invoice.id,
+                payment.id,
+                None,
+            ))
+            .await
+            .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn an_exact_payment_settles_the_invoice_and_the_order() {
+    let fixture = Fixture::new(10_000).await;
+    fixture.pay(10_000, "completed").await;
+
+    let settlement = fixture
+        .reconciler()
+        .reconcile_invoice(&fixture.invoice.id)
+        .await
+        .unwrap();
+
+    assert_eq!(settlement.coverage, PaymentCoverage::Exact);
+    assert!(settlement.marked_paid);
+    assert_eq!(settlement.invoice.status, "paid");
+    assert!(settlement.invoice.paid_at.is_some());
+    assert_eq!(settlement.outstanding, eur(0));
+    assert_eq!(settlement.paid_orders.len(), 1);
+    let order = fixture.stores.orders_store.get(&fixture.order.id).await;
+    assert_eq!(order.unwrap().status, "paid");
+}
+
+#[tokio::test]
+async fn a_partial_payment_leaves_the_invoice_open() {
+    let fixture = Fixture::new(10_000).await;
+    fixture.pay(4_000, "completed").await;
+    // Not counted until completed
+    fixture.pay(6_000, "pending").await;
+
+    let settlement = fixture
+        .reconciler()
+        .reconcile_invoice(&fixture.invoice.id)
+        .await
+        .unwrap();
+
+    assert_eq!(settlement.coverage, PaymentCoverage::Partial);
+    assert!(!settlement.marked_paid);
+    assert_eq!(settlement.paid_amount, eur(4_000));
+    assert_eq!(settlement.outstanding, eur(6_000));
+    assert_eq!(settlement.invoice.status, "sent");
+    assert!(settlement.paid_orders.is_empty());
+}
+
+#[tokio::test]
+async fn an_overpayment_settles_with_a_negative_outstanding() {
+    let fixture = Fixture::new(10_000).await;
+    fixture.pay(7_000, "completed").await;
+    fixture.pay(5_000, "completed").await;
+
+    let settlement = fixture
+        .reconciler()
+        .reconcile_invoice(&fixture.invoice.id)
+        .await
+        .unwrap();
+
+    assert_eq!(settlement.coverage, PaymentCoverage::Overpaid);
+    assert!(settlement.marked_paid);
+    assert_eq!(settlement.outstanding, eur(-2_000));
+}
+
+#[tokio::test]
+async fn a_zero_amount_invoice_is_settled_without_payments() {
+    let fixture = Fixture::new(0).await;
+
+    let settlement = fixture
+        .reconciler()
+        .reconcile_invoice(&fixture.invoice.id)
+        .await
+        .unwrap();
+
+    assert_eq!(settlement.coverage, PaymentCoverage::Exact);
+    assert!(settlement.marked_paid);
+    assert_eq!(settlement.paid_orders.len(), 1);
+}
+
+#[tokio::test]
+async fn a_payment_in_another_currency_fails_reconciliation() {
+    let fixture = Fixture::new(10_000).await;
+    let payment = Payment::new(
+        "Payment".into(),
+        "completed".into(),
+        "PAY-USD".into(),
+        Money::from_minor(10_000, Currency::USD),
+        "cash".into(),
+        None,
+        None,
+        Version::default(),
+    );
+    let payment = fixture.stores.payments_store.create(payment).await.unwrap();
+    fixture
+        .links
+        .create(LinkEntity::new(
+            "payment",
+            fixture.invoice.id,
+            payment.id,
+            None,
+        ))
+        .await
+        .unwrap();
+
+    let result = fixture
+        .reconciler()
+        .reconcile_invoice(&fixture.invoice.id)
+        .await;
+
+    assert!(result.is_err());
+    let invoice = fixture.stores.invoices_store.get(&fixture.invoice.id).await;
+    assert_eq!(invoice.unwrap().status, "sent");
+}
+
+#[tokio::test]
+async fn the_listener_publishes_partial_payments() {
+    let fixture = Fixture::new(10_000).await;
+    let event_bus = EventBus::new(16);
+    let mut events = event_bus.subscribe();
+    let listener = Arc::new(fixture.reconciler()).spawn(event_bus.clone());
+    let link = fixture.pay(2_500, "completed").await;
+
+    event_bus.publish(FrameworkEvent::Link(LinkEvent::Created {
+        link_type: link.link_type.clone(),
+        link_id: link.id,
+        source_id: link.source_id,
+        target_id: link.target_id,
+        metadata: None,
+    }));
+
+    let data = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let FrameworkEvent::Entity(EntityEvent::Updated {
+                entity_type,
+                entity_id,
+                data,
+            }) = events.recv().await.unwrap().event
+                && entity_type == SETTLEMENT_EVENT
+            {
+                assert_eq!(entity_id, fixture.invoice.id);
+                return data;
+            }
+        }
+    })
+    .await
+    .expect("a settlement event");
+    listener.abort();
+
+    assert_eq!(data["coverage"], "partial");
+    assert_eq!(data["paid_amount"], "25.00 EUR");
+    assert_eq!(data["outstanding"], "75.00 EUR");
+}
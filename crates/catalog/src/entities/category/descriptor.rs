@@ -5,13 +5,16 @@ use this::server::entity_registry::EntityDescriptor;
 
 use super::CategoryStore;
 use super::handlers::{
-    CategoryState, create_category, delete_category, get_category, list_categories, update_category,
+    CategoryState, create_category, delete_category, get_category, list_all_products,
+    list_ancestors, list_categories, list_descendants, patch_category, update_category,
 };
+use crate::services::CategoryHierarchy;
 
 #[derive(Clone)]
 pub struct CategoryDescriptor {
     store: Arc<dyn CategoryStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    hierarchy: Option<Arc<CategoryHierarchy>>,
 }
 
 impl CategoryDescriptor {
@@ -26,8 +29,15 @@ impl CategoryDescriptor {
         Self {
             store,
             entity_creator,
+            hierarchy: None,
         }
     }
+
+    /// Serve the hierarchy routes of a category with `hierarchy`
+    pub fn with_hierarchy(mut self, hierarchy: Option<Arc<CategoryHierarchy>>) -> Self {
+        self.hierarchy = hierarchy;
+        self
+    }
 }
 
 impl EntityDescriptor for CategoryDescriptor {
@@ -43,15 +53,25 @@ impl EntityDescriptor for CategoryDescriptor {
         let state = CategoryState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            hierarchy: self.hierarchy.clone(),
         };
-        Router::new()
+        let router = Router::new()
             .route("/categories", get(list_categories).post(create_category))
             .route(
                 "/categories/{id}",
                 get(get_category)
                     .put(update_category)
+                    .patch(patch_category)
                     .delete(delete_category),
-            )
-            .with_state(state)
+            );
+        let router = if self.hierarchy.is_some() {
+            router
+                .route("/categories/{id}/ancestors", get(list_ancestors))
+                .route("/categories/{id}/descendants", get(list_descendants))
+                .route("/categories/{id}/all_products", get(list_all_products))
+        } else {
+            router
+        };
+        router.with_state(state)
     }
 }
@@ -1,69 +1,139 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Category, CategoryStore};
+use super::{Category, CategoryStore, CategoryStoreError};
+use crate::entities::product::Product;
+use crate::services::{CategoryHierarchy, CategoryTree};
 
 #[derive(Clone)]
 pub struct CategoryState {
     pub store: Arc<dyn CategoryStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub hierarchy: Option<Arc<CategoryHierarchy>>,
 }
 
-pub async fn list_categories(State(state): State<CategoryState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List categories error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list categories",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_categories(
+    State(state): State<CategoryState>,
+    query: ListQuery,
+) -> Result<Json<Page<Category>>, Problem> {
+    query.check::<Category>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List categories error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_category(
     State(state): State<CategoryState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Category>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Category>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_category(
     State(state): State<CategoryState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating category with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Category created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create category error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create category",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<CategoryStoreError>(e))
         }
     }
 }
 
 pub async fn update_category(
     State(state): State<CategoryState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(category): Json<Category>,
-) -> Json<Option<Category>> {
-    let updated = state.store.update(category).await.ok();
-    Json(updated)
+) -> Result<Tagged<Category>, Problem> {
+    check_id(id, category.id)?;
+    let mut category = validate_entity(&Category::validation_config("update"), category)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    category.version = current.version;
+    category.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(category).await?))
+}
+
+/// Apply a JSON Merge Patch to an existing category
+pub async fn patch_category(
+    State(state): State<CategoryState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Category>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut category = patch_entity(&Category::validation_config("update"), current, patch)?;
+    category.touch();
+    Ok(Tagged(state.store.update(category).await?))
 }
 
 pub async fn delete_category(
     State(state): State<CategoryState>,
     Path(id): Path<Uuid>,
-) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn hierarchy(state: &CategoryState) -> Result<&CategoryHierarchy, Problem> {
+    state.hierarchy.as_deref().ok_or_else(|| {
+        Problem::with_status(
+            StatusCode::NOT_IMPLEMENTED,
+            "The category hierarchy is not enabled",
+        )
+    })
+}
+
+/// Ancestors of a category as a breadcrumb, the root first
+pub async fn list_ancestors(
+    State(state): State<CategoryState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Category>>, Problem> {
+    let ancestors = hierarchy(&state)?.ancestors(&id).await.map_err(|e| {
+        eprintln!("Ancestors of category {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Json(ancestors))
+}
+
+/// A category with its whole subtree of descendants
+pub async fn list_descendants(
+    State(state): State<CategoryState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<CategoryTree>, Problem> {
+    let tree = hierarchy(&state)?.descendants(&id).await.map_err(|e| {
+        eprintln!("Descendants of category {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Json(tree))
+}
+
+/// Products in a category or any of its descendants
+pub async fn list_all_products(
+    State(state): State<CategoryState>,
+    Path(id): Path<Uuid>,
+    query: ListQuery,
+) -> Result<Json<Page<Product>>, Problem> {
+    query.check::<Product>()?;
+    let products = hierarchy(&state)?.products(&id).await.map_err(|e| {
+        eprintln!("Products under category {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Json(query.apply(products)))
 }
@@ -8,3 +8,17 @@ pub use store::{CategoryStore, CategoryStoreError, InMemoryCategoryStore};
 
 #[cfg(feature = "dynamodb")]
 pub use store::CategoryDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::CategoryLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::CategoryMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::CategoryMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::CategoryNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::CategoryPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::CategoryScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::CategorySqliteStore;
@@ -1,3 +1,4 @@
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -7,6 +8,8 @@ impl_data_entity_validated!(
     {
         slug: String,
         description: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
@@ -31,3 +34,5 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(Category);
@@ -1,264 +1,59 @@
-use async_trait::async_trait;
-use std::sync::Arc;
-use this::prelude::*;
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{Payload, ValidationError, Version};
+use this::core::validation::extractor::ValidatableEntity;
 
 use super::Category;
 
-#[derive(Debug, thiserror::Error)]
-pub enum CategoryStoreError {
-    #[error("not found: {0}")]
-    NotFound(String),
-    #[error("conflict: {0}")]
-    Conflict(String),
-    #[error("validation: {0}")]
-    Validation(String),
-    #[error(transparent)]
-    Other(#[from] anyhow::Error),
-}
-
-#[async_trait]
-pub trait CategoryStore: Send + Sync {
-    async fn create(&self, category: Category) -> Result<Category, CategoryStoreError>;
-    async fn get(&self, id: &Uuid) -> Result<Category, CategoryStoreError>;
-    async fn update(&self, category: Category) -> Result<Category, CategoryStoreError>;
-    async fn delete(&self, id: &Uuid) -> Result<(), CategoryStoreError>;
-    async fn list(&self) -> Result<Vec<Category>, CategoryStoreError>;
-}
-
-// ============================================================================
-// InMemory Store Implementation
-// ============================================================================
+pub type CategoryStoreError = StoreError;
 
-use tokio::sync::RwLock;
+/// Store of categories
+pub trait CategoryStore: EntityStore<Category> {}
 
-#[derive(Clone, Default)]
-pub struct InMemoryCategoryStore {
-    inner: Arc<RwLock<Vec<Category>>>,
-}
-
-#[async_trait::async_trait]
-impl EntityFetcher for InMemoryCategoryStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let category = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Category not found: {}", entity_id))?;
-        Ok(serde_json::to_value(category)?)
-    }
+impl<S: EntityStore<Category> + ?Sized> CategoryStore for S {}
 
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_categories = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
+impl StoredEntity for Category {
+    type Error = CategoryStoreError;
+    const NAME: &'static str = "Category";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name", "status", "slug"];
 
-        let categories: Vec<Category> = all_categories
-            .into_iter()
-            .skip(offset)
-            .take(limit)
-            .collect();
-        categories
-            .into_iter()
-            .map(|category| serde_json::to_value(category).map_err(Into::into))
-            .collect()
-    }
-}
-
-#[async_trait::async_trait]
-impl EntityCreator for InMemoryCategoryStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
+    /// Build a new category from a create payload
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&Category::validation_config("create"), entity_data);
         let category = Category::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("Category")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("active")
-                .to_string(),
-            entity_data["slug"]
-                .as_str()
-                .unwrap_or("category")
-                .to_string(),
-            entity_data["description"].as_str().map(String::from),
+            payload.required_string("name"),
+            payload.required_string("status"),
+            payload.required_string("slug"),
+            payload.string("description"),
+            payload.uuid("owned_by"),
+            Version::default(),
         );
-
-        self.create(category.clone()).await?;
-        Ok(serde_json::to_value(category)?)
-    }
-}
-
-#[async_trait::async_trait]
-impl CategoryStore for InMemoryCategoryStore {
-    async fn create(&self, category: Category) -> Result<Category, CategoryStoreError> {
-        let mut g = self.inner.write().await;
-        if g.iter().any(|c| c.id == category.id) {
-            return Err(CategoryStoreError::Conflict(category.id.to_string()));
-        }
-        g.push(category.clone());
+        payload.finish()?;
         Ok(category)
     }
-
-    async fn get(&self, id: &Uuid) -> Result<Category, CategoryStoreError> {
-        let g = self.inner.read().await;
-        g.iter()
-            .find(|c| &c.id == id)
-            .cloned()
-            .ok_or_else(|| CategoryStoreError::NotFound(id.to_string()))
-    }
-
-    async fn update(&self, category: Category) -> Result<Category, CategoryStoreError> {
-        let mut g = self.inner.write().await;
-        if let Some(x) = g.iter_mut().find(|c| c.id == category.id) {
-            *x = category.clone();
-            Ok(category)
-        } else {
-            Err(CategoryStoreError::NotFound(category.id.to_string()))
-        }
-    }
-
-    async fn delete(&self, id: &Uuid) -> Result<(), CategoryStoreError> {
-        let mut g = self.inner.write().await;
-        let before = g.len();
-        g.retain(|c| &c.id != id);
-        if g.len() == before {
-            return Err(CategoryStoreError::NotFound(id.to_string()));
-        }
-        Ok(())
-    }
-
-    async fn list(&self) -> Result<Vec<Category>, CategoryStoreError> {
-        Ok(self.inner.read().await.clone())
-    }
 }
 
-// ============================================================================
-// DynamoDB Store Implementation
-// ============================================================================
+pub type InMemoryCategoryStore = InMemoryEntityStore<Category>;
 
 #[cfg(feature = "dynamodb")]
-use aws_sdk_dynamodb::Client as DynamoDBClient;
-#[cfg(feature = "dynamodb")]
-use this::storage::DynamoDBDataService;
+pub type CategoryDynamoDBStore = common::store::DynamoDBStore<Category>;
 
-#[cfg(feature = "dynamodb")]
-/// DynamoDB store for Category entities
-#[derive(Clone)]
-pub struct CategoryDynamoDBStore {
-    service: Arc<DynamoDBDataService<Category>>,
-}
+#[cfg(feature = "postgres")]
+pub type CategoryPostgresStore = common::store::PostgresStore<Category>;
 
-#[cfg(feature = "dynamodb")]
-impl CategoryDynamoDBStore {
-    pub fn new(client: DynamoDBClient, table_name: String) -> Self {
-        Self {
-            service: Arc::new(DynamoDBDataService::new(client, table_name)),
-        }
-    }
-}
-
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityFetcher for CategoryDynamoDBStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let category = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Category not found: {}", entity_id))?;
-        Ok(serde_json::to_value(category)?)
-    }
-
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_categories = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let categories: Vec<Category> = all_categories
-            .into_iter()
-            .skip(offset)
-            .take(limit)
-            .collect();
-        categories
-            .into_iter()
-            .map(|category| serde_json::to_value(category).map_err(Into::into))
-            .collect()
-    }
-}
-
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityCreator for CategoryDynamoDBStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
-        let category = Category::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("Category")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("active")
-                .to_string(),
-            entity_data["slug"]
-                .as_str()
-                .unwrap_or("category")
-                .to_string(),
-            entity_data["description"].as_str().map(String::from),
-        );
-
-        self.create(category.clone()).await?;
-        Ok(serde_json::to_value(category)?)
-    }
-}
+#[cfg(feature = "mongodb_backend")]
+pub type CategoryMongoStore = common::store::MongoStore<Category>;
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl CategoryStore for CategoryDynamoDBStore {
-    async fn create(&self, category: Category) -> Result<Category, CategoryStoreError> {
-        self.service
-            .create(category.clone())
-            .await
-            .map_err(|e| CategoryStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "neo4j")]
+pub type CategoryNeo4jStore = common::store::Neo4jStore<Category>;
 
-    async fn get(&self, id: &Uuid) -> Result<Category, CategoryStoreError> {
-        self.service
-            .get(id)
-            .await
-            .map_err(|e| CategoryStoreError::Other(anyhow::anyhow!(e)))?
-            .ok_or_else(|| CategoryStoreError::NotFound(id.to_string()))
-    }
+#[cfg(feature = "scylladb")]
+pub type CategoryScyllaStore = common::store::ScyllaStore<Category>;
 
-    async fn update(&self, category: Category) -> Result<Category, CategoryStoreError> {
-        self.service
-            .update(&category.id, category.clone())
-            .await
-            .map_err(|e| CategoryStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "mysql")]
+pub type CategoryMysqlStore = common::store::MysqlStore<Category>;
 
-    async fn delete(&self, id: &Uuid) -> Result<(), CategoryStoreError> {
-        self.service
-            .delete(id)
-            .await
-            .map_err(|e| CategoryStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "lmdb")]
+pub type CategoryLmdbStore = common::store::LmdbStore<Category>;
 
-    async fn list(&self) -> Result<Vec<Category>, CategoryStoreError> {
-        self.service
-            .list()
-            .await
-            .map_err(|e| CategoryStoreError::Other(anyhow::anyhow!(e)))
-    }
-}
+#[cfg(feature = "sqlite")]
+pub type CategorySqliteStore = common::store::SqliteStore<Category>;
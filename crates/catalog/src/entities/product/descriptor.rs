@@ -5,7 +5,8 @@ use this::server::entity_registry::EntityDescriptor;
 
 use super::ProductStore;
 use super::handlers::{
-    ProductState, create_product, delete_product, get_product, list_products, update_product,
+    ProductState, create_product, delete_product, get_product, list_products, patch_product,
+    update_product,
 };
 
 #[derive(Clone)]
@@ -48,7 +49,10 @@ impl EntityDescriptor for ProductDescriptor {
             .route("/products", get(list_products).post(create_product))
             .route(
                 "/products/{id}",
-                get(get_product).put(update_product).delete(delete_product),
+                get(get_product)
+                    .put(update_product)
+                    .patch(patch_product)
+                    .delete(delete_product),
             )
             .with_state(state)
     }
@@ -1,9 +1,11 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
 use this::prelude::*;
 
-use super::{Product, ProductStore};
+use super::{Product, ProductStore, ProductStoreError};
 
 #[derive(Clone)]
 pub struct ProductState {
@@ -11,56 +13,76 @@ pub struct ProductState {
     pub entity_creator: Arc<dyn EntityCreator>,
 }
 
-pub async fn list_products(State(state): State<ProductState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List products error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list products",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_products(
+    State(state): State<ProductState>,
+    query: ListQuery,
+) -> Result<Json<Page<Product>>, Problem> {
+    query.check::<Product>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List products error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_product(
     State(state): State<ProductState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Product>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Product>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_product(
     State(state): State<ProductState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating product with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Product created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create product error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create product",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<ProductStoreError>(e))
         }
     }
 }
 
 pub async fn update_product(
     State(state): State<ProductState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(product): Json<Product>,
-) -> Json<Option<Product>> {
-    let updated = state.store.update(product).await.ok();
-    Json(updated)
+) -> Result<Tagged<Product>, Problem> {
+    check_id(id, product.id)?;
+    let mut product = validate_entity(&Product::validation_rules("update"), product)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    product.version = current.version;
+    product.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(product).await?))
 }
 
-pub async fn delete_product(State(state): State<ProductState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing product
+pub async fn patch_product(
+    State(state): State<ProductState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Product>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut product = patch_entity(&Product::validation_rules("update"), current, patch)?;
+    product.touch();
+    Ok(Tagged(state.store.update(product).await?))
+}
+
+pub async fn delete_product(
+    State(state): State<ProductState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
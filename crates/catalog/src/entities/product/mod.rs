@@ -8,3 +8,17 @@ pub use store::{InMemoryProductStore, ProductStore, ProductStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::ProductDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::ProductLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::ProductMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::ProductMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::ProductNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::ProductPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::ProductScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::ProductSqliteStore;
@@ -1,3 +1,8 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use common::money::{self, Money};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -6,18 +11,20 @@ impl_data_entity_validated!(
     ["name", "sku"],
     {
         sku: String,
-        price: f64,
+        price: Money,
         stock_quantity: i32,
         description: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
             sku: [required string_length(3, 50)],
-            price: [required positive max_value(1_000_000.0)],
+            price: [required],
             status: [required in_list("active", "inactive", "discontinued")],
         },
         update: {
-            price: [optional positive max_value(1_000_000.0)],
+            price: [optional],
             status: [optional in_list("active", "inactive", "discontinued")],
         },
     },
@@ -25,11 +32,23 @@ impl_data_entity_validated!(
         create: {
             sku: [trim uppercase],
             status: [trim lowercase],
-            price: [round_decimals(2)],
         },
         update: {
             status: [trim lowercase],
-            price: [round_decimals(2)],
         },
     }
 );
+
+common::impl_versioned!(Product);
+
+impl Product {
+    /// Declared rules for `operation` extended with the checks on `price`,
+    /// which is a [`Money`] and not a plain number
+    pub fn validation_rules(operation: &str) -> EntityValidationConfig {
+        let mut config = <Self as ValidatableEntity>::validation_config(operation);
+        config.add_filter("price", money::filters::normalize());
+        config.add_validator("price", money::validators::positive());
+        config.add_validator("price", money::validators::max_amount(1_000_000));
+        config
+    }
+}
@@ -1,254 +1,60 @@
-use async_trait::async_trait;
-use std::sync::Arc;
-use this::prelude::*;
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{Payload, ValidationError, Version};
 
 use super::Product;
 
-#[derive(Debug, thiserror::Error)]
-pub enum ProductStoreError {
-    #[error("not found: {0}")]
-    NotFound(String),
-    #[error("conflict: {0}")]
-    Conflict(String),
-    #[error("validation: {0}")]
-    Validation(String),
-    #[error(transparent)]
-    Other(#[from] anyhow::Error),
-}
-
-#[async_trait]
-pub trait ProductStore: Send + Sync {
-    async fn create(&self, product: Product) -> Result<Product, ProductStoreError>;
-    async fn get(&self, id: &Uuid) -> Result<Product, ProductStoreError>;
-    async fn update(&self, product: Product) -> Result<Product, ProductStoreError>;
-    async fn delete(&self, id: &Uuid) -> Result<(), ProductStoreError>;
-    async fn list(&self) -> Result<Vec<Product>, ProductStoreError>;
-}
-
-// ============================================================================
-// InMemory Store Implementation
-// ============================================================================
+pub type ProductStoreError = StoreError;
 
-use tokio::sync::RwLock;
+/// Store of products
+pub trait ProductStore: EntityStore<Product> {}
 
-#[derive(Clone, Default)]
-pub struct InMemoryProductStore {
-    inner: Arc<RwLock<Vec<Product>>>,
-}
-
-#[async_trait::async_trait]
-impl EntityFetcher for InMemoryProductStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let product = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Product not found: {}", entity_id))?;
-        Ok(serde_json::to_value(product)?)
-    }
+impl<S: EntityStore<Product> + ?Sized> ProductStore for S {}
 
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_products = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
+impl StoredEntity for Product {
+    type Error = ProductStoreError;
+    const NAME: &'static str = "Product";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name", "status", "sku"];
 
-        let products: Vec<Product> = all_products.into_iter().skip(offset).take(limit).collect();
-        products
-            .into_iter()
-            .map(|product| serde_json::to_value(product).map_err(Into::into))
-            .collect()
-    }
-}
-
-#[async_trait::async_trait]
-impl EntityCreator for InMemoryProductStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
+    /// Build a new product from a create payload
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&Product::validation_rules("create"), entity_data);
         let product = Product::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("Product")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("active")
-                .to_string(),
-            entity_data["sku"].as_str().unwrap_or("SKU-000").to_string(),
-            entity_data["price"].as_f64().unwrap_or(0.0),
-            entity_data["stock_quantity"].as_i64().unwrap_or(0) as i32,
-            entity_data["description"].as_str().map(String::from),
+            payload.required_string("name"),
+            payload.required_string("status"),
+            payload.required_string("sku"),
+            payload.required_money("price"),
+            payload.required_i32("stock_quantity"),
+            payload.string("description"),
+            payload.uuid("owned_by"),
+            Version::default(),
         );
-
-        self.create(product.clone()).await?;
-        Ok(serde_json::to_value(product)?)
-    }
-}
-
-#[async_trait::async_trait]
-impl ProductStore for InMemoryProductStore {
-    async fn create(&self, product: Product) -> Result<Product, ProductStoreError> {
-        let mut g = self.inner.write().await;
-        if g.iter().any(|p| p.id == product.id) {
-            return Err(ProductStoreError::Conflict(product.id.to_string()));
-        }
-        g.push(product.clone());
+        payload.finish()?;
         Ok(product)
     }
-
-    async fn get(&self, id: &Uuid) -> Result<Product, ProductStoreError> {
-        let g = self.inner.read().await;
-        g.iter()
-            .find(|p| &p.id == id)
-            .cloned()
-            .ok_or_else(|| ProductStoreError::NotFound(id.to_string()))
-    }
-
-    async fn update(&self, product: Product) -> Result<Product, ProductStoreError> {
-        let mut g = self.inner.write().await;
-        if let Some(x) = g.iter_mut().find(|p| p.id == product.id) {
-            *x = product.clone();
-            Ok(product)
-        } else {
-            Err(ProductStoreError::NotFound(product.id.to_string()))
-        }
-    }
-
-    async fn delete(&self, id: &Uuid) -> Result<(), ProductStoreError> {
-        let mut g = self.inner.write().await;
-        let before = g.len();
-        g.retain(|p| &p.id != id);
-        if g.len() == before {
-            return Err(ProductStoreError::NotFound(id.to_string()));
-        }
-        Ok(())
-    }
-
-    async fn list(&self) -> Result<Vec<Product>, ProductStoreError> {
-        Ok(self.inner.read().await.clone())
-    }
 }
 
-// ============================================================================
-// DynamoDB Store Implementation
-// ============================================================================
+pub type InMemoryProductStore = InMemoryEntityStore<Product>;
 
 #[cfg(feature = "dynamodb")]
-use aws_sdk_dynamodb::Client as DynamoDBClient;
-#[cfg(feature = "dynamodb")]
-use this::storage::DynamoDBDataService;
+pub type ProductDynamoDBStore = common::store::DynamoDBStore<Product>;
 
-#[cfg(feature = "dynamodb")]
-/// DynamoDB store for Product entities
-#[derive(Clone)]
-pub struct ProductDynamoDBStore {
-    service: Arc<DynamoDBDataService<Product>>,
-}
+#[cfg(feature = "postgres")]
+pub type ProductPostgresStore = common::store::PostgresStore<Product>;
 
-#[cfg(feature = "dynamodb")]
-impl ProductDynamoDBStore {
-    pub fn new(client: DynamoDBClient, table_name: String) -> Self {
-        Self {
-            service: Arc::new(DynamoDBDataService::new(client, table_name)),
-        }
-    }
-}
-
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityFetcher for ProductDynamoDBStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let product = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("Product not found: {}", entity_id))?;
-        Ok(serde_json::to_value(product)?)
-    }
-
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_products = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let products: Vec<Product> = all_products.into_iter().skip(offset).take(limit).collect();
-        products
-            .into_iter()
-            .map(|product| serde_json::to_value(product).map_err(Into::into))
-            .collect()
-    }
-}
-
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityCreator for ProductDynamoDBStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
-        let product = Product::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("Product")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("active")
-                .to_string(),
-            entity_data["sku"].as_str().unwrap_or("SKU-000").to_string(),
-            entity_data["price"].as_f64().unwrap_or(0.0),
-            entity_data["stock_quantity"].as_i64().unwrap_or(0) as i32,
-            entity_data["description"].as_str().map(String::from),
-        );
-
-        self.create(product.clone()).await?;
-        Ok(serde_json::to_value(product)?)
-    }
-}
+#[cfg(feature = "mongodb_backend")]
+pub type ProductMongoStore = common::store::MongoStore<Product>;
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl ProductStore for ProductDynamoDBStore {
-    async fn create(&self, product: Product) -> Result<Product, ProductStoreError> {
-        self.service
-            .create(product.clone())
-            .await
-            .map_err(|e| ProductStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "neo4j")]
+pub type ProductNeo4jStore = common::store::Neo4jStore<Product>;
 
-    async fn get(&self, id: &Uuid) -> Result<Product, ProductStoreError> {
-        self.service
-            .get(id)
-            .await
-            .map_err(|e| ProductStoreError::Other(anyhow::anyhow!(e)))?
-            .ok_or_else(|| ProductStoreError::NotFound(id.to_string()))
-    }
+#[cfg(feature = "scylladb")]
+pub type ProductScyllaStore = common::store::ScyllaStore<Product>;
 
-    async fn update(&self, product: Product) -> Result<Product, ProductStoreError> {
-        self.service
-            .update(&product.id, product.clone())
-            .await
-            .map_err(|e| ProductStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "mysql")]
+pub type ProductMysqlStore = common::store::MysqlStore<Product>;
 
-    async fn delete(&self, id: &Uuid) -> Result<(), ProductStoreError> {
-        self.service
-            .delete(id)
-            .await
-            .map_err(|e| ProductStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "lmdb")]
+pub type ProductLmdbStore = common::store::LmdbStore<Product>;
 
-    async fn list(&self) -> Result<Vec<Product>, ProductStoreError> {
-        self.service
-            .list()
-            .await
-            .map_err(|e| ProductStoreError::Other(anyhow::anyhow!(e)))
-    }
-}
+#[cfg(feature = "sqlite")]
+pub type ProductSqliteStore = common::store::SqliteStore<Product>;
@@ -4,7 +4,9 @@ use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
 
 use super::TagStore;
-use super::handlers::{TagState, create_tag, delete_tag, get_tag, list_tags, update_tag};
+use super::handlers::{
+    TagState, create_tag, delete_tag, get_tag, list_tags, patch_tag, update_tag,
+};
 
 #[derive(Clone)]
 pub struct TagDescriptor {
@@ -46,7 +48,10 @@ impl EntityDescriptor for TagDescriptor {
             .route("/tags", get(list_tags).post(create_tag))
             .route(
                 "/tags/{id}",
-                get(get_tag).put(update_tag).delete(delete_tag),
+                get(get_tag)
+                    .put(update_tag)
+                    .patch(patch_tag)
+                    .delete(delete_tag),
             )
             .with_state(state)
     }
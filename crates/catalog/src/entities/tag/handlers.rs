@@ -1,9 +1,12 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Tag, TagStore};
+use super::{Tag, TagStore, TagStoreError};
 
 #[derive(Clone)]
 pub struct TagState {
@@ -11,50 +14,76 @@ pub struct TagState {
     pub entity_creator: Arc<dyn EntityCreator>,
 }
 
-pub async fn list_tags(State(state): State<TagState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List tags error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list tags",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_tags(
+    State(state): State<TagState>,
+    query: ListQuery,
+) -> Result<Json<Page<Tag>>, Problem> {
+    query.check::<Tag>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List tags error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
-pub async fn get_tag(State(state): State<TagState>, Path(id): Path<Uuid>) -> Json<Option<Tag>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+pub async fn get_tag(
+    State(state): State<TagState>,
+    Path(id): Path<Uuid>,
+) -> Result<Tagged<Tag>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_tag(
     State(state): State<TagState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating tag with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Tag created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create tag error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create tag",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<TagStoreError>(e))
         }
     }
 }
 
-pub async fn update_tag(State(state): State<TagState>, Json(tag): Json<Tag>) -> Json<Option<Tag>> {
-    let updated = state.store.update(tag).await.ok();
-    Json(updated)
+pub async fn update_tag(
+    State(state): State<TagState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(tag): Json<Tag>,
+) -> Result<Tagged<Tag>, Problem> {
+    check_id(id, tag.id)?;
+    let mut tag = validate_entity(&Tag::validation_config("update"), tag)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    tag.version = current.version;
+    tag.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(tag).await?))
 }
 
-pub async fn delete_tag(State(state): State<TagState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing tag
+pub async fn patch_tag(
+    State(state): State<TagState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Tag>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut tag = patch_entity(&Tag::validation_config("update"), current, patch)?;
+    tag.touch();
+    Ok(Tagged(state.store.update(tag).await?))
+}
+
+pub async fn delete_tag(
+    State(state): State<TagState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -8,3 +8,17 @@ pub use store::{InMemoryTagStore, TagStore, TagStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::TagDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::TagLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::TagMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::TagMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::TagNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::TagPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::TagScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::TagSqliteStore;
@@ -1,3 +1,4 @@
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -7,6 +8,8 @@ impl_data_entity_validated!(
     {
         color: Option<String>,
         description: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
@@ -27,3 +30,5 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(Tag);
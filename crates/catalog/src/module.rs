@@ -1,7 +1,8 @@
+use common::links::LinkRules;
 use std::sync::Arc;
 use this::core::module::Module;
 use this::prelude::LinksConfig;
-use this::prelude::{EntityCreator, EntityFetcher};
+use this::prelude::{EntityCreator, EntityFetcher, LinkService};
 use this::server::entity_registry::EntityRegistry;
 
 use crate::entities::category::CategoryStore;
@@ -10,6 +11,10 @@ use crate::entities::product::ProductStore;
 use crate::entities::product::descriptor::ProductDescriptor;
 use crate::entities::tag::TagStore;
 use crate::entities::tag::descriptor::TagDescriptor;
+use crate::services::CategoryHierarchy;
+
+/// The `links.yaml` of the module
+const LINKS_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
 
 // Combined trait for entity stores
 pub trait EntityStore: EntityFetcher + EntityCreator + Send + Sync {}
@@ -28,11 +33,36 @@ pub struct CatalogStores {
 
 pub struct CatalogModule {
     pub stores: CatalogStores,
+    pub hierarchy: Option<Arc<CategoryHierarchy>>,
 }
 
 impl CatalogModule {
     pub fn new(stores: CatalogStores) -> Self {
-        Self { stores }
+        Self {
+            stores,
+            hierarchy: None,
+        }
+    }
+
+    /// Enable `GET /categories/{id}/ancestors`, `/descendants` and
+    /// `/all_products`
+    ///
+    /// `link_service` is the one the host is built with. Add
+    /// [`CatalogModule::hierarchy`] to the host's `LinkIntegrity` as a check
+    /// to refuse the `has_parent` links that would make a cycle.
+    pub fn with_hierarchy(mut self, link_service: Arc<dyn LinkService>) -> Self {
+        self.hierarchy = Some(Arc::new(CategoryHierarchy::new(
+            self.stores.categories_store.clone(),
+            self.stores.products_store.clone(),
+            link_service,
+        )));
+        self
+    }
+
+    /// The `cardinality` and `on_delete` rules of the module's links, see
+    /// `common::links`
+    pub fn link_rules(&self) -> Result<LinkRules, anyhow::Error> {
+        LinkRules::from_yaml_file(LINKS_CONFIG)
     }
 }
 
@@ -51,8 +81,7 @@ impl Module for CatalogModule {
 
     fn links_config(&self) -> Result<LinksConfig, anyhow::Error> {
         // Load configuration from YAML file
-        let config_path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
-        LinksConfig::from_yaml_file(config_path)
+        LinksConfig::from_yaml_file(LINKS_CONFIG)
     }
 
     fn get_entity_fetcher(&self, entity_type: &str) -> Option<Arc<dyn EntityFetcher>> {
@@ -78,10 +107,13 @@ impl Module for CatalogModule {
             self.stores.products_store.clone(),
             self.stores.products_entity.clone(),
         )));
-        registry.register(Box::new(CategoryDescriptor::new_with_creator(
-            self.stores.categories_store.clone(),
-            self.stores.categories_entity.clone(),
-        )));
+        registry.register(Box::new(
+            CategoryDescriptor::new_with_creator(
+                self.stores.categories_store.clone(),
+                self.stores.categories_entity.clone(),
+            )
+            .with_hierarchy(self.hierarchy.clone()),
+        ));
         registry.register(Box::new(TagDescriptor::new_with_creator(
             self.stores.tags_store.clone(),
             self.stores.tags_entity.clone(),
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::links::LinkCheck;
+use common::{ErrorKind, HttpError, Problem};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use this::prelude::{LinkEntity, LinkService, Uuid};
+
+use crate::entities::category::{Category, CategoryStore, CategoryStoreError};
+use crate::entities::product::{Product, ProductStore, ProductStoreError};
+
+/// Link from a category to its parent
+pub const HAS_PARENT: &str = "has_parent";
+/// Link from a product to one of its categories
+pub const HAS_CATEGORY: &str = "has_category";
+
+#[derive(Debug, thiserror::Error)]
+pub enum HierarchyError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error(
+        "conflict: category {parent_id} is {category_id} or one of its descendants and cannot be its parent"
+    )]
+    Cycle { category_id: Uuid, parent_id: Uuid },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for HierarchyError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            HierarchyError::NotFound(_) => ErrorKind::NotFound,
+            HierarchyError::Cycle { .. } => ErrorKind::Conflict,
+            HierarchyError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+        if let HierarchyError::Cycle {
+            category_id,
+            parent_id,
+        } = self
+        {
+            extensions.insert("category_id".to_string(), category_id.to_string().into());
+            extensions.insert("parent_id".to_string(), parent_id.to_string().into());
+        }
+        extensions
+    }
+}
+
+impl From<CategoryStoreError> for HierarchyError {
+    fn from(e: CategoryStoreError) -> Self {
+        match e {
+            CategoryStoreError::NotFound(id) => {
+                HierarchyError::NotFound(format!("category {}", id))
+            }
+            e => HierarchyError::Other(e.into()),
+        }
+    }
+}
+
+/// A category with its descendants
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryTree {
+    #[serde(flatten)]
+    pub category: Category,
+    pub children: Vec<CategoryTree>,
+}
+
+/// Walks the category tree made of `has_parent` links, from a category to
+/// its parent
+///
+/// Only the `LinkService` trait is used, so any link backend will do. A
+/// `has_parent` link that would make a category its own ancestor is refused
+/// once the hierarchy is added to the host's `LinkIntegrity` as a
+/// [`LinkCheck`]. The walks still stop at categories already visited, so a
+/// cycle created before cannot make them loop.
+pub struct CategoryHierarchy {
+    categories: Arc<dyn CategoryStore>,
+    products: Arc<dyn ProductStore>,
+    link_service: Arc<dyn LinkService>,
+}
+
+impl CategoryHierarchy {
+    pub fn new(
+        categories: Arc<dyn CategoryStore>,
+        products: Arc<dyn ProductStore>,
+        link_service: Arc<dyn LinkService>,
+    ) -> Self {
+        Self {
+            categories,
+            products,
+            link_service,
+        }
+    }
+
+    /// The ancestors of `category_id` as a breadcrumb: the root first, its
+    /// parent last
+    pub async fn ancestors(&self, category_id: &Uuid) -> Result<Vec<Category>, HierarchyError> {
+        self.categories.get(category_id).await?;
+        let mut ancestors = Vec::new();
+        for id in self.ancestor_ids(*category_id).await? {
+            ancestors.push(self.categories.get(&id).await?);
+        }
+        ancestors.reverse();
+        Ok(ancestors)
+    }
+
+    /// `category_id` with all its descendants
+    pub async fn descendants(&self, category_id: &Uuid) -> Result<CategoryTree, HierarchyError> {
+        let root = self.categories.get(category_id).await?;
+        let children = self.subtree(*category_id).await?;
+        let mut categories = HashMap::new();
+        for id in children.values().flatten() {
+            match self.categories.get(id).await {
+                Ok(category) => {
+                    categories.insert(*id, category);
+                }
+                // A link left behind by a deleted category
+                Err(CategoryStoreError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(tree(root, &children, &mut categories))
+    }
+
+    /// The products in `category_id` or any of its descendants
+    pub async fn products(&self, category_id: &Uuid) -> Result<Vec<Product>, HierarchyError> {
+        self.categories.get(category_id).await?;
+        let subtree = self.subtree(*category_id).await?;
+        let mut seen = HashSet::new();
+        let mut products = Vec::new();
+        for id in subtree.keys() {
+            let links = self
+                .link_service
+                .find_by_target(id, Some(HAS_CATEGORY), Some("product"))
+                .await?;
+            for link in links {
+                if !seen.insert(link.source_id) {
+                    continue;
+                }
+                match self.products.get(&link.source_id).await {
+                    Ok(product) => products.push(product),
+                    Err(ProductStoreError::NotFound(_)) => {}
+                    Err(e) => return Err(anyhow::Error::new(e).into()),
+                }
+            }
+        }
+        Ok(products)
+    }
+
+    /// Fail if `parent_id` cannot become the parent of `category_id`, i.e.
+    /// is the category itself or one of its descendants
+    pub async fn check_parent(
+        &self,
+        category_id: &Uuid,
+        parent_id: &Uuid,
+    ) -> Result<(), HierarchyError> {
+        let cycle = HierarchyError::Cycle {
+            category_id: *category_id,
+            parent_id: *parent_id,
+        };
+        if category_id == parent_id {
+            return Err(cycle);
+        }
+        if self.ancestor_ids(*parent_id).await?.contains(category_id) {
+            return Err(cycle);
+        }
+        Ok(())
+    }
+
+    /// The ids of the ancestors of `category_id`, its parent first
+    async fn ancestor_ids(&self, category_id: Uuid) -> anyhow::Result<Vec<Uuid>> {
+        let mut ancestors = Vec::new();
+        let mut seen = HashSet::from([category_id]);
+        let mut current = category_id;
+        while let Some(parent) = self.parent_of(&current).await? {
+            if !seen.insert(parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = parent;
+        }
+        Ok(ancestors)
+    }
+
+    async fn parent_of(&self, category_id: &Uuid) -> anyhow::Result<Option<Uuid>> {
+        let links = self
+            .link_service
+            .find_by_source(category_id, Some(HAS_PARENT), Some("category"))
+            .await?;
+        Ok(links.first().map(|link| link.target_id))
+    }
+
+    /// The children of every category of the subtree of `category_id`,
+    /// itself included
+    async fn subtree(&self, category_id: Uuid) -> anyhow::Result<HashMap<Uuid, Vec<Uuid>>> {
+        let mut children = HashMap::new();
+        let mut queue = VecDeque::from([category_id]);
+        while let Some(id) = queue.pop_front() {
+            if children.contains_key(&id) {
+                continue;
+            }
+            let links = self
+                .link_service
+                .find_by_target(&id, Some(HAS_PARENT), Some("category"))
+                .await?;
+            let ids: Vec<Uuid> = links
+                .iter()
+                .map(|link| link.source_id)
+                .filter(|child| !children.contains_key(child))
+                .collect();
+            queue.extend(ids.iter().copied());
+            children.insert(id, ids);
+        }
+        Ok(children)
+    }
+}
+
+/// The tree of `category`, taking each descendant out of `categories` so
+/// that none appears twice
+fn tree(
+    category: Category,
+    children: &HashMap<Uuid, Vec<Uuid>>,
+    categories: &mut HashMap<Uuid, Category>,
+) -> CategoryTree {
+    let mut subtrees = Vec::new();
+    for id in children.get(&category.id).into_iter().flatten() {
+        if let Some(child) = categories.remove(id) {
+            subtrees.push(tree(child, children, categories));
+        }
+    }
+    CategoryTree {
+        category,
+        children: subtrees,
+    }
+}
+
+#[async_trait]
+impl LinkCheck for CategoryHierarchy {
+    async fn check_link(&self, link: &LinkEntity) -> Result<(), Problem> {
+        if link.link_type != HAS_PARENT {
+            return Ok(());
+        }
+        Ok(self.check_parent(&link.source_id, &link.target_id).await?)
+    }
+}
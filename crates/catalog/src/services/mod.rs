@@ -0,0 +1,3 @@
+pub mod hierarchy;
+
+pub use hierarchy::{CategoryHierarchy, CategoryTree, HierarchyError};
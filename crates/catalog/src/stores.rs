@@ -16,6 +16,65 @@ use crate::entities::tag::TagDynamoDBStore;
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::Client as DynamoDBClient;
 
+#[cfg(feature = "postgres")]
+use crate::entities::category::CategoryPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::product::ProductPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::tag::TagPostgresStore;
+#[cfg(feature = "postgres")]
+use sqlx::PgPool;
+
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::category::CategoryMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::product::ProductMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::tag::TagMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use mongodb::Database as MongoDatabase;
+
+#[cfg(feature = "neo4j")]
+use crate::entities::category::CategoryNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::product::ProductNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::tag::TagNeo4jStore;
+#[cfg(feature = "neo4j")]
+use neo4rs::Graph;
+
+#[cfg(feature = "scylladb")]
+use crate::entities::category::CategoryScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::product::ProductScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::tag::TagScyllaStore;
+#[cfg(feature = "scylladb")]
+use scylla::client::session::Session;
+
+#[cfg(feature = "mysql")]
+use crate::entities::category::CategoryMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::product::ProductMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::tag::TagMysqlStore;
+#[cfg(feature = "mysql")]
+use sqlx::MySqlPool;
+
+#[cfg(feature = "lmdb")]
+use crate::entities::category::CategoryLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::product::ProductLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::tag::TagLmdbStore;
+
+#[cfg(feature = "sqlite")]
+use crate::entities::category::CategorySqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::product::ProductSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::tag::TagSqliteStore;
+
 // ============================================================================
 // Store Factories
 // ============================================================================
@@ -37,6 +96,24 @@ impl CatalogStores {
         }
     }
 
+    /// Create in-memory stores journaled in `dir`, recovering what they held
+    /// when last stopped (see `common::journal`)
+    pub fn new_journal(dir: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let products = Arc::new(InMemoryProductStore::open(dir)?);
+        let categories = Arc::new(InMemoryCategoryStore::open(dir)?);
+        let tags = Arc::new(InMemoryTagStore::open(dir)?);
+
+        Ok(Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        })
+    }
+
     #[cfg(feature = "dynamodb")]
     /// Create stores with DynamoDB implementations
     pub fn new_dynamodb(
@@ -58,4 +135,128 @@ impl CatalogStores {
             tags_entity: tags,
         }
     }
+
+    #[cfg(feature = "postgres")]
+    /// Create stores with PostgreSQL implementations
+    pub fn new_postgres(pool: PgPool) -> Self {
+        let products = Arc::new(ProductPostgresStore::new(pool.clone()));
+        let categories = Arc::new(CategoryPostgresStore::new(pool.clone()));
+        let tags = Arc::new(TagPostgresStore::new(pool));
+
+        Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        }
+    }
+
+    #[cfg(feature = "mongodb_backend")]
+    /// Create stores with MongoDB implementations
+    pub fn new_mongodb(database: MongoDatabase) -> Self {
+        let products = Arc::new(ProductMongoStore::new(database.clone()));
+        let categories = Arc::new(CategoryMongoStore::new(database.clone()));
+        let tags = Arc::new(TagMongoStore::new(database));
+
+        Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        }
+    }
+
+    #[cfg(feature = "neo4j")]
+    /// Create stores with Neo4j implementations
+    pub fn new_neo4j(graph: Graph) -> Self {
+        let products = Arc::new(ProductNeo4jStore::new(graph.clone()));
+        let categories = Arc::new(CategoryNeo4jStore::new(graph.clone()));
+        let tags = Arc::new(TagNeo4jStore::new(graph));
+
+        Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        }
+    }
+
+    #[cfg(feature = "scylladb")]
+    /// Create stores with ScyllaDB implementations
+    pub fn new_scylladb(session: Arc<Session>, keyspace: impl Into<String>) -> Self {
+        let ks: String = keyspace.into();
+        let products = Arc::new(ProductScyllaStore::new(session.clone(), ks.clone()));
+        let categories = Arc::new(CategoryScyllaStore::new(session.clone(), ks.clone()));
+        let tags = Arc::new(TagScyllaStore::new(session, ks));
+
+        Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        }
+    }
+
+    #[cfg(feature = "mysql")]
+    /// Create stores with MySQL implementations
+    pub fn new_mysql(pool: MySqlPool) -> Self {
+        let products = Arc::new(ProductMysqlStore::new(pool.clone()));
+        let categories = Arc::new(CategoryMysqlStore::new(pool.clone()));
+        let tags = Arc::new(TagMysqlStore::new(pool));
+
+        Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        }
+    }
+
+    #[cfg(feature = "lmdb")]
+    /// Create stores with LMDB implementations, each entity in its own
+    /// environment under `path`
+    pub fn new_lmdb(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let products = Arc::new(ProductLmdbStore::open(path.join("products"))?);
+        let categories = Arc::new(CategoryLmdbStore::open(path.join("categories"))?);
+        let tags = Arc::new(TagLmdbStore::open(path.join("tags"))?);
+
+        Ok(Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    /// Create stores with SQLite implementations, in the database file at
+    /// `path`
+    pub async fn new_sqlite(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let pool = common::sqlite::connect(path).await?;
+        let products = Arc::new(ProductSqliteStore::new(pool.clone()));
+        let categories = Arc::new(CategorySqliteStore::new(pool.clone()));
+        let tags = Arc::new(TagSqliteStore::new(pool));
+
+        Ok(Self {
+            products_store: products.clone(),
+            products_entity: products,
+            categories_store: categories.clone(),
+            categories_entity: categories,
+            tags_store: tags.clone(),
+            tags_entity: tags,
+        })
+    }
 }
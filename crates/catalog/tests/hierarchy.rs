@@ -0,0 +1,191 @@
+//! The category tree of `has_parent` links, on in-memory stores
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use catalog::entities::category::Category;
+use catalog::entities::product::Product;
+use catalog::module::CatalogStores;
+use catalog::services::hierarchy::{HAS_CATEGORY, HAS_PARENT};
+use catalog::services::{CategoryHierarchy, CategoryTree, HierarchyError};
+use common::links::LinkCheck;
+use common::{Currency, ErrorKind, HttpError, Money, Version};
+use this::prelude::{LinkEntity, LinkService};
+use this::storage::InMemoryLinkService;
+use uuid::Uuid;
+
+struct Fixture {
+    stores: CatalogStores,
+    links: Arc<InMemoryLinkService>,
+    hierarchy: CategoryHierarchy,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let stores = CatalogStores::new_in_memory();
+        let links = Arc::new(InMemoryLinkService::new());
+        let hierarchy = CategoryHierarchy::new(
+            stores.categories_store.clone(),
+            stores.products_store.clone(),
+            links.clone(),
+        );
+        Self {
+            stores,
+            links,
+            hierarchy,
+        }
+    }
+
+    /// A category under `parent`
+    async fn category(&self, name: &str, parent: Option<Uuid>) -> Uuid {
+        let category = Category::new(
+            name.into(),
+            "active".into(),
+            name.to_lowercase(),
+            None,
+            None,
+            Version::default(),
+        );
+        let id = self
+            .stores
+            .categories_store
+            .create(category)
+            .await
+            .unwrap()
+            .id;
+        if let Some(parent) = parent {
+            self.link(HAS_PARENT, id, parent).await;
+        }
+        id
+    }
+
+    async fn product(&self, sku: &str, categories: &[Uuid]) -> Uuid {
+        let product = Product::new(
+            sku.into(),
+            "active".into(),
+            sku.into(),
+            Money::from_minor(1_000, Currency::EUR),
+            1,
+            None,
+            None,
+            Version::default(),
+        );
+        let id = self.stores.products_store.create(product).await.unwrap().id;
+        for category in categories {
+            self.link(HAS_CATEGORY, id, *category).await;
+        }
+        id
+    }
+
+    async fn link(&self, link_type: &str, source: Uuid, target: Uuid) {
+        self.links
+            .create(LinkEntity::new(link_type, source, target, None))
+            .await
+            .unwrap();
+    }
+}
+
+fn names(tree: &CategoryTree) -> Vec<String> {
+    let mut all = vec![tree.category.name.clone()];
+    for child in &tree.children {
+        all.extend(names(child));
+    }
+    all
+}
+
+#[tokio::test]
+async fn a_category_cannot_become_its_own_ancestor() {
+    let fixture = Fixture::new();
+    let root = fixture.category("Root", None).await;
+    let child = fixture.category("Child", Some(root)).await;
+    let grandchild = fixture.category("Grandchild", Some(child)).await;
+    let other = fixture.category("Other", None).await;
+
+    for (category, parent) in [(root, grandchild), (root, child), (child, child)] {
+        let error = fixture
+            .hierarchy
+            .check_parent(&category, &parent)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            HierarchyError::Cycle { category_id, parent_id }
+                if category_id == category && parent_id == parent
+        ));
+        assert_eq!(error.kind(), ErrorKind::Conflict);
+    }
+    assert!(
+        fixture
+            .hierarchy
+            .check_parent(&grandchild, &root)
+            .await
+            .is_ok()
+    );
+    assert!(fixture.hierarchy.check_parent(&root, &other).await.is_ok());
+}
+
+#[tokio::test]
+async fn only_parent_links_are_checked_for_cycles() {
+    let fixture = Fixture::new();
+    let root = fixture.category("Root", None).await;
+    let child = fixture.category("Child", Some(root)).await;
+
+    let cycle = LinkEntity::new(HAS_PARENT, root, child, None);
+    let problem = fixture.hierarchy.check_link(&cycle).await.unwrap_err();
+    let other = LinkEntity::new(HAS_CATEGORY, root, child, None);
+
+    assert_eq!(problem.status, StatusCode::CONFLICT);
+    assert!(fixture.hierarchy.check_link(&other).await.is_ok());
+}
+
+#[tokio::test]
+async fn ancestors_run_from_the_root_to_the_parent() {
+    let fixture = Fixture::new();
+    let root = fixture.category("Root", None).await;
+    let child = fixture.category("Child", Some(root)).await;
+    let grandchild = fixture.category("Grandchild", Some(child)).await;
+
+    let ancestors = fixture.hierarchy.ancestors(&grandchild).await.unwrap();
+    let missing = fixture.hierarchy.ancestors(&Uuid::new_v4()).await;
+
+    let names: Vec<&str> = ancestors.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["Root", "Child"]);
+    assert!(fixture.hierarchy.ancestors(&root).await.unwrap().is_empty());
+    assert!(matches!(missing, Err(HierarchyError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn descendants_and_their_products_cover_the_whole_subtree() {
+    let fixture = Fixture::new();
+    let root = fixture.category("Root", None).await;
+    let child = fixture.category("Child", Some(root)).await;
+    let grandchild = fixture.category("Grandchild", Some(child)).await;
+    let sibling = fixture.category("Sibling", Some(root)).await;
+    fixture.product("IN-BOTH", &[child, grandchild]).await;
+    fixture.product("DEEP", &[grandchild]).await;
+    fixture.product("ASIDE", &[sibling]).await;
+
+    let tree = fixture.hierarchy.descendants(&child).await.unwrap();
+    let products = fixture.hierarchy.products(&child).await.unwrap();
+
+    assert_eq!(names(&tree), ["Child", "Grandchild"]);
+    let mut skus: Vec<&str> = products.iter().map(|p| p.sku.as_str()).collect();
+    skus.sort();
+    assert_eq!(skus, ["DEEP", "IN-BOTH"]);
+    assert_eq!(fixture.hierarchy.products(&root).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn walks_stop_at_a_cycle_created_before_the_check() {
+    let fixture = Fixture::new();
+    let first = fixture.category("First", None).await;
+    let second = fixture.category("Second", Some(first)).await;
+    // As if linked by a backend without the check
+    fixture.link(HAS_PARENT, first, second).await;
+
+    let ancestors = fixture.hierarchy.ancestors(&first).await.unwrap();
+    let tree = fixture.hierarchy.descendants(&first).await.unwrap();
+
+    assert_eq!(ancestors.len(), 1);
+    assert_eq!(names(&tree), ["First", "Second"]);
+}
@@ -0,0 +1,539 @@
+//! The middleware enforcing the `auth:` policies on the REST routes
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, Method, StatusCode, Uri, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+use this::config::{EntityAuthConfig, LinksConfig};
+use this::core::link::LinkAuthConfig;
+use this::core::{AuthContext, AuthPolicy, AuthProvider};
+use this::prelude::EntityFetcher;
+use this::server::host::ServerHost;
+use uuid::Uuid;
+
+use super::OWNER_FIELD;
+use super::policy::{UnknownPolicy, allows, needs_owner, parse_policy};
+use crate::problem::{ErrorKind, Problem};
+
+/// Largest create payload read to record its owner, as axum's default limit
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Middleware checking the `auth:` policies of the host's `links.yaml`
+///
+/// Requests are matched against the routes the host builds:
+///
+/// | Route | Policy |
+/// |-------|--------|
+/// | `GET /{plural}`, `POST /{plural}` | entity `list`, `create` |
+/// | `GET`, `PUT`/`PATCH`, `DELETE /{plural}/{id}` | entity `get`, `update`, `delete` |
+/// | `GET /{plural}/{id}/links` | entity `list_links` |
+/// | `/{plural}/{id}/{action}` (e.g. `confirm`) | entity `get` for `GET`, `update` otherwise |
+/// | `/{plural}/{id}/{route_name}[/{target_id}]` | link `list`, `get`, `create`, `update`, `delete` |
+///
+/// Link policies are checked against the entity of the URL, and creating an
+/// entity through a link also takes the `create` policy of that entity.
+/// Nested routes check each link they go through. Other routes (health,
+/// GraphQL, WebSocket, ...) are left alone.
+///
+/// `owner` policies compare the user to the [`OWNER_FIELD`] of the entity,
+/// falling back to [`AuthProvider::is_owner`]. On a list, which has no single
+/// entity, a user only allowed as owner gets the entities they own. Creates
+/// record the user as the owner of the new entity; services may instead
+/// send the owner they act for.
+///
+/// Requests without credentials are rejected with `401 Unauthorized`,
+/// others that the policy does not allow with `403 Forbidden`. Admitted
+/// requests carry their `AuthContext` as an extension.
+pub struct Authorization<P> {
+    provider: Arc<P>,
+    pub(super) policies: Arc<Policies>,
+    fetchers: Arc<HashMap<String, Arc<dyn EntityFetcher>>>,
+}
+
+impl<P> Clone for Authorization<P> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            policies: self.policies.clone(),
+            fetchers: self.fetchers.clone(),
+        }
+    }
+}
+
+impl<P: AuthProvider + 'static> Authorization<P> {
+    /// Enforce the policies of `config`, reading owners with `fetchers`
+    /// (keyed by entity type)
+    pub fn new(
+        config: &LinksConfig,
+        fetchers: Arc<HashMap<String, Arc<dyn EntityFetcher>>>,
+        provider: P,
+    ) -> Result<Self, UnknownPolicy> {
+        Ok(Self {
+            provider: Arc::new(provider),
+            policies: Arc::new(Policies::from_config(config)?),
+            fetchers,
+        })
+    }
+
+    /// Enforce the merged policies of the modules of `host`
+    pub fn from_host(host: &ServerHost, provider: P) -> Result<Self, UnknownPolicy> {
+        Self::new(&host.config, host.entity_fetchers.clone(), provider)
+    }
+
+    /// Check the requests of `router`
+    pub fn layer(self, router: Router) -> Router {
+        router.layer(middleware::from_fn_with_state(self, authorize::<P>))
+    }
+
+    async fn admit(&self, mut request: Request) -> Result<Request, Response> {
+        let Some(plan) = self.policies.plan(request.method(), request.uri().path()) else {
+            return Ok(request);
+        };
+        let context = self.context(head(&request)).await?;
+        if let Some(user_id) = self.check(&context, &plan).await.map_err(respond)? {
+            scope_to_owner(&mut request, user_id);
+        }
+        if let Some(stamp) = plan.stamp {
+            request = stamp_owner(request, &context, stamp).await?;
+        }
+        request.extensions_mut().insert(context);
+        Ok(request)
+    }
+
+    /// Who sent the request of `head`
+    pub(super) async fn context(
+        &self,
+        head: axum::http::Request<()>,
+    ) -> Result<AuthContext, Response> {
+        self.provider
+            .extract_context(&head)
+            .await
+            .map_err(|e| respond(unauthorized(format!("invalid credentials: {}", e))))
+    }
+
+    /// Run the checks of `plan`
+    ///
+    /// Returns the user to restrict a list to, when they may only list
+    /// what they own.
+    pub(super) async fn check(
+        &self,
+        context: &AuthContext,
+        plan: &Plan<'_>,
+    ) -> Result<Option<Uuid>, Problem> {
+        let mut scope = None;
+        for check in &plan.checks {
+            let policy = &check.rule.policy;
+            if allows(policy, context, false) {
+                continue;
+            }
+            let as_owner = needs_owner(policy) && allows(policy, context, true);
+            match (as_owner, context.user_id(), check.resource) {
+                (true, Some(user_id), Some((entity_type, id)))
+                    if self.owns(user_id, entity_type, id).await =>
+                {
+                    continue;
+                }
+                (true, Some(user_id), None) if check.scoped => scope = Some(user_id),
+                _ => return Err(deny(context, check)),
+            }
+        }
+        Ok(scope)
+    }
+
+    async fn owns(&self, user_id: Uuid, entity_type: &str, id: Uuid) -> bool {
+        if let Some(fetcher) = self.fetchers.get(entity_type)
+            && let Ok(entity) = fetcher.fetch_as_json(&id).await
+            && entity.get(OWNER_FIELD).and_then(Value::as_str) == Some(&user_id.to_string())
+        {
+            return true;
+        }
+        self.provider
+            .is_owner(&user_id, &id, entity_type)
+            .await
+            .unwrap_or(false)
+    }
+}
+
+/// The request without its body, which is not `Sync` and so cannot be
+/// borrowed across the provider's `await`
+pub(super) fn head(request: &Request) -> axum::http::Request<()> {
+    let mut head = axum::http::Request::new(());
+    *head.method_mut() = request.method().clone();
+    *head.uri_mut() = request.uri().clone();
+    *head.headers_mut() = request.headers().clone();
+    head
+}
+
+async fn authorize<P: AuthProvider + 'static>(
+    State(authorization): State<Authorization<P>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authorization.admit(request).await {
+        Ok(request) => next.run(request).await,
+        Err(response) => response,
+    }
+}
+
+/// A policy and the name it was declared with
+pub(super) struct Rule {
+    name: String,
+    policy: AuthPolicy,
+}
+
+impl Rule {
+    fn parse(name: &str) -> Result<Self, UnknownPolicy> {
+        Ok(Self {
+            name: name.to_string(),
+            policy: parse_policy(name)?,
+        })
+    }
+}
+
+pub(super) struct EntityRules {
+    singular: String,
+    pub(super) list: Rule,
+    pub(super) get: Rule,
+    pub(super) create: Rule,
+    pub(super) update: Rule,
+    pub(super) delete: Rule,
+    list_links: Rule,
+}
+
+impl EntityRules {
+    fn parse(singular: &str, auth: &EntityAuthConfig) -> Result<Self, UnknownPolicy> {
+        Ok(Self {
+            singular: singular.to_string(),
+            list: Rule::parse(&auth.list)?,
+            get: Rule::parse(&auth.get)?,
+            create: Rule::parse(&auth.create)?,
+            update: Rule::parse(&auth.update)?,
+            delete: Rule::parse(&auth.delete)?,
+            list_links: Rule::parse(&auth.list_links)?,
+        })
+    }
+}
+
+/// The policies of a link route, from the entity it starts at
+pub(super) struct LinkRules {
+    link_type: String,
+    /// The entity type the route leads to
+    pub(super) next: String,
+    pub(super) list: Rule,
+    pub(super) get: Rule,
+    pub(super) create: Rule,
+    pub(super) update: Rule,
+    pub(super) delete: Rule,
+}
+
+impl LinkRules {
+    fn parse(link_type: &str, next: &str, auth: &LinkAuthConfig) -> Result<Self, UnknownPolicy> {
+        Ok(Self {
+            link_type: link_type.to_string(),
+            next: next.to_string(),
+            list: Rule::parse(&auth.list)?,
+            get: Rule::parse(&auth.get)?,
+            create: Rule::parse(&auth.create)?,
+            update: Rule::parse(&auth.update)?,
+            delete: Rule::parse(&auth.delete)?,
+        })
+    }
+}
+
+/// Every policy of a `links.yaml`, parsed once
+pub(super) struct Policies {
+    /// By plural, as in the routes
+    entities: HashMap<String, EntityRules>,
+    /// By entity type and route name, like the host's link route registry
+    links: HashMap<(String, String), LinkRules>,
+    /// By link type, from the source entity, with the source entity type
+    link_types: HashMap<String, (String, LinkRules)>,
+}
+
+impl Policies {
+    fn from_config(config: &LinksConfig) -> Result<Self, UnknownPolicy> {
+        let mut entities = HashMap::new();
+        for entity in &config.entities {
+            entities.insert(
+                entity.plural.clone(),
+                EntityRules::parse(&entity.singular, &entity.auth)?,
+            );
+        }
+
+        let mut links = HashMap::new();
+        let mut link_types = HashMap::new();
+        for link in &config.links {
+            let auth = link.auth.clone().unwrap_or_default();
+            link_types.insert(
+                link.link_type.clone(),
+                (
+                    link.source_type.clone(),
+                    LinkRules::parse(&link.link_type, &link.target_type, &auth)?,
+                ),
+            );
+            links.insert(
+                (link.source_type.clone(), link.forward_route_name.clone()),
+                LinkRules::parse(&link.link_type, &link.target_type, &auth)?,
+            );
+            links.insert(
+                (link.target_type.clone(), link.reverse_route_name.clone()),
+                LinkRules::parse(&link.link_type, &link.source_type, &auth)?,
+            );
+        }
+        Ok(Self {
+            entities,
+            links,
+            link_types,
+        })
+    }
+
+    fn link(&self, entity_type: &str, route_name: &str) -> Option<&LinkRules> {
+        self.links
+            .get(&(entity_type.to_string(), route_name.to_string()))
+    }
+
+    /// The plural and the rules of the entity type `singular`
+    pub(super) fn entity(&self, singular: &str) -> Option<(&str, &EntityRules)> {
+        self.entities
+            .iter()
+            .find(|(_, rules)| rules.singular == singular)
+            .map(|(plural, rules)| (plural.as_str(), rules))
+    }
+
+    /// The source entity type and the rules of the links of `link_type`
+    pub(super) fn link_type(&self, link_type: &str) -> Option<(&str, &LinkRules)> {
+        self.link_types
+            .get(link_type)
+            .map(|(source_type, rules)| (source_type.as_str(), rules))
+    }
+
+    /// The checks for `method` on `path`, or `None` for other routes
+    fn plan(&self, method: &Method, path: &str) -> Option<Plan<'_>> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let (plural, entity) = self.entities.get_key_value(segments[0])?;
+        let mut plan = Plan::default();
+
+        let Some(id) = segments.get(1) else {
+            match *method {
+                Method::GET => plan.check(&entity.list, "list", plural, None).scoped = true,
+                Method::POST => {
+                    plan.check(&entity.create, "create", plural, None);
+                    plan.stamp = Some(Stamp::Entity);
+                }
+                _ => return None,
+            }
+            return Some(plan);
+        };
+        let id = Uuid::parse_str(id).ok()?;
+        let resource = Some((entity.singular.as_str(), id));
+
+        match &segments[2..] {
+            [] => {
+                let (rule, operation) = match *method {
+                    Method::GET => (&entity.get, "get"),
+                    Method::PUT | Method::PATCH => (&entity.update, "update"),
+                    Method::DELETE => (&entity.delete, "delete"),
+                    _ => return None,
+                };
+                plan.check(rule, operation, plural, resource);
+            }
+            ["links"] => {
+                plan.check(&entity.list_links, "list links of", plural, resource);
+            }
+            [action] if self.link(&entity.singular, action).is_none() => {
+                let (rule, operation) = match *method {
+                    Method::GET => (&entity.get, "get"),
+                    _ => (&entity.update, "update"),
+                };
+                plan.check(rule, operation, plural, resource);
+            }
+            mut rest => {
+                let mut at = (entity.singular.as_str(), id);
+                while let [route_name, tail @ ..] = rest {
+                    let Some(link) = self.link(at.0, route_name) else {
+                        break;
+                    };
+                    let target = tail.first().and_then(|id| Uuid::parse_str(id).ok());
+                    let last = tail.len() <= 1;
+                    let (rule, operation) = match (last, method, target) {
+                        (false, _, _) | (true, &Method::GET, Some(_)) => (&link.get, "get"),
+                        (true, &Method::GET, None) => (&link.list, "list"),
+                        (true, &Method::POST, _) => (&link.create, "create"),
+                        (true, &Method::PUT | &Method::PATCH, _) => (&link.update, "update"),
+                        (true, &Method::DELETE, _) => (&link.delete, "delete"),
+                        _ => break,
+                    };
+                    plan.check(rule, operation, &link.link_type, Some(at)).link = true;
+                    if last && method == Method::POST && target.is_none() {
+                        // The entity created along with the link
+                        if let Some((_, created)) = self.entity(&link.next) {
+                            plan.check(&created.create, "create", &link.next, None);
+                        }
+                        plan.stamp = Some(Stamp::Linked);
+                    }
+                    match target {
+                        Some(target) => at = (link.next.as_str(), target),
+                        None => break,
+                    }
+                    rest = tail.get(1..).unwrap_or_default();
+                }
+            }
+        }
+        Some(plan)
+    }
+}
+
+/// What a request has to be allowed to do
+#[derive(Default)]
+pub(super) struct Plan<'a> {
+    checks: Vec<Check<'a>>,
+    /// Where a create records the owner of the new entity
+    stamp: Option<Stamp>,
+}
+
+impl<'a> Plan<'a> {
+    pub(super) fn check(
+        &mut self,
+        rule: &'a Rule,
+        operation: &'static str,
+        target: &'a str,
+        resource: Option<(&'a str, Uuid)>,
+    ) -> &mut Check<'a> {
+        self.checks.push(Check {
+            rule,
+            operation,
+            target,
+            resource,
+            scoped: false,
+            link: false,
+        });
+        self.checks.last_mut().expect("just pushed")
+    }
+}
+
+pub(super) struct Check<'a> {
+    rule: &'a Rule,
+    operation: &'static str,
+    /// The entity plural or link type, for messages
+    target: &'a str,
+    /// The entity whose owner `owner` policies look at
+    resource: Option<(&'a str, Uuid)>,
+    /// A list that may be narrowed to the user's own entities
+    pub(super) scoped: bool,
+    pub(super) link: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stamp {
+    /// The payload is the entity
+    Entity,
+    /// The payload carries the entity under `entity`, as link routes take it
+    Linked,
+}
+
+fn deny(context: &AuthContext, check: &Check<'_>) -> Problem {
+    let what = if check.link {
+        format!("{} links", check.target)
+    } else {
+        check.target.to_string()
+    };
+    let detail = format!(
+        "'{}' on {} requires {}",
+        check.operation, what, check.rule.name
+    );
+    match context {
+        AuthContext::Anonymous => unauthorized(detail),
+        _ => Problem::new(ErrorKind::Forbidden, detail).with("policy", check.rule.name.as_str()),
+    }
+}
+
+pub(super) fn unauthorized(detail: String) -> Problem {
+    Problem::new(ErrorKind::Unauthorized, detail)
+}
+
+/// The response of a refused request, asking for credentials on a `401`
+pub(super) fn respond(problem: Problem) -> Response {
+    let challenge = problem.status == StatusCode::UNAUTHORIZED;
+    let mut response = problem.into_response();
+    if challenge {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Add an equality filter on the owner to the query of a list request
+fn scope_to_owner(request: &mut Request, user_id: Uuid) {
+    let uri = request.uri();
+    let query = match uri.query().filter(|query| !query.is_empty()) {
+        Some(query) => format!("{}&{}={}", query, OWNER_FIELD, user_id),
+        None => format!("{}={}", OWNER_FIELD, user_id),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = format!("{}?{}", uri.path(), query).parse().ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *request.uri_mut() = uri;
+    }
+}
+
+/// Record the user as the owner of the entity a create payload describes
+///
+/// Services and admins without a user keep the owner they send; payloads
+/// that are not JSON objects are left for the handler to reject.
+async fn stamp_owner(
+    request: Request,
+    context: &AuthContext,
+    stamp: Stamp,
+) -> Result<Request, Response> {
+    if matches!(
+        context,
+        AuthContext::Service { .. } | AuthContext::Admin { .. }
+    ) {
+        return Ok(request);
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = to_bytes(body, BODY_LIMIT).await.map_err(|e| {
+        Problem::with_status(StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response()
+    })?;
+    let Ok(mut payload) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(Request::from_parts(parts, Body::from(bytes)));
+    };
+    let entity = match stamp {
+        Stamp::Entity => Some(&mut payload),
+        Stamp::Linked => payload.get_mut("entity"),
+    };
+    if let Some(entity) = entity {
+        record_owner(entity, context);
+    }
+
+    let body = serde_json::to_vec(&payload).unwrap_or_else(|_| bytes.to_vec());
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Ok(Request::from_parts(parts, Body::from(body)))
+}
+
+/// Record the user as the owner of a new `entity`, unless a service or an
+/// admin without a user creates it
+pub(super) fn record_owner(entity: &mut Value, context: &AuthContext) {
+    if matches!(
+        context,
+        AuthContext::Service { .. } | AuthContext::Admin { .. }
+    ) {
+        return;
+    }
+    if let Value::Object(fields) = entity {
+        match context.user_id() {
+            Some(user_id) => fields.insert(OWNER_FIELD.to_string(), user_id.to_string().into()),
+            None => fields.remove(OWNER_FIELD),
+        };
+    }
+}
@@ -0,0 +1,371 @@
+//! The `auth:` policies on the operations of a host, for the exposures that
+//! do not go through the REST routes (GraphQL, gRPC, WebSocket)
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use serde_json::Value;
+use this::core::link::LinkEntity;
+use this::core::{AuthContext, AuthProvider, LinkService};
+use this::prelude::{EntityCreator, EntityFetcher};
+use this::server::host::ServerHost;
+use uuid::Uuid;
+
+use super::OWNER_FIELD;
+use super::authorization::{
+    Authorization, EntityRules, LinkRules, Plan, Rule, head, record_owner, respond, unauthorized,
+};
+use super::policy::is_admin;
+use crate::problem::{ErrorKind, Problem};
+use crate::query::{Filter, FilterOp};
+use crate::store::with_list_filters;
+
+tokio::task_local! {
+    /// Who sent the request being handled, as [`Authorization::authenticate`]
+    /// found it
+    static CALLER: AuthContext;
+}
+
+/// The caller of the request being handled; anonymous outside of one
+fn caller() -> AuthContext {
+    CALLER
+        .try_with(Clone::clone)
+        .unwrap_or(AuthContext::Anonymous)
+}
+
+impl<P: AuthProvider + 'static> Authorization<P> {
+    /// Check every operation on the entities and links of `host` against the
+    /// policies, for the caller that [`Authorization::authenticate`] found
+    ///
+    /// Entity operations take the policies of the entity, as on the REST
+    /// routes, and link operations those of the link type, against its
+    /// source entity (its target for `find_by_target`). Lists only keep what
+    /// the caller may see. Outside of an authenticated request, every
+    /// operation is refused: background services keep the host's services
+    /// from before the guard.
+    pub fn guard(&self, host: &mut ServerHost) {
+        let fetchers = host
+            .entity_fetchers
+            .iter()
+            .map(|(entity_type, inner)| {
+                let fetcher: Arc<dyn EntityFetcher> = Arc::new(GuardedFetcher {
+                    entity_type: entity_type.clone(),
+                    inner: inner.clone(),
+                    authorization: self.clone(),
+                });
+                (entity_type.clone(), fetcher)
+            })
+            .collect();
+        let creators = host
+            .entity_creators
+            .iter()
+            .map(|(entity_type, inner)| {
+                let creator: Arc<dyn EntityCreator> = Arc::new(GuardedCreator {
+                    entity_type: entity_type.clone(),
+                    inner: inner.clone(),
+                    authorization: self.clone(),
+                });
+                (entity_type.clone(), creator)
+            })
+            .collect();
+        host.entity_fetchers = Arc::new(fetchers);
+        host.entity_creators = Arc::new(creators);
+        host.link_service = Arc::new(GuardedLinkService {
+            inner: host.link_service.clone(),
+            authorization: self.clone(),
+        });
+    }
+
+    /// Authenticate the requests of `router`, whose handlers then work on a
+    /// host guarded with [`Authorization::guard`] as their caller
+    ///
+    /// Requests without credentials are rejected with `401 Unauthorized`.
+    pub fn authenticate(self, router: Router) -> Router {
+        router.layer(middleware::from_fn_with_state(self, run_as_caller::<P>))
+    }
+
+    /// Check the `rule` of an entity operation for `context`
+    ///
+    /// Returns the user to restrict a list to, when they may only list what
+    /// they own.
+    async fn check_entity(
+        &self,
+        context: &AuthContext,
+        entity_type: &str,
+        operation: &'static str,
+        rule: fn(&EntityRules) -> &Rule,
+        id: Option<Uuid>,
+    ) -> Result<Option<Uuid>, Problem> {
+        let Some((plural, rules)) = self.policies.entity(entity_type) else {
+            return Ok(None);
+        };
+        let mut plan = Plan::default();
+        plan.check(
+            rule(rules),
+            operation,
+            plural,
+            id.map(|id| (entity_type, id)),
+        )
+        .scoped = operation == "list";
+        self.check(context, &plan).await
+    }
+
+    /// Check the `rule` of an operation on `link` for `context`, against its
+    /// target entity when `from_target`, its source otherwise
+    async fn check_link(
+        &self,
+        context: &AuthContext,
+        link: &LinkEntity,
+        operation: &'static str,
+        rule: fn(&LinkRules) -> &Rule,
+        from_target: bool,
+    ) -> Result<(), Problem> {
+        let Some((source_type, rules)) = self.policies.link_type(&link.link_type) else {
+            return Ok(());
+        };
+        let resource = if from_target {
+            (rules.next.as_str(), link.target_id)
+        } else {
+            (source_type, link.source_id)
+        };
+        let mut plan = Plan::default();
+        plan.check(rule(rules), operation, &link.link_type, Some(resource))
+            .link = true;
+        self.check(context, &plan).await.map(|_| ())
+    }
+
+    /// The `links` the caller may list
+    async fn visible(&self, links: Vec<LinkEntity>, from_target: bool) -> Vec<LinkEntity> {
+        let context = caller();
+        let mut visible = Vec::new();
+        for link in links {
+            let check = self.check_link(&context, &link, "list", |rules| &rules.list, from_target);
+            if check.await.is_ok() {
+                visible.push(link);
+            }
+        }
+        visible
+    }
+}
+
+async fn run_as_caller<P: AuthProvider + 'static>(
+    State(authorization): State<Authorization<P>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let context = match authorization.context(head(&request)).await {
+        Ok(AuthContext::Anonymous) => {
+            return respond(unauthorized("credentials required".to_string()));
+        }
+        Ok(context) => context,
+        Err(response) => return response,
+    };
+    CALLER.scope(context, next.run(request)).await
+}
+
+/// A fetcher of the host checking the `get` and `list` policies
+struct GuardedFetcher<P> {
+    entity_type: String,
+    inner: Arc<dyn EntityFetcher>,
+    authorization: Authorization<P>,
+}
+
+#[async_trait]
+impl<P: AuthProvider + 'static> EntityFetcher for GuardedFetcher<P> {
+    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<Value> {
+        let context = caller();
+        self.authorization
+            .check_entity(
+                &context,
+                &self.entity_type,
+                "get",
+                |rules| &rules.get,
+                Some(*entity_id),
+            )
+            .await?;
+        self.inner.fetch_as_json(entity_id).await
+    }
+
+    async fn list_as_json(&self, limit: Option<i32>, offset: Option<i32>) -> Result<Vec<Value>> {
+        let context = caller();
+        let scope = self
+            .authorization
+            .check_entity(
+                &context,
+                &self.entity_type,
+                "list",
+                |rules| &rules.list,
+                None,
+            )
+            .await?;
+        let Some(user_id) = scope else {
+            return self.inner.list_as_json(limit, offset).await;
+        };
+        // Only the user's own entities, picked out by the store's query before
+        // it pages
+        let owner = user_id.to_string();
+        let filter = Filter {
+            field: OWNER_FIELD.to_string(),
+            op: FilterOp::Eq,
+            value: owner.clone(),
+        };
+        let owned = with_list_filters(vec![filter], self.inner.list_as_json(limit, offset)).await?;
+        // A fetcher not listing through `store::list_as_json` ignores the
+        // filter: never hand out what it let through
+        Ok(owned
+            .into_iter()
+            .filter(|entity| entity.get(OWNER_FIELD).and_then(Value::as_str) == Some(&owner))
+            .collect())
+    }
+}
+
+/// A creator of the host checking the `create`, `update` and `delete`
+/// policies, and recording the user as the owner of what they create
+struct GuardedCreator<P> {
+    entity_type: String,
+    inner: Arc<dyn EntityCreator>,
+    authorization: Authorization<P>,
+}
+
+#[async_trait]
+impl<P: AuthProvider + 'static> EntityCreator for GuardedCreator<P> {
+    async fn create_from_json(&self, mut entity_data: Value) -> Result<Value> {
+        let context = caller();
+        self.authorization
+            .check_entity(
+                &context,
+                &self.entity_type,
+                "create",
+                |rules| &rules.create,
+                None,
+            )
+            .await?;
+        record_owner(&mut entity_data, &context);
+        self.inner.create_from_json(entity_data).await
+    }
+
+    async fn update_from_json(&self, entity_id: &Uuid, entity_data: Value) -> Result<Value> {
+        let context = caller();
+        self.authorization
+            .check_entity(
+                &context,
+                &self.entity_type,
+                "update",
+                |rules| &rules.update,
+                Some(*entity_id),
+            )
+            .await?;
+        self.inner.update_from_json(entity_id, entity_data).await
+    }
+
+    async fn delete(&self, entity_id: &Uuid) -> Result<()> {
+        let context = caller();
+        self.authorization
+            .check_entity(
+                &context,
+                &self.entity_type,
+                "delete",
+                |rules| &rules.delete,
+                Some(*entity_id),
+            )
+            .await?;
+        self.inner.delete(entity_id).await
+    }
+}
+
+/// The link service of the host checking the policies of each link type
+struct GuardedLinkService<P> {
+    inner: Arc<dyn LinkService>,
+    authorization: Authorization<P>,
+}
+
+#[async_trait]
+impl<P: AuthProvider + 'static> LinkService for GuardedLinkService<P> {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        self.authorization
+            .check_link(&caller(), &link, "create", |rules| &rules.create, false)
+            .await?;
+        self.inner.create(link).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        let Some(link) = self.inner.get(id).await? else {
+            return Ok(None);
+        };
+        self.authorization
+            .check_link(&caller(), &link, "get", |rules| &rules.get, false)
+            .await?;
+        Ok(Some(link))
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        let links = self.inner.list().await?;
+        Ok(self.authorization.visible(links, false).await)
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        let links = self
+            .inner
+            .find_by_source(source_id, link_type, target_type)
+            .await?;
+        Ok(self.authorization.visible(links, false).await)
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        let links = self
+            .inner
+            .find_by_target(target_id, link_type, source_type)
+            .await?;
+        Ok(self.authorization.visible(links, true).await)
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        let context = caller();
+        if let Some(stored) = self.inner.get(id).await? {
+            self.authorization
+                .check_link(&context, &stored, "update", |rules| &rules.update, false)
+                .await?;
+        }
+        self.authorization
+            .check_link(&context, &link, "update", |rules| &rules.update, false)
+            .await?;
+        self.inner.update(id, link).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        if let Some(stored) = self.inner.get(id).await? {
+            self.authorization
+                .check_link(&caller(), &stored, "delete", |rules| &rules.delete, false)
+                .await?;
+        }
+        self.inner.delete(id).await
+    }
+
+    /// Only for admins and services: the links may be of any type
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        let context = caller();
+        if !(is_admin(&context) || context.is_service()) {
+            return Err(Problem::new(
+                ErrorKind::Forbidden,
+                "deleting every link of an entity requires admin_only or service_only",
+            )
+            .into());
+        }
+        self.inner.delete_by_entity(entity_id).await
+    }
+}
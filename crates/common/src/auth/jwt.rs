@@ -0,0 +1,121 @@
+//! Bearer JSON Web Tokens signed with a shared key
+
+use std::future::ready;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use axum::http::{HeaderMap, Request, header};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use this::core::{AuthContext, AuthProvider};
+use uuid::Uuid;
+
+use super::ContextFuture;
+
+/// The claims of the tokens [`JwtAuth`] reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user
+    pub sub: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<Uuid>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Expiry, in seconds since the epoch
+    pub exp: u64,
+}
+
+impl Claims {
+    /// Claims for `user_id` with `roles`, valid for `ttl`
+    pub fn new(user_id: Uuid, roles: Vec<String>, ttl: Duration) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            sub: user_id,
+            tenant_id: None,
+            roles,
+            exp: (now + ttl).as_secs(),
+        }
+    }
+}
+
+/// Users authenticated by an `Authorization: Bearer <jwt>` header
+///
+/// Tokens are HS256 JWTs signed with a key shared with whoever issues them;
+/// [`JwtAuth::sign`] issues tokens with the same key, for tests and local
+/// development. A request without the header is anonymous, one with an
+/// invalid or expired token is rejected.
+pub struct JwtAuth {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtAuth {
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            validation: Validation::new(Algorithm::HS256),
+        }
+    }
+
+    /// A token carrying `claims`
+    pub fn sign(&self, claims: &Claims) -> Result<String> {
+        Ok(jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &self.encoding,
+        )?)
+    }
+
+    /// The claims of `token`, if it is signed with the key and not expired
+    pub fn verify(&self, token: &str) -> Result<Claims> {
+        Ok(jsonwebtoken::decode::<Claims>(token, &self.decoding, &self.validation)?.claims)
+    }
+
+    fn context(&self, headers: &HeaderMap) -> Result<AuthContext> {
+        let Some(value) = headers.get(header::AUTHORIZATION) else {
+            return Ok(AuthContext::Anonymous);
+        };
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| anyhow!("expected a bearer token"))?;
+        let claims = self.verify(token.trim())?;
+        Ok(AuthContext::User {
+            user_id: claims.sub,
+            tenant_id: claims.tenant_id.unwrap_or_default(),
+            roles: claims.roles,
+        })
+    }
+}
+
+#[async_trait]
+impl AuthProvider for JwtAuth {
+    fn extract_context<'life0, 'life1, 'async_trait, B>(
+        &'life0 self,
+        req: &'life1 Request<B>,
+    ) -> ContextFuture<'async_trait>
+    where
+        B: 'async_trait,
+        'life0: 'async_trait,
+        'life1: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(ready(self.context(req.headers())))
+    }
+
+    /// Ownership is recorded on the entities, not in the tokens
+    async fn is_owner(&self, _: &Uuid, _: &Uuid, _: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Roles are only known from the token, see [`Claims::roles`]
+    async fn has_role(&self, _: &Uuid, _: &str) -> Result<bool> {
+        Ok(false)
+    }
+}
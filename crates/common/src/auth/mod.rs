@@ -0,0 +1,105 @@
+//! Authentication and the `auth:` policies of `links.yaml`
+//!
+//! Each module declares who may do what with its entities and links:
+//!
+//! ```yaml
+//! entities:
+//!   - singular: invoice
+//!     plural: invoices
+//!     auth:
+//!       list: authenticated
+//!       create: service_only
+//!       delete: admin_only
+//! ```
+//!
+//! [`Authorization`] enforces these policies on the REST routes of a host,
+//! and, with [`Authorization::guard`], on the operations of the host that the
+//! other exposures call.
+//! An [`AuthProvider`] tells who sent the request:
+//!
+//! - [`JwtAuth`]: users, from an `Authorization: Bearer <jwt>` header
+//! - [`ServiceTokenAuth`]: services, from an `X-Service-Token` header
+//! - [`AnyOf`]: the first of two providers that recognizes the request
+//!
+//! ```ignore
+//! let provider = AnyOf(
+//!     ServiceTokenAuth::new().with_token("billing", service_token),
+//!     JwtAuth::hs256(secret.as_bytes()),
+//! );
+//! let app = Authorization::from_host(&host, provider)?
+//!     .layer(RestExposure::build_router(host.clone(), vec![])?);
+//! ```
+//!
+//! The owner of an entity is the user recorded in its [`OWNER_FIELD`] when
+//! it was created.
+
+mod authorization;
+mod guard;
+mod jwt;
+mod policy;
+mod service_token;
+
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::http::Request;
+use uuid::Uuid;
+
+pub use authorization::Authorization;
+pub use jwt::{Claims, JwtAuth};
+pub use policy::{ADMIN_ROLE, UnknownPolicy, allows, is_admin, needs_owner, parse_policy};
+pub use service_token::{SERVICE_TOKEN_HEADER, ServiceTokenAuth};
+pub use this::core::{AuthContext, AuthPolicy, AuthProvider, NoAuthProvider};
+
+/// Field of every entity holding the user who owns it
+pub const OWNER_FIELD: &str = "owned_by";
+
+/// What `AuthProvider::extract_context` returns once `#[async_trait]` expands it
+///
+/// The providers here write the method out instead of using `async fn`: the
+/// future must not hold the request, whose body type is not required to be
+/// `Sync`.
+type ContextFuture<'a> = Pin<Box<dyn Future<Output = Result<AuthContext>> + Send + 'a>>;
+
+/// Authenticate with `A`, or with `B` when `A` finds no credentials
+pub struct AnyOf<A, B>(pub A, pub B);
+
+#[async_trait]
+impl<A: AuthProvider, B: AuthProvider> AuthProvider for AnyOf<A, B> {
+    fn extract_context<'life0, 'life1, 'async_trait, R>(
+        &'life0 self,
+        req: &'life1 Request<R>,
+    ) -> ContextFuture<'async_trait>
+    where
+        R: 'async_trait,
+        'life0: 'async_trait,
+        'life1: 'async_trait,
+        Self: 'async_trait,
+    {
+        // Neither future holds the request; the second only runs if needed
+        let first = self.0.extract_context(req);
+        let second = self.1.extract_context(req);
+        Box::pin(async move {
+            match first.await? {
+                AuthContext::Anonymous => second.await,
+                context => Ok(context),
+            }
+        })
+    }
+
+    async fn is_owner(
+        &self,
+        user_id: &Uuid,
+        resource_id: &Uuid,
+        resource_type: &str,
+    ) -> Result<bool> {
+        Ok(self.0.is_owner(user_id, resource_id, resource_type).await?
+            || self.1.is_owner(user_id, resource_id, resource_type).await?)
+    }
+
+    async fn has_role(&self, user_id: &Uuid, role: &str) -> Result<bool> {
+        Ok(self.0.has_role(user_id, role).await? || self.1.has_role(user_id, role).await?)
+    }
+}
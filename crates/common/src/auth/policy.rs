@@ -0,0 +1,83 @@
+//! The policies of `links.yaml` and how a request context satisfies them
+
+use this::core::{AuthContext, AuthPolicy};
+
+/// Role that grants `admin_only` and `role:admin` to a user
+pub const ADMIN_ROLE: &str = "admin";
+
+/// A policy name of `links.yaml` that is not one of [`parse_policy`]'s
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown auth policy '{0}'")]
+pub struct UnknownPolicy(pub String);
+
+/// Read a policy of an `auth:` block
+///
+/// | Name | Allowed |
+/// |------|---------|
+/// | `public` | everyone, even without credentials |
+/// | `authenticated` | any user or service |
+/// | `owner` | the user who owns the entity |
+/// | `service_only` | services |
+/// | `admin_only` | admins |
+/// | `role:<role>` | users with the role |
+/// | `owner_or_role:<role>` | the owner, or users with the role |
+/// | `owner_or_service` | the owner, or services |
+///
+/// Unlike `AuthPolicy::parse_policy`, which falls back to `authenticated`,
+/// an unknown name is an error so that a typo cannot open a route.
+pub fn parse_policy(name: &str) -> Result<AuthPolicy, UnknownPolicy> {
+    let policy = match name.trim() {
+        "public" => AuthPolicy::Public,
+        "authenticated" => AuthPolicy::Authenticated,
+        "owner" => AuthPolicy::Owner,
+        "service_only" => AuthPolicy::ServiceOnly,
+        "admin_only" => AuthPolicy::AdminOnly,
+        "owner_or_service" => AuthPolicy::Or(vec![AuthPolicy::Owner, AuthPolicy::ServiceOnly]),
+        other => match other.split_once(':') {
+            Some(("role", role)) if !role.is_empty() => AuthPolicy::HasRole(vec![role.to_string()]),
+            Some(("owner_or_role", role)) if !role.is_empty() => AuthPolicy::Or(vec![
+                AuthPolicy::Owner,
+                AuthPolicy::HasRole(vec![role.to_string()]),
+            ]),
+            _ => return Err(UnknownPolicy(name.to_string())),
+        },
+    };
+    Ok(policy)
+}
+
+/// Whether `context` satisfies `policy`, `owns` telling whether it is the
+/// owner of the entity the request is about
+///
+/// Admins hold every role, and users with the [`ADMIN_ROLE`] are admins.
+pub fn allows(policy: &AuthPolicy, context: &AuthContext, owns: bool) -> bool {
+    match policy {
+        AuthPolicy::Public => true,
+        AuthPolicy::Authenticated => !matches!(context, AuthContext::Anonymous),
+        AuthPolicy::Owner => owns || matches!(context, AuthContext::Owner { .. }),
+        AuthPolicy::HasRole(roles) => {
+            is_admin(context) || roles.iter().any(|role| has_role(context, role))
+        }
+        AuthPolicy::ServiceOnly => context.is_service(),
+        AuthPolicy::AdminOnly => is_admin(context),
+        AuthPolicy::And(policies) => policies.iter().all(|p| allows(p, context, owns)),
+        AuthPolicy::Or(policies) => policies.iter().any(|p| allows(p, context, owns)),
+        AuthPolicy::Custom(check) => check(context),
+    }
+}
+
+/// Whether `policy` can only be satisfied by knowing the owner of the entity
+pub fn needs_owner(policy: &AuthPolicy) -> bool {
+    match policy {
+        AuthPolicy::Owner => true,
+        AuthPolicy::And(policies) | AuthPolicy::Or(policies) => policies.iter().any(needs_owner),
+        _ => false,
+    }
+}
+
+pub fn is_admin(context: &AuthContext) -> bool {
+    context.is_admin() || has_role(context, ADMIN_ROLE)
+}
+
+fn has_role(context: &AuthContext, role: &str) -> bool {
+    matches!(context, AuthContext::User { roles, .. } if roles.iter().any(|r| r == role))
+}
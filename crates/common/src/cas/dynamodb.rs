@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
+use serde::Serialize;
+use serde_json::Value;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::CompareAndSwap;
+use crate::version::{Version, Versioned};
+
+/// Conditional `PutItem`s and `DeleteItem`s on the table of a
+/// `DynamoDBDataService`
+///
+/// The same writes can be staged with [`DynamoDBCas::swap_write`] and
+/// [`DynamoDBCas::create_write`], and made together, on any tables of the
+/// client, with [`DynamoDBCas::transact`].
+#[derive(Clone)]
+pub struct DynamoDBCas {
+    client: Client,
+    table_name: String,
+}
+
+/// Outcome of [`DynamoDBCas::transact`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Transacted {
+    /// Every write was made
+    Written,
+    /// Nothing was written: the writes at these positions failed their
+    /// condition
+    Rejected(Vec<usize>),
+}
+
+impl DynamoDBCas {
+    pub fn new(client: Client, table_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            table_name: table_name.into(),
+        }
+    }
+
+    /// The write of [`CompareAndSwap::swap`], for [`DynamoDBCas::transact`]
+    pub fn swap_write<T>(&self, expected: Version, entity: &T) -> Result<TransactWriteItem>
+    where
+        T: Serialize,
+    {
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(item(entity)?))
+            .condition_expression(swap_condition(expected))
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_values(":expected", AttributeValue::N(expected.to_string()))
+            .build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    /// The write of [`CompareAndSwap::create`], for [`DynamoDBCas::transact`]
+    pub fn create_write<T: Serialize>(&self, entity: &T) -> Result<TransactWriteItem> {
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(item(entity)?))
+            .condition_expression("attribute_not_exists(id)")
+            .build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    /// Make all of `writes` or none of them, in one `TransactWriteItems`
+    pub async fn transact(&self, writes: Vec<TransactWriteItem>) -> Result<Transacted> {
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(writes))
+            .send()
+            .await;
+        let e = match result {
+            Ok(_) => return Ok(Transacted::Written),
+            Err(e) => e,
+        };
+        if let Some(TransactWriteItemsError::TransactionCanceledException(cancelled)) =
+            e.as_service_error()
+        {
+            let rejected: Vec<usize> = cancelled
+                .cancellation_reasons()
+                .iter()
+                .enumerate()
+                .filter(|(_, reason)| reason.code() == Some("ConditionalCheckFailed"))
+                .map(|(position, _)| position)
+                .collect();
+            if !rejected.is_empty() {
+                return Ok(Transacted::Rejected(rejected));
+            }
+        }
+        Err(e.into())
+    }
+}
+
+fn swap_condition(expected: Version) -> &'static str {
+    if expected == Version::default() {
+        // Items stored before they were versioned have no `version`
+        "attribute_exists(id) AND (attribute_not_exists(#version) OR #version = :expected)"
+    } else {
+        "attribute_exists(id) AND #version = :expected"
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for DynamoDBCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item(entity)?))
+            .condition_expression(swap_condition(expected))
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_values(":expected", AttributeValue::N(expected.to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item(entity)?))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let delete = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("id", AttributeValue::S(id.to_string()));
+        let delete = match expected {
+            Some(expected) => delete
+                .condition_expression(swap_condition(expected))
+                .expression_attribute_names("#version", "version")
+                .expression_attribute_values(":expected", AttributeValue::N(expected.to_string())),
+            None => delete.condition_expression("attribute_exists(id)"),
+        };
+        match delete.send().await {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The item `DynamoDBDataService` stores for `entity`: strings, numbers and
+/// booleans as such, lists of them as lists, other values as JSON strings and
+/// `null`s left out
+fn item<T: Serialize>(entity: &T) -> Result<HashMap<String, AttributeValue>> {
+    let json = serde_json::to_value(entity)?;
+    let mut item = HashMap::new();
+    for (key, value) in json.as_object().into_iter().flatten() {
+        let attribute = match value {
+            Value::Null => continue,
+            Value::Array(values) => {
+                let values: Vec<AttributeValue> = values.iter().filter_map(scalar).collect();
+                if values.is_empty() {
+                    continue;
+                }
+                AttributeValue::L(values)
+            }
+            other => scalar(other).unwrap_or_else(|| AttributeValue::S(other.to_string())),
+        };
+        item.insert(key.clone(), attribute);
+    }
+    Ok(item)
+}
+
+fn scalar(value: &Value) -> Option<AttributeValue> {
+    match value {
+        Value::String(text) => Some(AttributeValue::S(text.clone())),
+        Value::Number(number) => number
+            .as_f64()
+            .map(|number| AttributeValue::N(number.to_string())),
+        Value::Bool(flag) => Some(AttributeValue::Bool(*flag)),
+        _ => None,
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use this::core::{Data, DataService};
+use this::storage::LmdbDataService;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::CompareAndSwap;
+use crate::version::{Version, Versioned};
+
+/// The write lock of each environment opened by this process, by path
+static LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Compare and write under the lock of the environment
+///
+/// LMDB serializes its write transactions but `LmdbDataService` reads and
+/// writes in separate ones, so the comparison is only atomic among the
+/// writers of this process. Every write to the environment, plain creates,
+/// updates and deletes included, takes the same lock: `LmdbCas` is also the
+/// `DataService` of `LmdbStore`.
+pub struct LmdbCas<T: Data> {
+    service: Arc<LmdbDataService<T>>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl<T: Data> LmdbCas<T> {
+    /// Open the environment at `path`, created if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let service = Arc::new(LmdbDataService::open(path)?);
+        let lock = LOCKS
+            .lock()
+            .map_err(|_| anyhow::anyhow!("LMDB lock registry poisoned"))?
+            .entry(path.canonicalize()?)
+            .or_default()
+            .clone();
+        Ok(Self { service, lock })
+    }
+}
+
+impl<T: Data> Clone for LmdbCas<T> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            lock: self.lock.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for LmdbCas<T>
+where
+    T: Data + Serialize + DeserializeOwned + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        match self.service.get(&entity.id()).await? {
+            Some(stored) if stored.version() == expected => {
+                self.service.update(&entity.id(), entity.clone()).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        if self.service.get(&entity.id()).await?.is_some() {
+            return Ok(false);
+        }
+        self.service.create(entity.clone()).await?;
+        Ok(true)
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let _guard = self.lock.lock().await;
+        match self.service.get(id).await? {
+            Some(stored) if expected.is_none_or(|expected| stored.version() == expected) => {
+                self.service.delete(id).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> DataService<T> for LmdbCas<T>
+where
+    T: Data + Serialize + DeserializeOwned,
+{
+    async fn create(&self, entity: T) -> Result<T> {
+        let _guard = self.lock.lock().await;
+        self.service.create(entity).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<T>> {
+        self.service.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<T>> {
+        self.service.list().await
+    }
+
+    async fn update(&self, id: &Uuid, entity: T) -> Result<T> {
+        let _guard = self.lock.lock().await;
+        self.service.update(id, entity).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        self.service.delete(id).await
+    }
+
+    async fn search(&self, field: &str, value: &str) -> Result<Vec<T>> {
+        self.service.search(field, value).await
+    }
+}
@@ -0,0 +1,150 @@
+//! Compare-and-swap writes for the database backends
+//!
+//! The `DataService` of each backend overwrites an entity unconditionally.
+//! The handles in this module write it only while the stored entity is still
+//! at the version the update was made against, in a single conditional
+//! statement where the database has one:
+//!
+//! | Backend | Condition |
+//! |---------|-----------|
+//! | PostgreSQL, MySQL | `UPDATE ... WHERE` on the `version` in the JSON data |
+//! | MongoDB | `replaceOne` filtered on `version` |
+//! | Neo4j | `MATCH ... WHERE n.version = $expected SET ...` |
+//! | DynamoDB | `PutItem` with a condition expression |
+//! | ScyllaDB | lightweight transaction (`UPDATE ... IF entity_data = ?`) |
+//! | LMDB | read and write under a lock of the environment |
+//! | SQLite | `UPDATE ... WHERE` on the `version` in the JSON data |
+//!
+//! Creates and deletes are conditioned the same way: a create fails on the
+//! database's own duplicate key (see [`CompareAndSwap::create`]) and a delete
+//! reports whether there was a row to delete, at the version asked for (see
+//! [`CompareAndSwap::delete`]). Stores go through [`swap`], which bumps the
+//! version and tells a missing entity from a stale one. On DynamoDB, writes
+//! to several entities can also be made together, see
+//! `DynamoDBCas::transact`.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use this::core::{Data, DataService};
+use uuid::Uuid;
+
+use crate::version::{Version, VersionMismatch, Versioned};
+
+#[cfg(feature = "dynamodb")]
+mod dynamodb;
+#[cfg(feature = "lmdb")]
+mod lmdb;
+#[cfg(feature = "mongodb_backend")]
+mod mongodb;
+#[cfg(feature = "mysql")]
+mod mysql;
+#[cfg(feature = "neo4j")]
+mod neo4j;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "scylladb")]
+mod scylla;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "dynamodb")]
+pub use dynamodb::{DynamoDBCas, Transacted};
+#[cfg(feature = "lmdb")]
+pub use lmdb::LmdbCas;
+#[cfg(feature = "mongodb_backend")]
+pub use mongodb::MongoCas;
+#[cfg(feature = "mysql")]
+pub use mysql::MysqlCas;
+#[cfg(feature = "neo4j")]
+pub use neo4j::Neo4jCas;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresCas;
+#[cfg(feature = "scylladb")]
+pub use scylla::ScyllaCas;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteCas;
+
+/// Conditional writes of an entity
+#[async_trait]
+pub trait CompareAndSwap<T>: Send + Sync {
+    /// Write `entity` if the stored one exists and is at `expected`
+    ///
+    /// Returns `false`, without writing, otherwise.
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool>;
+
+    /// Write `entity` if no stored entity has its id
+    ///
+    /// Returns `false`, without writing, otherwise.
+    async fn create(&self, entity: &T) -> Result<bool>;
+
+    /// Delete the entity `id` if it exists and, when `expected` is given, is
+    /// at that version
+    ///
+    /// Returns `false`, without deleting, otherwise.
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool>;
+}
+
+/// Outcome of [`swap`]
+#[derive(Debug)]
+pub enum Swapped<T> {
+    /// The entity was written, at its next version
+    Written(T),
+    /// There is no such entity
+    Missing,
+    /// The entity was updated since it was read
+    Stale(VersionMismatch),
+}
+
+/// Write `entity` at the next version, provided the store still holds the
+/// version it carries
+pub async fn swap<T, C, S>(cas: &C, service: &S, mut entity: T) -> Result<Swapped<T>>
+where
+    T: Data + Versioned,
+    C: CompareAndSwap<T> + ?Sized,
+    S: DataService<T> + ?Sized,
+{
+    let expected = entity.version();
+    entity.set_version(expected.next());
+    if cas.swap(expected, &entity).await? {
+        return Ok(Swapped::Written(entity));
+    }
+    Ok(match service.get(&entity.id()).await? {
+        None => Swapped::Missing,
+        Some(current) => Swapped::Stale(VersionMismatch {
+            expected,
+            current: current.version(),
+        }),
+    })
+}
+
+/// The JSON of `entity` without the fields the SQL backends keep in their own
+/// columns, as they store it in `data`
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+fn sql_data<T: serde::Serialize>(entity: &T) -> Result<serde_json::Value> {
+    const COLUMNS: [&str; 7] = [
+        "id",
+        "name",
+        "status",
+        "tenant_id",
+        "created_at",
+        "updated_at",
+        "deleted_at",
+    ];
+    let mut data = serde_json::to_value(entity)?;
+    if let Some(fields) = data.as_object_mut() {
+        for column in COLUMNS {
+            fields.remove(column);
+        }
+    }
+    Ok(data)
+}
+
+/// Whether an insert was made, rather than refused on a duplicate key
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+fn inserted<R>(result: Result<R, sqlx::Error>) -> Result<bool> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use mongodb::Database;
+use mongodb::bson::{self, Bson, Document, doc};
+use mongodb::error::{ErrorKind, WriteFailure};
+use serde::Serialize;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::CompareAndSwap;
+use crate::version::{Version, Versioned};
+
+/// Conditional writes to the collections of `MongoDataService`
+#[derive(Clone)]
+pub struct MongoCas {
+    database: Database,
+}
+
+impl MongoCas {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for MongoCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let result = self
+            .database
+            .collection::<Document>(T::resource_name())
+            .replace_one(filter(Some(expected), &entity.id())?, document(entity)?)
+            .await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let result = self
+            .database
+            .collection::<Document>(T::resource_name())
+            .insert_one(document(entity)?)
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e)
+                if matches!(
+                    e.kind.as_ref(),
+                    ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == DUPLICATE_KEY
+                ) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let result = self
+            .database
+            .collection::<Document>(T::resource_name())
+            .delete_one(filter(expected, id)?)
+            .await?;
+        Ok(result.deleted_count == 1)
+    }
+}
+
+/// The server's code for a write refused on a duplicate key
+const DUPLICATE_KEY: i32 = 11000;
+
+/// The document `MongoDataService` stores for `entity`, with its id as `_id`
+fn document<T: Serialize>(entity: &T) -> Result<Document> {
+    let mut document = match bson::to_bson(&serde_json::to_value(entity)?)? {
+        Bson::Document(document) => document,
+        other => return Err(anyhow!("expected a document, got {}", other)),
+    };
+    if let Some(id) = document.remove("id") {
+        document.insert("_id", id);
+    }
+    Ok(document)
+}
+
+/// The document `id`, provided it is at `expected` when given
+fn filter(expected: Option<Version>, id: &Uuid) -> Result<Document> {
+    let id = id.to_string();
+    let Some(expected) = expected else {
+        return Ok(doc! { "_id": id });
+    };
+    let version = i64::try_from(expected.get())?;
+    Ok(if expected == Version::default() {
+        // Entities stored before they were versioned have no `version`
+        doc! { "_id": id, "$or": [{ "version": version }, { "version": { "$exists": false } }] }
+    } else {
+        doc! { "_id": id, "version": version }
+    })
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::MySqlPool;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::{CompareAndSwap, inserted, sql_data};
+use crate::version::{Version, Versioned};
+
+/// Conditional writes to the `entities` table of `MysqlDataService`
+#[derive(Clone)]
+pub struct MysqlCas {
+    pool: MySqlPool,
+}
+
+impl MysqlCas {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for MysqlCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE entities \
+             SET name = ?, status = ?, tenant_id = ?, data = ?, updated_at = ?, deleted_at = ? \
+             WHERE id = ? AND entity_type = ? \
+             AND COALESCE(CAST(JSON_EXTRACT(data, '$.version') AS UNSIGNED), 0) = ?",
+        )
+        .bind(entity.name())
+        .bind(entity.status())
+        .bind(entity.tenant_id().map(|id| id.to_string()))
+        .bind(sql_data(entity)?)
+        .bind(entity.updated_at())
+        .bind(entity.deleted_at())
+        .bind(entity.id().to_string())
+        .bind(T::resource_name_singular())
+        .bind(expected.get())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO entities \
+             (id, entity_type, name, status, tenant_id, data, created_at, updated_at, deleted_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(entity.id().to_string())
+        .bind(T::resource_name_singular())
+        .bind(entity.name())
+        .bind(entity.status())
+        .bind(entity.tenant_id().map(|id| id.to_string()))
+        .bind(sql_data(entity)?)
+        .bind(entity.created_at())
+        .bind(entity.updated_at())
+        .bind(entity.deleted_at())
+        .execute(&self.pool)
+        .await;
+        inserted(result)
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let expected = expected.map(Version::get);
+        let result = sqlx::query(
+            "DELETE FROM entities WHERE id = ? AND entity_type = ? \
+             AND (? IS NULL OR COALESCE(CAST(JSON_EXTRACT(data, '$.version') AS UNSIGNED), 0) = ?)",
+        )
+        .bind(id.to_string())
+        .bind(T::resource_name_singular())
+        .bind(expected)
+        .bind(expected)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
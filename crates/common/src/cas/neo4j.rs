@@ -0,0 +1,118 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use neo4rs::{BoltMap, BoltNull, BoltString, BoltType, Graph, query};
+use serde::Serialize;
+use serde_json::Value;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::CompareAndSwap;
+use crate::version::{Version, Versioned};
+
+/// Conditional writes to the nodes of `Neo4jDataService`
+#[derive(Clone)]
+pub struct Neo4jCas {
+    graph: Graph,
+}
+
+impl Neo4jCas {
+    pub fn new(graph: Graph) -> Self {
+        Self { graph }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for Neo4jCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let cypher = format!(
+            "MATCH (n:`{}` {{id: $id}}) WHERE coalesce(n.version, 0) = $expected \
+             SET n = $props RETURN n.id",
+            T::resource_name_singular()
+        );
+        let mut result = self
+            .graph
+            .execute(
+                query(&cypher)
+                    .param("id", entity.id().to_string())
+                    .param("expected", i64::try_from(expected.get())?)
+                    .param("props", properties(entity)?),
+            )
+            .await?;
+        Ok(result.next().await?.is_some())
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        // MERGE only creates when no node has the id, atomically so under the
+        // uniqueness constraint of `Neo4jDataService::ensure_indexes`
+        let cypher = format!(
+            "MERGE (n:`{}` {{id: $id}}) ON CREATE SET n = $props, n.__created = true \
+             WITH n, coalesce(n.__created, false) AS created REMOVE n.__created \
+             RETURN created",
+            T::resource_name_singular()
+        );
+        let mut result = self
+            .graph
+            .execute(
+                query(&cypher)
+                    .param("id", entity.id().to_string())
+                    .param("props", properties(entity)?),
+            )
+            .await?;
+        match result.next().await? {
+            Some(row) => Ok(row.get::<bool>("created")?),
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let condition = match expected {
+            Some(_) => "WHERE coalesce(n.version, 0) = $expected",
+            None => "",
+        };
+        let cypher = format!(
+            "MATCH (n:`{}` {{id: $id}}) {} DELETE n RETURN count(*) AS deleted",
+            T::resource_name_singular(),
+            condition
+        );
+        let mut delete = query(&cypher).param("id", id.to_string());
+        if let Some(expected) = expected {
+            delete = delete.param("expected", i64::try_from(expected.get())?);
+        }
+        let mut result = self.graph.execute(delete).await?;
+        let deleted = match result.next().await? {
+            Some(row) => row.get::<i64>("deleted")?,
+            None => 0,
+        };
+        Ok(deleted == 1)
+    }
+}
+
+/// Node properties as `Neo4jDataService` writes them: the scalar fields, plus
+/// the whole entity as JSON in `__data`
+fn properties<T: Serialize>(entity: &T) -> Result<BoltType> {
+    let json = serde_json::to_value(entity)?;
+    let fields = json
+        .as_object()
+        .ok_or_else(|| anyhow!("expected a JSON object, got {}", json))?;
+
+    let mut map = BoltMap::new();
+    for (key, value) in fields {
+        let value = match value {
+            Value::String(text) => BoltType::from(text.clone()),
+            Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+                (Some(integer), _) => BoltType::from(integer),
+                (None, Some(float)) => BoltType::from(float),
+                (None, None) => BoltType::from(number.to_string()),
+            },
+            Value::Bool(flag) => BoltType::from(*flag),
+            Value::Null => BoltType::Null(BoltNull),
+            other => BoltType::from(other.to_string()),
+        };
+        map.put(BoltString::from(key.as_str()), value);
+    }
+    map.put(BoltString::from("__data"), BoltType::from(json.to_string()));
+    Ok(BoltType::Map(map))
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::PgPool;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::{CompareAndSwap, inserted, sql_data};
+use crate::version::{Version, Versioned};
+
+/// Conditional writes to the `entities` table of `PostgresDataService`
+#[derive(Clone)]
+pub struct PostgresCas {
+    pool: PgPool,
+}
+
+impl PostgresCas {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for PostgresCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE entities \
+             SET name = $1, status = $2, tenant_id = $3, data = $4, updated_at = $5, deleted_at = $6 \
+             WHERE id = $7 AND entity_type = $8 \
+             AND COALESCE((data->>'version')::bigint, 0) = $9",
+        )
+        .bind(entity.name())
+        .bind(entity.status())
+        .bind(entity.tenant_id())
+        .bind(sql_data(entity)?)
+        .bind(entity.updated_at())
+        .bind(entity.deleted_at())
+        .bind(entity.id())
+        .bind(T::resource_name_singular())
+        .bind(i64::try_from(expected.get())?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO entities \
+             (id, entity_type, name, status, tenant_id, data, created_at, updated_at, deleted_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(entity.id())
+        .bind(T::resource_name_singular())
+        .bind(entity.name())
+        .bind(entity.status())
+        .bind(entity.tenant_id())
+        .bind(sql_data(entity)?)
+        .bind(entity.created_at())
+        .bind(entity.updated_at())
+        .bind(entity.deleted_at())
+        .execute(&self.pool)
+        .await;
+        inserted(result)
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let result = sqlx::query(
+            "DELETE FROM entities WHERE id = $1 AND entity_type = $2 \
+             AND ($3::bigint IS NULL OR COALESCE((data->>'version')::bigint, 0) = $3)",
+        )
+        .bind(id)
+        .bind(T::resource_name_singular())
+        .bind(
+            expected
+                .map(|expected| i64::try_from(expected.get()))
+                .transpose()?,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use scylla::client::session::Session;
+use scylla::value::{CqlValue, Row};
+use serde::Serialize;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::CompareAndSwap;
+use crate::version::{Version, Versioned};
+
+/// Lightweight transactions on the `entities` table of `ScyllaDataService`
+///
+/// The stored JSON is read and its version checked, then the write is
+/// conditioned on that JSON being unchanged.
+#[derive(Clone)]
+pub struct ScyllaCas {
+    session: Arc<Session>,
+    keyspace: String,
+}
+
+impl ScyllaCas {
+    pub fn new(session: Arc<Session>, keyspace: impl Into<String>) -> Self {
+        Self {
+            session,
+            keyspace: keyspace.into(),
+        }
+    }
+
+    /// The stored JSON of an entity, provided it is at `expected`
+    async fn stored(
+        &self,
+        entity_type: &str,
+        id: &str,
+        expected: Version,
+    ) -> Result<Option<String>> {
+        let select = format!(
+            "SELECT entity_data FROM {}.entities WHERE entity_type = ? AND id = ?",
+            self.keyspace
+        );
+        let stored = self
+            .session
+            .query_unpaged(select, (entity_type, id))
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<(String,)>()?;
+        let Some((stored,)) = stored else {
+            return Ok(None);
+        };
+        let stored_version = serde_json::from_str::<serde_json::Value>(&stored)?
+            .get("version")
+            .map(|version| serde_json::from_value::<Version>(version.clone()))
+            .transpose()?
+            .unwrap_or_default();
+        Ok((stored_version == expected).then_some(stored))
+    }
+}
+
+/// Whether the result of a lightweight transaction says it was applied
+fn applied(row: Option<Row>) -> bool {
+    matches!(
+        row.as_ref().and_then(|row| row.columns.first()),
+        Some(Some(CqlValue::Boolean(true)))
+    )
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for ScyllaCas
+where
+    T: Data + Serialize + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let entity_type = T::resource_name_singular();
+        let id = entity.id().to_string();
+
+        let Some(stored) = self.stored(entity_type, &id, expected).await? else {
+            return Ok(false);
+        };
+
+        let json = serde_json::to_value(entity)?;
+        let text = |field: &str| {
+            json.get(field)
+                .and_then(|value| value.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        let update = format!(
+            "UPDATE {}.entities SET name = ?, status = ?, entity_data = ?, updated_at = ? \
+             WHERE entity_type = ? AND id = ? IF entity_data = ?",
+            self.keyspace
+        );
+        let row = self
+            .session
+            .query_unpaged(
+                update,
+                (
+                    text("name"),
+                    text("status"),
+                    json.to_string(),
+                    text("updated_at"),
+                    entity_type,
+                    id.as_str(),
+                    stored.as_str(),
+                ),
+            )
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<Row>()?;
+        Ok(applied(row))
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let json = serde_json::to_value(entity)?;
+        let text = |field: &str| {
+            json.get(field)
+                .and_then(|value| value.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        let insert = format!(
+            "INSERT INTO {}.entities \
+             (entity_type, id, name, status, entity_data, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?) IF NOT EXISTS",
+            self.keyspace
+        );
+        let row = self
+            .session
+            .query_unpaged(
+                insert,
+                (
+                    T::resource_name_singular(),
+                    entity.id().to_string(),
+                    text("name"),
+                    text("status"),
+                    json.to_string(),
+                    text("created_at"),
+                    text("updated_at"),
+                ),
+            )
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<Row>()?;
+        Ok(applied(row))
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let entity_type = T::resource_name_singular();
+        let id = id.to_string();
+        let row = match expected {
+            Some(expected) => {
+                let Some(stored) = self.stored(entity_type, &id, expected).await? else {
+                    return Ok(false);
+                };
+                let delete = format!(
+                    "DELETE FROM {}.entities WHERE entity_type = ? AND id = ? IF entity_data = ?",
+                    self.keyspace
+                );
+                self.session
+                    .query_unpaged(delete, (entity_type, id.as_str(), stored.as_str()))
+                    .await?
+            }
+            None => {
+                let delete = format!(
+                    "DELETE FROM {}.entities WHERE entity_type = ? AND id = ? IF EXISTS",
+                    self.keyspace
+                );
+                self.session
+                    .query_unpaged(delete, (entity_type, id.as_str()))
+                    .await?
+            }
+        };
+        Ok(applied(row.into_rows_result()?.maybe_first_row::<Row>()?))
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use this::core::Data;
+use uuid::Uuid;
+
+use super::{CompareAndSwap, inserted};
+use crate::sqlite::SqliteDataService;
+use crate::version::{Version, Versioned};
+
+/// Conditional writes to the table of a `SqliteDataService`
+pub struct SqliteCas<T> {
+    service: Arc<SqliteDataService<T>>,
+}
+
+impl<T> SqliteCas<T> {
+    pub fn new(service: Arc<SqliteDataService<T>>) -> Self {
+        Self { service }
+    }
+}
+
+impl<T> Clone for SqliteCas<T> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> CompareAndSwap<T> for SqliteCas<T>
+where
+    T: Data + Serialize + DeserializeOwned + Versioned,
+{
+    async fn swap(&self, expected: Version, entity: &T) -> Result<bool> {
+        let table = self.service.table().await?;
+        let result = sqlx::query(&format!(
+            "UPDATE \"{}\" SET data = ? \
+             WHERE id = ? AND COALESCE(json_extract(data, '$.version'), 0) = ?",
+            table.name()
+        ))
+        .bind(serde_json::to_string(entity)?)
+        .bind(entity.id().to_string())
+        .bind(i64::try_from(expected.get())?)
+        .execute(table.pool().await?)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn create(&self, entity: &T) -> Result<bool> {
+        let table = self.service.table().await?;
+        let result = sqlx::query(&format!(
+            "INSERT INTO \"{}\" (id, data) VALUES (?, ?)",
+            table.name()
+        ))
+        .bind(entity.id().to_string())
+        .bind(serde_json::to_string(entity)?)
+        .execute(table.pool().await?)
+        .await;
+        inserted(result)
+    }
+
+    async fn delete(&self, expected: Option<Version>, id: &Uuid) -> Result<bool> {
+        let table = self.service.table().await?;
+        let result = sqlx::query(&format!(
+            "DELETE FROM \"{}\" \
+             WHERE id = ?1 AND (?2 IS NULL OR COALESCE(json_extract(data, '$.version'), 0) = ?2)",
+            table.name()
+        ))
+        .bind(id.to_string())
+        .bind(
+            expected
+                .map(|expected| i64::try_from(expected.get()))
+                .transpose()?,
+        )
+        .execute(table.pool().await?)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use this::core::LinkService;
+use this::core::link::LinkEntity;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::Journal;
+
+/// `LinkService` keeping the links in memory, journaled when opened on a
+/// directory
+#[derive(Clone, Default)]
+pub struct JournaledLinkService {
+    links: Arc<RwLock<Vec<LinkEntity>>>,
+    journal: Option<Arc<Journal<LinkEntity>>>,
+}
+
+impl JournaledLinkService {
+    /// Links kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Links journaled in `dir`, as `links.snapshot.json` and `links.log`
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let (journal, links) = Journal::open(dir, "links", |link: &LinkEntity| link.id)?;
+        Ok(Self {
+            links: Arc::new(RwLock::new(links)),
+            journal: Some(Arc::new(journal)),
+        })
+    }
+
+    fn log_put(&self, link: &LinkEntity) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.put(link),
+            None => Ok(()),
+        }
+    }
+
+    fn log_delete(&self, id: &Uuid) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.delete(id),
+            None => Ok(()),
+        }
+    }
+
+    fn compact(&self, links: &[LinkEntity]) {
+        if let Some(journal) = &self.journal {
+            journal.compact_if_due(links);
+        }
+    }
+
+    async fn find(&self, matches: impl Fn(&LinkEntity) -> bool) -> Vec<LinkEntity> {
+        let links = self.links.read().await;
+        links.iter().filter(|l| matches(l)).cloned().collect()
+    }
+}
+
+#[async_trait]
+impl LinkService for JournaledLinkService {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        let mut links = self.links.write().await;
+        self.log_put(&link)?;
+        match links.iter_mut().find(|l| l.id == link.id) {
+            Some(existing) => *existing = link.clone(),
+            None => links.push(link.clone()),
+        }
+        self.compact(&links);
+        Ok(link)
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        Ok(self.find(|l| &l.id == id).await.into_iter().next())
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        Ok(self.links.read().await.clone())
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        _target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        Ok(self
+            .find(|l| &l.source_id == source_id && link_type.is_none_or(|t| l.link_type == t))
+            .await)
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        _source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        Ok(self
+            .find(|l| &l.target_id == target_id && link_type.is_none_or(|t| l.link_type == t))
+            .await)
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        let mut links = self.links.write().await;
+        let Some(i) = links.iter().position(|l| &l.id == id) else {
+            return Err(anyhow!("Link not found: {}", id));
+        };
+        self.log_put(&link)?;
+        links[i] = link.clone();
+        self.compact(&links);
+        Ok(link)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        let mut links = self.links.write().await;
+        if !links.iter().any(|l| &l.id == id) {
+            return Ok(());
+        }
+        self.log_delete(id)?;
+        links.retain(|l| &l.id != id);
+        self.compact(&links);
+        Ok(())
+    }
+
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        let mut links = self.links.write().await;
+        let ids: Vec<Uuid> = links
+            .iter()
+            .filter(|l| &l.source_id == entity_id || &l.target_id == entity_id)
+            .map(|l| l.id)
+            .collect();
+        for id in &ids {
+            self.log_delete(id)?;
+            links.retain(|l| &l.id != id);
+        }
+        self.compact(&links);
+        Ok(())
+    }
+}
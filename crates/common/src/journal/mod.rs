@@ -0,0 +1,230 @@
+//! Write-ahead log and snapshot of in-memory data
+//!
+//! A [`Journal`] keeps one in-memory collection on disk, in two
+//! human-readable files of its directory:
+//!
+//! | File | Content |
+//! |------|---------|
+//! | `<name>.snapshot.json` | every row, as of the last compaction |
+//! | `<name>.log` | one JSON line per write since: `{"op":"put","row":{...}}` or `{"op":"delete","id":"..."}` |
+//!
+//! A write is appended to the log and synced to disk before it is applied in
+//! memory, so a write that returned survives a crash; an append that fails
+//! is cut off the log again. Every [`COMPACT_EVERY`] writes the rows are
+//! written to a new snapshot that replaces the old one, after which the log
+//! starts over. Opening a journal replays the snapshot and the log, skipping
+//! a last line cut short by a crash, and compacts them.
+//!
+//! `InMemoryEntityStore::open(dir)` journals a store, and
+//! [`JournaledLinkService::open`] the links, one pair of files each:
+//!
+//! ```ignore
+//! let stores = BillingStores::new_journal("data/journal")?;
+//! let links = JournaledLinkService::open("data/journal")?;
+//! ```
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+mod links;
+
+pub use links::JournaledLinkService;
+
+/// Writes to the log between two compactions
+pub const COMPACT_EVERY: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record<T> {
+    Put { row: T },
+    Delete { id: Uuid },
+}
+
+/// Log and snapshot of the rows of type `T`
+pub struct Journal<T> {
+    snapshot: PathBuf,
+    log_path: PathBuf,
+    log: Mutex<Log>,
+    _rows: PhantomData<fn(&T)>,
+}
+
+struct Log {
+    file: File,
+    /// Length of the log up to its last complete record
+    len: u64,
+    /// Whether a failed append may have left part of a record after `len`
+    torn: bool,
+    /// Writes since the last compaction
+    writes: usize,
+}
+
+impl<T: Serialize + DeserializeOwned> Journal<T> {
+    /// Open the journal `name` in `dir`, creating it if needed, and return
+    /// its rows in the order they were first written
+    pub fn open(dir: impl AsRef<Path>, name: &str, id: fn(&T) -> Uuid) -> Result<(Self, Vec<T>)> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create journal directory {}", dir.display()))?;
+        let snapshot = dir.join(format!("{}.snapshot.json", name));
+        let log_path = dir.join(format!("{}.log", name));
+
+        let mut rows: Vec<T> = match fs::read(&snapshot) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Corrupt snapshot {}", snapshot.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        if log_path.exists() {
+            replay(&log_path, &mut rows, id)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .with_context(|| format!("Failed to open {}", log_path.display()))?;
+        let journal = Self {
+            snapshot,
+            log_path,
+            log: Mutex::new(Log {
+                file,
+                len: 0,
+                torn: false,
+                writes: 0,
+            }),
+            _rows: PhantomData,
+        };
+        journal.compact(&rows)?;
+        Ok((journal, rows))
+    }
+
+    /// Log that `row` was created or replaced
+    pub fn put(&self, row: &T) -> Result<()> {
+        self.append(&Record::Put { row })
+    }
+
+    /// Log that the row `id` was deleted
+    pub fn delete(&self, id: &Uuid) -> Result<()> {
+        self.append(&Record::<&T>::Delete { id: *id })
+    }
+
+    /// Append `record` and sync it, or leave the log as it was
+    fn append(&self, record: &Record<&T>) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut log = self.lock()?;
+        if log.torn {
+            // Later records must not be glued to the rest of a failed one,
+            // which would make the whole log unreadable
+            let len = log.len;
+            log.file
+                .set_len(len)
+                .with_context(|| format!("Failed to repair {}", self.log_path.display()))?;
+            log.torn = false;
+        }
+        let written = log
+            .file
+            .write_all(&line)
+            .and_then(|()| log.file.sync_data());
+        if let Err(e) = written {
+            let len = log.len;
+            log.torn = log.file.set_len(len).is_err();
+            return Err(e)
+                .with_context(|| format!("Failed to append to {}", self.log_path.display()));
+        }
+        log.len += line.len() as u64;
+        log.writes += 1;
+        Ok(())
+    }
+
+    /// Compact once [`COMPACT_EVERY`] writes were logged, `rows` being the
+    /// rows once they are all applied
+    ///
+    /// The writes are already in the log, so a failure is only logged and
+    /// compaction is tried again on the next write.
+    pub fn compact_if_due(&self, rows: &[T]) {
+        let due = self.lock().is_ok_and(|log| log.writes >= COMPACT_EVERY);
+        if !due {
+            return;
+        }
+        if let Err(e) = self.compact(rows) {
+            tracing::warn!(
+                "journal: failed to compact {}: {:#}",
+                self.log_path.display(),
+                e
+            );
+        }
+    }
+
+    /// Replace the snapshot with `rows` and empty the log
+    pub fn compact(&self, rows: &[T]) -> Result<()> {
+        let mut log = self.lock()?;
+        let tmp = self.snapshot.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer_pretty(&mut file, rows)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.snapshot)
+            .with_context(|| format!("Failed to write {}", self.snapshot.display()))?;
+        log.file.set_len(0)?;
+        log.file.sync_data()?;
+        log.len = 0;
+        log.torn = false;
+        log.writes = 0;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Log>> {
+        self.log
+            .lock()
+            .map_err(|_| anyhow!("journal {} poisoned", self.log_path.display()))
+    }
+}
+
+/// Apply the records of the log at `path` to `rows`
+fn replay<T: DeserializeOwned>(path: &Path, rows: &mut Vec<T>, id: fn(&T) -> Uuid) -> Result<()> {
+    let lines: Vec<String> = BufReader::new(File::open(path)?)
+        .lines()
+        .collect::<std::io::Result<_>>()?;
+    let last = lines.len();
+    for (n, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = match serde_json::from_str::<Record<T>>(line) {
+            Ok(record) => record,
+            // The process died while writing it
+            Err(e) if n + 1 == last => {
+                tracing::warn!(
+                    "journal: skipping the incomplete last line of {}: {}",
+                    path.display(),
+                    e
+                );
+                break;
+            }
+            Err(e) => {
+                return Err(anyhow!(
+                    "Corrupt log {} at line {}: {}",
+                    path.display(),
+                    n + 1,
+                    e
+                ));
+            }
+        };
+        match record {
+            Record::Put { row } => match rows.iter_mut().find(|r| id(r) == id(&row)) {
+                Some(existing) => *existing = row,
+                None => rows.push(row),
+            },
+            Record::Delete { id: deleted } => rows.retain(|r| id(r) != deleted),
+        }
+    }
+    Ok(())
+}
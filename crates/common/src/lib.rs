@@ -0,0 +1,21 @@
+pub mod auth;
+pub mod cas;
+pub mod journal;
+pub mod links;
+pub mod money;
+pub mod patch;
+pub mod problem;
+pub mod query;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod validation;
+pub mod version;
+
+// Re-export for convenience
+pub use money::{Currency, Money, MoneyError};
+pub use patch::{merge_patch, patch_entity};
+pub use problem::{ErrorKind, HttpError, Problem};
+pub use query::{ListQuery, Page};
+pub use validation::{Payload, ValidationError, check_id, validate_entity};
+pub use version::{IfMatch, Tagged, Version, VersionMismatch, Versioned};
@@ -0,0 +1,239 @@
+//! Link creations that follow the `cardinality` rules
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use this::core::link::LinkEntity;
+use this::prelude::LinkService;
+use uuid::Uuid;
+
+use super::integrity::LinkIntegrity;
+use super::{Cardinality, LinkRule};
+use crate::problem::{ErrorKind, HttpError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CardinalityError {
+    #[error(
+        "conflict: {entity_type} {entity_id} already has a '{link_type}' link and the link is {}",
+        .cardinality.as_str()
+    )]
+    Exceeded {
+        link_type: String,
+        cardinality: Cardinality,
+        entity_type: String,
+        entity_id: Uuid,
+        /// The link in the way
+        existing: Uuid,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for CardinalityError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CardinalityError::Exceeded { .. } => ErrorKind::Conflict,
+            CardinalityError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+        if let CardinalityError::Exceeded {
+            link_type,
+            cardinality,
+            existing,
+            ..
+        } = self
+        {
+            extensions.insert("link_type".to_string(), link_type.as_str().into());
+            extensions.insert("cardinality".to_string(), cardinality.as_str().into());
+            extensions.insert("existing_link".to_string(), existing.to_string().into());
+        }
+        extensions
+    }
+}
+
+impl LinkIntegrity {
+    /// Check that `link` can be created next to the existing links
+    ///
+    /// Links of a type without a rule are not checked.
+    pub async fn check_link(&self, link: &LinkEntity) -> Result<(), CardinalityError> {
+        match self.rules.of_type(&link.link_type).next() {
+            Some(rule) => {
+                self.check_cardinality(rule, Some(link.source_id), Some(link.target_id))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Check that a new `rule` link from `source` to `target` respects the
+    /// cardinality of the rule, `None` standing for an entity that does not
+    /// exist yet and so has no links
+    pub async fn check_cardinality(
+        &self,
+        rule: &LinkRule,
+        source: Option<Uuid>,
+        target: Option<Uuid>,
+    ) -> Result<(), CardinalityError> {
+        if let (true, Some(source)) = (rule.cardinality.single_target(), source) {
+            let links = self
+                .link_service
+                .find_by_source(&source, Some(&rule.link_type), Some(&rule.target_type))
+                .await?;
+            if let Some(existing) = links.first() {
+                return Err(exceeded(rule, &rule.source_type, source, existing.id));
+            }
+        }
+        if let (true, Some(target)) = (rule.cardinality.single_source(), target) {
+            let links = self
+                .link_service
+                .find_by_target(&target, Some(&rule.link_type), Some(&rule.source_type))
+                .await?;
+            if let Some(existing) = links.first() {
+                return Err(exceeded(rule, &rule.target_type, target, existing.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `link` again once it is stored, next to the links created at
+    /// the same time
+    ///
+    /// Any other link exceeding the cardinality refuses `link`: of links
+    /// created at the same time, at most one stays, and possibly none.
+    pub async fn recheck_link(&self, link: &LinkEntity) -> Result<(), CardinalityError> {
+        let Some(rule) = self.rules.of_type(&link.link_type).next() else {
+            return Ok(());
+        };
+        let other = |links: Vec<LinkEntity>| links.into_iter().find(|other| other.id != link.id);
+        if rule.cardinality.single_target() {
+            let links = self
+                .link_service
+                .find_by_source(
+                    &link.source_id,
+                    Some(&rule.link_type),
+                    Some(&rule.target_type),
+                )
+                .await?;
+            if let Some(existing) = other(links) {
+                return Err(exceeded(
+                    rule,
+                    &rule.source_type,
+                    link.source_id,
+                    existing.id,
+                ));
+            }
+        }
+        if rule.cardinality.single_source() {
+            let links = self
+                .link_service
+                .find_by_target(
+                    &link.target_id,
+                    Some(&rule.link_type),
+                    Some(&rule.source_type),
+                )
+                .await?;
+            if let Some(existing) = other(links) {
+                return Err(exceeded(
+                    rule,
+                    &rule.target_type,
+                    link.target_id,
+                    existing.id,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn exceeded(
+    rule: &LinkRule,
+    entity_type: &str,
+    entity_id: Uuid,
+    existing: Uuid,
+) -> CardinalityError {
+    CardinalityError::Exceeded {
+        link_type: rule.link_type.clone(),
+        cardinality: rule.cardinality,
+        entity_type: entity_type.to_string(),
+        entity_id,
+        existing,
+    }
+}
+
+/// A link service refusing the links that break the `cardinality` rules or
+/// that a [`LinkCheck`](super::LinkCheck) refuses
+///
+/// The cardinality is checked before the link is created and again after:
+/// links created at the same time that both passed the first check are
+/// deleted again and their creation fails, so the caller may retry.
+pub struct CheckedLinkService {
+    inner: Arc<dyn LinkService>,
+    integrity: LinkIntegrity,
+}
+
+impl CheckedLinkService {
+    pub fn new(inner: Arc<dyn LinkService>, integrity: LinkIntegrity) -> Self {
+        Self { inner, integrity }
+    }
+}
+
+#[async_trait]
+impl LinkService for CheckedLinkService {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        self.integrity.check_link(&link).await?;
+        self.integrity.run_checks(&link).await?;
+        let created = self.inner.create(link).await?;
+        if let Err(e) = self.integrity.recheck_link(&created).await {
+            self.inner.delete(&created.id).await?;
+            return Err(e.into());
+        }
+        Ok(created)
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        self.inner.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        self.inner.list().await
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_source(source_id, link_type, target_type)
+            .await
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_target(target_id, link_type, source_type)
+            .await
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        self.inner.update(id, link).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        self.inner.delete_by_entity(entity_id).await
+    }
+}
@@ -0,0 +1,402 @@
+//! Deletions that follow the `on_delete` rules
+//!
+//! The `cardinality` rules are checked in [`super::cardinality`].
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use serde_json::{Map, Value};
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
+use this::core::link::LinkEntity;
+use this::links::registry::{LinkDirection, LinkRouteRegistry};
+use this::prelude::{EntityCreator, LinkService};
+use this::server::host::ServerHost;
+use uuid::Uuid;
+
+use super::cardinality::CheckedLinkService;
+use super::{LinkCheck, LinkRule, LinkRules, OnDelete};
+use crate::problem::{ErrorKind, HttpError, Problem};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteError {
+    #[error(
+        "conflict: {entity_type} {id} has {count} '{link_type}' link(s) to {target_type} and cannot be deleted"
+    )]
+    Restricted {
+        entity_type: String,
+        id: Uuid,
+        link_type: String,
+        target_type: String,
+        count: usize,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for DeleteError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DeleteError::Restricted { .. } => ErrorKind::Conflict,
+            DeleteError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+        if let DeleteError::Restricted {
+            entity_type,
+            id,
+            link_type,
+            ..
+        } = self
+        {
+            extensions.insert("entity_type".to_string(), entity_type.as_str().into());
+            extensions.insert("entity_id".to_string(), id.to_string().into());
+            extensions.insert("link_type".to_string(), link_type.as_str().into());
+        }
+        extensions
+    }
+}
+
+/// The link rules applied to the link creations and deletions of a host
+///
+/// Links are created with the host's link service and entities deleted with
+/// its creators, so every exposure is covered once the host uses
+/// [`LinkIntegrity::checked_link_service`] and
+/// [`LinkIntegrity::guarded_creators`] (GraphQL, gRPC) and the REST router
+/// is wrapped with [`LinkIntegrity::layer`]:
+///
+/// ```ignore
+/// let mut host = builder.build_host()?;
+/// let integrity = LinkIntegrity::from_host(&host, rules);
+/// host.link_service = integrity.checked_link_service();
+/// host.entity_creators = integrity.guarded_creators();
+/// let app = integrity.layer(RestExposure::build_router(Arc::new(host), vec![])?);
+/// ```
+///
+/// A link that would give an entity more links of its type than the
+/// `cardinality` allows is refused, and so is one that a [`LinkCheck`] added
+/// with [`LinkIntegrity::with_check`] refuses. The checked link service checks
+/// the cardinality again once the link is stored, so that concurrent
+/// creations cannot both exceed it.
+///
+/// A deletion first collects the entities it cascades to and is refused if
+/// any of them has a `restrict` link. Once the entity is deleted, so are
+/// the entities it cascades to, and the links of all of them are dropped.
+/// These last steps are not atomic with the deletion: a failure is logged
+/// and leaves the remaining entities and links in place.
+#[derive(Clone)]
+pub struct LinkIntegrity {
+    pub(super) rules: Arc<LinkRules>,
+    pub(super) link_service: Arc<dyn LinkService>,
+    creators: Arc<HashMap<String, Arc<dyn EntityCreator>>>,
+    /// Entity type of each plural, and link of each route, to read REST paths
+    singulars: Arc<HashMap<String, String>>,
+    routes: Arc<LinkRouteRegistry>,
+    checks: Vec<Arc<dyn LinkCheck>>,
+    event_bus: Option<Arc<EventBus>>,
+}
+
+impl LinkIntegrity {
+    /// Apply `rules` to the entities and links of `host`
+    pub fn from_host(host: &ServerHost, rules: LinkRules) -> Self {
+        let singulars = host
+            .config
+            .entities
+            .iter()
+            .map(|entity| (entity.plural.clone(), entity.singular.clone()))
+            .collect();
+        Self {
+            rules: Arc::new(rules),
+            link_service: host.link_service.clone(),
+            creators: host.entity_creators.clone(),
+            singulars: Arc::new(singulars),
+            routes: host.registry.clone(),
+            checks: Vec::new(),
+            event_bus: host.event_bus.clone(),
+        }
+    }
+
+    /// Also refuse the links `check` refuses
+    pub fn with_check(mut self, check: Arc<dyn LinkCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Run the [`LinkCheck`]s on a new `link`
+    pub async fn run_checks(&self, link: &LinkEntity) -> Result<(), Problem> {
+        for check in &self.checks {
+            check.check_link(link).await?;
+        }
+        Ok(())
+    }
+
+    /// The host's link service, creating links through
+    /// [`LinkIntegrity::check_link`] and [`LinkIntegrity::run_checks`]
+    pub fn checked_link_service(&self) -> Arc<dyn LinkService> {
+        Arc::new(CheckedLinkService::new(
+            self.link_service.clone(),
+            self.clone(),
+        ))
+    }
+
+    /// The host's creators, deleting through [`LinkIntegrity::delete`]
+    pub fn guarded_creators(&self) -> Arc<HashMap<String, Arc<dyn EntityCreator>>> {
+        let creators = self
+            .creators
+            .keys()
+            .map(|entity_type| {
+                let creator: Arc<dyn EntityCreator> = Arc::new(GuardedCreator {
+                    entity_type: entity_type.clone(),
+                    integrity: self.clone(),
+                });
+                (entity_type.clone(), creator)
+            })
+            .collect();
+        Arc::new(creators)
+    }
+
+    /// Apply the rules to the `DELETE /{plural}/{id}` requests of `router`,
+    /// and to the requests creating links, nested routes included
+    pub fn layer(self, router: Router) -> Router {
+        router.layer(middleware::from_fn_with_state(self, enforce))
+    }
+
+    /// Delete the `entity_type` `id` and what it cascades to
+    pub async fn delete(&self, entity_type: &str, id: Uuid) -> Result<(), DeleteError> {
+        let plan = self.plan(entity_type, id).await?;
+        self.creator(entity_type)?.delete(&id).await?;
+        self.complete(&plan).await;
+        Ok(())
+    }
+
+    /// The entities deleted along with the `entity_type` `id`, itself first
+    ///
+    /// Fails if one of them has a `restrict` link.
+    pub async fn plan(
+        &self,
+        entity_type: &str,
+        id: Uuid,
+    ) -> Result<Vec<(String, Uuid)>, DeleteError> {
+        let mut plan = vec![(entity_type.to_string(), id)];
+        let mut seen = HashSet::from([id]);
+        let mut next = 0;
+        while let Some((entity_type, id)) = plan.get(next).cloned() {
+            next += 1;
+            for rule in self.rules.from_source(&entity_type) {
+                let links = self
+                    .link_service
+                    .find_by_source(&id, Some(&rule.link_type), Some(&rule.target_type))
+                    .await?;
+                if links.is_empty() {
+                    continue;
+                }
+                match rule.on_delete {
+                    OnDelete::Restrict => {
+                        return Err(DeleteError::Restricted {
+                            entity_type: entity_type.clone(),
+                            id,
+                            link_type: rule.link_type.clone(),
+                            target_type: rule.target_type.clone(),
+                            count: links.len(),
+                        });
+                    }
+                    OnDelete::Cascade => {
+                        for link in links {
+                            if seen.insert(link.target_id) {
+                                plan.push((rule.target_type.clone(), link.target_id));
+                            }
+                        }
+                    }
+                    OnDelete::Unlink => {}
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Delete the rest of `plan`, whose first entity is gone, and drop the
+    /// links of all its entities
+    async fn complete(&self, plan: &[(String, Uuid)]) {
+        for (entity_type, id) in plan.iter().skip(1) {
+            let deleted = match self.creator(entity_type) {
+                Ok(creator) => creator.delete(id).await,
+                Err(e) => Err(e),
+            };
+            match deleted {
+                Ok(()) => self.publish(entity_type, *id),
+                Err(e) => eprintln!(
+                    "Failed to cascade delete to {} {}: {:?}",
+                    entity_type, id, e
+                ),
+            }
+        }
+        for (entity_type, id) in plan {
+            if let Err(e) = self.link_service.delete_by_entity(id).await {
+                eprintln!(
+                    "Failed to drop the links of {} {}: {:?}",
+                    entity_type, id, e
+                );
+            }
+        }
+    }
+
+    fn creator(&self, entity_type: &str) -> anyhow::Result<Arc<dyn EntityCreator>> {
+        self.creators
+            .get(entity_type)
+            .cloned()
+            .ok_or_else(|| anyhow!("no creator for entity type '{}'", entity_type))
+    }
+
+    /// Tell subscribers about an entity deleted by cascade
+    fn publish(&self, entity_type: &str, id: Uuid) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(FrameworkEvent::Entity(EntityEvent::Deleted {
+                entity_type: entity_type.to_string(),
+                entity_id: id,
+            }));
+        }
+    }
+
+    /// The entity a `DELETE /{plural}/{id}` request deletes
+    ///
+    /// The longer paths, such as `DELETE /{plural}/{id}/{route_name}/{target_id}`,
+    /// delete a link rather than an entity.
+    fn deletion(&self, method: &Method, path: &str) -> Option<(String, Uuid)> {
+        if method != Method::DELETE {
+            return None;
+        }
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments[..] {
+            [_, _] => self.entity_at(&segments),
+            _ => None,
+        }
+    }
+
+    /// The entity a path of link routes leads to, e.g.
+    /// `/orders/{id}/invoices/{id}` for an invoice: `/{plural}/{id}` followed
+    /// by any number of `/{route_name}/{id}`
+    fn entity_at(&self, segments: &[&str]) -> Option<(String, Uuid)> {
+        let [plural, id, steps @ ..] = segments else {
+            return None;
+        };
+        let mut entity = (self.singulars.get(*plural)?.clone(), id.parse().ok()?);
+        for step in steps.chunks(2) {
+            let [route_name, id] = step else {
+                return None;
+            };
+            let (definition, direction) = self.routes.resolve_route(&entity.0, route_name).ok()?;
+            let entity_type = match direction {
+                LinkDirection::Forward => definition.target_type,
+                LinkDirection::Reverse => definition.source_type,
+            };
+            entity = (entity_type, id.parse().ok()?);
+        }
+        Some(entity)
+    }
+
+    /// The link a `POST /{plural}/{id}/{route_name}[/{target_id}]` request
+    /// creates, as its rule and ends, `None` for an entity it creates
+    ///
+    /// Nested routes such as `POST /orders/{id}/invoices/{id}/payments` link
+    /// from the last entity of their path.
+    fn linking(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> Option<(LinkRule, Option<Uuid>, Option<Uuid>)> {
+        if method != Method::POST {
+            return None;
+        }
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        if segments.len() < 3 {
+            return None;
+        }
+        let (entity, route_name, target) = if segments.len() % 2 == 1 {
+            let (route_name, entity) = segments.split_last()?;
+            (entity, *route_name, None)
+        } else {
+            let (entity, last) = segments.split_at(segments.len() - 2);
+            (entity, last[0], Some(last[1].parse().ok()?))
+        };
+        let (entity_type, id) = self.entity_at(entity)?;
+        let (definition, direction) = self.routes.resolve_route(&entity_type, route_name).ok()?;
+        let rule = self.rules.of_type(&definition.link_type).find(|rule| {
+            rule.source_type == definition.source_type && rule.target_type == definition.target_type
+        })?;
+        // As the link routes do: a link to an existing entity starts at the
+        // entity of the URL, a link to a new one follows the route
+        let ends = match (target, direction) {
+            (Some(target), _) => (Some(id), Some(target)),
+            (None, LinkDirection::Forward) => (Some(id), None),
+            (None, LinkDirection::Reverse) => (None, Some(id)),
+        };
+        Some((rule.clone(), ends.0, ends.1))
+    }
+}
+
+async fn enforce(State(integrity): State<LinkIntegrity>, request: Request, next: Next) -> Response {
+    let (method, path) = (request.method(), request.uri().path());
+    if let Some((rule, source, target)) = integrity.linking(method, path) {
+        if let Err(e) = integrity.check_cardinality(&rule, source, target).await {
+            return Problem::from(e).into_response();
+        }
+        if let (Some(source), Some(target)) = (source, target) {
+            let link = LinkEntity::new(&rule.link_type, source, target, None);
+            if let Err(problem) = integrity.run_checks(&link).await {
+                return problem.into_response();
+            }
+        }
+        return next.run(request).await;
+    }
+    let Some((entity_type, id)) = integrity.deletion(method, path) else {
+        return next.run(request).await;
+    };
+    let plan = match integrity.plan(&entity_type, id).await {
+        Ok(plan) => plan,
+        Err(e) => return Problem::from(e).into_response(),
+    };
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        integrity.complete(&plan).await;
+    }
+    response
+}
+
+/// A creator of the host whose deletions follow the rules
+struct GuardedCreator {
+    entity_type: String,
+    integrity: LinkIntegrity,
+}
+
+#[async_trait]
+impl EntityCreator for GuardedCreator {
+    async fn create_from_json(&self, entity_data: Value) -> anyhow::Result<Value> {
+        self.integrity
+            .creator(&self.entity_type)?
+            .create_from_json(entity_data)
+            .await
+    }
+
+    async fn update_from_json(
+        &self,
+        entity_id: &Uuid,
+        entity_data: Value,
+    ) -> anyhow::Result<Value> {
+        self.integrity
+            .creator(&self.entity_type)?
+            .update_from_json(entity_id, entity_data)
+            .await
+    }
+
+    async fn delete(&self, entity_id: &Uuid) -> anyhow::Result<()> {
+        Ok(self.integrity.delete(&self.entity_type, *entity_id).await?)
+    }
+}
@@ -0,0 +1,153 @@
+//! Rules of the `links:` of `links.yaml` beyond what `LinksConfig` reads
+//!
+//! Each link definition may say how many links an entity can have and what
+//! happens to them when their source entity is deleted:
+//!
+//! ```yaml
+//! links:
+//!   - link_type: payment
+//!     source_type: invoice
+//!     target_type: payment
+//!     cardinality: one_to_many   # a payment is for a single invoice
+//!     on_delete: restrict        # an invoice with payments cannot be deleted
+//! ```
+//!
+//! | `cardinality` | Links per source | Links per target |
+//! |---------------|------------------|------------------|
+//! | `one_to_one` | one | one |
+//! | `one_to_many` | many | one |
+//! | `many_to_one` | one | many |
+//! | `many_to_many` (default) | many | many |
+//!
+//! | `on_delete` | Deleting the source |
+//! |-------------|---------------------|
+//! | `cascade` | also deletes the targets, following their own rules |
+//! | `restrict` | is refused while the link exists |
+//! | `unlink` (default) | drops the links, the targets stay |
+//!
+//! Deleting the target of a link always drops the link. [`LinkIntegrity`]
+//! applies these rules to the link creations and deletions of a host, along
+//! with the [`LinkCheck`]s of the modules.
+
+mod cardinality;
+mod integrity;
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use this::core::link::LinkEntity;
+
+use crate::problem::Problem;
+
+pub use cardinality::{CardinalityError, CheckedLinkService};
+pub use integrity::{DeleteError, LinkIntegrity};
+
+/// A check of a module on the links created, beyond their `cardinality`,
+/// e.g. that a hierarchy has no cycles
+#[async_trait]
+pub trait LinkCheck: Send + Sync {
+    /// Refuse `link`, usually with a conflict, or let it be created
+    async fn check_link(&self, link: &LinkEntity) -> Result<(), Problem>;
+}
+
+/// How many links of a type an entity can have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    /// A source links to one target, which has no other source
+    OneToOne,
+    /// A source links to many targets, each of which has no other source
+    OneToMany,
+    /// Many sources link to the same target, each to no other target
+    ManyToOne,
+    #[default]
+    ManyToMany,
+}
+
+impl Cardinality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cardinality::OneToOne => "one_to_one",
+            Cardinality::OneToMany => "one_to_many",
+            Cardinality::ManyToOne => "many_to_one",
+            Cardinality::ManyToMany => "many_to_many",
+        }
+    }
+
+    /// Whether a source can only have one link of the type
+    pub fn single_target(&self) -> bool {
+        matches!(self, Cardinality::OneToOne | Cardinality::ManyToOne)
+    }
+
+    /// Whether a target can only have one link of the type
+    pub fn single_source(&self) -> bool {
+        matches!(self, Cardinality::OneToOne | Cardinality::OneToMany)
+    }
+}
+
+/// What deleting the source of a link does to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDelete {
+    /// Delete the targets too
+    Cascade,
+    /// Refuse the deletion while the link exists
+    Restrict,
+    /// Drop the link, keep the targets
+    #[default]
+    Unlink,
+}
+
+/// A link definition of `links.yaml`, as far as the rules go
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkRule {
+    pub link_type: String,
+    pub source_type: String,
+    pub target_type: String,
+    #[serde(default)]
+    pub cardinality: Cardinality,
+    #[serde(default)]
+    pub on_delete: OnDelete,
+}
+
+/// The link rules of one or more modules
+///
+/// A link type names one kind of link, between one source type and one
+/// target type, so its rule is found from the type alone.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LinkRules {
+    #[serde(default)]
+    pub links: Vec<LinkRule>,
+}
+
+impl LinkRules {
+    /// Read the `links:` of a module's `links.yaml`
+    pub fn from_yaml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let yaml =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_yaml::from_str(&yaml).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// The rules of both `self` and `other`, e.g. of two modules
+    pub fn merge(mut self, other: LinkRules) -> Self {
+        self.links.extend(other.links);
+        self
+    }
+
+    /// The rules of the links of type `link_type`
+    pub fn of_type<'a>(&'a self, link_type: &'a str) -> impl Iterator<Item = &'a LinkRule> {
+        self.links
+            .iter()
+            .filter(move |rule| rule.link_type == link_type)
+    }
+
+    /// The rules of the links whose source is an `entity_type`
+    pub fn from_source<'a>(&'a self, entity_type: &'a str) -> impl Iterator<Item = &'a LinkRule> {
+        self.links
+            .iter()
+            .filter(move |rule| rule.source_type == entity_type)
+    }
+}
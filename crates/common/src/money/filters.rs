@@ -0,0 +1,18 @@
+//! Money counterpart of the `round_decimals` field filter
+
+use serde_json::Value;
+
+use super::Money;
+
+/// Rewrite any accepted money shape to its canonical `"999.99 EUR"` string
+///
+/// Amounts with more decimals than the currency allows are rejected instead
+/// of rounded.
+pub fn normalize() -> impl Fn(&str, Value) -> anyhow::Result<Value> + Send + Sync + Clone {
+    |_: &str, value: Value| {
+        if value.is_null() {
+            return Ok(value);
+        }
+        Ok(Value::String(Money::from_json(&value)?.to_string()))
+    }
+}
@@ -0,0 +1,271 @@
+//! Exact monetary amounts
+//!
+//! A [`Money`] is an integer number of minor units (cents for EUR) plus an
+//! ISO-4217 [`Currency`]. It serializes to a single JSON string such as
+//! `"999.99 EUR"`, which every storage backend keeps verbatim, so amounts
+//! round-trip without going through `f64`.
+//!
+//! Accepted JSON input:
+//!
+//! ```text
+//! "999.99 EUR"
+//! {"amount": "999.99", "currency": "EUR"}
+//! {"amount": 999.99, "currency": "EUR"}
+//! ```
+//!
+//! A bare number is rejected because it carries no currency.
+
+mod currency;
+pub mod filters;
+pub mod validators;
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+pub use currency::Currency;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MoneyError {
+    #[error("unknown ISO-4217 currency '{0}'")]
+    UnknownCurrency(String),
+
+    #[error("invalid amount '{0}'")]
+    InvalidAmount(String),
+
+    #[error("amount '{amount}' has more than {digits} decimals, the precision of {currency}")]
+    TooPrecise {
+        amount: String,
+        currency: Currency,
+        digits: u32,
+    },
+
+    #[error("amount '{0}' has no currency (expected e.g. \"{0} EUR\")")]
+    MissingCurrency(String),
+
+    #[error("currency mismatch: {0} and {1}")]
+    CurrencyMismatch(Currency, Currency),
+
+    #[error("amount out of range")]
+    Overflow,
+}
+
+/// An exact amount of money in a single currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor_units: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn from_minor(minor_units: i64, currency: Currency) -> Self {
+        Self {
+            minor_units,
+            currency,
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::from_minor(0, currency)
+    }
+
+    /// Parse a decimal amount such as `"999.99"` in `currency`
+    ///
+    /// Trailing zeros beyond the currency precision are accepted
+    /// (`"1.500"` EUR), any other extra digit is an error rather than rounded.
+    pub fn parse(amount: &str, currency: Currency) -> Result<Self, MoneyError> {
+        let invalid = || MoneyError::InvalidAmount(amount.to_string());
+        let text = amount.trim();
+        let (negative, digits) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let precision = currency.minor_unit_digits() as usize;
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > precision {
+            return Err(MoneyError::TooPrecise {
+                amount: amount.to_string(),
+                currency,
+                digits: currency.minor_unit_digits(),
+            });
+        }
+
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| MoneyError::Overflow)?
+        };
+        let fraction: i64 = format!("{:0<precision$}", fraction).parse().unwrap_or(0);
+        let minor_units = whole
+            .checked_mul(currency.minor_units_per_major())
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or(MoneyError::Overflow)?;
+
+        Ok(Self::from_minor(
+            if negative { -minor_units } else { minor_units },
+            currency,
+        ))
+    }
+
+    /// Read a money value from any of the accepted JSON shapes
+    pub fn from_json(value: &Value) -> Result<Self, MoneyError> {
+        match value {
+            Value::String(text) => text.parse(),
+            Value::Object(fields) => {
+                let currency = fields
+                    .get("currency")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| MoneyError::MissingCurrency(value.to_string()))?;
+                let currency = Currency::new(currency)?;
+                match fields.get("amount") {
+                    Some(Value::String(amount)) => Money::parse(amount, currency),
+                    Some(Value::Number(amount)) => Money::parse(&amount.to_string(), currency),
+                    _ => Err(MoneyError::InvalidAmount(value.to_string())),
+                }
+            }
+            Value::Number(amount) => Err(MoneyError::MissingCurrency(amount.to_string())),
+            _ => Err(MoneyError::InvalidAmount(value.to_string())),
+        }
+    }
+
+    pub fn minor_units(&self) -> i64 {
+        self.minor_units
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor_units == 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.minor_units > 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor_units < 0
+    }
+
+    /// The decimal amount without currency, e.g. `"999.99"`
+    pub fn amount(&self) -> String {
+        let precision = self.currency.minor_unit_digits() as usize;
+        let per_major = self.currency.minor_units_per_major().unsigned_abs();
+        let units = self.minor_units.unsigned_abs();
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        if precision == 0 {
+            format!("{}{}", sign, units)
+        } else {
+            format!(
+                "{}{}.{:0precision$}",
+                sign,
+                units / per_major,
+                units % per_major
+            )
+        }
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        self.minor_units
+            .checked_add(other.minor_units)
+            .map(|units| Money::from_minor(units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        self.minor_units
+            .checked_sub(other.minor_units)
+            .map(|units| Money::from_minor(units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Multiply by a whole quantity (line totals)
+    pub fn checked_mul(self, quantity: i64) -> Result<Money, MoneyError> {
+        self.minor_units
+            .checked_mul(quantity)
+            .map(|units| Money::from_minor(units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Multiply by a fractional quantity (metered usage), rounding half away
+    /// from zero to the nearest minor unit
+    pub fn checked_mul_rounded(self, quantity: f64) -> Result<Money, MoneyError> {
+        let units = (self.minor_units as f64 * quantity).round();
+        if !units.is_finite() || units.abs() >= i64::MAX as f64 {
+            return Err(MoneyError::Overflow);
+        }
+        Ok(Money::from_minor(units as i64, self.currency))
+    }
+
+    /// Add up `amounts`, all of which must be in `currency`
+    pub fn sum<I>(currency: Currency, amounts: I) -> Result<Money, MoneyError>
+    where
+        I: IntoIterator<Item = Money>,
+    {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency), Money::checked_add)
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch(self.currency, other.currency))
+        }
+    }
+}
+
+/// Amounts in different currencies are not comparable
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.minor_units.cmp(&other.minor_units))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency)
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    /// Parse the canonical `"<amount> <CODE>"` form
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.rsplit_once(char::is_whitespace) {
+            Some((amount, currency)) => Money::parse(amount, Currency::new(currency)?),
+            None => Err(MoneyError::MissingCurrency(s.to_string())),
+        }
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Money::from_json(&value).map_err(serde::de::Error::custom)
+    }
+}
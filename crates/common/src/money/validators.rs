@@ -0,0 +1,59 @@
+//! Money counterparts of the `positive` and `max_value` field validators
+//!
+//! They have the signature expected by
+//! `EntityValidationConfig::add_validator`. Unlike the numeric validators,
+//! a value that is not money is an error rather than ignored. `null` is left
+//! to `required`/`optional`.
+
+use serde_json::Value;
+
+use super::Money;
+
+/// The field must hold a valid money value
+pub fn money() -> impl Fn(&str, &Value) -> Result<(), String> + Send + Sync + Clone {
+    |field: &str, value: &Value| parse(field, value).map(|_| ())
+}
+
+/// The field must hold a strictly positive amount
+pub fn positive() -> impl Fn(&str, &Value) -> Result<(), String> + Send + Sync + Clone {
+    |field: &str, value: &Value| {
+        let Some(money) = parse(field, value)? else {
+            return Ok(());
+        };
+        if money.is_positive() {
+            Ok(())
+        } else {
+            Err(format!("'{}' must be positive (got {})", field, money))
+        }
+    }
+}
+
+/// The amount must not exceed `max` major units of its currency
+pub fn max_amount(max: i64) -> impl Fn(&str, &Value) -> Result<(), String> + Send + Sync + Clone {
+    move |field: &str, value: &Value| {
+        let Some(money) = parse(field, value)? else {
+            return Ok(());
+        };
+        let limit = max.saturating_mul(money.currency().minor_units_per_major());
+        if money.minor_units() <= limit {
+            Ok(())
+        } else {
+            Err(format!(
+                "'{}' must not exceed {} {} (got {})",
+                field,
+                max,
+                money.currency(),
+                money
+            ))
+        }
+    }
+}
+
+fn parse(field: &str, value: &Value) -> Result<Option<Money>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    Money::from_json(value)
+        .map(Some)
+        .map_err(|e| format!("'{}' is not a valid money value: {}", field, e))
+}
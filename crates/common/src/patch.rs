@@ -0,0 +1,116 @@
+//! Partial updates with JSON Merge Patch
+//!
+//! `PATCH /{plural}/{id}` takes an [RFC 7396](https://www.rfc-editor.org/rfc/rfc7396)
+//! merge patch: the fields it carries replace those of the entity, `null`
+//! clears an optional field, and fields it leaves out are kept.
+//!
+//! ```ignore
+//! let current = state.store.get(&id).await?;
+//! let mut order = patch_entity(&Order::validation_config("update"), current, patch)?;
+//! order.touch();
+//! state.store.update(order).await?;
+//! ```
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use this::core::validation::EntityValidationConfig;
+
+use crate::validation::{Payload, ValidationError};
+
+/// Fields a patch may repeat but not change
+const READ_ONLY_FIELDS: [&str; 6] = [
+    "id",
+    "type",
+    "created_at",
+    "updated_at",
+    "version",
+    "owned_by",
+];
+
+/// Merge `patch` into `target` as RFC 7396 describes
+pub fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(fields) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                fields.remove(&key);
+            } else {
+                merge_patch(fields.entry(key).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Apply a merge patch to `entity`, holding the fields it sets to the
+/// `update` rules of `config`
+///
+/// `id`, `type`, `created_at` and `updated_at` may only be repeated
+/// unchanged. A patch that leaves a non-optional field empty fails like any
+/// other invalid payload. The caller bumps `updated_at`.
+pub fn patch_entity<T>(
+    config: &EntityValidationConfig,
+    entity: T,
+    patch: Value,
+) -> Result<T, ValidationError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut merged =
+        serde_json::to_value(entity).map_err(|e| ValidationError::field("$", e.to_string()))?;
+    let Value::Object(fields) = patch else {
+        return Err(ValidationError::field(
+            "$",
+            format!("expected a JSON object, got {}", patch),
+        ));
+    };
+
+    let mut errors = ValidationError::default();
+    let mut cleared = Map::new();
+    let mut changes = Map::new();
+    for (field, value) in fields {
+        if READ_ONLY_FIELDS.contains(&field.as_str()) {
+            if merged.get(&field) != Some(&value) {
+                errors.add(&field, format!("'{}' cannot be changed", field));
+            }
+        } else if value.is_null() {
+            cleared.insert(field, value);
+        } else {
+            changes.insert(field, value);
+        }
+    }
+    let mut changes = match Payload::validate(config, Value::Object(changes)).into_values() {
+        Ok(changes) => changes,
+        Err(invalid) => {
+            for (field, messages) in invalid.errors {
+                for message in messages {
+                    errors.add(&field, message);
+                }
+            }
+            Map::new()
+        }
+    };
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let cleared_fields: Vec<String> = cleared.keys().cloned().collect();
+    changes.extend(cleared);
+    merge_patch(&mut merged, Value::Object(changes));
+    serde_json::from_value(merged).map_err(|e| {
+        let message = e.to_string();
+        match cleared_fields
+            .iter()
+            .find(|field| message.contains(&format!("missing field `{}`", field)))
+        {
+            Some(field) => ValidationError::field(field, format!("'{}' cannot be null", field)),
+            None => ValidationError::field("$", message),
+        }
+    })
+}
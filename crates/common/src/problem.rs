@@ -0,0 +1,165 @@
+//! HTTP error responses for entity handlers
+//!
+//! Handlers return `Result<_, Problem>`. A [`Problem`] answers with the status
+//! of its [`ErrorKind`] and an RFC 9457 `application/problem+json` body:
+//!
+//! ```text
+//! HTTP/1.1 404 Not Found
+//! Content-Type: application/problem+json
+//!
+//! {"type": "about:blank", "title": "Not Found", "status": 404,
+//!  "detail": "not found: 6f9c..."}
+//! ```
+//!
+//! Store errors implement [`HttpError`] to say which kind they are, after
+//! which `?` turns them into a problem.
+
+use axum::Json;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde_json::{Map, Value};
+
+use crate::links::CardinalityError;
+use crate::validation::ValidationError;
+
+/// How a failure is reported to HTTP clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The addressed entity does not exist (404)
+    NotFound,
+    /// The request contradicts the current state (409)
+    Conflict,
+    /// The payload is invalid (422)
+    Validation,
+    /// The entity is no longer at the version the request was made against (412)
+    PreconditionFailed,
+    /// The request carries no valid credentials (401)
+    Unauthorized,
+    /// The credentials do not allow the operation (403)
+    Forbidden,
+    /// Anything else (500)
+    Other,
+}
+
+impl ErrorKind {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Conflict => StatusCode::CONFLICT,
+            ErrorKind::Validation => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorKind::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorKind::Forbidden => StatusCode::FORBIDDEN,
+            ErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// An error that knows how it is reported over HTTP
+pub trait HttpError: std::error::Error {
+    fn kind(&self) -> ErrorKind;
+
+    /// Extra members of the problem body, e.g. the states of a rejected
+    /// transition
+    fn extensions(&self) -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+/// An RFC 9457 problem details response
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub status: StatusCode,
+    pub detail: String,
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub fn new(kind: ErrorKind, detail: impl Into<String>) -> Self {
+        Self {
+            status: kind.status(),
+            detail: detail.into(),
+            extensions: Map::new(),
+        }
+    }
+
+    /// Problem with a status outside the [`ErrorKind`] mapping, e.g.
+    /// `501 Not Implemented` for a disabled feature
+    pub fn with_status(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            detail: detail.into(),
+            extensions: Map::new(),
+        }
+    }
+
+    /// Add an extension member to the body
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.extensions.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Problem for an error returned by an `EntityCreator`
+    ///
+    /// The creator erases the store error type `E`; it is recovered here,
+    /// along with the per-field errors of a rejected payload and the
+    /// problems and cardinality errors of link checks. Any other error, e.g.
+    /// of a link service or of a lookup in another module, is an `Other`.
+    pub fn from_creator<E>(error: anyhow::Error) -> Self
+    where
+        E: HttpError + Send + Sync + 'static,
+    {
+        recover::<ValidationError>(error)
+            .or_else(recover::<E>)
+            .or_else(|error| error.downcast::<Problem>())
+            .or_else(recover::<CardinalityError>)
+            .unwrap_or_else(|error| Problem::new(ErrorKind::Other, format!("{:#}", error)))
+    }
+}
+
+/// The problem of `error` if it is an `E`, or `error` back
+fn recover<E>(error: anyhow::Error) -> Result<Problem, anyhow::Error>
+where
+    E: HttpError + Send + Sync + 'static,
+{
+    error.downcast::<E>().map(Into::into)
+}
+
+impl<E: HttpError> From<E> for Problem {
+    fn from(error: E) -> Self {
+        Self {
+            status: error.kind().status(),
+            detail: error.to_string(),
+            extensions: error.extensions(),
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.detail)
+    }
+}
+
+/// Lets a problem travel as an `anyhow::Error`, e.g. out of a `LinkService`
+impl std::error::Error for Problem {}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let mut body = self.extensions;
+        body.insert("type".to_string(), Value::from("about:blank"));
+        body.insert(
+            "title".to_string(),
+            Value::from(self.status.canonical_reason().unwrap_or("Error")),
+        );
+        body.insert("status".to_string(), Value::from(self.status.as_u16()));
+        body.insert("detail".to_string(), Value::from(self.detail));
+
+        let mut response = (self.status, Json(Value::Object(body))).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
+    }
+}
@@ -0,0 +1,484 @@
+//! Filtering, sorting and pagination of entity lists
+//!
+//! List routes read a [`ListQuery`] from the query string:
+//!
+//! ```text
+//! GET /invoices?status=paid&amount_gte=100&sort=-created_at&limit=50&cursor=eyJzb3J0Ijo...
+//! ```
+//!
+//! - `field=value` keeps the entities whose `field` equals `value`; the
+//!   `_ne`, `_gt`, `_gte`, `_lt` and `_lte` suffixes compare instead. Numbers
+//!   and amounts (`"199.99 EUR"`) compare numerically, other values as text,
+//!   which orders RFC 3339 timestamps chronologically.
+//! - `sort` lists the fields to order by, comma separated, `-` for descending.
+//!   Entities are ordered by `id` after (or without) them.
+//! - `limit` is the page size, [`DEFAULT_LIMIT`] by default and at most
+//!   [`MAX_LIMIT`].
+//! - `cursor` is the `next_cursor` of the previous page, an opaque [`Cursor`].
+//!
+//! The answer is a [`Page`]: `{"data": [...], "total": 120, "next_cursor": "eyJz..."}`.
+//! Handlers reject filters and sort keys on fields the entity does not have
+//! with [`ListQuery::check`].
+//!
+//! Stores answer `query` through [`ListQuery::apply`]. Backends that can
+//! search narrow the candidates first with [`ListQuery::pushdown`], so that
+//! e.g. `status=paid` is a `WHERE` clause rather than a full scan.
+
+use std::cmp::Ordering;
+
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::problem::{ErrorKind, Problem};
+use crate::validation::ValidationError;
+
+/// Page size when the query has no `limit`
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Largest accepted `limit`
+pub const MAX_LIMIT: usize = 500;
+
+/// How a [`Filter`] compares the field with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl FilterOp {
+    const SUFFIXES: [(&'static str, FilterOp); 5] = [
+        ("_ne", FilterOp::Ne),
+        ("_gte", FilterOp::Gte),
+        ("_gt", FilterOp::Gt),
+        ("_lte", FilterOp::Lte),
+        ("_lt", FilterOp::Lt),
+    ];
+
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            FilterOp::Eq => ordering == Ordering::Equal,
+            FilterOp::Ne => ordering != Ordering::Equal,
+            FilterOp::Gt => ordering == Ordering::Greater,
+            FilterOp::Gte => ordering != Ordering::Less,
+            FilterOp::Lt => ordering == Ordering::Less,
+            FilterOp::Lte => ordering != Ordering::Greater,
+        }
+    }
+}
+
+/// A condition on one field of the listed entities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+impl Filter {
+    /// The query string key of the filter, e.g. `amount_gte`
+    pub fn key(&self) -> String {
+        let suffix = FilterOp::SUFFIXES
+            .iter()
+            .find(|(_, op)| *op == self.op)
+            .map_or("", |(suffix, _)| suffix);
+        format!("{}{}", self.field, suffix)
+    }
+
+    /// Read `key=value`, where `key` is a field name with an optional
+    /// operator suffix
+    pub fn parse(key: &str, value: String) -> Self {
+        for (suffix, op) in FilterOp::SUFFIXES {
+            if let Some(field) = key.strip_suffix(suffix)
+                && !field.is_empty()
+            {
+                return Self {
+                    field: field.to_string(),
+                    op,
+                    value,
+                };
+            }
+        }
+        Self {
+            field: key.to_string(),
+            op: FilterOp::Eq,
+            value,
+        }
+    }
+
+    /// Whether the serialized entity passes the filter
+    ///
+    /// A missing or `null` field only passes `_ne`.
+    pub fn matches(&self, entity: &Value) -> bool {
+        let Some(actual) = entity.get(&self.field).filter(|value| !value.is_null()) else {
+            return self.op == FilterOp::Ne;
+        };
+        let ordering = match actual {
+            Value::Number(number) => number
+                .as_f64()
+                .zip(self.value.parse::<f64>().ok())
+                .and_then(|(actual, expected)| actual.partial_cmp(&expected)),
+            Value::String(text) => match (numeric(text), self.value.parse::<f64>()) {
+                (Some(actual), Ok(expected)) => actual.partial_cmp(&expected),
+                _ => Some(text.as_str().cmp(&self.value)),
+            },
+            Value::Bool(flag) => self
+                .value
+                .parse::<bool>()
+                .ok()
+                .map(|expected| flag.cmp(&expected)),
+            _ => None,
+        };
+        match ordering {
+            Some(ordering) => self.op.accepts(ordering),
+            None => self.op == FilterOp::Ne,
+        }
+    }
+}
+
+/// A sort key: a field and its direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+/// Where the next page of a list starts: after the entity with these sort
+/// values and `id`
+///
+/// Clients see it as an opaque `cursor` string, base64url-encoded JSON. A
+/// page keeps its place when entities are added or removed before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The `sort` parameter the cursor was made for
+    sort: String,
+    values: Vec<Value>,
+    id: Value,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(text).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// The cursor as an entity, for [`ListQuery::compare`]
+    fn as_row(&self, sort: &[SortKey], id_field: &str) -> Value {
+        let mut row = serde_json::Map::new();
+        for (key, value) in sort.iter().zip(&self.values) {
+            row.insert(key.field.clone(), value.clone());
+        }
+        row.insert(id_field.to_string(), self.id.clone());
+        Value::Object(row)
+    }
+}
+
+/// Filters, order and page of a list request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListQuery {
+    pub filters: Vec<Filter>,
+    pub sort: Vec<SortKey>,
+    pub limit: usize,
+    /// Start after this entity, from the `cursor` of the request
+    pub after: Option<Cursor>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            sort: Vec::new(),
+            limit: DEFAULT_LIMIT,
+            after: None,
+        }
+    }
+}
+
+impl ListQuery {
+    /// Read the query string pairs of a list request
+    ///
+    /// `sort`, `limit` and `cursor` are reserved, every other key is a
+    /// filter. Invalid values are reported against their parameter.
+    pub fn from_pairs(
+        pairs: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ValidationError> {
+        let mut query = Self::default();
+        let mut errors = ValidationError::default();
+        let mut cursor = None;
+        for (key, value) in pairs {
+            match key.as_str() {
+                "sort" => {
+                    for field in value.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+                        let (field, descending) = match field.strip_prefix('-') {
+                            Some(field) => (field, true),
+                            None => (field.strip_prefix('+').unwrap_or(field), false),
+                        };
+                        query.sort.push(SortKey {
+                            field: field.to_string(),
+                            descending,
+                        });
+                    }
+                }
+                "limit" => match value.parse::<usize>() {
+                    Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => query.limit = limit,
+                    _ => errors.add(
+                        "limit",
+                        format!("'limit' must be an integer between 1 and {}", MAX_LIMIT),
+                    ),
+                },
+                "cursor" => cursor = Some(value),
+                _ => query.filters.push(Filter::parse(&key, value)),
+            }
+        }
+        if let Some(cursor) = cursor {
+            // A cursor only keeps its place in the order it was made for
+            match Cursor::decode(&cursor)
+                .filter(|cursor| cursor.sort == query.sort_param())
+                .filter(|cursor| cursor.values.len() == query.sort.len())
+            {
+                Some(cursor) => query.after = Some(cursor),
+                None => errors.add("cursor", "'cursor' is not a cursor of this list"),
+            }
+        }
+        if errors.is_empty() {
+            Ok(query)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Reject the filters and sort keys on fields a `T` does not have, each
+    /// against its parameter
+    pub fn check<T: DeserializeOwned>(&self) -> Result<(), ValidationError> {
+        self.check_fields(fields_of::<T>())
+    }
+
+    /// [`ListQuery::check`] against a list of `fields`
+    pub fn check_fields(&self, fields: &[&str]) -> Result<(), ValidationError> {
+        let mut errors = ValidationError::default();
+        for filter in &self.filters {
+            if !fields.contains(&filter.field.as_str()) {
+                let key = filter.key();
+                errors.add(&key, format!("'{}' is not a filter of this list", key));
+            }
+        }
+        for key in &self.sort {
+            if !fields.contains(&key.field.as_str()) {
+                errors.add(
+                    "sort",
+                    format!("'{}' is not a field of this list", key.field),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The `sort` parameter, normalized
+    fn sort_param(&self) -> String {
+        self.sort
+            .iter()
+            .map(|key| {
+                if key.descending {
+                    format!("-{}", key.field)
+                } else {
+                    key.field.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The first equality filter on one of `fields`, for backends that can
+    /// search on it
+    ///
+    /// `fields` are those the entity stores as strings, which every backend
+    /// compares like [`Filter::matches`]. The filter still runs in
+    /// [`ListQuery::apply`], so pushing it down only narrows the candidates.
+    pub fn pushdown<'a>(&'a self, fields: &[&str]) -> Option<(&'a str, &'a str)> {
+        self.filters
+            .iter()
+            .find(|filter| filter.op == FilterOp::Eq && fields.contains(&filter.field.as_str()))
+            .map(|filter| (filter.field.as_str(), filter.value.as_str()))
+    }
+
+    /// [`ListQuery::pushdown`] restricted to reference fields (`*_id`)
+    ///
+    /// The DynamoDB search interpolates the field name into its scan filter,
+    /// which fails for reserved words such as `name`, `status` or `number`.
+    pub fn dynamodb_pushdown<'a>(&'a self, fields: &[&str]) -> Option<(&'a str, &'a str)> {
+        let references: Vec<&str> = fields
+            .iter()
+            .copied()
+            .filter(|field| field.ends_with("_id"))
+            .collect();
+        self.pushdown(&references)
+    }
+
+    /// Filter, sort and page `items`
+    ///
+    /// Items are ordered by the `sort` keys, then by `id`, so that every
+    /// entity has one place and pages never overlap.
+    pub fn apply<T: Serialize>(&self, items: Vec<T>) -> Page<T> {
+        self.apply_keyed(items, "id")
+    }
+
+    /// [`ListQuery::apply`] to items told apart by `id_field` rather than
+    /// `id`, e.g. the `stock_item_id` of low-stock alerts
+    pub fn apply_keyed<T: Serialize>(&self, items: Vec<T>, id_field: &str) -> Page<T> {
+        let mut rows: Vec<(Value, T)> = items
+            .into_iter()
+            .map(|item| (serde_json::to_value(&item).unwrap_or(Value::Null), item))
+            .filter(|(json, _)| self.filters.iter().all(|filter| filter.matches(json)))
+            .collect();
+        let total = rows.len();
+        rows.sort_by(|(a, _), (b, _)| self.compare(a, b, id_field));
+        if let Some(after) = &self.after {
+            let after = after.as_row(&self.sort, id_field);
+            rows.retain(|(json, _)| self.compare(json, &after, id_field).is_gt());
+        }
+        let more = rows.len() > self.limit;
+        rows.truncate(self.limit);
+        let next_cursor = match rows.last() {
+            Some((last, _)) if more => Some(
+                Cursor {
+                    sort: self.sort_param(),
+                    values: self
+                        .sort
+                        .iter()
+                        .map(|key| last.get(&key.field).cloned().unwrap_or(Value::Null))
+                        .collect(),
+                    id: last.get(id_field).cloned().unwrap_or(Value::Null),
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+        Page {
+            data: rows.into_iter().map(|(_, item)| item).collect(),
+            total,
+            next_cursor,
+        }
+    }
+
+    fn compare(&self, a: &Value, b: &Value, id_field: &str) -> Ordering {
+        self.sort
+            .iter()
+            .map(|key| {
+                let ordering = compare_values(a.get(&key.field), b.get(&key.field));
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .chain(std::iter::once_with(|| {
+                compare_values(a.get(id_field), b.get(id_field))
+            }))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ListQuery {
+    type Rejection = Problem;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(pairs) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map_err(|e| Problem::new(ErrorKind::Validation, e.body_text()))?;
+        Ok(Self::from_pairs(pairs)?)
+    }
+}
+
+/// One page of a filtered list
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    /// Number of entities matching the filters, over all pages
+    pub total: usize,
+    /// Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// The numeric value of a number or an amount such as `"199.99 EUR"`
+fn numeric(text: &str) -> Option<f64> {
+    let (number, currency) = match text.split_once(' ') {
+        Some((number, currency)) => (number, Some(currency)),
+        None => (text, None),
+    };
+    if let Some(code) = currency
+        && !(code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()))
+    {
+        return None;
+    }
+    number.parse().ok().filter(|value: &f64| value.is_finite())
+}
+
+/// Order two field values, missing and `null` ones last
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let a = a.filter(|value| !value.is_null());
+    let b = b.filter(|value| !value.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => match (numeric(a), numeric(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => a.cmp(b),
+        },
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (Some(a), Some(b)) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+/// The field names of a `T`, as its `Deserialize` impl asks for them
+pub fn fields_of<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Fields(Option<&'static [&'static str]>);
+
+    impl<'de> de::Deserializer<'de> for &mut Fields {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.0 = Some(fields);
+            Err(de::Error::custom("fields read"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields = Fields(None);
+    let _ = T::deserialize(&mut fields);
+    fields.0.unwrap_or(&[])
+}
@@ -0,0 +1,87 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use this::core::LinkService;
+use this::core::link::LinkEntity;
+use uuid::Uuid;
+
+use super::Table;
+
+/// `LinkService` keeping the links in the `links` table
+pub struct SqliteLinkService {
+    table: Table,
+}
+
+impl SqliteLinkService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            table: Table::new::<LinkEntity>(
+                pool,
+                "links",
+                &["source_id", "target_id", "link_type"],
+            ),
+        }
+    }
+
+    async fn find(&self, end: &str, id: &Uuid, link_type: Option<&str>) -> Result<Vec<LinkEntity>> {
+        let id = id.to_string();
+        let mut filters = vec![(end, id.as_str())];
+        if let Some(link_type) = link_type {
+            filters.push(("link_type", link_type));
+        }
+        self.table.select(&filters).await
+    }
+}
+
+#[async_trait]
+impl LinkService for SqliteLinkService {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        self.table.insert(&link.id, &link).await?;
+        Ok(link)
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        self.table.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        self.table.select(&[]).await
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        _target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.find("source_id", source_id, link_type).await
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        _source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.find("target_id", target_id, link_type).await
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        if !self.table.replace(id, &link).await? {
+            return Err(anyhow!("Link not found: {}", id));
+        }
+        Ok(link)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        self.table.delete_where("id", &id.to_string()).await?;
+        Ok(())
+    }
+
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        let id = entity_id.to_string();
+        self.table.delete_where("source_id", &id).await?;
+        self.table.delete_where("target_id", &id).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! SQLite storage, for local development and CI
+//!
+//! One database file holds a table per entity, named after its resource
+//! (`orders`, `stock_items`, ...), and a `links` table. Each table keeps the
+//! JSON of its rows in `data`, with a column generated from `data` for every
+//! field of the entity, so the schema follows the entity: it is created on
+//! first use, and a field added later gets its column the next time the
+//! service starts. The entity's `indexed_fields` are indexed.
+//!
+//! ```ignore
+//! let pool = common::sqlite::connect("data/app.db").await?;
+//! let orders = SqliteDataService::<Order>::new(pool.clone());
+//! let links = SqliteLinkService::new(pool);
+//! ```
+
+use std::marker::PhantomData;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sqlx::SqlitePool;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use this::core::{Data, DataService};
+use uuid::Uuid;
+
+mod links;
+mod table;
+
+pub use links::SqliteLinkService;
+pub(crate) use table::Table;
+
+/// Open the database at `path`, creating the file if needed
+pub async fn connect(path: impl AsRef<Path>) -> Result<SqlitePool> {
+    let options = SqliteConnectOptions::new()
+        .filename(path.as_ref())
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(5));
+    SqlitePoolOptions::new()
+        .connect_with(options)
+        .await
+        .map_err(|e| anyhow!("Failed to open {}: {}", path.as_ref().display(), e))
+}
+
+/// `DataService` keeping the entities of type `T` in their own table
+pub struct SqliteDataService<T> {
+    table: Table,
+    _entity: PhantomData<fn() -> T>,
+}
+
+impl<T: Data + DeserializeOwned> SqliteDataService<T> {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            table: Table::new::<T>(pool, T::resource_name(), T::indexed_fields()),
+            _entity: PhantomData,
+        }
+    }
+
+    /// The table, once created
+    pub(crate) async fn table(&self) -> Result<&Table> {
+        self.table.pool().await?;
+        Ok(&self.table)
+    }
+}
+
+#[async_trait]
+impl<T> DataService<T> for SqliteDataService<T>
+where
+    T: Data + Serialize + DeserializeOwned,
+{
+    async fn create(&self, entity: T) -> Result<T> {
+        self.table.insert(&entity.id(), &entity).await?;
+        Ok(entity)
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<T>> {
+        self.table.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<T>> {
+        self.table.select(&[]).await
+    }
+
+    async fn update(&self, id: &Uuid, entity: T) -> Result<T> {
+        if !self.table.replace(id, &entity).await? {
+            return Err(anyhow!("Entity not found: {}", id));
+        }
+        Ok(entity)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        self.table.delete_where("id", &id.to_string()).await?;
+        Ok(())
+    }
+
+    async fn search(&self, field: &str, value: &str) -> Result<Vec<T>> {
+        self.table.select(&[(field, value)]).await
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use sqlx::SqlitePool;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+/// A table keeping the JSON of its rows in `data`, with a column generated
+/// from `data` for each field of the row type
+pub(crate) struct Table {
+    pool: SqlitePool,
+    name: String,
+    fields: &'static [&'static str],
+    indexed: Vec<&'static str>,
+    ready: OnceCell<()>,
+}
+
+impl Table {
+    /// Table `name` of the rows of type `R`, indexed on `indexed`
+    pub(crate) fn new<R: DeserializeOwned>(
+        pool: SqlitePool,
+        name: &str,
+        indexed: &[&'static str],
+    ) -> Self {
+        let fields = field_names::<R>();
+        Self {
+            pool,
+            name: name.to_string(),
+            fields,
+            indexed: indexed
+                .iter()
+                .copied()
+                .filter(|field| fields.contains(field))
+                .collect(),
+            ready: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The pool, once the table and its columns exist
+    pub(crate) async fn pool(&self) -> Result<&SqlitePool> {
+        self.ready.get_or_try_init(|| self.create()).await?;
+        Ok(&self.pool)
+    }
+
+    /// Create the table, then the columns and indexes it does not have yet
+    async fn create(&self) -> Result<()> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" (id TEXT PRIMARY KEY NOT NULL, data TEXT NOT NULL)",
+            self.name
+        ))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| anyhow!("Failed to create table {}: {}", self.name, e))?;
+
+        let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_xinfo(?)")
+            .bind(&self.name)
+            .fetch_all(&self.pool)
+            .await?;
+        for field in self.fields {
+            if existing.iter().any(|column| column == field) {
+                continue;
+            }
+            sqlx::query(&format!(
+                "ALTER TABLE \"{table}\" ADD COLUMN \"{field}\" \
+                 GENERATED ALWAYS AS (json_extract(data, '$.{field}')) VIRTUAL",
+                table = self.name,
+                field = field
+            ))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| anyhow!("Failed to add column {}.{}: {}", self.name, field, e))?;
+        }
+        for field in &self.indexed {
+            sqlx::query(&format!(
+                "CREATE INDEX IF NOT EXISTS \"{table}_{field}\" ON \"{table}\" (\"{field}\")",
+                table = self.name,
+                field = field
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn insert<R: Serialize>(&self, id: &Uuid, row: &R) -> Result<()> {
+        sqlx::query(&format!(
+            "INSERT INTO \"{}\" (id, data) VALUES (?, ?)",
+            self.name
+        ))
+        .bind(id.to_string())
+        .bind(serde_json::to_string(row)?)
+        .execute(self.pool().await?)
+        .await
+        .map_err(|e| anyhow!("Failed to insert into {}: {}", self.name, e))?;
+        Ok(())
+    }
+
+    /// Overwrite a row, returning whether it exists
+    pub(crate) async fn replace<R: Serialize>(&self, id: &Uuid, row: &R) -> Result<bool> {
+        let result = sqlx::query(&format!(
+            "UPDATE \"{}\" SET data = ? WHERE id = ?",
+            self.name
+        ))
+        .bind(serde_json::to_string(row)?)
+        .bind(id.to_string())
+        .execute(self.pool().await?)
+        .await
+        .map_err(|e| anyhow!("Failed to update {}: {}", self.name, e))?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub(crate) async fn get<R: DeserializeOwned>(&self, id: &Uuid) -> Result<Option<R>> {
+        let data: Option<String> =
+            sqlx::query_scalar(&format!("SELECT data FROM \"{}\" WHERE id = ?", self.name))
+                .bind(id.to_string())
+                .fetch_optional(self.pool().await?)
+                .await?;
+        data.map(|data| Ok(serde_json::from_str(&data)?))
+            .transpose()
+    }
+
+    /// The rows matching every `(field, value)` filter, in the order they
+    /// were inserted
+    pub(crate) async fn select<R: DeserializeOwned>(
+        &self,
+        filters: &[(&str, &str)],
+    ) -> Result<Vec<R>> {
+        let mut sql = format!("SELECT data FROM \"{}\"", self.name);
+        for (i, (field, _)) in filters.iter().enumerate() {
+            sql.push_str(if i == 0 { " WHERE " } else { " AND " });
+            sql.push_str(&self.column(field));
+            sql.push_str(" = ?");
+        }
+        sql.push_str(" ORDER BY rowid");
+        let mut query = sqlx::query_scalar::<_, String>(&sql);
+        for (_, value) in filters {
+            query = query.bind(*value);
+        }
+        query
+            .fetch_all(self.pool().await?)
+            .await?
+            .iter()
+            .map(|data| Ok(serde_json::from_str(data)?))
+            .collect()
+    }
+
+    /// Delete the rows whose `field` holds `value`
+    pub(crate) async fn delete_where(&self, field: &str, value: &str) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM \"{}\" WHERE {} = ?",
+            self.name,
+            self.column(field)
+        ))
+        .bind(value)
+        .execute(self.pool().await?)
+        .await
+        .map_err(|e| anyhow!("Failed to delete from {}: {}", self.name, e))?;
+        Ok(result.rows_affected())
+    }
+
+    /// The column of `field`, or its JSON path for fields the row type does
+    /// not declare
+    fn column(&self, field: &str) -> String {
+        if field == "id" || self.fields.contains(&field) {
+            format!("\"{}\"", field)
+        } else {
+            format!(
+                "json_extract(data, '$.\"{}\"')",
+                field.replace(['\'', '"'], "")
+            )
+        }
+    }
+}
+
+/// The field names of `R`, as its `Deserialize` impl asks for them
+///
+/// Empty for types that do not deserialize from a struct.
+pub(crate) fn field_names<R: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields = FieldNames(&[]);
+    let _ = R::deserialize(&mut fields);
+    fields.0
+}
+
+struct FieldNames(&'static [&'static str]);
+
+impl<'de> Deserializer<'de> for &mut FieldNames {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0 = fields;
+        Err(de::Error::custom("fields read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,344 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use this::core::DataService;
+use this::prelude::{EntityCreator, EntityFetcher};
+use uuid::Uuid;
+
+use super::{EntityStore, StoreError, StoredEntity};
+use crate::cas::{self, CompareAndSwap, Swapped};
+use crate::query::{ListQuery, Page};
+use crate::version::{Version, VersionMismatch};
+
+/// How many times [`BackendStore::modify`] reads and tries again when another
+/// write got in between
+const ATTEMPTS: usize = 5;
+
+/// Store of a database backend: a `DataService` for reads, and a
+/// [`CompareAndSwap`] for writes, which it conditions on the id being free
+/// for creates and on the version for updates and deletes
+pub struct BackendStore<T, S, C> {
+    service: Arc<S>,
+    cas: C,
+    /// Search only on `*_id` fields (see `ListQuery::dynamodb_pushdown`)
+    references_only: bool,
+    _entity: PhantomData<fn() -> T>,
+}
+
+impl<T, S, C: Clone> Clone for BackendStore<T, S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            cas: self.cas.clone(),
+            references_only: self.references_only,
+            _entity: PhantomData,
+        }
+    }
+}
+
+impl<T, S, C> BackendStore<T, S, C> {
+    pub fn from_parts(service: Arc<S>, cas: C) -> Self {
+        Self {
+            service,
+            cas,
+            references_only: false,
+            _entity: PhantomData,
+        }
+    }
+
+    /// The conditional writes of the store
+    pub fn cas(&self) -> &C {
+        &self.cas
+    }
+
+    /// Push down only searches on `*_id` fields, for backends that cannot
+    /// search other fields efficiently
+    pub fn references_only(mut self) -> Self {
+        self.references_only = true;
+        self
+    }
+}
+
+fn other(e: anyhow::Error) -> StoreError {
+    StoreError::Other(e)
+}
+
+impl<T, S, C> BackendStore<T, S, C>
+where
+    T: StoredEntity,
+    S: DataService<T> + 'static,
+    C: CompareAndSwap<T> + 'static,
+{
+    /// Apply `change` to a stored entity and write it at the next version,
+    /// reading it again when another write got in between
+    pub async fn modify(
+        &self,
+        id: &Uuid,
+        change: impl Fn(&mut T) -> Result<(), T::Error> + Send + Sync,
+    ) -> Result<T, T::Error> {
+        for _ in 0..ATTEMPTS {
+            let current = self.get(id).await?;
+            let mut changed = current.clone();
+            change(&mut changed)?;
+            T::check_update(&current, &changed)?;
+            match cas::swap(&self.cas, self.service.as_ref(), changed)
+                .await
+                .map_err(other)?
+            {
+                Swapped::Written(changed) => return Ok(changed),
+                Swapped::Missing => return Err(StoreError::NotFound(id.to_string()).into()),
+                Swapped::Stale(_) => continue,
+            }
+        }
+        Err(StoreError::Conflict(format!(
+            "{} {} kept changing, gave up after {} attempts",
+            T::NAME,
+            id,
+            ATTEMPTS
+        ))
+        .into())
+    }
+}
+
+#[async_trait]
+impl<T, S, C> EntityStore<T> for BackendStore<T, S, C>
+where
+    T: StoredEntity,
+    S: DataService<T> + 'static,
+    C: CompareAndSwap<T> + 'static,
+{
+    async fn create(&self, entity: T) -> Result<T, T::Error> {
+        if self.cas.create(&entity).await.map_err(other)? {
+            Ok(entity)
+        } else {
+            Err(StoreError::Conflict(entity.id().to_string()).into())
+        }
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<T, T::Error> {
+        self.service
+            .get(id)
+            .await
+            .map_err(other)?
+            .ok_or_else(|| StoreError::NotFound(id.to_string()).into())
+    }
+
+    async fn update(&self, entity: T) -> Result<T, T::Error> {
+        let id = entity.id();
+        let current = self.get(&id).await?;
+        T::check_update(&current, &entity)?;
+        match cas::swap(&self.cas, self.service.as_ref(), entity)
+            .await
+            .map_err(other)?
+        {
+            Swapped::Written(entity) => Ok(entity),
+            Swapped::Missing => Err(StoreError::NotFound(id.to_string()).into()),
+            Swapped::Stale(mismatch) => Err(StoreError::from(mismatch).into()),
+        }
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), T::Error> {
+        if self.cas.delete(None, id).await.map_err(other)? {
+            Ok(())
+        } else {
+            Err(StoreError::NotFound(id.to_string()).into())
+        }
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), T::Error> {
+        if self.cas.delete(Some(expected), id).await.map_err(other)? {
+            return Ok(());
+        }
+        match self.service.get(id).await.map_err(other)? {
+            None => Err(StoreError::NotFound(id.to_string()).into()),
+            Some(current) => Err(StoreError::from(VersionMismatch {
+                expected,
+                current: current.version(),
+            })
+            .into()),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<T>, T::Error> {
+        Ok(self.service.list().await.map_err(other)?)
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<T>, T::Error> {
+        let pushdown = if self.references_only {
+            query.dynamodb_pushdown(T::SEARCH_FIELDS)
+        } else {
+            query.pushdown(T::SEARCH_FIELDS)
+        };
+        let candidates = match pushdown {
+            Some((field, value)) => self.service.search(field, value).await,
+            None => self.service.list().await,
+        };
+        Ok(query.apply(candidates.map_err(other)?))
+    }
+}
+
+#[async_trait]
+impl<T, S, C> EntityFetcher for BackendStore<T, S, C>
+where
+    T: StoredEntity,
+    S: DataService<T> + 'static,
+    C: CompareAndSwap<T> + 'static,
+{
+    async fn fetch_as_json(&self, entity_id: &Uuid) -> anyhow::Result<Value> {
+        super::fetch_as_json::<T, _>(self, entity_id).await
+    }
+
+    async fn list_as_json(
+        &self,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> anyhow::Result<Vec<Value>> {
+        super::list_as_json::<T, _>(self, limit, offset).await
+    }
+}
+
+#[async_trait]
+impl<T, S, C> EntityCreator for BackendStore<T, S, C>
+where
+    T: StoredEntity,
+    S: DataService<T> + 'static,
+    C: CompareAndSwap<T> + 'static,
+{
+    async fn create_from_json(&self, entity_data: Value) -> anyhow::Result<Value> {
+        super::create_from_json::<T, _>(self, entity_data).await
+    }
+
+    async fn update_from_json(
+        &self,
+        entity_id: &Uuid,
+        entity_data: Value,
+    ) -> anyhow::Result<Value> {
+        super::update_from_json::<T, _>(self, entity_id, entity_data).await
+    }
+
+    async fn delete(&self, entity_id: &Uuid) -> anyhow::Result<()> {
+        Ok(EntityStore::delete(self, entity_id).await?)
+    }
+}
+
+// ============================================================================
+// Backends
+// ============================================================================
+
+#[cfg(feature = "dynamodb")]
+pub type DynamoDBStore<T> =
+    BackendStore<T, this::storage::DynamoDBDataService<T>, crate::cas::DynamoDBCas>;
+
+#[cfg(feature = "dynamodb")]
+impl<T: StoredEntity> DynamoDBStore<T> {
+    pub fn new(client: aws_sdk_dynamodb::Client, table_name: String) -> Self {
+        Self::from_parts(
+            Arc::new(this::storage::DynamoDBDataService::new(
+                client.clone(),
+                table_name.clone(),
+            )),
+            crate::cas::DynamoDBCas::new(client, table_name),
+        )
+        .references_only()
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub type PostgresStore<T> =
+    BackendStore<T, this::storage::PostgresDataService<T>, crate::cas::PostgresCas>;
+
+#[cfg(feature = "postgres")]
+impl<T: StoredEntity> PostgresStore<T> {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self::from_parts(
+            Arc::new(this::storage::PostgresDataService::new(pool.clone())),
+            crate::cas::PostgresCas::new(pool),
+        )
+    }
+}
+
+#[cfg(feature = "mongodb_backend")]
+pub type MongoStore<T> = BackendStore<T, this::storage::MongoDataService<T>, crate::cas::MongoCas>;
+
+#[cfg(feature = "mongodb_backend")]
+impl<T: StoredEntity> MongoStore<T> {
+    pub fn new(database: mongodb::Database) -> Self {
+        Self::from_parts(
+            Arc::new(this::storage::MongoDataService::new(database.clone())),
+            crate::cas::MongoCas::new(database),
+        )
+    }
+}
+
+#[cfg(feature = "neo4j")]
+pub type Neo4jStore<T> = BackendStore<T, this::storage::Neo4jDataService<T>, crate::cas::Neo4jCas>;
+
+#[cfg(feature = "neo4j")]
+impl<T: StoredEntity> Neo4jStore<T> {
+    pub fn new(graph: neo4rs::Graph) -> Self {
+        Self::from_parts(
+            Arc::new(this::storage::Neo4jDataService::new(graph.clone())),
+            crate::cas::Neo4jCas::new(graph),
+        )
+    }
+}
+
+#[cfg(feature = "scylladb")]
+pub type ScyllaStore<T> =
+    BackendStore<T, this::storage::ScyllaDataService<T>, crate::cas::ScyllaCas>;
+
+#[cfg(feature = "scylladb")]
+impl<T: StoredEntity> ScyllaStore<T> {
+    pub fn new(
+        session: Arc<scylla::client::session::Session>,
+        keyspace: impl Into<String>,
+    ) -> Self {
+        let keyspace = keyspace.into();
+        Self::from_parts(
+            Arc::new(this::storage::ScyllaDataService::new(
+                session.clone(),
+                keyspace.clone(),
+            )),
+            crate::cas::ScyllaCas::new(session, keyspace),
+        )
+    }
+}
+
+#[cfg(feature = "mysql")]
+pub type MysqlStore<T> = BackendStore<T, this::storage::MysqlDataService<T>, crate::cas::MysqlCas>;
+
+#[cfg(feature = "mysql")]
+impl<T: StoredEntity> MysqlStore<T> {
+    pub fn new(pool: sqlx::MySqlPool) -> Self {
+        Self::from_parts(
+            Arc::new(this::storage::MysqlDataService::new(pool.clone())),
+            crate::cas::MysqlCas::new(pool),
+        )
+    }
+}
+
+/// Every write goes through `LmdbCas`, under the lock of the environment
+#[cfg(feature = "lmdb")]
+pub type LmdbStore<T> = BackendStore<T, crate::cas::LmdbCas<T>, crate::cas::LmdbCas<T>>;
+
+#[cfg(feature = "lmdb")]
+impl<T: StoredEntity> LmdbStore<T> {
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let cas = crate::cas::LmdbCas::open(path)?;
+        Ok(Self::from_parts(Arc::new(cas.clone()), cas))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub type SqliteStore<T> =
+    BackendStore<T, crate::sqlite::SqliteDataService<T>, crate::cas::SqliteCas<T>>;
+
+#[cfg(feature = "sqlite")]
+impl<T: StoredEntity> SqliteStore<T> {
+    pub fn new(pool: sqlx::SqlitePool) -> Self {
+        let service = Arc::new(crate::sqlite::SqliteDataService::new(pool));
+        Self::from_parts(service.clone(), crate::cas::SqliteCas::new(service))
+    }
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use this::prelude::{EntityCreator, EntityFetcher};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::{EntityStore, StoreError, StoredEntity};
+use crate::journal::Journal;
+use crate::version::{Version, VersionMismatch};
+
+/// Store keeping its entities in memory, journaled on disk when opened on a
+/// directory (see [`crate::journal`])
+pub struct InMemoryEntityStore<T> {
+    inner: Arc<RwLock<Vec<T>>>,
+    journal: Option<Arc<Journal<T>>>,
+}
+
+impl<T> Clone for InMemoryEntityStore<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            journal: self.journal.clone(),
+        }
+    }
+}
+
+impl<T> Default for InMemoryEntityStore<T> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Vec::new())),
+            journal: None,
+        }
+    }
+}
+
+impl<T: StoredEntity> InMemoryEntityStore<T> {
+    /// Store journaled in `dir`, as `<resource>.snapshot.json` and
+    /// `<resource>.log`, starting with the entities it recovers from them
+    pub fn open(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let (journal, entities) = Journal::open(dir, T::resource_name(), |e: &T| e.id())?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new(entities)),
+            journal: Some(Arc::new(journal)),
+        })
+    }
+
+    fn log_put(&self, entity: &T) -> Result<(), StoreError> {
+        match &self.journal {
+            Some(journal) => Ok(journal.put(entity)?),
+            None => Ok(()),
+        }
+    }
+
+    fn log_delete(&self, id: &Uuid) -> Result<(), StoreError> {
+        match &self.journal {
+            Some(journal) => Ok(journal.delete(id)?),
+            None => Ok(()),
+        }
+    }
+
+    fn compact(&self, entities: &[T]) {
+        if let Some(journal) = &self.journal {
+            journal.compact_if_due(entities);
+        }
+    }
+
+    /// Atomically apply `change` to a stored entity and write it at the next
+    /// version
+    pub async fn modify(
+        &self,
+        id: &Uuid,
+        change: impl FnOnce(&mut T) -> Result<(), T::Error> + Send,
+    ) -> Result<T, T::Error> {
+        let mut g = self.inner.write().await;
+        let i = g
+            .iter()
+            .position(|e| &e.id() == id)
+            .ok_or_else(|| StoreError::NotFound(id.to_string()))?;
+        let mut changed = g[i].clone();
+        change(&mut changed)?;
+        changed.set_version(g[i].version().next());
+        self.log_put(&changed)?;
+        g[i] = changed.clone();
+        self.compact(&g);
+        Ok(changed)
+    }
+}
+
+#[async_trait]
+impl<T: StoredEntity> EntityStore<T> for InMemoryEntityStore<T> {
+    async fn create(&self, entity: T) -> Result<T, T::Error> {
+        let mut g = self.inner.write().await;
+        if g.iter().any(|e| e.id() == entity.id()) {
+            return Err(StoreError::Conflict(entity.id().to_string()).into());
+        }
+        self.log_put(&entity)?;
+        g.push(entity.clone());
+        self.compact(&g);
+        Ok(entity)
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<T, T::Error> {
+        let g = self.inner.read().await;
+        g.iter()
+            .find(|e| &e.id() == id)
+            .cloned()
+            .ok_or_else(|| StoreError::NotFound(id.to_string()).into())
+    }
+
+    async fn update(&self, mut entity: T) -> Result<T, T::Error> {
+        let mut g = self.inner.write().await;
+        let Some(i) = g.iter().position(|e| e.id() == entity.id()) else {
+            return Err(StoreError::NotFound(entity.id().to_string()).into());
+        };
+        entity.advance(g[i].version()).map_err(StoreError::from)?;
+        T::check_update(&g[i], &entity)?;
+        self.log_put(&entity)?;
+        g[i] = entity.clone();
+        self.compact(&g);
+        Ok(entity)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), T::Error> {
+        let mut g = self.inner.write().await;
+        if !g.iter().any(|e| &e.id() == id) {
+            return Err(StoreError::NotFound(id.to_string()).into());
+        }
+        self.log_delete(id)?;
+        g.retain(|e| &e.id() != id);
+        self.compact(&g);
+        Ok(())
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), T::Error> {
+        let mut g = self.inner.write().await;
+        let Some(current) = g.iter().find(|e| &e.id() == id) else {
+            return Err(StoreError::NotFound(id.to_string()).into());
+        };
+        if current.version() != expected {
+            return Err(StoreError::from(VersionMismatch {
+                expected,
+                current: current.version(),
+            })
+            .into());
+        }
+        self.log_delete(id)?;
+        g.retain(|e| &e.id() != id);
+        self.compact(&g);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<T>, T::Error> {
+        Ok(self.inner.read().await.clone())
+    }
+}
+
+#[async_trait]
+impl<T: StoredEntity> EntityFetcher for InMemoryEntityStore<T> {
+    async fn fetch_as_json(&self, entity_id: &Uuid) -> anyhow::Result<Value> {
+        super::fetch_as_json::<T, _>(self, entity_id).await
+    }
+
+    async fn list_as_json(
+        &self,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> anyhow::Result<Vec<Value>> {
+        super::list_as_json::<T, _>(self, limit, offset).await
+    }
+}
+
+#[async_trait]
+impl<T: StoredEntity> EntityCreator for InMemoryEntityStore<T> {
+    async fn create_from_json(&self, entity_data: Value) -> anyhow::Result<Value> {
+        super::create_from_json::<T, _>(self, entity_data).await
+    }
+
+    async fn update_from_json(
+        &self,
+        entity_id: &Uuid,
+        entity_data: Value,
+    ) -> anyhow::Result<Value> {
+        super::update_from_json::<T, _>(self, entity_id, entity_data).await
+    }
+
+    async fn delete(&self, entity_id: &Uuid) -> anyhow::Result<()> {
+        Ok(EntityStore::delete(self, entity_id).await?)
+    }
+}
@@ -0,0 +1,286 @@
+//! Generic entity stores
+//!
+//! Every entity is stored the same way: an [`EntityStore`] of create, get,
+//! update, delete, list and query, kept in memory by [`InMemoryEntityStore`]
+//! or in a database by [`BackendStore`], whose updates go through
+//! [`crate::cas`]. What differs between entities is said once, in their
+//! [`StoredEntity`] impl:
+//!
+//! ```ignore
+//! impl StoredEntity for Tag {
+//!     type Error = StoreError;
+//!     const NAME: &'static str = "Tag";
+//!     const SEARCH_FIELDS: &'static [&'static str] = &["name", "status"];
+//!
+//!     fn from_json(entity_data: Value) -> Result<Self, ValidationError> { ... }
+//! }
+//!
+//! pub type InMemoryTagStore = InMemoryEntityStore<Tag>;
+//! pub type TagPostgresStore = PostgresStore<Tag>;
+//! ```
+//!
+//! Both stores implement `EntityFetcher` and `EntityCreator`; stores wrapping
+//! another one get them from [`impl_entity_json!`](crate::impl_entity_json).
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use this::core::Data;
+use uuid::Uuid;
+
+use crate::problem::{ErrorKind, HttpError};
+use crate::query::{Filter, ListQuery, Page};
+use crate::validation::ValidationError;
+use crate::version::{IfMatch, Version, VersionMismatch, Versioned};
+
+mod backend;
+mod memory;
+
+pub use backend::BackendStore;
+#[cfg(feature = "dynamodb")]
+pub use backend::DynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use backend::LmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use backend::MongoStore;
+#[cfg(feature = "mysql")]
+pub use backend::MysqlStore;
+#[cfg(feature = "neo4j")]
+pub use backend::Neo4jStore;
+#[cfg(feature = "postgres")]
+pub use backend::PostgresStore;
+#[cfg(feature = "scylladb")]
+pub use backend::ScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use backend::SqliteStore;
+pub use memory::InMemoryEntityStore;
+
+tokio::task_local! {
+    /// Filters [`list_as_json`] adds to its query, for callers that only see
+    /// the `EntityFetcher` of a store
+    static LIST_FILTERS: Vec<Filter>;
+}
+
+/// Run `f`, narrowing the `EntityFetcher::list_as_json` of the stores it
+/// calls to the entities passing `filters`
+pub async fn with_list_filters<F: std::future::Future>(filters: Vec<Filter>, f: F) -> F::Output {
+    LIST_FILTERS.scope(filters, f).await
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("validation: {0}")]
+    Validation(String),
+    #[error(transparent)]
+    VersionMismatch(#[from] VersionMismatch),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for StoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StoreError::NotFound(_) => ErrorKind::NotFound,
+            StoreError::Conflict(_) => ErrorKind::Conflict,
+            StoreError::Validation(_) => ErrorKind::Validation,
+            StoreError::VersionMismatch(e) => e.kind(),
+            StoreError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> serde_json::Map<String, serde_json::Value> {
+        match self {
+            StoreError::VersionMismatch(e) => e.extensions(),
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+/// An entity kept in an [`EntityStore`]
+pub trait StoredEntity:
+    Data + Versioned + Clone + Serialize + DeserializeOwned + Send + Sync + 'static
+{
+    /// Error of its stores, [`StoreError`] unless the entity has more to
+    /// report
+    type Error: From<StoreError> + HttpError + Send + Sync + 'static;
+
+    /// Name in error messages, e.g. `"StockItem"`
+    const NAME: &'static str;
+
+    /// Fields stored as strings, which backends can search on (see
+    /// `ListQuery::pushdown`)
+    const SEARCH_FIELDS: &'static [&'static str];
+
+    /// Build a new entity from a create payload
+    fn from_json(entity_data: Value) -> Result<Self, ValidationError>;
+
+    /// Merge a partial JSON payload into the entity, for
+    /// `EntityCreator::update_from_json`
+    fn merge_json(self, _entity_data: Value) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "Update operation not implemented for this entity type"
+        ))
+    }
+
+    /// Check an update against the stored entity before it is written
+    fn check_update(_current: &Self, _next: &Self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait EntityStore<T: StoredEntity>: Send + Sync {
+    async fn create(&self, entity: T) -> Result<T, T::Error>;
+    async fn get(&self, id: &Uuid) -> Result<T, T::Error>;
+    async fn update(&self, entity: T) -> Result<T, T::Error>;
+    async fn delete(&self, id: &Uuid) -> Result<(), T::Error>;
+
+    /// Delete the entity provided it is still at `expected`, failing with
+    /// `VersionMismatch` otherwise
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), T::Error>;
+
+    async fn list(&self) -> Result<Vec<T>, T::Error>;
+
+    /// Delete the entity if `if_match` accepts its version; an update made
+    /// after the check still fails the delete
+    async fn delete_matching(&self, id: &Uuid, if_match: &IfMatch) -> Result<(), T::Error> {
+        if if_match.is_any() {
+            return self.delete(id).await;
+        }
+        let current = self.get(id).await?.version();
+        if_match.check(current).map_err(StoreError::from)?;
+        self.delete_at(id, current).await
+    }
+
+    /// Filter, sort and page the entities; backends push down what they
+    /// can search on
+    async fn query(&self, query: &ListQuery) -> Result<Page<T>, T::Error> {
+        Ok(query.apply(self.list().await?))
+    }
+}
+
+/// `EntityFetcher::fetch_as_json` of a store
+pub async fn fetch_as_json<T, S>(store: &S, entity_id: &Uuid) -> anyhow::Result<Value>
+where
+    T: StoredEntity,
+    S: EntityStore<T> + ?Sized,
+{
+    // Other modules only see the fetcher: a missing entity is a `StoreError`
+    // they can tell from a failing store
+    let entity = store.get(entity_id).await.map_err(|e| match e.kind() {
+        ErrorKind::NotFound => {
+            anyhow::Error::new(StoreError::NotFound(format!("{} {}", T::NAME, entity_id)))
+        }
+        _ => anyhow::Error::new(e),
+    })?;
+    Ok(serde_json::to_value(entity)?)
+}
+
+/// `EntityFetcher::list_as_json` of a store
+///
+/// Entities are ordered by `id` and go through [`EntityStore::query`], with
+/// the filters of [`with_list_filters`] if any.
+pub async fn list_as_json<T, S>(
+    store: &S,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> anyhow::Result<Vec<Value>>
+where
+    T: StoredEntity,
+    S: EntityStore<T> + ?Sized,
+{
+    let offset = offset.unwrap_or(0).max(0) as usize;
+    let limit = limit.unwrap_or(20).max(0) as usize;
+    // `query` pages with cursors: the page is the end of the first
+    // `offset + limit` entities
+    let query = ListQuery {
+        filters: LIST_FILTERS.try_with(Clone::clone).unwrap_or_default(),
+        limit: offset.saturating_add(limit),
+        ..ListQuery::default()
+    };
+    store
+        .query(&query)
+        .await?
+        .data
+        .into_iter()
+        .skip(offset)
+        .map(|entity| serde_json::to_value(entity).map_err(Into::into))
+        .collect()
+}
+
+/// `EntityCreator::create_from_json` of a store
+pub async fn create_from_json<T, S>(store: &S, entity_data: Value) -> anyhow::Result<Value>
+where
+    T: StoredEntity,
+    S: EntityStore<T> + ?Sized,
+{
+    let entity = store.create(T::from_json(entity_data)?).await?;
+    Ok(serde_json::to_value(entity)?)
+}
+
+/// `EntityCreator::update_from_json` of a store
+pub async fn update_from_json<T, S>(
+    store: &S,
+    entity_id: &Uuid,
+    entity_data: Value,
+) -> anyhow::Result<Value>
+where
+    T: StoredEntity,
+    S: EntityStore<T> + ?Sized,
+{
+    let entity = store.get(entity_id).await?.merge_json(entity_data)?;
+    let updated = store.update(entity).await?;
+    Ok(serde_json::to_value(updated)?)
+}
+
+/// `EntityFetcher` and `EntityCreator` of a store, going through its
+/// [`EntityStore`] impl for `$entity`
+#[macro_export]
+macro_rules! impl_entity_json {
+    ($store:ty, $entity:ty) => {
+        #[async_trait::async_trait]
+        impl this::prelude::EntityFetcher for $store {
+            async fn fetch_as_json(
+                &self,
+                entity_id: &uuid::Uuid,
+            ) -> Result<serde_json::Value, anyhow::Error> {
+                $crate::store::fetch_as_json::<$entity, _>(self, entity_id).await
+            }
+
+            async fn list_as_json(
+                &self,
+                limit: Option<i32>,
+                offset: Option<i32>,
+            ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+                $crate::store::list_as_json::<$entity, _>(self, limit, offset).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl this::prelude::EntityCreator for $store {
+            async fn create_from_json(
+                &self,
+                entity_data: serde_json::Value,
+            ) -> Result<serde_json::Value, anyhow::Error> {
+                $crate::store::create_from_json::<$entity, _>(self, entity_data).await
+            }
+
+            async fn update_from_json(
+                &self,
+                entity_id: &uuid::Uuid,
+                entity_data: serde_json::Value,
+            ) -> Result<serde_json::Value, anyhow::Error> {
+                $crate::store::update_from_json::<$entity, _>(self, entity_id, entity_data).await
+            }
+
+            async fn delete(&self, entity_id: &uuid::Uuid) -> Result<(), anyhow::Error> {
+                Ok(<Self as $crate::store::EntityStore<$entity>>::delete(self, entity_id).await?)
+            }
+        }
+    };
+}
@@ -0,0 +1,287 @@
+//! Validation of entity payloads
+//!
+//! `impl_data_entity_validated!` declares filters and validators per
+//! operation, but nothing runs them on the REST paths by itself. [`Payload`]
+//! applies them field by field, so that every error is reported against the
+//! field it concerns, and then reads typed values out of the filtered payload
+//! without inventing defaults: a missing field the entity cannot do without is
+//! a `required` error like any other.
+//!
+//! ```ignore
+//! let mut payload = Payload::validate(&Order::validation_rules("create"), entity_data);
+//! let number = payload.required_string("number");
+//! let notes = payload.string("notes");
+//! payload.finish()?;
+//! ```
+//!
+//! Updates go through [`validate_entity`], which runs the `update` rules over
+//! the whole entity.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use this::core::validation::EntityValidationConfig;
+use this::core::validation::validators;
+use uuid::Uuid;
+
+use crate::money::{Currency, Money};
+use crate::problem::{ErrorKind, HttpError};
+
+/// Per-field validation failures
+///
+/// Answered as `422 Unprocessable Entity` with the messages under `errors`:
+///
+/// ```text
+/// {"type": "about:blank", "title": "Unprocessable Entity", "status": 422,
+///  "detail": "validation failed: number: Le champ 'number' est requis",
+///  "errors": {"number": ["Le champ 'number' est requis"]}}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationError {
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationError {
+    /// A single failure on `field`
+    pub fn field(field: &str, message: impl Into<String>) -> Self {
+        let mut error = Self::default();
+        error.add(field, message);
+        error
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors
+            .entry(field.to_string())
+            .or_default()
+            .push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn has(&self, field: &str) -> bool {
+        self.errors.contains_key(field)
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "validation failed")?;
+        let mut separator = ": ";
+        for (field, messages) in &self.errors {
+            for message in messages {
+                write!(f, "{}{}: {}", separator, field, message)?;
+                separator = "; ";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl HttpError for ValidationError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Validation
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+        if let Ok(errors) = serde_json::to_value(&self.errors) {
+            extensions.insert("errors".to_string(), errors);
+        }
+        extensions
+    }
+}
+
+/// A filtered payload and the errors found so far
+pub struct Payload {
+    values: Map<String, Value>,
+    errors: ValidationError,
+}
+
+impl Payload {
+    /// Apply the filters and validators of `config` to each field of `payload`
+    ///
+    /// Fields without rules are kept as sent. A payload that is not a JSON
+    /// object is an error on the `$` pseudo-field.
+    pub fn validate(config: &EntityValidationConfig, payload: Value) -> Self {
+        let mut values = Map::new();
+        let mut errors = ValidationError::default();
+        match payload {
+            Value::Object(fields) => {
+                for (field, value) in fields {
+                    let single = Value::Object(Map::from_iter([(field.clone(), value)]));
+                    match config.validate_and_filter(single) {
+                        Ok(Value::Object(mut filtered)) => {
+                            if let Some(value) = filtered.remove(&field) {
+                                values.insert(field, value);
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(messages) => {
+                            for message in messages {
+                                errors.add(&field, message);
+                            }
+                        }
+                    }
+                }
+            }
+            other => errors.add("$", format!("expected a JSON object, got {}", other)),
+        }
+        Self { values, errors }
+    }
+
+    /// The filtered value of `field`, if it is present and not `null`
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.values.get(field).filter(|value| !value.is_null())
+    }
+
+    /// Record an error on `field`
+    pub fn reject(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.add(field, message);
+    }
+
+    pub fn string(&mut self, field: &str) -> Option<String> {
+        self.read(field, "a string", |value| value.as_str().map(String::from))
+    }
+
+    pub fn required_string(&mut self, field: &str) -> String {
+        let value = self.string(field);
+        self.required(field, value, Default::default())
+    }
+
+    pub fn i32(&mut self, field: &str) -> Option<i32> {
+        self.read(field, "an integer", |value| {
+            value.as_i64().and_then(|value| i32::try_from(value).ok())
+        })
+    }
+
+    pub fn required_i32(&mut self, field: &str) -> i32 {
+        let value = self.i32(field);
+        self.required(field, value, Default::default())
+    }
+
+    pub fn f64(&mut self, field: &str) -> Option<f64> {
+        self.read(field, "a number", Value::as_f64)
+    }
+
+    pub fn required_f64(&mut self, field: &str) -> f64 {
+        let value = self.f64(field);
+        self.required(field, value, Default::default())
+    }
+
+    pub fn uuid(&mut self, field: &str) -> Option<Uuid> {
+        self.read(field, "a UUID", |value| {
+            value.as_str().and_then(|id| Uuid::parse_str(id).ok())
+        })
+    }
+
+    pub fn required_uuid(&mut self, field: &str) -> Uuid {
+        let value = self.uuid(field);
+        self.required(field, value, Default::default())
+    }
+
+    pub fn money(&mut self, field: &str) -> Option<Money> {
+        let value = self.get(field)?;
+        match Money::from_json(value) {
+            Ok(money) => Some(money),
+            Err(e) => {
+                if !self.errors.has(field) {
+                    self.errors.add(field, format!("'{}' {}", field, e));
+                }
+                None
+            }
+        }
+    }
+
+    pub fn required_money(&mut self, field: &str) -> Money {
+        let value = self.money(field);
+        self.required(field, value, Money::zero(Currency::EUR))
+    }
+
+    /// All errors found, or `Ok` when there were none
+    pub fn finish(self) -> Result<(), ValidationError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// The filtered fields, or all errors found
+    pub fn into_values(self) -> Result<Map<String, Value>, ValidationError> {
+        if self.errors.is_empty() {
+            Ok(self.values)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn read<T>(
+        &mut self,
+        field: &str,
+        expected: &str,
+        convert: impl FnOnce(&Value) -> Option<T>,
+    ) -> Option<T> {
+        let value = self.get(field)?;
+        let converted = convert(value);
+        if converted.is_none() && !self.errors.has(field) {
+            self.errors
+                .add(field, format!("'{}' must be {}", field, expected));
+        }
+        converted
+    }
+
+    /// Unwrap a value the entity cannot do without
+    ///
+    /// A missing value is reported with the message of the `required` rule;
+    /// the returned placeholder is never used since [`Payload::finish`] fails.
+    fn required<T>(&mut self, field: &str, value: Option<T>, placeholder: T) -> T {
+        match value {
+            Some(value) => value,
+            None => {
+                if !self.errors.has(field)
+                    && let Err(message) = validators::required()(field, &Value::Null)
+                {
+                    self.errors.add(field, message);
+                }
+                placeholder
+            }
+        }
+    }
+}
+
+/// Run the `update` rules of `config` over a whole entity
+///
+/// The entity is validated as JSON and read back with the filters applied, so
+/// `PUT` bodies are held to the same rules as `update_from_json` payloads.
+pub fn validate_entity<T>(config: &EntityValidationConfig, entity: T) -> Result<T, ValidationError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let json =
+        serde_json::to_value(entity).map_err(|e| ValidationError::field("$", e.to_string()))?;
+    let values = Payload::validate(config, json).into_values()?;
+    serde_json::from_value(Value::Object(values))
+        .map_err(|e| ValidationError::field("$", e.to_string()))
+}
+
+/// Fail unless a `PUT` body is the entity `id` of the URL it was sent to
+///
+/// Authorization and `If-Match` are checked against the URL, so the body may
+/// not name another entity to write.
+pub fn check_id(id: Uuid, body: Uuid) -> Result<(), ValidationError> {
+    if id == body {
+        Ok(())
+    } else {
+        Err(ValidationError::field(
+            "id",
+            format!("'id' must be {}, the id of the URL", id),
+        ))
+    }
+}
@@ -0,0 +1,275 @@
+//! Optimistic concurrency with entity versions
+//!
+//! Every entity carries a [`Version`] that its store bumps on each update.
+//! Stores only write an entity whose version is still the one it was read
+//! with, and fail with [`VersionMismatch`] otherwise, so that two concurrent
+//! updates cannot silently overwrite each other.
+//!
+//! Over HTTP, the version is the entity's strong `ETag`:
+//!
+//! ```text
+//! GET /orders/6f9c...            -> 200, ETag: "3"
+//! PATCH /orders/6f9c...          <- If-Match: "3"
+//!                                -> 200, ETag: "4"   (or 412 if it moved on)
+//! ```
+//!
+//! Handlers read the header with [`IfMatch`] and answer with [`Tagged`].
+
+use std::fmt;
+
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::problem::{ErrorKind, HttpError, Problem};
+
+/// Number of updates an entity went through
+///
+/// Serialized as a plain number. A payload or a stored entity without one
+/// reads as version 0, which is also the version of a new entity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(u64);
+
+impl Version {
+    pub const fn new(version: u64) -> Self {
+        Self(version)
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    /// The version of the entity after one more update
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+
+    /// The strong entity tag of this version, e.g. `"3"`
+    pub fn etag(self) -> String {
+        format!("\"{}\"", self.0)
+    }
+
+    /// Read a strong entity tag; weak tags (`W/"3"`) never match
+    fn from_etag(tag: &str) -> Option<Self> {
+        tag.strip_prefix('"')?
+            .strip_suffix('"')?
+            .parse()
+            .ok()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Version {
+    /// Accepts any non-negative integer, including integral floats (DynamoDB
+    /// reads every number back as one); `null` or no field at all is 0
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_option(VersionVisitor)
+    }
+}
+
+struct VersionVisitor;
+
+impl<'de> Visitor<'de> for VersionVisitor {
+    type Value = Version;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a non-negative integer version")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Version, E> {
+        Ok(Version::default())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Version, E> {
+        Ok(Version::default())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Version, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Version, E> {
+        Ok(Version(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Version, E> {
+        u64::try_from(value)
+            .map(Version)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Version, E> {
+        if value >= 0.0 && value.fract() == 0.0 && value <= u64::MAX as f64 {
+            Ok(Version(value as u64))
+        } else {
+            Err(E::invalid_value(de::Unexpected::Float(value), &self))
+        }
+    }
+}
+
+/// An entity whose updates are versioned
+pub trait Versioned {
+    fn version(&self) -> Version;
+    fn set_version(&mut self, version: Version);
+
+    /// Move past `stored`, the version the store holds, provided this is the
+    /// version the entity was read at
+    ///
+    /// For stores that compare and write under a lock; the others compare in
+    /// the database (see [`crate::cas`]).
+    fn advance(&mut self, stored: Version) -> Result<(), VersionMismatch> {
+        self.ensure_current(stored)?;
+        self.set_version(stored.next());
+        Ok(())
+    }
+
+    /// Fail unless the entity was read at `stored`, for updates that have
+    /// side effects to make before their write
+    fn ensure_current(&self, stored: Version) -> Result<(), VersionMismatch> {
+        if self.version() == stored {
+            Ok(())
+        } else {
+            Err(VersionMismatch {
+                expected: self.version(),
+                current: stored,
+            })
+        }
+    }
+}
+
+/// An update was made against another version than the stored one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("precondition failed: expected version {expected}, found {current}")]
+pub struct VersionMismatch {
+    pub expected: Version,
+    pub current: Version,
+}
+
+impl HttpError for VersionMismatch {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::PreconditionFailed
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+        extensions.insert("expected".to_string(), self.expected.get().into());
+        extensions.insert("current".to_string(), self.current.get().into());
+        extensions
+    }
+}
+
+/// The `If-Match` request header
+///
+/// Without the header, or with `*`, any version matches. Otherwise the
+/// current version must be one of the listed tags; a malformed or weak tag
+/// matches none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IfMatch(Option<Vec<Option<Version>>>);
+
+impl IfMatch {
+    /// Accept any version
+    pub fn any() -> Self {
+        Self(None)
+    }
+
+    /// Accept only `version`
+    pub fn version(version: Version) -> Self {
+        Self(Some(vec![Some(version)]))
+    }
+
+    /// Whether any version is accepted
+    pub fn is_any(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Read the header value
+    pub fn parse(value: &str) -> Self {
+        if value.trim() == "*" {
+            return Self::any();
+        }
+        Self(Some(
+            value
+                .split(',')
+                .map(|tag| Version::from_etag(tag.trim()))
+                .collect(),
+        ))
+    }
+
+    /// Whether an entity at `current` may be changed
+    pub fn check(&self, current: Version) -> Result<(), VersionMismatch> {
+        match &self.0 {
+            None => Ok(()),
+            Some(tags) if tags.contains(&Some(current)) => Ok(()),
+            Some(tags) => Err(VersionMismatch {
+                expected: tags.iter().flatten().next().copied().unwrap_or_default(),
+                current,
+            }),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Problem;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let values: Vec<&str> = parts
+            .headers
+            .get_all(header::IF_MATCH)
+            .iter()
+            .map(|value| value.to_str().unwrap_or(""))
+            .collect();
+        if values.is_empty() {
+            Ok(Self::any())
+        } else {
+            Ok(Self::parse(&values.join(",")))
+        }
+    }
+}
+
+/// A JSON entity response carrying the entity's version as its `ETag`
+#[derive(Debug, Clone)]
+pub struct Tagged<T>(pub T);
+
+impl<T: Serialize + Versioned> IntoResponse for Tagged<T> {
+    fn into_response(self) -> Response {
+        let etag = HeaderValue::from_str(&self.0.version().etag());
+        let mut response = Json(self.0).into_response();
+        if let Ok(etag) = etag {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        response
+    }
+}
+
+/// Implement [`Versioned`] for an entity with a `version: Version` field
+#[macro_export]
+macro_rules! impl_versioned {
+    ($type:ty) => {
+        impl $crate::Versioned for $type {
+            fn version(&self) -> $crate::Version {
+                self.version
+            }
+
+            fn set_version(&mut self, version: $crate::Version) {
+                self.version = version;
+            }
+        }
+    };
+}
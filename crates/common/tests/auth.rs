@@ -0,0 +1,358 @@
+//! Authentication providers and the `auth:` policies, on in-memory stores
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{Body, to_bytes};
+use axum::extract::Request as Incoming;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use common::auth::{
+    AnyOf, Authorization, Claims, JwtAuth, SERVICE_TOKEN_HEADER, ServiceTokenAuth, allows,
+    parse_policy,
+};
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{ValidationError, Version};
+use serde_json::{Value, json};
+use this::core::{AuthContext, AuthPolicy, AuthProvider};
+use this::prelude::*;
+use this::server::host::ServerHost;
+use this::storage::InMemoryLinkService;
+use tower::ServiceExt;
+
+const SECRET: &[u8] = b"test secret";
+const TOKEN: &str = "billing-token";
+
+const LINKS: &str = r#"
+entities:
+  - singular: note
+    plural: notes
+    auth:
+      list: owner_or_role:auditor
+      get: owner
+      create: authenticated
+      update: owner_or_service
+      delete: admin_only
+links: []
+"#;
+
+impl_data_entity!(
+    Note,
+    "note",
+    ["name"],
+    {
+        owned_by: Option<Uuid>,
+        version: Version,
+    }
+);
+
+common::impl_versioned!(Note);
+
+impl StoredEntity for Note {
+    type Error = StoreError;
+    const NAME: &'static str = "Note";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name"];
+
+    fn from_json(_entity_data: Value) -> Result<Self, ValidationError> {
+        Err(ValidationError::field("$", "not created from JSON"))
+    }
+}
+
+fn provider() -> AnyOf<ServiceTokenAuth, JwtAuth> {
+    AnyOf(
+        ServiceTokenAuth::new().with_token("billing", TOKEN),
+        JwtAuth::hs256(SECRET),
+    )
+}
+
+fn bearer(user_id: Uuid, roles: &[&str]) -> String {
+    let roles = roles.iter().map(|role| role.to_string()).collect();
+    let claims = Claims::new(user_id, roles, Duration::from_secs(60));
+    format!("Bearer {}", JwtAuth::hs256(SECRET).sign(&claims).unwrap())
+}
+
+fn request(method: &str, path: &str, credentials: Option<(&str, String)>) -> Request<Body> {
+    let mut builder = Request::builder().method(method).uri(path);
+    if let Some((name, value)) = credentials {
+        builder = builder.header(name, value);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+async fn context(
+    provider: &impl AuthProvider,
+    request: Request<Body>,
+) -> anyhow::Result<AuthContext> {
+    provider.extract_context(&request).await
+}
+
+struct Fixture {
+    host: ServerHost,
+    store: Arc<InMemoryEntityStore<Note>>,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let store = Arc::new(InMemoryEntityStore::<Note>::default());
+        let fetcher: Arc<dyn EntityFetcher> = store.clone();
+        let creator: Arc<dyn EntityCreator> = store.clone();
+        let host = ServerHost::from_builder_components(
+            Arc::new(InMemoryLinkService::new()),
+            LinksConfig::from_yaml_str(LINKS).unwrap(),
+            EntityRegistry::new(),
+            HashMap::from([("note".to_string(), fetcher)]),
+            HashMap::from([("note".to_string(), creator)]),
+        )
+        .unwrap();
+        Self { host, store }
+    }
+
+    async fn note(&self, owner: Uuid) -> Uuid {
+        let note = Note::new(
+            "Note".into(),
+            "active".into(),
+            Some(owner),
+            Version::default(),
+        );
+        self.store.create(note).await.unwrap().id
+    }
+
+    fn authorization(&self) -> Authorization<AnyOf<ServiceTokenAuth, JwtAuth>> {
+        Authorization::from_host(&self.host, provider()).unwrap()
+    }
+
+    /// Routes echoing the query they are sent, behind the policies
+    fn router(&self) -> Router {
+        let echo = |request: Incoming| async move {
+            Json(json!({ "query": request.uri().query().unwrap_or_default() }))
+        };
+        let routes = Router::new()
+            .route("/notes", get(echo))
+            .route("/notes/{id}", get(echo).delete(echo).put(echo));
+        self.authorization().layer(routes)
+    }
+}
+
+async fn send(router: Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn signed_tokens_authenticate_their_user() {
+    let auth = JwtAuth::hs256(SECRET);
+    let user_id = Uuid::new_v4();
+
+    let header = bearer(user_id, &["auditor"]);
+    let user = context(&auth, request("GET", "/", Some(("authorization", header)))).await;
+    let anonymous = context(&auth, request("GET", "/", None)).await;
+
+    match user.unwrap() {
+        AuthContext::User {
+            user_id: id, roles, ..
+        } => {
+            assert_eq!(id, user_id);
+            assert_eq!(roles, ["auditor"]);
+        }
+        other => panic!("expected a user, got {:?}", other),
+    }
+    assert!(matches!(anonymous.unwrap(), AuthContext::Anonymous));
+}
+
+#[tokio::test]
+async fn forged_expired_and_malformed_tokens_are_rejected() {
+    let auth = JwtAuth::hs256(SECRET);
+    let forged = JwtAuth::hs256(b"another secret")
+        .sign(&Claims::new(
+            Uuid::new_v4(),
+            vec![],
+            Duration::from_secs(60),
+        ))
+        .unwrap();
+    let mut claims = Claims::new(Uuid::new_v4(), vec![], Duration::ZERO);
+    claims.exp -= 3_600;
+    let expired = auth.sign(&claims).unwrap();
+
+    for header in [
+        format!("Bearer {}", forged),
+        format!("Bearer {}", expired),
+        "Basic dXNlcjpwYXNz".to_string(),
+        "Bearer not.a.token".to_string(),
+    ] {
+        let result = context(
+            &auth,
+            request("GET", "/", Some(("authorization", header.clone()))),
+        )
+        .await;
+        assert!(result.is_err(), "{}", header);
+    }
+}
+
+#[tokio::test]
+async fn service_tokens_name_their_service() {
+    let provider = provider();
+
+    let service = context(
+        &provider,
+        request("GET", "/", Some((SERVICE_TOKEN_HEADER, TOKEN.to_string()))),
+    )
+    .await;
+    let unknown = context(
+        &provider,
+        request(
+            "GET",
+            "/",
+            Some((SERVICE_TOKEN_HEADER, "billing-tokem".to_string())),
+        ),
+    )
+    .await;
+    let user = context(
+        &provider,
+        request(
+            "GET",
+            "/",
+            Some(("authorization", bearer(Uuid::new_v4(), &[]))),
+        ),
+    )
+    .await;
+
+    assert!(matches!(
+        service.unwrap(),
+        AuthContext::Service { service_name, .. } if service_name == "billing"
+    ));
+    assert!(unknown.is_err());
+    assert!(matches!(user.unwrap(), AuthContext::User { .. }));
+}
+
+#[test]
+fn policies_allow_who_they_name() {
+    let user = |roles: &[&str]| AuthContext::User {
+        user_id: Uuid::new_v4(),
+        tenant_id: Uuid::nil(),
+        roles: roles.iter().map(|role| role.to_string()).collect(),
+    };
+    let service = AuthContext::Service {
+        service_name: "billing".into(),
+        tenant_id: None,
+    };
+    let policy = |name: &str| parse_policy(name).unwrap();
+
+    assert!(allows(&policy("public"), &AuthContext::Anonymous, false));
+    assert!(!allows(
+        &policy("authenticated"),
+        &AuthContext::Anonymous,
+        false
+    ));
+    assert!(allows(&policy("authenticated"), &service, false));
+    assert!(!allows(&policy("owner"), &user(&[]), false));
+    assert!(allows(&policy("owner"), &user(&[]), true));
+    assert!(allows(&policy("owner_or_service"), &service, false));
+    assert!(allows(
+        &policy("owner_or_role:auditor"),
+        &user(&["auditor"]),
+        false
+    ));
+    assert!(!allows(&policy("service_only"), &user(&["admin"]), false));
+    assert!(allows(&policy("admin_only"), &user(&["admin"]), false));
+    assert!(allows(&policy("role:auditor"), &user(&["admin"]), false));
+    assert!(matches!(policy(" public "), AuthPolicy::Public));
+    for typo in ["authenticted", "role:", "owner_or_role:"] {
+        assert!(parse_policy(typo).is_err(), "{}", typo);
+    }
+}
+
+#[tokio::test]
+async fn entities_are_only_touched_by_who_their_policies_allow() {
+    let fixture = Fixture::new();
+    let (ada, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    let note = fixture.note(ada).await;
+    let path = format!("/notes/{}", note);
+    let as_user = |user_id| Some(("authorization", bearer(user_id, &[])));
+
+    let (anonymous, _) = send(fixture.router(), request("GET", &path, None)).await;
+    let (owner, _) = send(fixture.router(), request("GET", &path, as_user(ada))).await;
+    let (other, _) = send(fixture.router(), request("GET", &path, as_user(bob))).await;
+    let (service, _) = send(
+        fixture.router(),
+        request(
+            "PUT",
+            &path,
+            Some((SERVICE_TOKEN_HEADER, TOKEN.to_string())),
+        ),
+    )
+    .await;
+
+    assert_eq!(anonymous, StatusCode::UNAUTHORIZED);
+    assert_eq!(owner, StatusCode::OK);
+    assert_eq!(other, StatusCode::FORBIDDEN);
+    assert_eq!(service, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn only_admins_delete() {
+    let fixture = Fixture::new();
+    let ada = Uuid::new_v4();
+    let path = format!("/notes/{}", fixture.note(ada).await);
+
+    let as_owner = Some(("authorization", bearer(ada, &[])));
+    let as_admin = Some(("authorization", bearer(Uuid::new_v4(), &["admin"])));
+    let (owner, _) = send(fixture.router(), request("DELETE", &path, as_owner)).await;
+    let (admin, _) = send(fixture.router(), request("DELETE", &path, as_admin)).await;
+
+    assert_eq!(owner, StatusCode::FORBIDDEN);
+    assert_eq!(admin, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn users_listing_as_owners_are_scoped_to_their_entities() {
+    let fixture = Fixture::new();
+    let ada = Uuid::new_v4();
+
+    let as_owner = Some(("authorization", bearer(ada, &[])));
+    let as_auditor = Some(("authorization", bearer(Uuid::new_v4(), &["auditor"])));
+    let (_, owner) = send(fixture.router(), request("GET", "/notes?limit=5", as_owner)).await;
+    let (_, auditor) = send(fixture.router(), request("GET", "/notes", as_auditor)).await;
+
+    assert_eq!(owner["query"], format!("limit=5&owned_by={}", ada));
+    assert_eq!(auditor["query"], "");
+}
+
+#[tokio::test]
+async fn guarded_lists_page_through_the_callers_own_entities() {
+    let mut fixture = Fixture::new();
+    let (ada, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    for owner in [bob, ada, bob, ada, bob, ada, bob] {
+        fixture.note(owner).await;
+    }
+    let authorization = fixture.authorization();
+    authorization.guard(&mut fixture.host);
+    let fetchers = fixture.host.entity_fetchers.clone();
+    let page = move |request: Incoming| {
+        let fetcher = fetchers["note"].clone();
+        async move {
+            let offset = request.uri().query().and_then(|query| query.parse().ok());
+            match fetcher.list_as_json(Some(2), offset).await {
+                Ok(notes) => (StatusCode::OK, Json(Value::from(notes))),
+                Err(e) => (StatusCode::FORBIDDEN, Json(json!(e.to_string()))),
+            }
+        }
+    };
+    let router = authorization.authenticate(Router::new().route("/notes", get(page)));
+    let as_ada = || Some(("authorization", bearer(ada, &[])));
+
+    let (_, first) = send(router.clone(), request("GET", "/notes", as_ada())).await;
+    let (_, rest) = send(router.clone(), request("GET", "/notes?2", as_ada())).await;
+    let (anonymous, _) = send(router, request("GET", "/notes", None)).await;
+
+    let first = first.as_array().unwrap();
+    let rest = rest.as_array().unwrap();
+    assert_eq!((first.len(), rest.len()), (2, 1));
+    for note in first.iter().chain(rest) {
+        assert_eq!(note["owned_by"], ada.to_string());
+    }
+    assert_eq!(anonymous, StatusCode::UNAUTHORIZED);
+}
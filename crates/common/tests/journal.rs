@@ -0,0 +1,122 @@
+//! Replay of the journal of an in-memory collection
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use common::journal::{COMPACT_EVERY, Journal};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Row {
+    id: Uuid,
+    value: u32,
+}
+
+fn row(value: u32) -> Row {
+    Row {
+        id: Uuid::new_v4(),
+        value,
+    }
+}
+
+fn open(dir: &Path) -> (Journal<Row>, Vec<Row>) {
+    Journal::open(dir, "rows", |row: &Row| row.id).expect("open the journal")
+}
+
+/// Append `text` to the log, as a crash or another writer would leave it
+fn append_raw(dir: &Path, text: &str) {
+    let mut log = OpenOptions::new()
+        .append(true)
+        .open(dir.join("rows.log"))
+        .unwrap();
+    log.write_all(text.as_bytes()).unwrap();
+}
+
+#[test]
+fn reopening_replays_puts_and_deletes_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let (first, second, third) = (row(1), row(2), row(3));
+    {
+        let (journal, rows) = open(dir.path());
+        assert!(rows.is_empty());
+        for row in [&first, &second, &third] {
+            journal.put(row).unwrap();
+        }
+        journal
+            .put(&Row {
+                value: 20,
+                ..second.clone()
+            })
+            .unwrap();
+        journal.delete(&first.id).unwrap();
+    }
+
+    let (_, rows) = open(dir.path());
+
+    assert_eq!(
+        rows,
+        [
+            Row {
+                value: 20,
+                ..second
+            },
+            third
+        ]
+    );
+}
+
+#[test]
+fn an_incomplete_last_line_is_skipped_and_cut_off() {
+    let dir = tempfile::tempdir().unwrap();
+    let kept = row(1);
+    {
+        let (journal, _) = open(dir.path());
+        journal.put(&kept).unwrap();
+    }
+    append_raw(dir.path(), r#"{"op":"put","row":{"id":"#);
+
+    let (journal, rows) = open(dir.path());
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0], kept);
+    let later = row(2);
+    journal.put(&later).unwrap();
+    drop(journal);
+
+    let (_, rows) = open(dir.path());
+    assert_eq!(rows, [kept, later]);
+}
+
+#[test]
+fn a_corrupt_line_before_the_last_fails_the_open() {
+    let dir = tempfile::tempdir().unwrap();
+    drop(open(dir.path()));
+    let valid = serde_json::json!({ "op": "put", "row": row(1) });
+    append_raw(dir.path(), &format!("not a record\n{}\n", valid));
+
+    let error = Journal::<Row>::open(dir.path(), "rows", |row| row.id)
+        .err()
+        .expect("a corrupt log");
+
+    assert!(format!("{:#}", error).contains("line 1"), "{:#}", error);
+}
+
+#[test]
+fn compaction_moves_the_log_into_the_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let (journal, _) = open(dir.path());
+    let mut rows = Vec::new();
+    for value in 0..COMPACT_EVERY as u32 {
+        rows.push(row(value));
+        journal.put(rows.last().unwrap()).unwrap();
+    }
+
+    journal.compact_if_due(&rows);
+
+    assert_eq!(fs::metadata(dir.path().join("rows.log")).unwrap().len(), 0);
+    let snapshot = fs::read(dir.path().join("rows.snapshot.json")).unwrap();
+    assert_eq!(serde_json::from_slice::<Vec<Row>>(&snapshot).unwrap(), rows);
+    drop(journal);
+    assert_eq!(open(dir.path()).1, rows);
+}
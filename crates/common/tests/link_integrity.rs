@@ -0,0 +1,426 @@
+//! `on_delete` rules and link checks of `LinkIntegrity`, on in-memory stores
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use axum::routing::{delete, post};
+use common::links::{CardinalityError, LinkIntegrity, LinkRules};
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{ValidationError, Version};
+use serde_json::Value;
+use this::prelude::*;
+use this::server::host::ServerHost;
+use this::storage::InMemoryLinkService;
+use tokio::sync::Barrier;
+use tower::ServiceExt;
+
+const LINKS: &str = r#"
+entities:
+  - singular: order
+    plural: orders
+  - singular: invoice
+    plural: invoices
+  - singular: payment
+    plural: payments
+  - singular: receipt
+    plural: receipts
+
+links:
+  - link_type: has_invoice
+    source_type: order
+    target_type: invoice
+    forward_route_name: invoices
+    reverse_route_name: order
+    cardinality: one_to_many
+    on_delete: cascade
+
+  - link_type: payment
+    source_type: invoice
+    target_type: payment
+    forward_route_name: payments
+    reverse_route_name: invoice
+    cardinality: one_to_many
+    on_delete: restrict
+
+  - link_type: receipt
+    source_type: payment
+    target_type: receipt
+    forward_route_name: receipt
+    reverse_route_name: payment
+    cardinality: one_to_one
+
+  - link_type: refunds
+    source_type: payment
+    target_type: invoice
+    forward_route_name: refunded
+    reverse_route_name: refunds
+    cardinality: many_to_one
+"#;
+
+impl_data_entity!(Record, "record", ["name"], { version: Version });
+
+common::impl_versioned!(Record);
+
+impl StoredEntity for Record {
+    type Error = StoreError;
+    const NAME: &'static str = "Record";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name"];
+
+    fn from_json(_entity_data: Value) -> Result<Self, ValidationError> {
+        Err(ValidationError::field("$", "not created from JSON"))
+    }
+}
+
+type Store = Arc<InMemoryEntityStore<Record>>;
+
+struct Fixture {
+    integrity: LinkIntegrity,
+    links: Arc<dyn LinkService>,
+    stores: HashMap<&'static str, Store>,
+}
+
+impl Fixture {
+    fn new(links: Arc<dyn LinkService>) -> Self {
+        let stores: HashMap<&str, Store> = ["order", "invoice", "payment", "receipt"]
+            .into_iter()
+            .map(|entity_type| (entity_type, Store::default()))
+            .collect();
+        let creators = stores
+            .iter()
+            .map(|(entity_type, store)| {
+                let creator: Arc<dyn EntityCreator> = store.clone();
+                (entity_type.to_string(), creator)
+            })
+            .collect();
+        let host = ServerHost::from_builder_components(
+            links.clone(),
+            LinksConfig::from_yaml_str(LINKS).unwrap(),
+            EntityRegistry::new(),
+            HashMap::new(),
+            creators,
+        )
+        .unwrap();
+        let rules: LinkRules = serde_yaml::from_str(LINKS).unwrap();
+        Self {
+            integrity: LinkIntegrity::from_host(&host, rules),
+            links,
+            stores,
+        }
+    }
+
+    async fn record(&self, entity_type: &str) -> Uuid {
+        let record = Record::new(entity_type.into(), "active".into(), Version::default());
+        self.stores[entity_type].create(record).await.unwrap().id
+    }
+
+    async fn link(&self, link_type: &str, source: Uuid, target: Uuid) -> Uuid {
+        let link = LinkEntity::new(link_type, source, target, None);
+        self.links.create(link).await.unwrap().id
+    }
+
+    /// The entity type and link `check_link` finds in the way of a new link
+    async fn conflict(
+        &self,
+        link_type: &str,
+        source: Uuid,
+        target: Uuid,
+    ) -> Option<(String, Uuid)> {
+        let link = LinkEntity::new(link_type, source, target, None);
+        match self.integrity.check_link(&link).await {
+            Ok(()) => None,
+            Err(CardinalityError::Exceeded {
+                entity_type,
+                existing,
+                ..
+            }) => Some((entity_type, existing)),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    async fn exists(&self, entity_type: &str, id: Uuid) -> bool {
+        self.stores[entity_type].get(&id).await.is_ok()
+    }
+
+    /// Routes answering every request they are sent, behind the rules
+    fn router(&self) -> Router {
+        let ok = || async { StatusCode::NO_CONTENT };
+        let created = || async { StatusCode::CREATED };
+        let routes = Router::new()
+            .route("/{plural}/{id}", delete(ok))
+            .route("/{plural}/{id}/{route}/{target}", delete(ok).post(created))
+            .route("/{plural}/{id}/{route}/{target}/{next}", post(created))
+            .route(
+                "/{plural}/{id}/{route}/{target}/{next}/{last}",
+                post(created),
+            );
+        self.integrity.clone().layer(routes)
+    }
+}
+
+async fn send(router: Router, method: &str, path: String) -> StatusCode {
+    respond(router, method, path).await.0
+}
+
+async fn respond(router: Router, method: &str, path: String) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn deleting_an_order_cascades_to_its_invoices_and_drops_their_links() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let order = fixture.record("order").await;
+    let (first, second) = (
+        fixture.record("invoice").await,
+        fixture.record("invoice").await,
+    );
+    fixture.link("has_invoice", order, first).await;
+    fixture.link("has_invoice", order, second).await;
+
+    fixture.integrity.delete("order", order).await.unwrap();
+
+    assert!(!fixture.exists("order", order).await);
+    assert!(!fixture.exists("invoice", first).await);
+    assert!(!fixture.exists("invoice", second).await);
+    assert!(fixture.links.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn a_restricted_link_down_the_cascade_refuses_the_whole_deletion() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let order = fixture.record("order").await;
+    let invoice = fixture.record("invoice").await;
+    let payment = fixture.record("payment").await;
+    fixture.link("has_invoice", order, invoice).await;
+    fixture.link("payment", invoice, payment).await;
+
+    let error = fixture.integrity.delete("order", order).await.unwrap_err();
+
+    assert!(error.to_string().contains("invoice"), "{}", error);
+    assert!(fixture.exists("order", order).await);
+    assert!(fixture.exists("invoice", invoice).await);
+    assert_eq!(fixture.links.list().await.unwrap().len(), 2);
+    let status = send(fixture.router(), "DELETE", format!("/orders/{}", order)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn nested_routes_resolve_the_entity_they_link_from() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let order = fixture.record("order").await;
+    let (invoice, other) = (
+        fixture.record("invoice").await,
+        fixture.record("invoice").await,
+    );
+    let payment = fixture.record("payment").await;
+    fixture.link("has_invoice", order, invoice).await;
+    fixture.link("payment", other, payment).await;
+
+    // The payment already belongs to another invoice
+    let nested = format!(
+        "/orders/{}/invoices/{}/payments/{}",
+        order, invoice, payment
+    );
+    assert_eq!(
+        send(fixture.router(), "POST", nested).await,
+        StatusCode::CONFLICT
+    );
+    // A new payment has no invoice yet
+    let new = format!("/orders/{}/invoices/{}/payments", order, invoice);
+    assert_eq!(
+        send(fixture.router(), "POST", new).await,
+        StatusCode::CREATED
+    );
+    // Deleting through a link route deletes the link, not the invoice
+    let unlink = format!("/orders/{}/invoices/{}", order, invoice);
+    assert_eq!(
+        send(fixture.router(), "DELETE", unlink).await,
+        StatusCode::NO_CONTENT
+    );
+}
+
+#[tokio::test]
+async fn one_to_one_links_conflict_from_either_end() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let (payment, other_payment) = (
+        fixture.record("payment").await,
+        fixture.record("payment").await,
+    );
+    let (receipt, other_receipt) = (
+        fixture.record("receipt").await,
+        fixture.record("receipt").await,
+    );
+    let existing = fixture.link("receipt", payment, receipt).await;
+
+    assert_eq!(
+        fixture.conflict("receipt", payment, other_receipt).await,
+        Some(("payment".to_string(), existing))
+    );
+    assert_eq!(
+        fixture.conflict("receipt", other_payment, receipt).await,
+        Some(("receipt".to_string(), existing))
+    );
+    assert_eq!(
+        fixture
+            .conflict("receipt", other_payment, other_receipt)
+            .await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn many_to_one_links_only_limit_their_source() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let (payment, other_payment) = (
+        fixture.record("payment").await,
+        fixture.record("payment").await,
+    );
+    let (invoice, other_invoice) = (
+        fixture.record("invoice").await,
+        fixture.record("invoice").await,
+    );
+    let existing = fixture.link("refunds", payment, invoice).await;
+
+    assert_eq!(
+        fixture.conflict("refunds", payment, other_invoice).await,
+        Some(("payment".to_string(), existing))
+    );
+    assert_eq!(
+        fixture.conflict("refunds", other_payment, invoice).await,
+        None
+    );
+    // Link types without a rule are not checked
+    assert_eq!(fixture.conflict("tagged", payment, invoice).await, None);
+}
+
+#[tokio::test]
+async fn one_to_many_conflicts_name_the_link_in_the_way() {
+    let fixture = Fixture::new(Arc::new(InMemoryLinkService::new()));
+    let (invoice, other) = (
+        fixture.record("invoice").await,
+        fixture.record("invoice").await,
+    );
+    let (payment, second) = (
+        fixture.record("payment").await,
+        fixture.record("payment").await,
+    );
+    let existing = fixture.link("payment", invoice, payment).await;
+
+    let forward = format!("/invoices/{}/payments/{}", other, payment);
+    let (status, problem) = respond(fixture.router(), "POST", forward).await;
+    let more = format!("/invoices/{}/payments/{}", invoice, second);
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(problem["link_type"], "payment");
+    assert_eq!(problem["cardinality"], "one_to_many");
+    assert_eq!(problem["existing_link"], existing.to_string());
+    assert_eq!(
+        send(fixture.router(), "POST", more).await,
+        StatusCode::CREATED
+    );
+}
+
+/// A link service holding every creation until `n` of them are under way,
+/// so that they all pass the checks made before
+struct Simultaneous {
+    inner: InMemoryLinkService,
+    barrier: Barrier,
+}
+
+#[async_trait]
+impl LinkService for Simultaneous {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        self.barrier.wait().await;
+        self.inner.create(link).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        self.inner.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        self.inner.list().await
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_source(source_id, link_type, target_type)
+            .await
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_target(target_id, link_type, source_type)
+            .await
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        self.inner.update(id, link).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        self.inner.delete_by_entity(entity_id).await
+    }
+}
+
+#[tokio::test]
+async fn simultaneous_links_never_exceed_the_cardinality() {
+    const CREATORS: usize = 5;
+    let links = Arc::new(Simultaneous {
+        inner: InMemoryLinkService::new(),
+        barrier: Barrier::new(CREATORS),
+    });
+    let fixture = Fixture::new(links.clone());
+    let payment = fixture.record("payment").await;
+    let checked = fixture.integrity.checked_link_service();
+
+    let mut tasks = Vec::new();
+    for _ in 0..CREATORS {
+        let invoice = fixture.record("invoice").await;
+        let checked = checked.clone();
+        tasks.push(tokio::spawn(async move {
+            checked
+                .create(LinkEntity::new("payment", invoice, payment, None))
+                .await
+        }));
+    }
+    let mut created = Vec::new();
+    for task in tasks {
+        if let Ok(link) = task.await.unwrap() {
+            created.push(link);
+        }
+    }
+
+    let stored = links.inner.list().await.unwrap();
+    assert!(created.len() <= 1);
+    assert_eq!(
+        stored.iter().map(|link| link.id).collect::<Vec<_>>(),
+        created.iter().map(|link| link.id).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,145 @@
+//! Parsing, arithmetic and the JSON shapes of exact money amounts
+
+use common::money::{filters, validators};
+use common::{Currency, Money, MoneyError};
+use serde_json::{Value, json};
+
+fn eur(minor_units: i64) -> Money {
+    Money::from_minor(minor_units, Currency::EUR)
+}
+
+#[test]
+fn amounts_parse_to_the_minor_unit_of_their_currency() {
+    assert_eq!(Money::parse("999.99", Currency::EUR), Ok(eur(99_999)));
+    assert_eq!(Money::parse("-0.5", Currency::EUR), Ok(eur(-50)));
+    assert_eq!(Money::parse(".05", Currency::EUR), Ok(eur(5)));
+    assert_eq!(Money::parse("1.500", Currency::EUR), Ok(eur(150)));
+    assert_eq!(
+        Money::parse("1200", Currency::JPY),
+        Ok(Money::from_minor(1_200, Currency::JPY))
+    );
+    assert_eq!(
+        "1.234 KWD"
+            .parse::<Money>()
+            .map(|money| money.minor_units()),
+        Ok(1_234)
+    );
+}
+
+#[test]
+fn extra_decimals_and_malformed_amounts_are_rejected() {
+    assert!(matches!(
+        Money::parse("0.001", Currency::EUR),
+        Err(MoneyError::TooPrecise { digits: 2, .. })
+    ));
+    assert!(matches!(
+        Money::parse("1.5", Currency::JPY),
+        Err(MoneyError::TooPrecise { digits: 0, .. })
+    ));
+    for amount in ["", ".", "1,5", "1e3", "--1", "1.2.3"] {
+        assert!(
+            matches!(
+                Money::parse(amount, Currency::EUR),
+                Err(MoneyError::InvalidAmount(_))
+            ),
+            "{:?}",
+            amount
+        );
+    }
+    assert_eq!(
+        Money::parse("99999999999999999999", Currency::EUR),
+        Err(MoneyError::Overflow)
+    );
+    assert!(matches!(
+        "12.00".parse::<Money>(),
+        Err(MoneyError::MissingCurrency(_))
+    ));
+    assert!(matches!(
+        "12.00 XYZ".parse::<Money>(),
+        Err(MoneyError::UnknownCurrency(_))
+    ));
+}
+
+#[test]
+fn sums_are_exact_where_floats_are_not() {
+    let total = Money::sum(Currency::EUR, [eur(10), eur(20)]).unwrap();
+    assert_eq!(total.to_string(), "0.30 EUR");
+    assert_ne!(0.1_f64 + 0.2, 0.3);
+
+    let thousand = Money::sum(Currency::EUR, std::iter::repeat_n(eur(1), 1_000)).unwrap();
+    assert_eq!(thousand, eur(1_000));
+}
+
+#[test]
+fn arithmetic_checks_currencies_and_overflow() {
+    assert_eq!(eur(1_000).checked_sub(eur(2_500)), Ok(eur(-1_500)));
+    assert_eq!(eur(1_999).checked_mul(3), Ok(eur(5_997)));
+    assert_eq!(
+        eur(1_000).checked_add(Money::from_minor(1_000, Currency::USD)),
+        Err(MoneyError::CurrencyMismatch(Currency::EUR, Currency::USD))
+    );
+    assert_eq!(
+        Money::sum(Currency::EUR, [eur(1), Money::from_minor(1, Currency::GBP)]),
+        Err(MoneyError::CurrencyMismatch(Currency::EUR, Currency::GBP))
+    );
+    assert_eq!(eur(i64::MAX).checked_add(eur(1)), Err(MoneyError::Overflow));
+    assert_eq!(eur(i64::MAX).checked_mul(2), Err(MoneyError::Overflow));
+}
+
+#[test]
+fn fractional_quantities_round_half_away_from_zero() {
+    assert_eq!(eur(1).checked_mul_rounded(2.5), Ok(eur(3)));
+    assert_eq!(eur(-1).checked_mul_rounded(2.5), Ok(eur(-3)));
+    assert_eq!(eur(333).checked_mul_rounded(1.0 / 3.0), Ok(eur(111)));
+    assert_eq!(
+        eur(i64::MAX).checked_mul_rounded(2.0),
+        Err(MoneyError::Overflow)
+    );
+    assert_eq!(
+        eur(1).checked_mul_rounded(f64::NAN),
+        Err(MoneyError::Overflow)
+    );
+}
+
+#[test]
+fn amounts_in_different_currencies_do_not_compare() {
+    assert!(eur(100) < eur(101));
+    let dollar = Money::from_minor(100, Currency::USD);
+    assert_eq!(eur(100).partial_cmp(&dollar), None);
+}
+
+#[test]
+fn every_json_shape_reads_to_the_same_amount() {
+    let shapes = [
+        json!("999.99 EUR"),
+        json!({ "amount": "999.99", "currency": "eur" }),
+        json!({ "amount": 999.99, "currency": "EUR" }),
+    ];
+    for shape in shapes {
+        let money: Money = serde_json::from_value(shape.clone()).unwrap();
+        assert_eq!(money, eur(99_999), "{}", shape);
+        assert_eq!(serde_json::to_value(money).unwrap(), json!("999.99 EUR"));
+    }
+    assert!(matches!(
+        Money::from_json(&json!(999.99)),
+        Err(MoneyError::MissingCurrency(_))
+    ));
+    assert_eq!(eur(-5).to_string(), "-0.05 EUR");
+}
+
+#[test]
+fn validators_and_the_filter_work_on_any_shape() {
+    let positive = validators::positive();
+    let max = validators::max_amount(100);
+    let normalize = filters::normalize();
+    let object = json!({ "amount": 100, "currency": "EUR" });
+
+    assert_eq!(positive("amount", &object), Ok(()));
+    assert!(positive("amount", &json!("0.00 EUR")).is_err());
+    assert_eq!(max("amount", &object), Ok(()));
+    assert!(max("amount", &json!("100.01 EUR")).is_err());
+    assert!(validators::money()("amount", &json!(12)).is_err());
+    assert_eq!(positive("amount", &Value::Null), Ok(()));
+    assert_eq!(normalize("amount", object).unwrap(), json!("100.00 EUR"));
+    assert!(normalize("amount", json!({ "amount": "0.001", "currency": "EUR" })).is_err());
+}
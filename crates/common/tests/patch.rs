@@ -0,0 +1,101 @@
+//! JSON Merge Patch, and its checks on the fields of an entity
+
+use chrono::{DateTime, Utc};
+use common::{merge_patch, patch_entity};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use this::core::validation::{EntityValidationConfig, filters, validators};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Contact {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    name: String,
+    email: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn contact() -> Contact {
+    Contact {
+        id: Uuid::new_v4(),
+        created_at: Utc::now(),
+        name: "Ada".into(),
+        email: Some("ada@example.com".into()),
+        tags: vec!["vip".into()],
+    }
+}
+
+fn config() -> EntityValidationConfig {
+    let mut config = EntityValidationConfig::new("contact");
+    config.add_validator("name", validators::string_length(2, 50));
+    config.add_filter("name", filters::trim());
+    config
+}
+
+#[test]
+fn merge_patch_follows_rfc_7396() {
+    let mut target = json!({ "a": "b", "c": { "d": "e", "f": "g" }, "h": [1] });
+
+    merge_patch(
+        &mut target,
+        json!({ "a": "z", "c": { "f": null }, "h": [2, 3], "i": { "j": 1 } }),
+    );
+
+    assert_eq!(
+        target,
+        json!({ "a": "z", "c": { "d": "e" }, "h": [2, 3], "i": { "j": 1 } })
+    );
+    merge_patch(&mut target, json!(["replaced"]));
+    assert_eq!(target, json!(["replaced"]));
+}
+
+#[test]
+fn a_patch_changes_only_the_fields_it_carries() {
+    let current = contact();
+
+    let patched = patch_entity(&config(), current.clone(), json!({ "name": "  Grace " })).unwrap();
+
+    assert_eq!(patched.name, "Grace");
+    assert_eq!(patched.email, current.email);
+    assert_eq!(patched.tags, current.tags);
+    assert_eq!(patched.created_at, current.created_at);
+}
+
+#[test]
+fn null_clears_optional_fields_only() {
+    let cleared =
+        patch_entity(&config(), contact(), json!({ "email": null, "tags": null })).unwrap();
+    let required = patch_entity(&config(), contact(), json!({ "name": null })).unwrap_err();
+
+    assert_eq!(cleared.email, None);
+    assert!(cleared.tags.is_empty());
+    assert_eq!(
+        required.errors["name"],
+        vec!["'name' cannot be null".to_string()]
+    );
+}
+
+#[test]
+fn read_only_fields_may_only_be_repeated() {
+    let current = contact();
+    let repeated = json!({ "id": current.id, "name": "Grace" });
+    let changed =
+        json!({ "id": Uuid::new_v4(), "created_at": "2020-01-01T00:00:00Z", "name": "G" });
+
+    assert!(patch_entity(&config(), current.clone(), repeated).is_ok());
+    let error = patch_entity(&config(), current, changed).unwrap_err();
+
+    assert_eq!(
+        error.errors.keys().collect::<Vec<_>>(),
+        ["created_at", "id", "name"]
+    );
+}
+
+#[test]
+fn a_patch_must_be_an_object() {
+    let error = patch_entity(&config(), contact(), json!(["name"])).unwrap_err();
+
+    assert!(error.errors.contains_key("$"));
+}
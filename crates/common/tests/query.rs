@@ -0,0 +1,127 @@
+//! Filters, cursor paging and the checks of list queries
+
+use common::query::{Filter, FilterOp, ListQuery};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+struct Row {
+    id: String,
+    status: String,
+    amount: String,
+}
+
+fn rows() -> Vec<Row> {
+    (0..10)
+        .map(|i| Row {
+            id: format!("{:02}", i),
+            status: if i % 2 == 0 { "paid" } else { "sent" }.to_string(),
+            amount: format!("{}.50 EUR", (i * 7) % 10),
+        })
+        .collect()
+}
+
+fn query(pairs: &[(&str, &str)]) -> ListQuery {
+    ListQuery::from_pairs(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+        .expect("a valid query")
+}
+
+fn ids(rows: &[Row]) -> Vec<&str> {
+    rows.iter().map(|row| row.id.as_str()).collect()
+}
+
+#[test]
+fn cursors_walk_through_every_entity_once() {
+    let mut cursor: Option<String> = None;
+    let mut seen = Vec::new();
+    loop {
+        let mut pairs = vec![("sort", "-amount"), ("limit", "3")];
+        if let Some(cursor) = &cursor {
+            pairs.push(("cursor", cursor));
+        }
+        let page = query(&pairs).apply(rows());
+        assert_eq!(page.total, 10);
+        seen.extend(page.data);
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    let mut expected = rows();
+    expected.sort_by(|a, b| b.amount.cmp(&a.amount).then(a.id.cmp(&b.id)));
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn a_cursor_keeps_its_place_when_entities_are_added_before_it() {
+    let first = query(&[("limit", "4")]).apply(rows());
+    let cursor = first.next_cursor.expect("more pages");
+    let mut added = rows();
+    added.insert(
+        0,
+        Row {
+            id: "00a".to_string(),
+            status: "paid".to_string(),
+            amount: "1.00 EUR".to_string(),
+        },
+    );
+
+    let next = query(&[("limit", "4"), ("cursor", &cursor)]).apply(added);
+
+    assert_eq!(ids(&next.data), ["04", "05", "06", "07"]);
+}
+
+#[test]
+fn filters_compare_amounts_numerically() {
+    let page = query(&[("status", "paid"), ("amount_gte", "4.5")]).apply(rows());
+
+    assert_eq!(ids(&page.data), ["02", "04", "08"]);
+    assert_eq!(page.total, 3);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn filter_keys_take_their_operator_suffix() {
+    assert_eq!(
+        Filter::parse("amount_lt", "3".into()),
+        Filter {
+            field: "amount".into(),
+            op: FilterOp::Lt,
+            value: "3".into()
+        }
+    );
+    assert_eq!(Filter::parse("_ne", "x".into()).op, FilterOp::Eq);
+    assert!(!Filter::parse("owner", "a".into()).matches(&json!({ "owner": null })));
+    assert!(Filter::parse("owner_ne", "a".into()).matches(&json!({})));
+}
+
+#[test]
+fn unknown_filters_and_sort_keys_are_rejected() {
+    let error = query(&[("colour", "red"), ("amount_gt", "1"), ("sort", "-size")])
+        .check::<Row>()
+        .unwrap_err();
+
+    assert_eq!(error.errors.keys().collect::<Vec<_>>(), ["colour", "sort"]);
+}
+
+#[test]
+fn invalid_limits_and_foreign_cursors_are_rejected() {
+    let pairs = |pairs: &[(&str, &str)]| {
+        ListQuery::from_pairs(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+    };
+    let cursor = query(&[("sort", "status"), ("limit", "1")])
+        .apply(rows())
+        .next_cursor
+        .expect("more pages");
+
+    for limit in ["0", "501", "ten"] {
+        let error = pairs(&[("limit", limit)]).unwrap_err();
+        assert!(error.errors.contains_key("limit"), "limit={}", limit);
+    }
+    // Made for another order, or not a cursor at all
+    for cursor in [cursor.as_str(), "not-a-cursor"] {
+        let error = pairs(&[("sort", "amount"), ("cursor", cursor)]).unwrap_err();
+        assert!(error.errors.contains_key("cursor"));
+    }
+}
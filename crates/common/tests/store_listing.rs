@@ -0,0 +1,96 @@
+//! `EntityFetcher::list_as_json` of the stores, through their `query`
+
+use common::query::{Filter, FilterOp};
+use common::store::{
+    EntityStore, InMemoryEntityStore, StoreError, StoredEntity, with_list_filters,
+};
+use common::{ValidationError, Version};
+use serde_json::Value;
+use this::prelude::*;
+
+impl_data_entity!(
+    Note,
+    "note",
+    ["name"],
+    {
+        owned_by: Option<Uuid>,
+        version: Version,
+    }
+);
+
+common::impl_versioned!(Note);
+
+impl StoredEntity for Note {
+    type Error = StoreError;
+    const NAME: &'static str = "Note";
+    const SEARCH_FIELDS: &'static [&'static str] = &["name"];
+
+    fn from_json(_entity_data: Value) -> Result<Self, ValidationError> {
+        Err(ValidationError::field("$", "not created from JSON"))
+    }
+}
+
+async fn store(owners: &[Uuid]) -> InMemoryEntityStore<Note> {
+    let store = InMemoryEntityStore::default();
+    for (i, owner) in owners.iter().enumerate() {
+        let note = Note::new(
+            format!("Note {}", i),
+            "active".into(),
+            Some(*owner),
+            Version::default(),
+        );
+        store.create(note).await.unwrap();
+    }
+    store
+}
+
+fn ids(notes: &[Value]) -> Vec<String> {
+    notes.iter().map(|note| note["id"].to_string()).collect()
+}
+
+#[tokio::test]
+async fn pages_are_taken_in_id_order() {
+    let store = store(&[Uuid::new_v4(); 7]).await;
+    let mut all: Vec<Note> = store.list().await.unwrap();
+    all.sort_by_key(|note| note.id.to_string());
+
+    let first = store.list_as_json(Some(3), None).await.unwrap();
+    let second = store.list_as_json(Some(3), Some(3)).await.unwrap();
+    let last = store.list_as_json(Some(3), Some(6)).await.unwrap();
+    let beyond = store.list_as_json(Some(3), Some(9)).await.unwrap();
+
+    let paged: Vec<String> = [first, second, last.clone()]
+        .iter()
+        .flat_map(|page| ids(page))
+        .collect();
+    let expected: Vec<String> = all.iter().map(|note| format!("\"{}\"", note.id)).collect();
+    assert_eq!(paged, expected);
+    assert_eq!(last.len(), 1);
+    assert!(beyond.is_empty());
+}
+
+#[tokio::test]
+async fn scoped_filters_apply_before_paging() {
+    let (ada, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    let store = store(&[bob, ada, bob, bob, ada, bob, ada]).await;
+    let mine = Filter {
+        field: "owned_by".to_string(),
+        op: FilterOp::Eq,
+        value: ada.to_string(),
+    };
+
+    let first = with_list_filters(vec![mine.clone()], store.list_as_json(Some(2), None))
+        .await
+        .unwrap();
+    let rest = with_list_filters(vec![mine], store.list_as_json(Some(2), Some(2)))
+        .await
+        .unwrap();
+    let unscoped = store.list_as_json(Some(20), None).await.unwrap();
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(rest.len(), 1);
+    for note in first.iter().chain(&rest) {
+        assert_eq!(note["owned_by"], ada.to_string());
+    }
+    assert_eq!(unscoped.len(), 7);
+}
@@ -1,17 +1,20 @@
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
 use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
 
 use super::ActivityStore;
 use super::handlers::{
-    ActivityState, create_activity, delete_activity, get_activity, list_activities, update_activity,
+    ActivityState, create_activity, delete_activity, get_activity, invoice_activity,
+    list_activities, patch_activity, update_activity,
 };
+use crate::services::Refacturation;
 
 #[derive(Clone)]
 pub struct ActivityDescriptor {
     store: Arc<dyn ActivityStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    refacturation: Option<Arc<Refacturation>>,
 }
 
 impl ActivityDescriptor {
@@ -26,8 +29,15 @@ impl ActivityDescriptor {
         Self {
             store,
             entity_creator,
+            refacturation: None,
         }
     }
+
+    /// Serve `POST /activities/{id}/refacturation` with `refacturation`
+    pub fn with_refacturation(mut self, refacturation: Option<Arc<Refacturation>>) -> Self {
+        self.refacturation = refacturation;
+        self
+    }
 }
 
 impl EntityDescriptor for ActivityDescriptor {
@@ -43,15 +53,22 @@ impl EntityDescriptor for ActivityDescriptor {
         let state = ActivityState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            refacturation: self.refacturation.clone(),
         };
-        Router::new()
+        let router = Router::new()
             .route("/activities", get(list_activities).post(create_activity))
             .route(
                 "/activities/{id}",
                 get(get_activity)
                     .put(update_activity)
+                    .patch(patch_activity)
                     .delete(delete_activity),
-            )
-            .with_state(state)
+            );
+        let router = if self.refacturation.is_some() {
+            router.route("/activities/{id}/refacturation", post(invoice_activity))
+        } else {
+            router
+        };
+        router.with_state(state)
     }
 }
@@ -1,69 +1,120 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Activity, ActivityStore};
+use super::{Activity, ActivityStore, ActivityStoreError};
+use crate::services::{Refacturation, RefacturationRequest};
 
 #[derive(Clone)]
 pub struct ActivityState {
     pub store: Arc<dyn ActivityStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub refacturation: Option<Arc<Refacturation>>,
 }
 
-pub async fn list_activities(State(state): State<ActivityState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List activities error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list activities",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_activities(
+    State(state): State<ActivityState>,
+    query: ListQuery,
+) -> Result<Json<Page<Activity>>, Problem> {
+    query.check::<Activity>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List activities error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_activity(
     State(state): State<ActivityState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Activity>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Activity>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_activity(
     State(state): State<ActivityState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating activity with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Activity created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create activity error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create activity",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<ActivityStoreError>(e))
         }
     }
 }
 
 pub async fn update_activity(
     State(state): State<ActivityState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(activity): Json<Activity>,
-) -> Json<Option<Activity>> {
-    let updated = state.store.update(activity).await.ok();
-    Json(updated)
+) -> Result<Tagged<Activity>, Problem> {
+    check_id(id, activity.id)?;
+    let mut activity = validate_entity(&Activity::validation_config("update"), activity)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    activity.version = current.version;
+    activity.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(activity).await?))
+}
+
+/// Apply a JSON Merge Patch to an existing activity
+pub async fn patch_activity(
+    State(state): State<ActivityState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Activity>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut activity = patch_entity(&Activity::validation_config("update"), current, patch)?;
+    activity.touch();
+    Ok(Tagged(state.store.update(activity).await?))
 }
 
 pub async fn delete_activity(
     State(state): State<ActivityState>,
     Path(id): Path<Uuid>,
-) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Invoice the activity for the recorded usages it caused
+///
+/// Answers `204 No Content` when there is nothing to bill.
+pub async fn invoice_activity(
+    State(state): State<ActivityState>,
+    Path(id): Path<Uuid>,
+    request: Option<Json<RefacturationRequest>>,
+) -> Result<Response, Problem> {
+    let Some(refacturation) = state.refacturation.as_ref() else {
+        return Err(Problem::with_status(
+            StatusCode::NOT_IMPLEMENTED,
+            "Refacturation is not enabled",
+        ));
+    };
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let refactured = refacturation
+        .invoice_activity(&id, request)
+        .await
+        .map_err(|e| {
+            eprintln!("Refacturation of activity {} error: {:?}", id, e);
+            e
+        })?;
+    Ok(match refactured {
+        Some(refactured) => Json(refactured).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
 }
@@ -8,3 +8,17 @@ pub use store::{ActivityStore, ActivityStoreError, InMemoryActivityStore};
 
 #[cfg(feature = "dynamodb")]
 pub use store::ActivityDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::ActivityLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::ActivityMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::ActivityMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::ActivityNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::ActivityPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::ActivityScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::ActivitySqliteStore;
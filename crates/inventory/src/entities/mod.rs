@@ -1,6 +1,7 @@
 #![allow(clippy::module_inception)]
 
 pub mod activity;
+pub mod reservation;
 pub mod stock_item;
 pub mod stock_movement;
 pub mod store;
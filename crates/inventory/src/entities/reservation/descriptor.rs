@@ -0,0 +1,60 @@
+use axum::routing::{get, post};
+use std::sync::Arc;
+use this::prelude::Router;
+use this::server::entity_registry::EntityDescriptor;
+
+use super::ReservationStore;
+use super::handlers::{
+    ReservationState, commit_reservation, create_reservation, delete_reservation, get_reservation,
+    list_reservations, patch_reservation, release_reservation, update_reservation,
+};
+
+#[derive(Clone)]
+pub struct ReservationDescriptor {
+    store: Arc<dyn ReservationStore + Send + Sync>,
+    entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+}
+
+impl ReservationDescriptor {
+    pub fn new_with_creator(
+        store: Arc<dyn ReservationStore + Send + Sync>,
+        entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    ) -> Self {
+        Self {
+            store,
+            entity_creator,
+        }
+    }
+}
+
+impl EntityDescriptor for ReservationDescriptor {
+    fn entity_type(&self) -> &str {
+        "reservation"
+    }
+
+    fn plural(&self) -> &str {
+        "reservations"
+    }
+
+    fn build_routes(&self) -> Router {
+        let state = ReservationState {
+            store: self.store.clone(),
+            entity_creator: self.entity_creator.clone(),
+        };
+        Router::new()
+            .route(
+                "/reservations",
+                get(list_reservations).post(create_reservation),
+            )
+            .route(
+                "/reservations/{id}",
+                get(get_reservation)
+                    .put(update_reservation)
+                    .patch(patch_reservation)
+                    .delete(delete_reservation),
+            )
+            .route("/reservations/{id}/commit", post(commit_reservation))
+            .route("/reservations/{id}/release", post(release_reservation))
+            .with_state(state)
+    }
+}
@@ -0,0 +1,123 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
+use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
+use this::prelude::*;
+
+use super::{Reservation, ReservationStatus, ReservationStore, ReservationStoreError};
+
+#[derive(Clone)]
+pub struct ReservationState {
+    pub store: Arc<dyn ReservationStore>,
+    pub entity_creator: Arc<dyn EntityCreator>,
+}
+
+pub async fn list_reservations(
+    State(state): State<ReservationState>,
+    query: ListQuery,
+) -> Result<Json<Page<Reservation>>, Problem> {
+    query.check::<Reservation>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List reservations error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
+}
+
+pub async fn get_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+) -> Result<Tagged<Reservation>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
+}
+
+pub async fn create_reservation(
+    State(state): State<ReservationState>,
+    Json(entity_data): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
+    eprintln!("Creating reservation with data: {:?}", entity_data);
+    match state.entity_creator.create_from_json(entity_data).await {
+        Ok(created) => {
+            eprintln!("Reservation created successfully: {:?}", created);
+            Ok((StatusCode::CREATED, Json(created)))
+        }
+        Err(e) => {
+            eprintln!("Create reservation error: {:?}", e);
+            Err(Problem::from_creator::<ReservationStoreError>(e))
+        }
+    }
+}
+
+pub async fn update_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(reservation): Json<Reservation>,
+) -> Result<Tagged<Reservation>, Problem> {
+    check_id(id, reservation.id)?;
+    let mut reservation = validate_entity(&Reservation::validation_config("update"), reservation)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    reservation.version = current.version;
+    reservation.owned_by = current.owned_by;
+    let updated = state.store.update(reservation).await.map_err(|e| {
+        eprintln!("Update reservation {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing reservation
+pub async fn patch_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Reservation>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut reservation = patch_entity(&Reservation::validation_config("update"), current, patch)?;
+    reservation.touch();
+    let updated = state.store.update(reservation).await.map_err(|e| {
+        eprintln!("Update reservation {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Tagged(updated))
+}
+
+pub async fn delete_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn commit_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Reservation>, Problem> {
+    transition_reservation(&state, &id, ReservationStatus::Committed).await
+}
+
+pub async fn release_reservation(
+    State(state): State<ReservationState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Reservation>, Problem> {
+    transition_reservation(&state, &id, ReservationStatus::Released).await
+}
+
+async fn transition_reservation(
+    state: &ReservationState,
+    id: &Uuid,
+    to: ReservationStatus,
+) -> Result<Json<Reservation>, Problem> {
+    let updated = state.store.transition(id, to).await.map_err(|e| {
+        eprintln!("Transition reservation {} to {} error: {:?}", id, to, e);
+        e
+    })?;
+    Ok(Json(updated))
+}
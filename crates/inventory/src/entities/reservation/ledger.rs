@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use common::store::EntityStore;
+use common::{ListQuery, Page, Version, Versioned};
+use std::sync::Arc;
+use this::prelude::*;
+use tokio::sync::Mutex;
+
+use super::{Reservation, ReservationStatus, ReservationStore, ReservationStoreError};
+use crate::entities::stock_item::StockItemStore;
+use crate::entities::stock_movement::{MovementStatus, StockMovement, StockMovementStore};
+
+/// Reservation store that holds stock on the reserved stock items
+///
+/// Wraps the reservation store of any backend. Creating an `active`
+/// reservation adds its quantity to the `reserved_quantity` of its stock item
+/// with [`StockItemStore::adjust_reserved`], which is atomic in every backend
+/// and never reserves more than the available stock, so concurrent
+/// reservations cannot oversell. Releasing or expiring a reservation gives the
+/// units back; committing it records a completed `out` movement that consumes
+/// them (see [`StockLedger`](crate::entities::stock_movement::StockLedger)).
+/// Each step is undone if the reservation cannot be saved afterwards.
+pub struct ReservationLedger {
+    reservations: Arc<dyn ReservationStore>,
+    stock_items: Arc<dyn StockItemStore>,
+    stock_movements: Arc<dyn StockMovementStore>,
+    lock: Mutex<()>,
+}
+
+impl ReservationLedger {
+    pub fn new(
+        reservations: Arc<dyn ReservationStore>,
+        stock_items: Arc<dyn StockItemStore>,
+        stock_movements: Arc<dyn StockMovementStore>,
+    ) -> Self {
+        Self {
+            reservations,
+            stock_items,
+            stock_movements,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Hold the stock of a new reservation, then save it
+    async fn hold(&self, reservation: Reservation) -> Result<Reservation, ReservationStoreError> {
+        self.stock_items
+            .adjust_reserved(&reservation.stock_item_id, reservation.quantity)
+            .await?;
+        match self.reservations.create(reservation.clone()).await {
+            Ok(created) => Ok(created),
+            Err(e) => {
+                self.undo_reserved(&reservation, -reservation.quantity)
+                    .await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Give the stock of `current` back, then save `next`
+    async fn release(
+        &self,
+        current: &Reservation,
+        next: Reservation,
+    ) -> Result<Reservation, ReservationStoreError> {
+        self.stock_items
+            .adjust_reserved(&current.stock_item_id, -current.quantity)
+            .await?;
+        match self.reservations.update(next).await {
+            Ok(updated) => Ok(updated),
+            Err(e) => {
+                self.undo_reserved(current, current.quantity).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Turn the stock of `current` into an `out` movement, then save `next`
+    async fn commit(
+        &self,
+        current: &Reservation,
+        mut next: Reservation,
+    ) -> Result<Reservation, ReservationStoreError> {
+        if current.expires_at <= Utc::now() {
+            return Err(ReservationStoreError::Conflict(format!(
+                "reservation {} expired at {}",
+                current.id, current.expires_at
+            )));
+        }
+
+        let movement = self
+            .stock_movements
+            .create(StockMovement::new(
+                format!("Reservation {}", current.id),
+                MovementStatus::Completed.to_string(),
+                current.stock_item_id,
+                "out".to_string(),
+                current.quantity,
+                Some(format!(
+                    "Committed reservation for {} {}",
+                    current.owner_type, current.owner_id
+                )),
+                None,
+                Some(current.id),
+                None,
+                current.owned_by,
+                Version::default(),
+            ))
+            .await?;
+        next.movement_id = Some(movement.id);
+        match self.reservations.update(next).await {
+            Ok(updated) => Ok(updated),
+            Err(e) => {
+                // Cancelling puts the units back as available stock; hold them
+                // again for the still active reservation
+                match self
+                    .stock_movements
+                    .transition(&movement.id, MovementStatus::Cancelled)
+                    .await
+                {
+                    Ok(_) => self.undo_reserved(current, current.quantity).await,
+                    Err(rollback) => eprintln!(
+                        "Failed to cancel movement {} of reservation {}: {:?}",
+                        movement.id, current.id, rollback
+                    ),
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete a reservation, provided it is at `expected` when given; an
+    /// active one releases its stock first
+    async fn remove(
+        &self,
+        id: &Uuid,
+        expected: Option<Version>,
+    ) -> Result<(), ReservationStoreError> {
+        let _guard = self.lock.lock().await;
+        let current = self.reservations.get(id).await?;
+        if let Some(expected) = expected {
+            current.ensure_current(expected)?;
+        }
+        let delete = async {
+            match expected {
+                Some(expected) => self.reservations.delete_at(id, expected).await,
+                None => self.reservations.delete(id).await,
+            }
+        };
+        if current.status != ReservationStatus::Active.as_str() {
+            return delete.await;
+        }
+        self.stock_items
+            .adjust_reserved(&current.stock_item_id, -current.quantity)
+            .await?;
+        if let Err(e) = delete.await {
+            self.undo_reserved(&current, current.quantity).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn undo_reserved(&self, reservation: &Reservation, delta: i32) {
+        if let Err(rollback) = self
+            .stock_items
+            .adjust_reserved(&reservation.stock_item_id, delta)
+            .await
+        {
+            eprintln!(
+                "Failed to roll back stock item {} after reservation {}: {:?}",
+                reservation.stock_item_id, reservation.id, rollback
+            );
+        }
+    }
+}
+
+fn parse_status(status: &str) -> Result<ReservationStatus, ReservationStoreError> {
+    status.parse().map_err(ReservationStoreError::Validation)
+}
+
+/// Whether two versions of a reservation hold the same stock for the same owner
+fn same_hold(a: &Reservation, b: &Reservation) -> bool {
+    a.stock_item_id == b.stock_item_id
+        && a.quantity == b.quantity
+        && a.owner_type == b.owner_type
+        && a.owner_id == b.owner_id
+        && a.movement_id == b.movement_id
+}
+
+common::impl_entity_json!(ReservationLedger, Reservation);
+
+#[async_trait]
+impl EntityStore<Reservation> for ReservationLedger {
+    async fn create(&self, reservation: Reservation) -> Result<Reservation, ReservationStoreError> {
+        let _guard = self.lock.lock().await;
+        if parse_status(&reservation.status)? != ReservationStatus::Active {
+            return Err(ReservationStoreError::Validation(format!(
+                "reservations are created 'active', not '{}'",
+                reservation.status
+            )));
+        }
+        if reservation.quantity <= 0 {
+            return Err(ReservationStoreError::Validation(format!(
+                "reservation quantity must be positive, got {}",
+                reservation.quantity
+            )));
+        }
+        if reservation.expires_at <= Utc::now() {
+            return Err(ReservationStoreError::Validation(format!(
+                "reservation would already have expired at {}",
+                reservation.expires_at
+            )));
+        }
+        self.hold(reservation).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Reservation, ReservationStoreError> {
+        self.reservations.get(id).await
+    }
+
+    async fn update(&self, reservation: Reservation) -> Result<Reservation, ReservationStoreError> {
+        let _guard = self.lock.lock().await;
+        let current = self.reservations.get(&reservation.id).await?;
+        reservation.ensure_current(current.version)?;
+        if !same_hold(&current, &reservation) {
+            return Err(ReservationStoreError::Conflict(format!(
+                "reservation {} cannot change its stock item, quantity or owner; release it and reserve again",
+                current.id
+            )));
+        }
+
+        let from = parse_status(&current.status)?;
+        let to = parse_status(&reservation.status)?;
+        if from == to {
+            if from != ReservationStatus::Active && current.expires_at != reservation.expires_at {
+                return Err(ReservationStoreError::Conflict(format!(
+                    "reservation {} is {}",
+                    current.id, current.status
+                )));
+            }
+            return self.reservations.update(reservation).await;
+        }
+
+        from.transition_to(to)
+            .map_err(ReservationStoreError::InvalidTransition)?;
+        match to {
+            ReservationStatus::Committed => self.commit(&current, reservation).await,
+            _ => self.release(&current, reservation).await,
+        }
+    }
+
+    /// Deleting an active reservation releases its stock first
+    async fn delete(&self, id: &Uuid) -> Result<(), ReservationStoreError> {
+        self.remove(id, None).await
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), ReservationStoreError> {
+        self.remove(id, Some(expected)).await
+    }
+
+    async fn list(&self) -> Result<Vec<Reservation>, ReservationStoreError> {
+        self.reservations.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<Reservation>, ReservationStoreError> {
+        self.reservations.query(query).await
+    }
+}
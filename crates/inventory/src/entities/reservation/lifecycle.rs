@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Lifecycle states of a [`Reservation`](super::Reservation)
+///
+/// ```text
+///          ┌──▶ committed
+/// active ──┼──▶ released
+///          └──▶ expired
+/// ```
+///
+/// An `active` reservation holds stock; every other state is terminal.
+/// Committing turns the reserved units into an `out` stock movement,
+/// releasing or expiring gives them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReservationStatus {
+    Active,
+    Committed,
+    Released,
+    Expired,
+}
+
+impl ReservationStatus {
+    pub const ALL: [ReservationStatus; 4] = [
+        ReservationStatus::Active,
+        ReservationStatus::Committed,
+        ReservationStatus::Released,
+        ReservationStatus::Expired,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReservationStatus::Active => "active",
+            ReservationStatus::Committed => "committed",
+            ReservationStatus::Released => "released",
+            ReservationStatus::Expired => "expired",
+        }
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`
+    pub fn can_transition_to(&self, next: ReservationStatus) -> bool {
+        *self == ReservationStatus::Active && next != ReservationStatus::Active
+    }
+
+    /// Validate a transition, returning the rejected pair on failure
+    pub fn transition_to(
+        &self,
+        next: ReservationStatus,
+    ) -> Result<ReservationStatus, ReservationTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(ReservationTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
+
+impl fmt::Display for ReservationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReservationStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReservationStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown reservation status '{}'", s))
+    }
+}
+
+/// A transition rejected by the reservation state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReservationTransition {
+    pub from: ReservationStatus,
+    pub to: ReservationStatus,
+}
+
+impl fmt::Display for ReservationTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reservation cannot transition from '{}' to '{}'",
+            self.from, self.to
+        )
+    }
+}
@@ -0,0 +1,31 @@
+pub mod descriptor;
+pub mod handlers;
+pub mod ledger;
+pub mod lifecycle;
+pub mod model;
+pub mod store;
+
+pub use ledger::ReservationLedger;
+pub use lifecycle::{ReservationStatus, ReservationTransition};
+pub use model::Reservation;
+pub use store::{
+    DEFAULT_RESERVATION_TTL_SECONDS, InMemoryReservationStore, ReservationStore,
+    ReservationStoreError,
+};
+
+#[cfg(feature = "dynamodb")]
+pub use store::ReservationDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::ReservationLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::ReservationMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::ReservationMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::ReservationNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::ReservationPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::ReservationScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::ReservationSqliteStore;
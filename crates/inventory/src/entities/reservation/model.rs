@@ -0,0 +1,50 @@
+#![allow(clippy::too_many_arguments)]
+
+use common::Version;
+use this::prelude::*;
+
+impl_data_entity_validated!(
+    Reservation,
+    "reservation",
+    ["name", "owner_type"],
+    {
+        stock_item_id: Uuid,
+        quantity: i32,
+        owner_type: String,
+        owner_id: Uuid,
+        expires_at: DateTime<Utc>,
+        movement_id: Option<Uuid>,
+        owned_by: Option<Uuid>,
+        version: Version,
+    },
+    validate: {
+        create: {
+            stock_item_id: [required],
+            quantity: [required positive],
+            owner_type: [required string_length(1, 50)],
+            owner_id: [required],
+            status: [required in_list("active", "committed", "released", "expired")],
+        },
+        update: {
+            status: [optional in_list("active", "committed", "released", "expired")],
+        },
+    },
+    filters: {
+        create: {
+            owner_type: [trim lowercase],
+            status: [trim lowercase],
+        },
+        update: {
+            status: [trim lowercase],
+        },
+    }
+);
+
+common::impl_versioned!(Reservation);
+
+impl Reservation {
+    /// Whether the reservation still holds stock at `now`
+    pub fn is_held(&self, now: DateTime<Utc>) -> bool {
+        self.status == "active" && self.expires_at > now
+    }
+}
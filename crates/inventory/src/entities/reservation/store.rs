@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use common::store::{EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{ErrorKind, HttpError, Payload, ValidationError, Version, VersionMismatch};
+use this::core::validation::extractor::ValidatableEntity;
+use this::prelude::*;
+
+use super::{Reservation, ReservationStatus, ReservationTransition};
+use crate::entities::stock_item::StockItemStoreError;
+use crate::entities::stock_movement::StockMovementStoreError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReservationStoreError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("validation: {0}")]
+    Validation(String),
+    #[error("{0}")]
+    InvalidTransition(ReservationTransition),
+    #[error(transparent)]
+    Stock(#[from] StockItemStoreError),
+    #[error(transparent)]
+    Movement(#[from] StockMovementStoreError),
+    #[error(transparent)]
+    VersionMismatch(#[from] VersionMismatch),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for ReservationStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ReservationStoreError::NotFound(_) => ErrorKind::NotFound,
+            ReservationStoreError::Conflict(_) | ReservationStoreError::InvalidTransition(_) => {
+                ErrorKind::Conflict
+            }
+            ReservationStoreError::Validation(_) => ErrorKind::Validation,
+            // The reservation points at a stock item that does not exist
+            ReservationStoreError::Stock(StockItemStoreError::NotFound(_)) => ErrorKind::Validation,
+            ReservationStoreError::Stock(e) => e.kind(),
+            ReservationStoreError::Movement(e) => e.kind(),
+            ReservationStoreError::VersionMismatch(e) => e.kind(),
+            ReservationStoreError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> serde_json::Map<String, serde_json::Value> {
+        match self {
+            ReservationStoreError::Stock(e) => e.extensions(),
+            ReservationStoreError::Movement(e) => e.extensions(),
+            ReservationStoreError::VersionMismatch(e) => e.extensions(),
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+impl From<StoreError> for ReservationStoreError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::NotFound(id) => ReservationStoreError::NotFound(id),
+            StoreError::Conflict(message) => ReservationStoreError::Conflict(message),
+            StoreError::Validation(message) => ReservationStoreError::Validation(message),
+            StoreError::VersionMismatch(e) => ReservationStoreError::VersionMismatch(e),
+            StoreError::Other(e) => ReservationStoreError::Other(e),
+        }
+    }
+}
+
+#[async_trait]
+pub trait ReservationStore: EntityStore<Reservation> {
+    /// Move a reservation to `to`
+    ///
+    /// Through a [`ReservationLedger`](super::ReservationLedger) this
+    /// releases or commits the reserved stock.
+    async fn transition(
+        &self,
+        id: &Uuid,
+        to: ReservationStatus,
+    ) -> Result<Reservation, ReservationStoreError> {
+        let mut reservation = self.get(id).await?;
+        reservation.set_status(to.to_string());
+        self.update(reservation).await
+    }
+}
+
+impl<S: EntityStore<Reservation> + ?Sized> ReservationStore for S {}
+
+/// How long a reservation holds stock when the payload gives neither
+/// `expires_at` nor `ttl_seconds`
+pub const DEFAULT_RESERVATION_TTL_SECONDS: i64 = 15 * 60;
+
+impl StoredEntity for Reservation {
+    type Error = ReservationStoreError;
+    const NAME: &'static str = "Reservation";
+    const SEARCH_FIELDS: &'static [&'static str] =
+        &["name", "status", "stock_item_id", "owner_type", "owner_id"];
+
+    /// Build a new reservation from a create payload
+    ///
+    /// It expires at `expires_at` (RFC 3339) if given, otherwise `ttl_seconds`
+    /// (default [`DEFAULT_RESERVATION_TTL_SECONDS`]) from now.
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&Reservation::validation_config("create"), entity_data);
+        let expires_at = match payload.string("expires_at") {
+            Some(at) => DateTime::parse_from_rfc3339(&at)
+                .map(|at| at.with_timezone(&Utc))
+                .unwrap_or_else(|e| {
+                    payload.reject("expires_at", format!("'expires_at' {}", e));
+                    Utc::now()
+                }),
+            None => {
+                let ttl = payload
+                    .i32("ttl_seconds")
+                    .map(i64::from)
+                    .unwrap_or(DEFAULT_RESERVATION_TTL_SECONDS);
+                Utc::now() + chrono::Duration::seconds(ttl)
+            }
+        };
+        let reservation = Reservation::new(
+            payload.required_string("name"),
+            payload.required_string("status"),
+            payload.required_uuid("stock_item_id"),
+            payload.required_i32("quantity"),
+            payload.required_string("owner_type"),
+            payload.required_uuid("owner_id"),
+            expires_at,
+            payload.uuid("movement_id"),
+            payload.uuid("owned_by"),
+            Version::default(),
+        );
+        payload.finish()?;
+        Ok(reservation)
+    }
+}
+
+pub type InMemoryReservationStore = InMemoryEntityStore<Reservation>;
+
+#[cfg(feature = "dynamodb")]
+pub type ReservationDynamoDBStore = common::store::DynamoDBStore<Reservation>;
+
+#[cfg(feature = "postgres")]
+pub type ReservationPostgresStore = common::store::PostgresStore<Reservation>;
+
+#[cfg(feature = "mongodb_backend")]
+pub type ReservationMongoStore = common::store::MongoStore<Reservation>;
+
+#[cfg(feature = "neo4j")]
+pub type ReservationNeo4jStore = common::store::Neo4jStore<Reservation>;
+
+#[cfg(feature = "scylladb")]
+pub type ReservationScyllaStore = common::store::ScyllaStore<Reservation>;
+
+#[cfg(feature = "mysql")]
+pub type ReservationMysqlStore = common::store::MysqlStore<Reservation>;
+
+#[cfg(feature = "lmdb")]
+pub type ReservationLmdbStore = common::store::LmdbStore<Reservation>;
+
+#[cfg(feature = "sqlite")]
+pub type ReservationSqliteStore = common::store::SqliteStore<Reservation>;
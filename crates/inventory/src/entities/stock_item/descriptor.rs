@@ -1,4 +1,4 @@
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
 use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
@@ -6,13 +6,15 @@ use this::server::entity_registry::EntityDescriptor;
 use super::StockItemStore;
 use super::handlers::{
     StockItemState, create_stock_item, delete_stock_item, get_stock_item, list_stock_items,
-    update_stock_item,
+    patch_stock_item, transfer_stock_item, update_stock_item,
 };
+use crate::services::StockTransfers;
 
 #[derive(Clone)]
 pub struct StockItemDescriptor {
     store: Arc<dyn StockItemStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    transfers: Option<Arc<StockTransfers>>,
 }
 
 impl StockItemDescriptor {
@@ -27,8 +29,15 @@ impl StockItemDescriptor {
         Self {
             store,
             entity_creator,
+            transfers: None,
         }
     }
+
+    /// Serve `POST /stock_items/{id}/transfer` with `transfers`
+    pub fn with_transfers(mut self, transfers: Option<Arc<StockTransfers>>) -> Self {
+        self.transfers = transfers;
+        self
+    }
 }
 
 impl EntityDescriptor for StockItemDescriptor {
@@ -44,8 +53,9 @@ impl EntityDescriptor for StockItemDescriptor {
         let state = StockItemState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            transfers: self.transfers.clone(),
         };
-        Router::new()
+        let router = Router::new()
             .route(
                 "/stock_items",
                 get(list_stock_items).post(create_stock_item),
@@ -54,8 +64,14 @@ impl EntityDescriptor for StockItemDescriptor {
                 "/stock_items/{id}",
                 get(get_stock_item)
                     .put(update_stock_item)
+                    .patch(patch_stock_item)
                     .delete(delete_stock_item),
-            )
-            .with_state(state)
+            );
+        let router = if self.transfers.is_some() {
+            router.route("/stock_items/{id}/transfer", post(transfer_stock_item))
+        } else {
+            router
+        };
+        router.with_state(state)
     }
 }
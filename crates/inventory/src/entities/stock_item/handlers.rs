@@ -1,69 +1,130 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{StockItem, StockItemStore};
+use super::{StockItem, StockItemStore, StockItemStoreError};
+use crate::services::{StockTransfer, StockTransfers, TransferRequest};
 
 #[derive(Clone)]
 pub struct StockItemState {
     pub store: Arc<dyn StockItemStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub transfers: Option<Arc<StockTransfers>>,
 }
 
-pub async fn list_stock_items(State(state): State<StockItemState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List stock_items error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list stock_items",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_stock_items(
+    State(state): State<StockItemState>,
+    query: ListQuery,
+) -> Result<Json<Page<StockItem>>, Problem> {
+    query.check::<StockItem>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List stock_items error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_stock_item(
     State(state): State<StockItemState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<StockItem>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<StockItem>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_stock_item(
     State(state): State<StockItemState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating stock_item with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("StockItem created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create stock_item error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create stock_item",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<StockItemStoreError>(e))
         }
     }
 }
 
+/// The quantities are kept as stored: they only change through stock
+/// movements, reservations and transfers
 pub async fn update_stock_item(
     State(state): State<StockItemState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(stock_item): Json<StockItem>,
-) -> Json<Option<StockItem>> {
-    let updated = state.store.update(stock_item).await.ok();
-    Json(updated)
+) -> Result<Tagged<StockItem>, Problem> {
+    check_id(id, stock_item.id)?;
+    let stock_item = validate_entity(&StockItem::validation_config("update"), stock_item)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let updated = state
+        .store
+        .update(StockItem {
+            quantity: current.quantity,
+            reserved_quantity: current.reserved_quantity,
+            version: current.version,
+            owned_by: current.owned_by,
+            ..stock_item
+        })
+        .await?;
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing stock item
+///
+/// As with `PUT`, the quantities are never patched.
+pub async fn patch_stock_item(
+    State(state): State<StockItemState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<StockItem>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let patched = patch_entity(
+        &StockItem::validation_config("update"),
+        current.clone(),
+        patch,
+    )?;
+    let mut stock_item = StockItem {
+        quantity: current.quantity,
+        reserved_quantity: current.reserved_quantity,
+        ..patched
+    };
+    stock_item.touch();
+    Ok(Tagged(state.store.update(stock_item).await?))
 }
 
 pub async fn delete_stock_item(
     State(state): State<StockItemState>,
     Path(id): Path<Uuid>,
-) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn transfer_stock_item(
+    State(state): State<StockItemState>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<TransferRequest>,
+) -> Result<Json<StockTransfer>, Problem> {
+    let Some(transfers) = state.transfers.as_ref() else {
+        return Err(Problem::with_status(
+            StatusCode::NOT_IMPLEMENTED,
+            "Stock transfers are not enabled",
+        ));
+    };
+    let transfer = transfers.transfer(&id, request).await.map_err(|e| {
+        eprintln!("Transfer stock_item {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Json(transfer))
 }
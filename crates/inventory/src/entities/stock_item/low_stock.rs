@@ -0,0 +1,189 @@
+use async_trait::async_trait;
+use common::store::EntityStore;
+use common::{ListQuery, Page, Version, Versioned};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use this::core::events::{EntityEvent, EventBus, FrameworkEvent};
+use this::prelude::*;
+
+use super::{StockItem, StockItemStore, StockItemStoreError};
+
+/// Entity type of the events published by [`LowStockDetector`]
+pub const LOW_STOCK_ALERT: &str = "low_stock_alert";
+
+/// How close a stock item is to running out, from the best level to the
+/// worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockLevel {
+    Ok,
+    /// Available stock is at or below the reorder point
+    Low,
+    /// No stock is available
+    Out,
+}
+
+impl StockLevel {
+    pub fn of(stock_item: &StockItem) -> Self {
+        let available = stock_item.available_quantity();
+        if available <= 0 {
+            StockLevel::Out
+        } else if stock_item
+            .reorder_point
+            .is_some_and(|reorder_point| available <= reorder_point)
+        {
+            StockLevel::Low
+        } else {
+            StockLevel::Ok
+        }
+    }
+}
+
+/// A stock item that needs restocking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowStockAlert {
+    pub stock_item_id: Uuid,
+    pub name: String,
+    pub warehouse_id: Uuid,
+    pub product_id: Option<Uuid>,
+    pub quantity: i32,
+    pub available_quantity: i32,
+    pub reorder_point: Option<i32>,
+    pub level: StockLevel,
+}
+
+impl LowStockAlert {
+    /// The alert for `stock_item`, unless its level is fine
+    pub fn for_item(stock_item: &StockItem) -> Option<Self> {
+        let level = StockLevel::of(stock_item);
+        (level != StockLevel::Ok).then(|| LowStockAlert {
+            stock_item_id: stock_item.id,
+            name: stock_item.name.clone(),
+            warehouse_id: stock_item.warehouse_id,
+            product_id: stock_item.product_id,
+            quantity: stock_item.quantity,
+            available_quantity: stock_item.available_quantity(),
+            reorder_point: stock_item.reorder_point,
+            level,
+        })
+    }
+}
+
+/// Stock item store that reports items running low
+///
+/// Wraps the stock item store of any backend and compares the
+/// [`StockLevel`] of an item before and after every change made through it,
+/// including the adjustments of the stock and reservation ledgers. Creating
+/// an item is not a change of level. When an
+/// item drops to `low` or `out`, a `Created` entity event of type
+/// [`LOW_STOCK_ALERT`] is published on the attached event bus, with the stock
+/// item id as `entity_id` and the [`LowStockAlert`] as data, again when it
+/// goes on from `low` to `out`. Nothing is published while it stays at its
+/// level or improves, e.g. when `out` is restocked to `low`.
+pub struct LowStockDetector {
+    inner: Arc<dyn StockItemStore>,
+    event_bus: RwLock<Option<EventBus>>,
+}
+
+impl LowStockDetector {
+    pub fn new(inner: Arc<dyn StockItemStore>) -> Self {
+        Self {
+            inner,
+            event_bus: RwLock::new(None),
+        }
+    }
+
+    /// Publish alerts on `event_bus` from now on
+    pub fn attach(&self, event_bus: EventBus) {
+        if let Ok(mut slot) = self.event_bus.write() {
+            *slot = Some(event_bus);
+        }
+    }
+
+    /// Publish an alert if `after` is at a worse level than `before`
+    fn check(&self, before: StockLevel, after: &StockItem) {
+        if StockLevel::of(after) <= before {
+            return;
+        }
+        let Some(alert) = LowStockAlert::for_item(after) else {
+            return;
+        };
+        let Ok(slot) = self.event_bus.read() else {
+            return;
+        };
+        if let (Some(event_bus), Ok(data)) = (slot.as_ref(), serde_json::to_value(&alert)) {
+            event_bus.publish(FrameworkEvent::Entity(EntityEvent::Created {
+                entity_type: LOW_STOCK_ALERT.to_string(),
+                entity_id: after.id,
+                data,
+            }));
+        }
+    }
+}
+
+common::impl_entity_json!(LowStockDetector, StockItem);
+
+#[async_trait]
+impl EntityStore<StockItem> for LowStockDetector {
+    /// A new item has no level to drop from, so creating it alerts nothing,
+    /// even at zero (e.g. the destination of a transfer)
+    async fn create(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
+        self.inner.create(stock_item).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<StockItem, StockItemStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
+        // The update only goes through at the version it was read at, so the
+        // item read here at that version is the one it replaces
+        let current = self.inner.get(&stock_item.id).await?;
+        stock_item.ensure_current(current.version)?;
+        let before = StockLevel::of(&current);
+        let updated = self.inner.update(stock_item).await?;
+        self.check(before, &updated);
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), StockItemStoreError> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), StockItemStoreError> {
+        self.inner.delete_at(id, expected).await
+    }
+
+    async fn list(&self) -> Result<Vec<StockItem>, StockItemStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<StockItem>, StockItemStoreError> {
+        self.inner.query(query).await
+    }
+}
+
+#[async_trait]
+impl StockItemStore for LowStockDetector {
+    async fn adjust(
+        &self,
+        id: &Uuid,
+        quantity_delta: i32,
+        reserved_delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        let adjusted = self
+            .inner
+            .adjust(id, quantity_delta, reserved_delta)
+            .await?;
+        let mut before = adjusted.clone();
+        before.quantity -= quantity_delta;
+        before.reserved_quantity = Some(adjusted.reserved_quantity.unwrap_or(0) - reserved_delta);
+        self.check(StockLevel::of(&before), &adjusted);
+        Ok(adjusted)
+    }
+
+    fn written(&self, before: &StockItem, after: &StockItem) {
+        self.inner.written(before, after);
+        self.check(StockLevel::of(before), after);
+    }
+}
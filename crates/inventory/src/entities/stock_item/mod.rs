@@ -1,10 +1,26 @@
 pub mod descriptor;
 pub mod handlers;
+pub mod low_stock;
 pub mod model;
 pub mod store;
 
+pub use low_stock::{LOW_STOCK_ALERT, LowStockAlert, LowStockDetector, StockLevel};
 pub use model::StockItem;
 pub use store::{InMemoryStockItemStore, StockItemStore, StockItemStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::StockItemDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::StockItemLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::StockItemMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::StockItemMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::StockItemNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::StockItemPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::StockItemScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::StockItemSqliteStore;
@@ -1,5 +1,6 @@
 #![allow(clippy::too_many_arguments)]
 
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -11,14 +12,18 @@ impl_data_entity_validated!(
         quantity: i32,
         warehouse_id: Uuid,
         reserved_quantity: Option<i32>,
+        reorder_point: Option<i32>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
             quantity: [required],
+            reorder_point: [optional],
             status: [required in_list("available", "reserved", "out_of_stock")],
         },
         update: {
-            quantity: [optional],
+            reorder_point: [optional],
             status: [optional in_list("available", "reserved", "out_of_stock")],
         },
     },
@@ -31,3 +36,30 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(StockItem);
+
+impl StockItem {
+    /// Units that are neither reserved nor gone: `quantity - reserved_quantity`
+    pub fn available_quantity(&self) -> i32 {
+        self.quantity - self.reserved_quantity.unwrap_or(0)
+    }
+
+    /// Status implied by the quantities, if it differs from `status`
+    ///
+    /// An item is `out_of_stock` at zero, `reserved` when reservations hold
+    /// all of its stock, and back to `available` as soon as some stock is
+    /// free again.
+    pub fn status_for_quantity(&self) -> Option<&'static str> {
+        let implied = if self.quantity == 0 {
+            "out_of_stock"
+        } else if self.available_quantity() <= 0 {
+            "reserved"
+        } else if matches!(self.status.as_str(), "out_of_stock" | "reserved") {
+            "available"
+        } else {
+            return None;
+        };
+        (implied != self.status).then_some(implied)
+    }
+}
@@ -1,5 +1,8 @@
 use async_trait::async_trait;
-use std::sync::Arc;
+use common::cas::CompareAndSwap;
+use common::store::{BackendStore, EntityStore, InMemoryEntityStore, StoreError, StoredEntity};
+use common::{ErrorKind, HttpError, Payload, ValidationError, Version, VersionMismatch};
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
 use super::StockItem;
@@ -12,252 +15,219 @@ pub enum StockItemStoreError {
     Conflict(String),
     #[error("validation: {0}")]
     Validation(String),
+    #[error("insufficient stock for {stock_item_id}: {available} available, {requested} requested")]
+    InsufficientStock {
+        stock_item_id: Uuid,
+        available: i32,
+        requested: i32,
+    },
+    #[error(transparent)]
+    VersionMismatch(#[from] VersionMismatch),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
-#[async_trait]
-pub trait StockItemStore: Send + Sync {
-    async fn create(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError>;
-    async fn get(&self, id: &Uuid) -> Result<StockItem, StockItemStoreError>;
-    async fn update(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError>;
-    async fn delete(&self, id: &Uuid) -> Result<(), StockItemStoreError>;
-    async fn list(&self) -> Result<Vec<StockItem>, StockItemStoreError>;
-}
-
-use tokio::sync::RwLock;
-
-#[derive(Clone, Default)]
-pub struct InMemoryStockItemStore {
-    inner: Arc<RwLock<Vec<StockItem>>>,
-}
-
-#[async_trait::async_trait]
-impl EntityFetcher for InMemoryStockItemStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let stock_item = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("StockItem not found: {}", entity_id))?;
-        Ok(serde_json::to_value(stock_item)?)
-    }
-
-    async fn list_as_json(
-        &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_stock_items = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let stock_items: Vec<StockItem> = all_stock_items
-            .into_iter()
-            .skip(offset)
-            .take(limit)
-            .collect();
-        stock_items
-            .into_iter()
-            .map(|stock_item| serde_json::to_value(stock_item).map_err(Into::into))
-            .collect()
+impl HttpError for StockItemStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StockItemStoreError::NotFound(_) => ErrorKind::NotFound,
+            StockItemStoreError::Conflict(_) | StockItemStoreError::InsufficientStock { .. } => {
+                ErrorKind::Conflict
+            }
+            StockItemStoreError::Validation(_) => ErrorKind::Validation,
+            StockItemStoreError::VersionMismatch(e) => e.kind(),
+            StockItemStoreError::Other(_) => ErrorKind::Other,
+        }
     }
-}
 
-#[async_trait::async_trait]
-impl EntityCreator for InMemoryStockItemStore {
-    async fn create_from_json(
-        &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
-        let stock_item = StockItem::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("StockItem")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("available")
-                .to_string(),
-            entity_data["product_id"]
-                .as_str()
-                .and_then(|s| Uuid::parse_str(s).ok()),
-            entity_data["quantity"].as_i64().unwrap_or(0) as i32,
-            entity_data["warehouse_id"]
-                .as_str()
-                .and_then(|s| Uuid::parse_str(s).ok())
-                .unwrap_or_else(Uuid::new_v4),
-            entity_data["reserved_quantity"].as_i64().map(|v| v as i32),
-        );
-
-        self.create(stock_item.clone()).await?;
-        Ok(serde_json::to_value(stock_item)?)
+    fn extensions(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut extensions = serde_json::Map::new();
+        match self {
+            StockItemStoreError::InsufficientStock {
+                available,
+                requested,
+                ..
+            } => {
+                extensions.insert("available".to_string(), (*available).into());
+                extensions.insert("requested".to_string(), (*requested).into());
+            }
+            StockItemStoreError::VersionMismatch(e) => extensions = e.extensions(),
+            _ => {}
+        }
+        extensions
     }
 }
 
-#[async_trait::async_trait]
-impl StockItemStore for InMemoryStockItemStore {
-    async fn create(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
-        let mut g = self.inner.write().await;
-        if g.iter().any(|s| s.id == stock_item.id) {
-            return Err(StockItemStoreError::Conflict(stock_item.id.to_string()));
+impl From<StoreError> for StockItemStoreError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::NotFound(id) => StockItemStoreError::NotFound(id),
+            StoreError::Conflict(message) => StockItemStoreError::Conflict(message),
+            StoreError::Validation(message) => StockItemStoreError::Validation(message),
+            StoreError::VersionMismatch(e) => StockItemStoreError::VersionMismatch(e),
+            StoreError::Other(e) => StockItemStoreError::Other(e),
         }
-        g.push(stock_item.clone());
-        Ok(stock_item)
     }
+}
 
-    async fn get(&self, id: &Uuid) -> Result<StockItem, StockItemStoreError> {
-        let g = self.inner.read().await;
-        g.iter()
-            .find(|s| &s.id == id)
-            .cloned()
-            .ok_or_else(|| StockItemStoreError::NotFound(id.to_string()))
-    }
+#[async_trait]
+pub trait StockItemStore: EntityStore<StockItem> {
+    /// Atomically change the quantity and the reserved quantity of a stock item
+    ///
+    /// Fails with `InsufficientStock` when the result would reserve more than
+    /// the quantity (or take the quantity below zero). The status follows the
+    /// new quantities (see [`StockItem::status_for_quantity`]).
+    async fn adjust(
+        &self,
+        id: &Uuid,
+        quantity_delta: i32,
+        reserved_delta: i32,
+    ) -> Result<StockItem, StockItemStoreError>;
 
-    async fn update(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
-        let mut g = self.inner.write().await;
-        if let Some(x) = g.iter_mut().find(|s| s.id == stock_item.id) {
-            *x = stock_item.clone();
-            Ok(stock_item)
-        } else {
-            Err(StockItemStoreError::NotFound(stock_item.id.to_string()))
-        }
+    /// Atomically add `delta` to the quantity, leaving reservations alone
+    async fn adjust_quantity(
+        &self,
+        id: &Uuid,
+        delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        self.adjust(id, delta, 0).await
     }
 
-    async fn delete(&self, id: &Uuid) -> Result<(), StockItemStoreError> {
-        let mut g = self.inner.write().await;
-        let before = g.len();
-        g.retain(|s| &s.id != id);
-        if g.len() == before {
-            return Err(StockItemStoreError::NotFound(id.to_string()));
-        }
-        Ok(())
+    /// Atomically reserve (`delta > 0`) or release (`delta < 0`) units
+    async fn adjust_reserved(
+        &self,
+        id: &Uuid,
+        delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        self.adjust(id, 0, delta).await
     }
 
-    async fn list(&self) -> Result<Vec<StockItem>, StockItemStoreError> {
-        Ok(self.inner.read().await.clone())
-    }
+    /// Note that `before` became `after` through a transaction written next
+    /// to this store (see `AtomicStockWrites`)
+    fn written(&self, _before: &StockItem, _after: &StockItem) {}
 }
 
-#[cfg(feature = "dynamodb")]
-use aws_sdk_dynamodb::Client as DynamoDBClient;
-#[cfg(feature = "dynamodb")]
-use this::storage::DynamoDBDataService;
-
-#[cfg(feature = "dynamodb")]
-#[derive(Clone)]
-pub struct StockItemDynamoDBStore {
-    service: Arc<DynamoDBDataService<StockItem>>,
+/// Apply both deltas to `stock_item` in place
+///
+/// The reserved quantity stays between zero and the quantity, so only
+/// available units can be taken out or reserved.
+pub(crate) fn apply_delta(
+    stock_item: &mut StockItem,
+    quantity_delta: i32,
+    reserved_delta: i32,
+) -> Result<(), StockItemStoreError> {
+    let reserved = stock_item.reserved_quantity.unwrap_or(0);
+    let new_reserved = reserved
+        .checked_add(reserved_delta)
+        .filter(|reserved| *reserved >= 0)
+        .ok_or_else(|| {
+            StockItemStoreError::Validation(format!(
+                "stock item {} has only {} reserved units, cannot release {}",
+                stock_item.id, reserved, -reserved_delta
+            ))
+        })?;
+    let new_quantity = stock_item
+        .quantity
+        .checked_add(quantity_delta)
+        .filter(|quantity| *quantity >= new_reserved)
+        .ok_or(StockItemStoreError::InsufficientStock {
+            stock_item_id: stock_item.id,
+            available: stock_item.available_quantity(),
+            requested: reserved_delta - quantity_delta,
+        })?;
+
+    stock_item.quantity = new_quantity;
+    if reserved_delta != 0 {
+        stock_item.reserved_quantity = Some(new_reserved);
+    }
+    match stock_item.status_for_quantity() {
+        Some(status) => stock_item.set_status(status.to_string()),
+        None => stock_item.touch(),
+    }
+    Ok(())
 }
 
-#[cfg(feature = "dynamodb")]
-impl StockItemDynamoDBStore {
-    pub fn new(client: DynamoDBClient, table_name: String) -> Self {
-        Self {
-            service: Arc::new(DynamoDBDataService::new(client, table_name)),
-        }
-    }
-}
+impl StoredEntity for StockItem {
+    type Error = StockItemStoreError;
+    const NAME: &'static str = "StockItem";
+    const SEARCH_FIELDS: &'static [&'static str] =
+        &["name", "status", "warehouse_id", "product_id"];
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityFetcher for StockItemDynamoDBStore {
-    async fn fetch_as_json(&self, entity_id: &Uuid) -> Result<serde_json::Value, anyhow::Error> {
-        let stock_item = self
-            .get(entity_id)
-            .await
-            .map_err(|_| anyhow::anyhow!("StockItem not found: {}", entity_id))?;
-        Ok(serde_json::to_value(stock_item)?)
+    /// Build a new stock item from a create payload
+    fn from_json(entity_data: serde_json::Value) -> Result<Self, ValidationError> {
+        let mut payload = Payload::validate(&StockItem::validation_config("create"), entity_data);
+        let stock_item = StockItem::new(
+            payload.required_string("name"),
+            payload.required_string("status"),
+            payload.uuid("product_id"),
+            payload.required_i32("quantity"),
+            payload.required_uuid("warehouse_id"),
+            payload.i32("reserved_quantity"),
+            payload.i32("reorder_point"),
+            payload.uuid("owned_by"),
+            Version::default(),
+        );
+        payload.finish()?;
+        Ok(stock_item)
     }
+}
 
-    async fn list_as_json(
+#[async_trait]
+impl StockItemStore for InMemoryEntityStore<StockItem> {
+    async fn adjust(
         &self,
-        limit: Option<i32>,
-        offset: Option<i32>,
-    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
-        let all_stock_items = self.list().await?;
-        let offset = offset.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(20) as usize;
-
-        let stock_items: Vec<StockItem> = all_stock_items
-            .into_iter()
-            .skip(offset)
-            .take(limit)
-            .collect();
-        stock_items
-            .into_iter()
-            .map(|stock_item| serde_json::to_value(stock_item).map_err(Into::into))
-            .collect()
+        id: &Uuid,
+        quantity_delta: i32,
+        reserved_delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        self.modify(id, |stock_item| {
+            apply_delta(stock_item, quantity_delta, reserved_delta)
+        })
+        .await
     }
 }
 
-#[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl EntityCreator for StockItemDynamoDBStore {
-    async fn create_from_json(
+#[async_trait]
+impl<S, C> StockItemStore for BackendStore<StockItem, S, C>
+where
+    S: DataService<StockItem> + 'static,
+    C: CompareAndSwap<StockItem> + 'static,
+{
+    async fn adjust(
         &self,
-        entity_data: serde_json::Value,
-    ) -> Result<serde_json::Value, anyhow::Error> {
-        let stock_item = StockItem::new(
-            entity_data["name"]
-                .as_str()
-                .unwrap_or("StockItem")
-                .to_string(),
-            entity_data["status"]
-                .as_str()
-                .unwrap_or("available")
-                .to_string(),
-            entity_data["product_id"]
-                .as_str()
-                .and_then(|s| Uuid::parse_str(s).ok()),
-            entity_data["quantity"].as_i64().unwrap_or(0) as i32,
-            entity_data["warehouse_id"]
-                .as_str()
-                .and_then(|s| Uuid::parse_str(s).ok())
-                .unwrap_or_else(Uuid::new_v4),
-            entity_data["reserved_quantity"].as_i64().map(|v| v as i32),
-        );
-
-        self.create(stock_item.clone()).await?;
-        Ok(serde_json::to_value(stock_item)?)
+        id: &Uuid,
+        quantity_delta: i32,
+        reserved_delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        self.modify(id, |stock_item| {
+            apply_delta(stock_item, quantity_delta, reserved_delta)
+        })
+        .await
     }
 }
 
+pub type InMemoryStockItemStore = InMemoryEntityStore<StockItem>;
+
 #[cfg(feature = "dynamodb")]
-#[async_trait::async_trait]
-impl StockItemStore for StockItemDynamoDBStore {
-    async fn create(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
-        self.service
-            .create(stock_item.clone())
-            .await
-            .map_err(|e| StockItemStoreError::Other(anyhow::anyhow!(e)))
-    }
+pub type StockItemDynamoDBStore = common::store::DynamoDBStore<StockItem>;
 
-    async fn get(&self, id: &Uuid) -> Result<StockItem, StockItemStoreError> {
-        self.service
-            .get(id)
-            .await
-            .map_err(|e| StockItemStoreError::Other(anyhow::anyhow!(e)))?
-            .ok_or_else(|| StockItemStoreError::NotFound(id.to_string()))
-    }
+#[cfg(feature = "postgres")]
+pub type StockItemPostgresStore = common::store::PostgresStore<StockItem>;
 
-    async fn update(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
-        self.service
-            .update(&stock_item.id, stock_item.clone())
-            .await
-            .map_err(|e| StockItemStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "mongodb_backend")]
+pub type StockItemMongoStore = common::store::MongoStore<StockItem>;
 
-    async fn delete(&self, id: &Uuid) -> Result<(), StockItemStoreError> {
-        self.service
-            .delete(id)
-            .await
-            .map_err(|e| StockItemStoreError::Other(anyhow::anyhow!(e)))
-    }
+#[cfg(feature = "neo4j")]
+pub type StockItemNeo4jStore = common::store::Neo4jStore<StockItem>;
 
-    async fn list(&self) -> Result<Vec<StockItem>, StockItemStoreError> {
-        self.service
-            .list()
-            .await
-            .map_err(|e| StockItemStoreError::Other(anyhow::anyhow!(e)))
-    }
-}
+#[cfg(feature = "scylladb")]
+pub type StockItemScyllaStore = common::store::ScyllaStore<StockItem>;
+
+#[cfg(feature = "mysql")]
+pub type StockItemMysqlStore = common::store::MysqlStore<StockItem>;
+
+#[cfg(feature = "lmdb")]
+pub type StockItemLmdbStore = common::store::LmdbStore<StockItem>;
+
+#[cfg(feature = "sqlite")]
+pub type StockItemSqliteStore = common::store::SqliteStore<StockItem>;
@@ -1,12 +1,13 @@
-use axum::routing::get;
+use axum::routing::{get, post};
 use std::sync::Arc;
 use this::prelude::Router;
 use this::server::entity_registry::EntityDescriptor;
 
 use super::StockMovementStore;
 use super::handlers::{
-    StockMovementState, create_stock_movement, delete_stock_movement, get_stock_movement,
-    list_stock_movements, update_stock_movement,
+    StockMovementState, cancel_stock_movement, complete_stock_movement, create_stock_movement,
+    delete_stock_movement, get_stock_movement, list_stock_movements, patch_stock_movement,
+    update_stock_movement,
 };
 
 #[derive(Clone)]
@@ -54,8 +55,14 @@ impl EntityDescriptor for StockMovementDescriptor {
                 "/stock_movements/{id}",
                 get(get_stock_movement)
                     .put(update_stock_movement)
+                    .patch(patch_stock_movement)
                     .delete(delete_stock_movement),
             )
+            .route(
+                "/stock_movements/{id}/complete",
+                post(complete_stock_movement),
+            )
+            .route("/stock_movements/{id}/cancel", post(cancel_stock_movement))
             .with_state(state)
     }
 }
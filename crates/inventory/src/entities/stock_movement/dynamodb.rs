@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use common::Versioned;
+use common::cas::Transacted;
+use common::store::EntityStore;
+use std::sync::Arc;
+
+use super::ledger::{AtomicStockWrites, MovementWrite, StockChange};
+use super::{StockMovement, StockMovementDynamoDBStore, StockMovementStoreError};
+use crate::entities::stock_item::store::apply_delta;
+use crate::entities::stock_item::{StockItem, StockItemDynamoDBStore};
+
+/// How many times a transaction is tried while its stock items keep changing
+const ATTEMPTS: usize = 5;
+
+/// Movements and stock item changes in one DynamoDB `TransactWriteItems`
+///
+/// The stock items are read, changed and written back on the condition that
+/// their version did not move in between, next to the conditional writes of
+/// the movements. When only a stock item moved, the transaction is tried
+/// again from a fresh read.
+pub struct DynamoDBStockWrites {
+    stock_items: Arc<StockItemDynamoDBStore>,
+    movements: Arc<StockMovementDynamoDBStore>,
+}
+
+impl DynamoDBStockWrites {
+    pub fn new(
+        stock_items: Arc<StockItemDynamoDBStore>,
+        movements: Arc<StockMovementDynamoDBStore>,
+    ) -> Self {
+        Self {
+            stock_items,
+            movements,
+        }
+    }
+
+    /// Read the stock items of `writes` and apply their deltas, one change
+    /// per stock item
+    async fn changes(
+        &self,
+        writes: &[MovementWrite],
+    ) -> Result<Vec<StockChange>, StockMovementStoreError> {
+        let mut changes: Vec<StockChange> = Vec::new();
+        for write in writes {
+            let (quantity_delta, reserved_delta) = write.delta;
+            if quantity_delta == 0 && reserved_delta == 0 {
+                continue;
+            }
+            let id = write.movement.stock_item_id;
+            let after = match changes.iter_mut().find(|change| change.before.id == id) {
+                Some(change) => &mut change.after,
+                None => {
+                    let before: StockItem = self.stock_items.get(&id).await?;
+                    let mut after = before.clone();
+                    after.set_version(before.version().next());
+                    changes.push(StockChange { before, after });
+                    &mut changes.last_mut().expect("just pushed").after
+                }
+            };
+            apply_delta(after, quantity_delta, reserved_delta)?;
+        }
+        Ok(changes)
+    }
+
+    /// Why the write of a movement failed its condition
+    async fn rejected(&self, write: &MovementWrite) -> StockMovementStoreError {
+        let movement = &write.movement;
+        if write.create {
+            return StockMovementStoreError::Conflict(format!(
+                "stock movement {} already exists",
+                movement.id
+            ));
+        }
+        match self.movements.get(&movement.id).await {
+            Ok(current) => match movement.ensure_current(current.version()) {
+                Err(mismatch) => mismatch.into(),
+                Ok(()) => StockMovementStoreError::Conflict(format!(
+                    "stock movement {} changed during the write",
+                    movement.id
+                )),
+            },
+            Err(e) => e,
+        }
+    }
+}
+
+#[async_trait]
+impl AtomicStockWrites for DynamoDBStockWrites {
+    async fn write(
+        &self,
+        writes: Vec<MovementWrite>,
+    ) -> Result<(Vec<StockMovement>, Vec<StockChange>), StockMovementStoreError> {
+        let mut movements = Vec::new();
+        let mut movement_writes = Vec::new();
+        for write in &writes {
+            let mut movement = write.movement.clone();
+            if write.create {
+                movement_writes.push(self.movements.cas().create_write(&movement)?);
+            } else {
+                let expected = movement.version();
+                movement.set_version(expected.next());
+                movement.touch();
+                movement_writes.push(self.movements.cas().swap_write(expected, &movement)?);
+            }
+            movements.push(movement);
+        }
+
+        for _ in 0..ATTEMPTS {
+            let changes = self.changes(&writes).await?;
+            let mut items = movement_writes.clone();
+            for change in &changes {
+                items.push(
+                    self.stock_items
+                        .cas()
+                        .swap_write(change.before.version(), &change.after)?,
+                );
+            }
+            match self.movements.cas().transact(items).await? {
+                Transacted::Written => return Ok((movements, changes)),
+                // The movement writes come first
+                Transacted::Rejected(positions) => match positions.first() {
+                    Some(&position) if position < writes.len() => {
+                        return Err(self.rejected(&writes[position]).await);
+                    }
+                    _ => continue,
+                },
+            }
+        }
+        Err(StockMovementStoreError::Conflict(format!(
+            "stock items kept changing, gave up after {ATTEMPTS} attempts"
+        )))
+    }
+}
@@ -1,9 +1,12 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{StockMovement, StockMovementStore};
+use super::{MovementStatus, StockMovement, StockMovementStore, StockMovementStoreError};
 
 #[derive(Clone)]
 pub struct StockMovementState {
@@ -13,59 +16,110 @@ pub struct StockMovementState {
 
 pub async fn list_stock_movements(
     State(state): State<StockMovementState>,
-) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List stock_movements error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list stock_movements",
-                "details": e.to_string()
-            }))
-        }
-    }
+    query: ListQuery,
+) -> Result<Json<Page<StockMovement>>, Problem> {
+    query.check::<StockMovement>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List stock_movements error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_stock_movement(
     State(state): State<StockMovementState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<StockMovement>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<StockMovement>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_stock_movement(
     State(state): State<StockMovementState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating stock_movement with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("StockMovement created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create stock_movement error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create stock_movement",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<StockMovementStoreError>(e))
         }
     }
 }
 
 pub async fn update_stock_movement(
     State(state): State<StockMovementState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(stock_movement): Json<StockMovement>,
-) -> Json<Option<StockMovement>> {
-    let updated = state.store.update(stock_movement).await.ok();
-    Json(updated)
+) -> Result<Tagged<StockMovement>, Problem> {
+    check_id(id, stock_movement.id)?;
+    let mut stock_movement =
+        validate_entity(&StockMovement::validation_config("update"), stock_movement)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    stock_movement.version = current.version;
+    stock_movement.owned_by = current.owned_by;
+    let updated = state.store.update(stock_movement).await.map_err(|e| {
+        eprintln!("Update stock_movement {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Tagged(updated))
+}
+
+/// Apply a JSON Merge Patch to an existing stock movement
+pub async fn patch_stock_movement(
+    State(state): State<StockMovementState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<StockMovement>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut stock_movement =
+        patch_entity(&StockMovement::validation_config("update"), current, patch)?;
+    stock_movement.touch();
+    let updated = state.store.update(stock_movement).await.map_err(|e| {
+        eprintln!("Update stock_movement {} error: {:?}", id, e);
+        e
+    })?;
+    Ok(Tagged(updated))
 }
 
 pub async fn delete_stock_movement(
     State(state): State<StockMovementState>,
     Path(id): Path<Uuid>,
-) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn complete_stock_movement(
+    State(state): State<StockMovementState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StockMovement>, Problem> {
+    transition_stock_movement(&state, &id, MovementStatus::Completed).await
+}
+
+pub async fn cancel_stock_movement(
+    State(state): State<StockMovementState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StockMovement>, Problem> {
+    transition_stock_movement(&state, &id, MovementStatus::Cancelled).await
+}
+
+async fn transition_stock_movement(
+    state: &StockMovementState,
+    id: &Uuid,
+    to: MovementStatus,
+) -> Result<Json<StockMovement>, Problem> {
+    let updated = state.store.transition(id, to).await.map_err(|e| {
+        eprintln!("Transition stock_movement {} to {} error: {:?}", id, to, e);
+        e
+    })?;
+    Ok(Json(updated))
 }
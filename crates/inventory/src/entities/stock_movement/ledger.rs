@@ -0,0 +1,306 @@
+use async_trait::async_trait;
+use common::store::EntityStore;
+use common::{ListQuery, Page, Version, Versioned};
+use std::sync::Arc;
+use this::prelude::*;
+use tokio::sync::Mutex;
+
+use super::{MovementStatus, StockMovement, StockMovementStore, StockMovementStoreError};
+use crate::entities::stock_item::{StockItem, StockItemStore};
+
+/// A movement to write together with the change it makes to its stock item
+#[derive(Debug, Clone)]
+pub struct MovementWrite {
+    /// The movement to store, at the version it was read for an update
+    pub movement: StockMovement,
+    /// Whether the movement is new
+    pub create: bool,
+    /// Changes to the quantity and reserved quantity of its stock item
+    pub delta: (i32, i32),
+}
+
+/// A stock item before and after a write of the ledger
+#[derive(Debug, Clone)]
+pub struct StockChange {
+    pub before: StockItem,
+    pub after: StockItem,
+}
+
+/// Writes movements and the changes they make to their stock items in one
+/// transaction of the backend, such as `DynamoDBStockWrites`
+#[async_trait]
+pub trait AtomicStockWrites: Send + Sync {
+    /// Make all of `writes` or none of them, returning the movements as
+    /// stored and the change of each stock item
+    async fn write(
+        &self,
+        writes: Vec<MovementWrite>,
+    ) -> Result<(Vec<StockMovement>, Vec<StockChange>), StockMovementStoreError>;
+}
+
+/// Stock movement store that keeps stock item quantities in step
+///
+/// Wraps the movement store of any backend. A movement that reaches
+/// `completed` adds its [`stock_delta`](StockMovement::stock_delta) to its
+/// stock item, cancelling a completed movement subtracts it again, and
+/// neither may take more than the available stock (`InsufficientStock`). An
+/// `out` movement with a `reservation_id` consumes reserved units instead.
+/// Completed movements cannot be edited or deleted, only cancelled.
+///
+/// With [`AtomicStockWrites`] (DynamoDB), the movement and its stock item are
+/// written in one transaction. Otherwise the stock item is changed first with
+/// [`StockItemStore::adjust`], which is atomic in every backend, and the
+/// change is undone if the movement cannot be saved afterwards; a crash in
+/// between leaves the stock changed without its movement.
+pub struct StockLedger {
+    movements: Arc<dyn StockMovementStore>,
+    stock_items: Arc<dyn StockItemStore>,
+    atomic: Option<Arc<dyn AtomicStockWrites>>,
+    lock: Mutex<()>,
+}
+
+impl StockLedger {
+    pub fn new(
+        movements: Arc<dyn StockMovementStore>,
+        stock_items: Arc<dyn StockItemStore>,
+    ) -> Self {
+        Self {
+            movements,
+            stock_items,
+            atomic: None,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Write movements and their stock changes together through `atomic`,
+    /// which must work on the same stores
+    pub fn with_atomic_writes(mut self, atomic: Arc<dyn AtomicStockWrites>) -> Self {
+        self.atomic = Some(atomic);
+        self
+    }
+
+    /// Move every movement of `ids` to `to`
+    ///
+    /// With [`AtomicStockWrites`] all of them move in one transaction, or
+    /// none does. Otherwise they move one after the other and the first
+    /// failure stops the rest, leaving the earlier ones moved.
+    pub async fn transition_all(
+        &self,
+        ids: &[Uuid],
+        to: MovementStatus,
+    ) -> Result<Vec<StockMovement>, StockMovementStoreError> {
+        let Some(atomic) = &self.atomic else {
+            let mut moved = Vec::new();
+            for id in ids {
+                moved.push(self.transition(id, to).await?);
+            }
+            return Ok(moved);
+        };
+
+        let _guard = self.lock.lock().await;
+        let mut writes = Vec::new();
+        for id in ids {
+            let current = self.movements.get(id).await?;
+            let mut movement = current.clone();
+            movement.set_status(to.to_string());
+            let delta = Self::delta_between(Some(&current), &movement)?;
+            writes.push(MovementWrite {
+                movement,
+                create: false,
+                delta,
+            });
+        }
+        let (moved, changes) = atomic.write(writes).await?;
+        for change in &changes {
+            self.stock_items.written(&change.before, &change.after);
+        }
+        Ok(moved)
+    }
+
+    /// Changes to the quantity and reserved quantity of the stock item needed
+    /// to go from `current` (`None` on create) to `next`
+    fn delta_between(
+        current: Option<&StockMovement>,
+        next: &StockMovement,
+    ) -> Result<(i32, i32), StockMovementStoreError> {
+        let next_delta = next
+            .stock_delta()
+            .map_err(StockMovementStoreError::Validation)?;
+        let next_reserved = next
+            .reserved_delta()
+            .map_err(StockMovementStoreError::Validation)?;
+        let next_status = parse_status(&next.status)?;
+        let Some(current) = current else {
+            return Ok(match next_status {
+                MovementStatus::Completed => (next_delta, next_reserved),
+                _ => (0, 0),
+            });
+        };
+
+        let current_status = parse_status(&current.status)?;
+        if current_status != next_status {
+            current_status
+                .transition_to(next_status)
+                .map_err(StockMovementStoreError::InvalidTransition)?;
+        }
+        if current_status == MovementStatus::Completed && !same_effect(current, next) {
+            return Err(StockMovementStoreError::Conflict(format!(
+                "stock movement {} is completed; cancel it instead of editing it",
+                current.id
+            )));
+        }
+
+        Ok(match (current_status, next_status) {
+            (MovementStatus::Pending, MovementStatus::Completed) => (next_delta, next_reserved),
+            // Same effect as when it was completed, see above. The units come
+            // back as available stock: the reservation they fulfilled is over.
+            (MovementStatus::Completed, MovementStatus::Cancelled) => (-next_delta, 0),
+            _ => (0, 0),
+        })
+    }
+
+    async fn save(
+        &self,
+        movement: StockMovement,
+        create: bool,
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        if create {
+            self.movements.create(movement).await
+        } else {
+            self.movements.update(movement).await
+        }
+    }
+
+    /// Create or update `movement` and apply `delta` to its stock item
+    async fn apply(
+        &self,
+        movement: StockMovement,
+        create: bool,
+        delta: (i32, i32),
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        let (quantity_delta, reserved_delta) = delta;
+        if quantity_delta == 0 && reserved_delta == 0 {
+            return self.save(movement, create).await;
+        }
+
+        if let Some(atomic) = &self.atomic {
+            let (mut saved, changes) = atomic
+                .write(vec![MovementWrite {
+                    movement,
+                    create,
+                    delta,
+                }])
+                .await?;
+            for change in &changes {
+                self.stock_items.written(&change.before, &change.after);
+            }
+            return saved
+                .pop()
+                .ok_or_else(|| anyhow::anyhow!("no movement written").into());
+        }
+
+        self.stock_items
+            .adjust(&movement.stock_item_id, quantity_delta, reserved_delta)
+            .await?;
+        match self.save(movement.clone(), create).await {
+            Ok(saved) => Ok(saved),
+            Err(e) => {
+                if let Err(rollback) = self
+                    .stock_items
+                    .adjust(&movement.stock_item_id, -quantity_delta, -reserved_delta)
+                    .await
+                {
+                    eprintln!(
+                        "Failed to roll back stock item {} after movement {}: {:?}",
+                        movement.stock_item_id, movement.id, rollback
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete a movement that is not completed, provided it is at `expected`
+    /// when given
+    async fn remove(
+        &self,
+        id: &Uuid,
+        expected: Option<Version>,
+    ) -> Result<(), StockMovementStoreError> {
+        let _guard = self.lock.lock().await;
+        let current = self.movements.get(id).await?;
+        if let Some(expected) = expected {
+            current.ensure_current(expected)?;
+        }
+        if current.status == MovementStatus::Completed.as_str() {
+            return Err(StockMovementStoreError::Conflict(format!(
+                "stock movement {} is completed; cancel it before deleting it",
+                id
+            )));
+        }
+        match expected {
+            Some(expected) => self.movements.delete_at(id, expected).await,
+            None => self.movements.delete(id).await,
+        }
+    }
+}
+
+fn parse_status(status: &str) -> Result<MovementStatus, StockMovementStoreError> {
+    status.parse().map_err(StockMovementStoreError::Validation)
+}
+
+/// Whether two versions of a movement change the same stock in the same way
+fn same_effect(a: &StockMovement, b: &StockMovement) -> bool {
+    a.stock_item_id == b.stock_item_id
+        && a.movement_type == b.movement_type
+        && a.quantity == b.quantity
+        && a.reservation_id == b.reservation_id
+}
+
+common::impl_entity_json!(StockLedger, StockMovement);
+
+#[async_trait]
+impl EntityStore<StockMovement> for StockLedger {
+    async fn create(
+        &self,
+        stock_movement: StockMovement,
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        let _guard = self.lock.lock().await;
+        let delta = Self::delta_between(None, &stock_movement)?;
+        self.apply(stock_movement, true, delta).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<StockMovement, StockMovementStoreError> {
+        self.movements.get(id).await
+    }
+
+    async fn update(
+        &self,
+        stock_movement: StockMovement,
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        let _guard = self.lock.lock().await;
+        let current = self.movements.get(&stock_movement.id).await?;
+        stock_movement.ensure_current(current.version)?;
+        let delta = Self::delta_between(Some(&current), &stock_movement)?;
+        self.apply(stock_movement, false, delta).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), StockMovementStoreError> {
+        self.remove(id, None).await
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), StockMovementStoreError> {
+        self.remove(id, Some(expected)).await
+    }
+
+    async fn list(&self) -> Result<Vec<StockMovement>, StockMovementStoreError> {
+        self.movements.list().await
+    }
+
+    async fn query(
+        &self,
+        query: &ListQuery,
+    ) -> Result<Page<StockMovement>, StockMovementStoreError> {
+        self.movements.query(query).await
+    }
+}
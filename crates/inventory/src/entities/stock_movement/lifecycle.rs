@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Lifecycle states of a [`StockMovement`](super::StockMovement)
+///
+/// ```text
+/// pending ──▶ completed
+///    │            │
+///    └────────────┴──▶ cancelled
+/// ```
+///
+/// A movement changes the quantity of its stock item when it becomes
+/// `completed`; cancelling a completed movement reverses that change.
+/// `cancelled` is terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MovementStatus {
+    Pending,
+    Completed,
+    Cancelled,
+}
+
+impl MovementStatus {
+    pub const ALL: [MovementStatus; 3] = [
+        MovementStatus::Pending,
+        MovementStatus::Completed,
+        MovementStatus::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementStatus::Pending => "pending",
+            MovementStatus::Completed => "completed",
+            MovementStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`
+    pub fn can_transition_to(&self, next: MovementStatus) -> bool {
+        matches!(
+            (self, next),
+            (MovementStatus::Pending, MovementStatus::Completed)
+                | (MovementStatus::Pending, MovementStatus::Cancelled)
+                | (MovementStatus::Completed, MovementStatus::Cancelled)
+        )
+    }
+
+    /// Validate a transition, returning the rejected pair on failure
+    pub fn transition_to(
+        &self,
+        next: MovementStatus,
+    ) -> Result<MovementStatus, MovementTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(MovementTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+}
+
+impl fmt::Display for MovementStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MovementStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MovementStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown stock movement status '{}'", s))
+    }
+}
+
+/// A transition rejected by the stock movement state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovementTransition {
+    pub from: MovementStatus,
+    pub to: MovementStatus,
+}
+
+impl fmt::Display for MovementTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stock movement cannot transition from '{}' to '{}'",
+            self.from, self.to
+        )
+    }
+}
@@ -1,10 +1,32 @@
 pub mod descriptor;
+#[cfg(feature = "dynamodb")]
+pub mod dynamodb;
 pub mod handlers;
+pub mod ledger;
+pub mod lifecycle;
 pub mod model;
 pub mod store;
 
+pub use ledger::{AtomicStockWrites, MovementWrite, StockChange, StockLedger};
+pub use lifecycle::{MovementStatus, MovementTransition};
 pub use model::StockMovement;
 pub use store::{InMemoryStockMovementStore, StockMovementStore, StockMovementStoreError};
 
+#[cfg(feature = "dynamodb")]
+pub use dynamodb::DynamoDBStockWrites;
 #[cfg(feature = "dynamodb")]
 pub use store::StockMovementDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::StockMovementLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::StockMovementMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::StockMovementMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::StockMovementNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::StockMovementPostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::StockMovementScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::StockMovementSqliteStore;
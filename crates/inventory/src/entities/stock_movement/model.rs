@@ -1,5 +1,6 @@
 #![allow(clippy::too_many_arguments)]
 
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -12,6 +13,10 @@ impl_data_entity_validated!(
         quantity: i32,
         reason: Option<String>,
         activity_id: Option<Uuid>,
+        reservation_id: Option<Uuid>,
+        transfer_id: Option<Uuid>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
@@ -36,3 +41,43 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(StockMovement);
+
+impl StockMovement {
+    /// Signed change this movement makes to its stock item once completed
+    ///
+    /// `in` adds `quantity`, `out` and `transfer` remove it; an `adjustment`
+    /// carries its own sign. Only adjustments may be negative, and no
+    /// movement may be zero.
+    pub fn stock_delta(&self) -> Result<i32, String> {
+        match self.movement_type.as_str() {
+            "in" | "out" | "transfer" if self.quantity <= 0 => Err(format!(
+                "'{}' movements need a positive quantity, got {}",
+                self.movement_type, self.quantity
+            )),
+            "in" => Ok(self.quantity),
+            "out" | "transfer" => Ok(-self.quantity),
+            "adjustment" if self.quantity == 0 => {
+                Err("adjustment movements need a non-zero quantity".to_string())
+            }
+            "adjustment" => Ok(self.quantity),
+            other => Err(format!("unknown movement type '{}'", other)),
+        }
+    }
+
+    /// Change to the reserved quantity of the stock item once completed
+    ///
+    /// An `out` movement that fulfils a reservation takes its units from the
+    /// reserved stock rather than from the available stock.
+    pub fn reserved_delta(&self) -> Result<i32, String> {
+        match (self.reservation_id, self.movement_type.as_str()) {
+            (None, _) => Ok(0),
+            (Some(_), "out") => self.stock_delta(),
+            (Some(_), other) => Err(format!(
+                "only 'out' movements can fulfil a reservation, not '{}'",
+                other
+            )),
+        }
+    }
+}
@@ -5,7 +5,7 @@ use this::server::entity_registry::EntityDescriptor;
 
 use super::StoreStore;
 use super::handlers::{
-    StoreState, create_store, delete_store, get_store, list_stores, update_store,
+    StoreState, create_store, delete_store, get_store, list_stores, patch_store, update_store,
 };
 
 #[derive(Clone)]
@@ -48,7 +48,10 @@ impl EntityDescriptor for StoreDescriptor {
             .route("/stores", get(list_stores).post(create_store))
             .route(
                 "/stores/{id}",
-                get(get_store).put(update_store).delete(delete_store),
+                get(get_store)
+                    .put(update_store)
+                    .patch(patch_store)
+                    .delete(delete_store),
             )
             .with_state(state)
     }
@@ -1,9 +1,12 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Store, StoreStore};
+use super::{Store, StoreStore, StoreStoreError};
 
 #[derive(Clone)]
 pub struct StoreState {
@@ -11,56 +14,76 @@ pub struct StoreState {
     pub entity_creator: Arc<dyn EntityCreator>,
 }
 
-pub async fn list_stores(State(state): State<StoreState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List stores error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list stores",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_stores(
+    State(state): State<StoreState>,
+    query: ListQuery,
+) -> Result<Json<Page<Store>>, Problem> {
+    query.check::<Store>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List stores error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_store(
     State(state): State<StoreState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Store>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Store>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_store(
     State(state): State<StoreState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating store with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Store created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create store error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create store",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<StoreStoreError>(e))
         }
     }
 }
 
 pub async fn update_store(
     State(state): State<StoreState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(store): Json<Store>,
-) -> Json<Option<Store>> {
-    let updated = state.store.update(store).await.ok();
-    Json(updated)
+) -> Result<Tagged<Store>, Problem> {
+    check_id(id, store.id)?;
+    let mut store = validate_entity(&Store::validation_config("update"), store)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    store.version = current.version;
+    store.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(store).await?))
 }
 
-pub async fn delete_store(State(state): State<StoreState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing store
+pub async fn patch_store(
+    State(state): State<StoreState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Store>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut store = patch_entity(&Store::validation_config("update"), current, patch)?;
+    store.touch();
+    Ok(Tagged(state.store.update(store).await?))
+}
+
+pub async fn delete_store(
+    State(state): State<StoreState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -8,3 +8,17 @@ pub use store::{InMemoryStoreStore, StoreStore, StoreStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::StoreDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::StoreLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::StoreMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::StoreMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::StoreNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::StorePostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::StoreScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::StoreSqliteStore;
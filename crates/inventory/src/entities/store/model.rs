@@ -1,3 +1,4 @@
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -6,6 +7,8 @@ impl_data_entity_validated!(
     ["name"],
     {
         address: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
@@ -26,3 +29,5 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(Store);
@@ -5,7 +5,7 @@ use this::server::entity_registry::EntityDescriptor;
 
 use super::UsageStore;
 use super::handlers::{
-    UsageState, create_usage, delete_usage, get_usage, list_usages, update_usage,
+    UsageState, create_usage, delete_usage, get_usage, list_usages, patch_usage, update_usage,
 };
 
 #[derive(Clone)]
@@ -48,7 +48,10 @@ impl EntityDescriptor for UsageDescriptor {
             .route("/usages", get(list_usages).post(create_usage))
             .route(
                 "/usages/{id}",
-                get(get_usage).put(update_usage).delete(delete_usage),
+                get(get_usage)
+                    .put(update_usage)
+                    .patch(patch_usage)
+                    .delete(delete_usage),
             )
             .with_state(state)
     }
@@ -1,9 +1,12 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Usage, UsageStore};
+use super::{Usage, UsageStore, UsageStoreError};
 
 #[derive(Clone)]
 pub struct UsageState {
@@ -11,56 +14,76 @@ pub struct UsageState {
     pub entity_creator: Arc<dyn EntityCreator>,
 }
 
-pub async fn list_usages(State(state): State<UsageState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List usages error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list usages",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_usages(
+    State(state): State<UsageState>,
+    query: ListQuery,
+) -> Result<Json<Page<Usage>>, Problem> {
+    query.check::<Usage>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List usages error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_usage(
     State(state): State<UsageState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Usage>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Usage>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_usage(
     State(state): State<UsageState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating usage with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Usage created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create usage error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create usage",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<UsageStoreError>(e))
         }
     }
 }
 
 pub async fn update_usage(
     State(state): State<UsageState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(usage): Json<Usage>,
-) -> Json<Option<Usage>> {
-    let updated = state.store.update(usage).await.ok();
-    Json(updated)
+) -> Result<Tagged<Usage>, Problem> {
+    check_id(id, usage.id)?;
+    let mut usage = validate_entity(&Usage::validation_config("update"), usage)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    usage.version = current.version;
+    usage.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(usage).await?))
 }
 
-pub async fn delete_usage(State(state): State<UsageState>, Path(id): Path<Uuid>) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+/// Apply a JSON Merge Patch to an existing usage
+pub async fn patch_usage(
+    State(state): State<UsageState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Usage>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut usage = patch_entity(&Usage::validation_config("update"), current, patch)?;
+    usage.touch();
+    Ok(Tagged(state.store.update(usage).await?))
+}
+
+pub async fn delete_usage(
+    State(state): State<UsageState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
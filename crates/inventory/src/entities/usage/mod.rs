@@ -8,3 +8,17 @@ pub use store::{InMemoryUsageStore, UsageStore, UsageStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::UsageDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::UsageLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::UsageMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::UsageMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::UsageNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::UsagePostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::UsageScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::UsageSqliteStore;
@@ -1,5 +1,6 @@
 #![allow(clippy::too_many_arguments)]
 
+use common::Version;
 use this::prelude::*;
 
 impl_data_entity_validated!(
@@ -13,6 +14,8 @@ impl_data_entity_validated!(
         unit: Option<String>,
         from_activity_id: Option<Uuid>,
         date: Option<String>,
+        owned_by: Option<Uuid>,
+        version: Version,
     },
     validate: {
         create: {
@@ -37,3 +40,5 @@ impl_data_entity_validated!(
         },
     }
 );
+
+common::impl_versioned!(Usage);
@@ -5,14 +5,16 @@ use this::server::entity_registry::EntityDescriptor;
 
 use super::WarehouseStore;
 use super::handlers::{
-    WarehouseState, create_warehouse, delete_warehouse, get_warehouse, list_warehouses,
-    update_warehouse,
+    WarehouseState, create_warehouse, delete_warehouse, get_warehouse, list_low_stock,
+    list_warehouses, patch_warehouse, update_warehouse,
 };
+use crate::entities::stock_item::StockItemStore;
 
 #[derive(Clone)]
 pub struct WarehouseDescriptor {
     store: Arc<dyn WarehouseStore + Send + Sync>,
     entity_creator: Arc<dyn this::prelude::EntityCreator + Send + Sync>,
+    stock_items: Option<Arc<dyn StockItemStore>>,
 }
 
 impl WarehouseDescriptor {
@@ -27,8 +29,15 @@ impl WarehouseDescriptor {
         Self {
             store,
             entity_creator,
+            stock_items: None,
         }
     }
+
+    /// Serve `GET /warehouses/{id}/low_stock` from `stock_items`
+    pub fn with_stock_items(mut self, stock_items: Arc<dyn StockItemStore>) -> Self {
+        self.stock_items = Some(stock_items);
+        self
+    }
 }
 
 impl EntityDescriptor for WarehouseDescriptor {
@@ -44,15 +53,22 @@ impl EntityDescriptor for WarehouseDescriptor {
         let state = WarehouseState {
             store: self.store.clone(),
             entity_creator: self.entity_creator.clone(),
+            stock_items: self.stock_items.clone(),
         };
-        Router::new()
+        let router = Router::new()
             .route("/warehouses", get(list_warehouses).post(create_warehouse))
             .route(
                 "/warehouses/{id}",
                 get(get_warehouse)
                     .put(update_warehouse)
+                    .patch(patch_warehouse)
                     .delete(delete_warehouse),
-            )
-            .with_state(state)
+            );
+        let router = if self.stock_items.is_some() {
+            router.route("/warehouses/{id}/low_stock", get(list_low_stock))
+        } else {
+            router
+        };
+        router.with_state(state)
     }
 }
@@ -1,69 +1,119 @@
 use axum::Json;
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use common::{IfMatch, ListQuery, Page, Problem, Tagged, check_id, patch_entity, validate_entity};
 use std::sync::Arc;
+use this::core::validation::extractor::ValidatableEntity;
 use this::prelude::*;
 
-use super::{Warehouse, WarehouseStore};
+use super::{Warehouse, WarehouseStore, WarehouseStoreError};
+use crate::entities::stock_item::{LowStockAlert, StockItemStore};
 
 #[derive(Clone)]
 pub struct WarehouseState {
     pub store: Arc<dyn WarehouseStore>,
     pub entity_creator: Arc<dyn EntityCreator>,
+    pub stock_items: Option<Arc<dyn StockItemStore>>,
 }
 
-pub async fn list_warehouses(State(state): State<WarehouseState>) -> Json<serde_json::Value> {
-    match state.store.list().await {
-        Ok(items) => Json(serde_json::to_value(items).unwrap_or_else(|_| serde_json::json!([]))),
-        Err(e) => {
-            eprintln!("List warehouses error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to list warehouses",
-                "details": e.to_string()
-            }))
-        }
-    }
+pub async fn list_warehouses(
+    State(state): State<WarehouseState>,
+    query: ListQuery,
+) -> Result<Json<Page<Warehouse>>, Problem> {
+    query.check::<Warehouse>()?;
+    let page = state.store.query(&query).await.map_err(|e| {
+        eprintln!("List warehouses error: {:?}", e);
+        e
+    })?;
+    Ok(Json(page))
 }
 
 pub async fn get_warehouse(
     State(state): State<WarehouseState>,
     Path(id): Path<Uuid>,
-) -> Json<Option<Warehouse>> {
-    let item = state.store.get(&id).await.ok();
-    Json(item)
+) -> Result<Tagged<Warehouse>, Problem> {
+    Ok(Tagged(state.store.get(&id).await?))
 }
 
 pub async fn create_warehouse(
     State(state): State<WarehouseState>,
     Json(entity_data): Json<serde_json::Value>,
-) -> Json<serde_json::Value> {
+) -> Result<(StatusCode, Json<serde_json::Value>), Problem> {
     eprintln!("Creating warehouse with data: {:?}", entity_data);
     match state.entity_creator.create_from_json(entity_data).await {
         Ok(created) => {
             eprintln!("Warehouse created successfully: {:?}", created);
-            Json(created)
+            Ok((StatusCode::CREATED, Json(created)))
         }
         Err(e) => {
             eprintln!("Create warehouse error: {:?}", e);
-            Json(serde_json::json!({
-                "error": "Failed to create warehouse",
-                "details": e.to_string()
-            }))
+            Err(Problem::from_creator::<WarehouseStoreError>(e))
         }
     }
 }
 
 pub async fn update_warehouse(
     State(state): State<WarehouseState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
     Json(warehouse): Json<Warehouse>,
-) -> Json<Option<Warehouse>> {
-    let updated = state.store.update(warehouse).await.ok();
-    Json(updated)
+) -> Result<Tagged<Warehouse>, Problem> {
+    check_id(id, warehouse.id)?;
+    let mut warehouse = validate_entity(&Warehouse::validation_config("update"), warehouse)?;
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    warehouse.version = current.version;
+    warehouse.owned_by = current.owned_by;
+    Ok(Tagged(state.store.update(warehouse).await?))
+}
+
+/// Apply a JSON Merge Patch to an existing warehouse
+pub async fn patch_warehouse(
+    State(state): State<WarehouseState>,
+    Path(id): Path<Uuid>,
+    if_match: IfMatch,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Tagged<Warehouse>, Problem> {
+    let current = state.store.get(&id).await?;
+    if_match.check(current.version)?;
+    let mut warehouse = patch_entity(&Warehouse::validation_config("update"), current, patch)?;
+    warehouse.touch();
+    Ok(Tagged(state.store.update(warehouse).await?))
 }
 
 pub async fn delete_warehouse(
     State(state): State<WarehouseState>,
     Path(id): Path<Uuid>,
-) -> Json<bool> {
-    let ok = state.store.delete(&id).await.is_ok();
-    Json(ok)
+    if_match: IfMatch,
+) -> Result<StatusCode, Problem> {
+    state.store.delete_matching(&id, &if_match).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stock items of a warehouse that are at or below their reorder point or out
+/// of stock
+pub async fn list_low_stock(
+    State(state): State<WarehouseState>,
+    Path(id): Path<Uuid>,
+    query: ListQuery,
+) -> Result<Json<Page<LowStockAlert>>, Problem> {
+    query.check::<LowStockAlert>()?;
+    let Some(stock_items) = state.stock_items.as_ref() else {
+        return Err(Problem::with_status(
+            StatusCode::NOT_IMPLEMENTED,
+            "Low stock reporting is not enabled",
+        ));
+    };
+    state.store.get(&id).await?;
+
+    let items = stock_items.list().await.map_err(|e| {
+        eprintln!("Low stock of warehouse {} error: {:?}", id, e);
+        e
+    })?;
+    let alerts = items
+        .iter()
+        .filter(|item| item.warehouse_id == id)
+        .filter_map(LowStockAlert::for_item)
+        .collect();
+    Ok(Json(query.apply_keyed(alerts, "stock_item_id")))
 }
@@ -8,3 +8,17 @@ pub use store::{InMemoryWarehouseStore, WarehouseStore, WarehouseStoreError};
 
 #[cfg(feature = "dynamodb")]
 pub use store::WarehouseDynamoDBStore;
+#[cfg(feature = "lmdb")]
+pub use store::WarehouseLmdbStore;
+#[cfg(feature = "mongodb_backend")]
+pub use store::WarehouseMongoStore;
+#[cfg(feature = "mysql")]
+pub use store::WarehouseMysqlStore;
+#[cfg(feature = "neo4j")]
+pub use store::WarehouseNeo4jStore;
+#[cfg(feature = "postgres")]
+pub use store::WarehousePostgresStore;
+#[cfg(feature = "scylladb")]
+pub use store::WarehouseScyllaStore;
+#[cfg(feature = "sqlite")]
+pub use store::WarehouseSqliteStore;
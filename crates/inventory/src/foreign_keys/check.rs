@@ -0,0 +1,211 @@
+//! Finding and repairing foreign keys and links that drifted apart
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use anyhow::anyhow;
+use serde::Serialize;
+use this::prelude::{LinkEntity, Uuid};
+
+use super::{
+    FOREIGN_KEYS, ForeignKey, ForeignKeys, STOCK_ITEM_WAREHOUSE, STOCK_MOVEMENT_STOCK_ITEM,
+    USAGE_ACTIVITY, WAREHOUSE_STORE, relink,
+};
+use crate::module::InventoryStores;
+
+/// How the links of an entity disagree with its foreign key
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MismatchKind {
+    /// The field names a parent that does not exist, which cannot be repaired
+    MissingParent { parent_id: Uuid },
+    /// The entity is not linked to the parent of its field, or also to
+    /// others; `linked` are the parents it is linked to
+    WrongLinks { parent_id: Uuid, linked: Vec<Uuid> },
+    /// A link to an entity that does not exist
+    DanglingLink { link_id: Uuid, parent_id: Uuid },
+}
+
+/// An entity whose links disagree with a foreign key
+#[derive(Debug, Clone, Serialize)]
+pub struct Mismatch {
+    pub entity_type: &'static str,
+    pub entity_id: Uuid,
+    pub field: &'static str,
+    pub link_type: &'static str,
+    #[serde(flatten)]
+    pub kind: MismatchKind,
+    /// Whether the check repaired it
+    pub repaired: bool,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: ", self.entity_type, self.entity_id)?;
+        match &self.kind {
+            MismatchKind::MissingParent { parent_id } => {
+                write!(f, "{} {} does not exist", self.field, parent_id)?
+            }
+            MismatchKind::WrongLinks { parent_id, linked } if linked.is_empty() => write!(
+                f,
+                "{} is {} but there is no '{}' link",
+                self.field, parent_id, self.link_type
+            )?,
+            MismatchKind::WrongLinks { parent_id, linked } => write!(
+                f,
+                "{} is {} but '{}' links come from {:?}",
+                self.field, parent_id, self.link_type, linked
+            )?,
+            MismatchKind::DanglingLink { link_id, parent_id } => write!(
+                f,
+                "'{}' link {} from {} to a missing entity",
+                self.link_type, link_id, parent_id
+            )?,
+        }
+        if self.repaired {
+            write!(f, " (repaired)")?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`ForeignKeys::check`] found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsistencyReport {
+    /// Number of entities checked
+    pub checked: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl ForeignKeys {
+    /// Compare the foreign keys of the entities of `stores` with their links,
+    /// and with `repair` make the links follow the fields
+    ///
+    /// Repairing creates the missing links and deletes the others, including
+    /// the links to entities that no longer exist. Fields naming a missing
+    /// parent are only reported.
+    pub async fn check(
+        &self,
+        stores: &InventoryStores,
+        repair: bool,
+    ) -> anyhow::Result<ConsistencyReport> {
+        let link_service = self
+            .link_service()
+            .ok_or_else(|| anyhow!("no link service attached to check the links against"))?;
+        let links = link_service.list().await?;
+        let mut report = ConsistencyReport::default();
+
+        for key in &FOREIGN_KEYS {
+            let entities = foreign_keys_of(stores, key).await?;
+            let mut linked: HashMap<Uuid, Vec<&LinkEntity>> = HashMap::new();
+            for link in links.iter().filter(|link| link.link_type == key.link_type) {
+                linked.entry(link.target_id).or_default().push(link);
+            }
+            report.checked += entities.len();
+
+            let ids: HashSet<Uuid> = entities.iter().map(|(id, _)| *id).collect();
+            for (entity_id, links) in &linked {
+                if ids.contains(entity_id) {
+                    continue;
+                }
+                for link in links {
+                    let repaired = repair && link_service.delete(&link.id).await.is_ok();
+                    report.mismatches.push(mismatch(
+                        key,
+                        *entity_id,
+                        MismatchKind::DanglingLink {
+                            link_id: link.id,
+                            parent_id: link.source_id,
+                        },
+                        repaired,
+                    ));
+                }
+            }
+
+            for (entity_id, parent_id) in entities {
+                let parents: Vec<Uuid> = linked
+                    .get(&entity_id)
+                    .map(|links| links.iter().map(|link| link.source_id).collect())
+                    .unwrap_or_default();
+                if !self.parent_exists(key, &parent_id).await? {
+                    report.mismatches.push(mismatch(
+                        key,
+                        entity_id,
+                        MismatchKind::MissingParent { parent_id },
+                        false,
+                    ));
+                } else if parents != [parent_id] {
+                    let repaired = repair
+                        && relink(link_service.as_ref(), key, parent_id, entity_id)
+                            .await
+                            .is_ok();
+                    report.mismatches.push(mismatch(
+                        key,
+                        entity_id,
+                        MismatchKind::WrongLinks {
+                            parent_id,
+                            linked: parents,
+                        },
+                        repaired,
+                    ));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn mismatch(key: &ForeignKey, entity_id: Uuid, kind: MismatchKind, repaired: bool) -> Mismatch {
+    Mismatch {
+        entity_type: key.entity_type,
+        entity_id,
+        field: key.field,
+        link_type: key.link_type,
+        kind,
+        repaired,
+    }
+}
+
+/// The entities of `key` with the parent their field names
+async fn foreign_keys_of(
+    stores: &InventoryStores,
+    key: &ForeignKey,
+) -> anyhow::Result<Vec<(Uuid, Uuid)>> {
+    Ok(match *key {
+        WAREHOUSE_STORE => stores
+            .warehouses_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|warehouse| (warehouse.id, warehouse.store_id))
+            .collect(),
+        STOCK_ITEM_WAREHOUSE => stores
+            .stock_items_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|stock_item| (stock_item.id, stock_item.warehouse_id))
+            .collect(),
+        STOCK_MOVEMENT_STOCK_ITEM => stores
+            .stock_movements_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|stock_movement| (stock_movement.id, stock_movement.stock_item_id))
+            .collect(),
+        USAGE_ACTIVITY => stores
+            .usages_store
+            .list()
+            .await?
+            .into_iter()
+            .map(|usage| (usage.id, usage.activity_id))
+            .collect(),
+        _ => return Err(anyhow!("unknown foreign key {}", key.field)),
+    })
+}
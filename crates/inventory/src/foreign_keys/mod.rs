@@ -0,0 +1,218 @@
+//! Links derived from the foreign-key fields of inventory entities
+//!
+//! Some entities name their parent both in a field and with a link from the
+//! parent:
+//!
+//! | Field | Link |
+//! |-------|------|
+//! | `Warehouse.store_id` | `has_warehouse` from the store |
+//! | `StockItem.warehouse_id` | `contains` from the warehouse |
+//! | `StockMovement.stock_item_id` | `has_movement` from the stock item |
+//! | `Usage.activity_id` | `has_usage` from the activity |
+//!
+//! The field is the source of truth. The stores of these entities are
+//! wrapped so that a field naming a missing parent is rejected and, once a
+//! link service is attached, the link follows the field: it is created with
+//! the entity, moved when the field changes and dropped with the entity.
+//! [`ForeignKeys::check`] reports the entities whose links drifted anyway,
+//! e.g. written before the link service was attached, and can repair them.
+
+mod check;
+mod stores;
+
+use std::sync::{Arc, RwLock};
+
+use this::prelude::{LinkEntity, LinkService, Uuid};
+
+use crate::entities::activity::{ActivityStore, ActivityStoreError};
+use crate::entities::stock_item::{StockItemStore, StockItemStoreError};
+use crate::entities::store::{StoreStore, StoreStoreError};
+use crate::entities::warehouse::{WarehouseStore, WarehouseStoreError};
+
+pub use check::{ConsistencyReport, Mismatch, MismatchKind};
+pub use stores::{
+    LinkedStockItemStore, LinkedStockMovementStore, LinkedUsageStore, LinkedWarehouseStore,
+};
+
+/// A field naming the parent of an entity, mirrored by a link from the parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub entity_type: &'static str,
+    pub field: &'static str,
+    pub parent_type: &'static str,
+    pub link_type: &'static str,
+}
+
+pub const WAREHOUSE_STORE: ForeignKey = ForeignKey {
+    entity_type: "warehouse",
+    field: "store_id",
+    parent_type: "store",
+    link_type: "has_warehouse",
+};
+
+pub const STOCK_ITEM_WAREHOUSE: ForeignKey = ForeignKey {
+    entity_type: "stock_item",
+    field: "warehouse_id",
+    parent_type: "warehouse",
+    link_type: "contains",
+};
+
+pub const STOCK_MOVEMENT_STOCK_ITEM: ForeignKey = ForeignKey {
+    entity_type: "stock_movement",
+    field: "stock_item_id",
+    parent_type: "stock_item",
+    link_type: "has_movement",
+};
+
+pub const USAGE_ACTIVITY: ForeignKey = ForeignKey {
+    entity_type: "usage",
+    field: "activity_id",
+    parent_type: "activity",
+    link_type: "has_usage",
+};
+
+impl ForeignKey {
+    /// Why an entity naming the missing `parent_id` is rejected
+    fn missing_parent(&self, parent_id: &Uuid) -> String {
+        format!(
+            "{} {} does not exist ({})",
+            self.parent_type, parent_id, self.field
+        )
+    }
+}
+
+/// The foreign keys kept in step with their links
+pub const FOREIGN_KEYS: [ForeignKey; 4] = [
+    WAREHOUSE_STORE,
+    STOCK_ITEM_WAREHOUSE,
+    STOCK_MOVEMENT_STOCK_ITEM,
+    USAGE_ACTIVITY,
+];
+
+/// The parents the foreign keys point to, and the links mirroring them
+pub struct ForeignKeys {
+    stores: Arc<dyn StoreStore>,
+    activities: Arc<dyn ActivityStore>,
+    warehouses: Arc<dyn WarehouseStore>,
+    stock_items: Arc<dyn StockItemStore>,
+    link_service: RwLock<Option<Arc<dyn LinkService>>>,
+}
+
+impl ForeignKeys {
+    pub fn new(
+        stores: Arc<dyn StoreStore>,
+        activities: Arc<dyn ActivityStore>,
+        warehouses: Arc<dyn WarehouseStore>,
+        stock_items: Arc<dyn StockItemStore>,
+    ) -> Self {
+        Self {
+            stores,
+            activities,
+            warehouses,
+            stock_items,
+            link_service: RwLock::new(None),
+        }
+    }
+
+    /// Keep the links in step through `link_service` from now on
+    pub fn attach(&self, link_service: Arc<dyn LinkService>) {
+        if let Ok(mut slot) = self.link_service.write() {
+            *slot = Some(link_service);
+        }
+    }
+
+    fn link_service(&self) -> Option<Arc<dyn LinkService>> {
+        self.link_service.read().ok()?.clone()
+    }
+
+    /// Whether the parent `key` names with `parent_id` exists
+    pub async fn parent_exists(&self, key: &ForeignKey, parent_id: &Uuid) -> anyhow::Result<bool> {
+        let found = match key.parent_type {
+            "store" => match self.stores.get(parent_id).await {
+                Err(StoreStoreError::NotFound(_)) => false,
+                found => found.map(|_| true)?,
+            },
+            "activity" => match self.activities.get(parent_id).await {
+                Err(ActivityStoreError::NotFound(_)) => false,
+                found => found.map(|_| true)?,
+            },
+            "warehouse" => match self.warehouses.get(parent_id).await {
+                Err(WarehouseStoreError::NotFound(_)) => false,
+                found => found.map(|_| true)?,
+            },
+            "stock_item" => match self.stock_items.get(parent_id).await {
+                Err(StockItemStoreError::NotFound(_)) => false,
+                found => found.map(|_| true)?,
+            },
+            other => anyhow::bail!("no store for parent type '{}'", other),
+        };
+        Ok(found)
+    }
+
+    /// Make `parent_id` the only `key` link to `entity_id`
+    ///
+    /// Does nothing until a link service is attached. A failure is logged and
+    /// left for [`ForeignKeys::check`] to find.
+    async fn link(&self, key: &ForeignKey, parent_id: Uuid, entity_id: Uuid) {
+        let Some(link_service) = self.link_service() else {
+            return;
+        };
+        if let Err(e) = relink(link_service.as_ref(), key, parent_id, entity_id).await {
+            eprintln!(
+                "Failed to link {} {} to its {} {}: {:?}",
+                key.entity_type, entity_id, key.parent_type, parent_id, e
+            );
+        }
+    }
+
+    /// Drop the `key` links to the deleted `entity_id`
+    async fn unlink(&self, key: &ForeignKey, entity_id: Uuid) {
+        let Some(link_service) = self.link_service() else {
+            return;
+        };
+        if let Err(e) = drop_links(link_service.as_ref(), key, entity_id, None).await {
+            eprintln!(
+                "Failed to drop the '{}' links of {} {}: {:?}",
+                key.link_type, key.entity_type, entity_id, e
+            );
+        }
+    }
+}
+
+/// Drop the `key` links to `entity_id` but one from `parent_id`, then create
+/// that one if it was missing
+async fn relink(
+    link_service: &dyn LinkService,
+    key: &ForeignKey,
+    parent_id: Uuid,
+    entity_id: Uuid,
+) -> anyhow::Result<()> {
+    if !drop_links(link_service, key, entity_id, Some(parent_id)).await? {
+        link_service
+            .create(LinkEntity::new(key.link_type, parent_id, entity_id, None))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Drop the `key` links to `entity_id` but one from `keep`, telling whether
+/// one was kept
+async fn drop_links(
+    link_service: &dyn LinkService,
+    key: &ForeignKey,
+    entity_id: Uuid,
+    keep: Option<Uuid>,
+) -> anyhow::Result<bool> {
+    let links = link_service
+        .find_by_target(&entity_id, Some(key.link_type), Some(key.parent_type))
+        .await?;
+    let mut kept = false;
+    for link in links {
+        if !kept && Some(link.source_id) == keep {
+            kept = true;
+        } else {
+            link_service.delete(&link.id).await?;
+        }
+    }
+    Ok(kept)
+}
@@ -0,0 +1,334 @@
+//! Stores keeping a foreign-key field and its link in step
+
+use async_trait::async_trait;
+use common::store::EntityStore;
+use common::{ListQuery, Page, Version};
+use std::sync::Arc;
+use this::prelude::*;
+
+use super::{
+    ForeignKey, ForeignKeys, STOCK_ITEM_WAREHOUSE, STOCK_MOVEMENT_STOCK_ITEM, USAGE_ACTIVITY,
+    WAREHOUSE_STORE,
+};
+use crate::entities::stock_item::{StockItem, StockItemStore, StockItemStoreError};
+use crate::entities::stock_movement::{StockMovement, StockMovementStore, StockMovementStoreError};
+use crate::entities::usage::{Usage, UsageStore, UsageStoreError};
+use crate::entities::warehouse::{Warehouse, WarehouseStore, WarehouseStoreError};
+
+/// Check the parent of a new or moved entity, then save it with `save` and
+/// link it to its parent
+///
+/// `current` is the parent before the change, `None` on create; the parent
+/// is neither checked nor relinked when it did not change.
+async fn save_linked<T, E, F>(
+    keys: &ForeignKeys,
+    key: &ForeignKey,
+    current: Option<Uuid>,
+    (parent_id, entity_id): (Uuid, Uuid),
+    invalid: impl FnOnce(String) -> E,
+    save: F,
+) -> Result<T, E>
+where
+    E: From<anyhow::Error>,
+    F: Future<Output = Result<T, E>>,
+{
+    if current == Some(parent_id) {
+        return save.await;
+    }
+    if !keys.parent_exists(key, &parent_id).await? {
+        return Err(invalid(key.missing_parent(&parent_id)));
+    }
+    let saved = save.await?;
+    keys.link(key, parent_id, entity_id).await;
+    Ok(saved)
+}
+
+/// Warehouse store keeping `store_id` and the `has_warehouse` link in step
+pub struct LinkedWarehouseStore {
+    inner: Arc<dyn WarehouseStore>,
+    keys: Arc<ForeignKeys>,
+}
+
+impl LinkedWarehouseStore {
+    pub fn new(inner: Arc<dyn WarehouseStore>, keys: Arc<ForeignKeys>) -> Self {
+        Self { inner, keys }
+    }
+}
+
+common::impl_entity_json!(LinkedWarehouseStore, Warehouse);
+
+#[async_trait]
+impl EntityStore<Warehouse> for LinkedWarehouseStore {
+    async fn create(&self, warehouse: Warehouse) -> Result<Warehouse, WarehouseStoreError> {
+        save_linked(
+            &self.keys,
+            &WAREHOUSE_STORE,
+            None,
+            (warehouse.store_id, warehouse.id),
+            WarehouseStoreError::Validation,
+            self.inner.create(warehouse.clone()),
+        )
+        .await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Warehouse, WarehouseStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, warehouse: Warehouse) -> Result<Warehouse, WarehouseStoreError> {
+        let current = self.inner.get(&warehouse.id).await?;
+        save_linked(
+            &self.keys,
+            &WAREHOUSE_STORE,
+            Some(current.store_id),
+            (warehouse.store_id, warehouse.id),
+            WarehouseStoreError::Validation,
+            self.inner.update(warehouse.clone()),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), WarehouseStoreError> {
+        self.inner.delete(id).await?;
+        self.keys.unlink(&WAREHOUSE_STORE, *id).await;
+        Ok(())
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), WarehouseStoreError> {
+        self.inner.delete_at(id, expected).await?;
+        self.keys.unlink(&WAREHOUSE_STORE, *id).await;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<Warehouse>, WarehouseStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<Warehouse>, WarehouseStoreError> {
+        self.inner.query(query).await
+    }
+}
+
+/// Stock item store keeping `warehouse_id` and the `contains` link in step
+pub struct LinkedStockItemStore {
+    inner: Arc<dyn StockItemStore>,
+    keys: Arc<ForeignKeys>,
+}
+
+impl LinkedStockItemStore {
+    pub fn new(inner: Arc<dyn StockItemStore>, keys: Arc<ForeignKeys>) -> Self {
+        Self { inner, keys }
+    }
+}
+
+common::impl_entity_json!(LinkedStockItemStore, StockItem);
+
+#[async_trait]
+impl EntityStore<StockItem> for LinkedStockItemStore {
+    async fn create(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
+        save_linked(
+            &self.keys,
+            &STOCK_ITEM_WAREHOUSE,
+            None,
+            (stock_item.warehouse_id, stock_item.id),
+            StockItemStoreError::Validation,
+            self.inner.create(stock_item.clone()),
+        )
+        .await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<StockItem, StockItemStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, stock_item: StockItem) -> Result<StockItem, StockItemStoreError> {
+        let current = self.inner.get(&stock_item.id).await?;
+        save_linked(
+            &self.keys,
+            &STOCK_ITEM_WAREHOUSE,
+            Some(current.warehouse_id),
+            (stock_item.warehouse_id, stock_item.id),
+            StockItemStoreError::Validation,
+            self.inner.update(stock_item.clone()),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), StockItemStoreError> {
+        self.inner.delete(id).await?;
+        self.keys.unlink(&STOCK_ITEM_WAREHOUSE, *id).await;
+        Ok(())
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), StockItemStoreError> {
+        self.inner.delete_at(id, expected).await?;
+        self.keys.unlink(&STOCK_ITEM_WAREHOUSE, *id).await;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<StockItem>, StockItemStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<StockItem>, StockItemStoreError> {
+        self.inner.query(query).await
+    }
+}
+
+#[async_trait]
+impl StockItemStore for LinkedStockItemStore {
+    async fn adjust(
+        &self,
+        id: &Uuid,
+        quantity_delta: i32,
+        reserved_delta: i32,
+    ) -> Result<StockItem, StockItemStoreError> {
+        self.inner.adjust(id, quantity_delta, reserved_delta).await
+    }
+
+    fn written(&self, before: &StockItem, after: &StockItem) {
+        self.inner.written(before, after)
+    }
+}
+
+/// Stock movement store keeping `stock_item_id` and the `has_movement` link
+/// in step
+pub struct LinkedStockMovementStore {
+    inner: Arc<dyn StockMovementStore>,
+    keys: Arc<ForeignKeys>,
+}
+
+impl LinkedStockMovementStore {
+    pub fn new(inner: Arc<dyn StockMovementStore>, keys: Arc<ForeignKeys>) -> Self {
+        Self { inner, keys }
+    }
+}
+
+common::impl_entity_json!(LinkedStockMovementStore, StockMovement);
+
+#[async_trait]
+impl EntityStore<StockMovement> for LinkedStockMovementStore {
+    async fn create(
+        &self,
+        stock_movement: StockMovement,
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        save_linked(
+            &self.keys,
+            &STOCK_MOVEMENT_STOCK_ITEM,
+            None,
+            (stock_movement.stock_item_id, stock_movement.id),
+            StockMovementStoreError::Validation,
+            self.inner.create(stock_movement.clone()),
+        )
+        .await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<StockMovement, StockMovementStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(
+        &self,
+        stock_movement: StockMovement,
+    ) -> Result<StockMovement, StockMovementStoreError> {
+        let current = self.inner.get(&stock_movement.id).await?;
+        save_linked(
+            &self.keys,
+            &STOCK_MOVEMENT_STOCK_ITEM,
+            Some(current.stock_item_id),
+            (stock_movement.stock_item_id, stock_movement.id),
+            StockMovementStoreError::Validation,
+            self.inner.update(stock_movement.clone()),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), StockMovementStoreError> {
+        self.inner.delete(id).await?;
+        self.keys.unlink(&STOCK_MOVEMENT_STOCK_ITEM, *id).await;
+        Ok(())
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), StockMovementStoreError> {
+        self.inner.delete_at(id, expected).await?;
+        self.keys.unlink(&STOCK_MOVEMENT_STOCK_ITEM, *id).await;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<StockMovement>, StockMovementStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(
+        &self,
+        query: &ListQuery,
+    ) -> Result<Page<StockMovement>, StockMovementStoreError> {
+        self.inner.query(query).await
+    }
+}
+
+/// Usage store keeping `activity_id` and the `has_usage` link in step
+pub struct LinkedUsageStore {
+    inner: Arc<dyn UsageStore>,
+    keys: Arc<ForeignKeys>,
+}
+
+impl LinkedUsageStore {
+    pub fn new(inner: Arc<dyn UsageStore>, keys: Arc<ForeignKeys>) -> Self {
+        Self { inner, keys }
+    }
+}
+
+common::impl_entity_json!(LinkedUsageStore, Usage);
+
+#[async_trait]
+impl EntityStore<Usage> for LinkedUsageStore {
+    async fn create(&self, usage: Usage) -> Result<Usage, UsageStoreError> {
+        save_linked(
+            &self.keys,
+            &USAGE_ACTIVITY,
+            None,
+            (usage.activity_id, usage.id),
+            UsageStoreError::Validation,
+            self.inner.create(usage.clone()),
+        )
+        .await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Usage, UsageStoreError> {
+        self.inner.get(id).await
+    }
+
+    async fn update(&self, usage: Usage) -> Result<Usage, UsageStoreError> {
+        let current = self.inner.get(&usage.id).await?;
+        save_linked(
+            &self.keys,
+            &USAGE_ACTIVITY,
+            Some(current.activity_id),
+            (usage.activity_id, usage.id),
+            UsageStoreError::Validation,
+            self.inner.update(usage.clone()),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<(), UsageStoreError> {
+        self.inner.delete(id).await?;
+        self.keys.unlink(&USAGE_ACTIVITY, *id).await;
+        Ok(())
+    }
+
+    async fn delete_at(&self, id: &Uuid, expected: Version) -> Result<(), UsageStoreError> {
+        self.inner.delete_at(id, expected).await?;
+        self.keys.unlink(&USAGE_ACTIVITY, *id).await;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<Usage>, UsageStoreError> {
+        self.inner.list().await
+    }
+
+    async fn query(&self, query: &ListQuery) -> Result<Page<Usage>, UsageStoreError> {
+        self.inner.query(query).await
+    }
+}
@@ -1,5 +1,7 @@
 pub mod entities;
+pub mod foreign_keys;
 pub mod module;
+pub mod services;
 pub mod stores;
 
 // Re-export for convenience
@@ -1,21 +1,31 @@
+use billing::BillingStores;
+use common::links::LinkRules;
 use std::sync::Arc;
+use this::core::events::EventBus;
 use this::core::module::Module;
 use this::prelude::LinksConfig;
-use this::prelude::{EntityCreator, EntityFetcher};
+use this::prelude::{EntityCreator, EntityFetcher, LinkService};
 use this::server::entity_registry::EntityRegistry;
 
 use crate::entities::activity::ActivityStore;
 use crate::entities::activity::descriptor::ActivityDescriptor;
-use crate::entities::stock_item::StockItemStore;
+use crate::entities::reservation::ReservationStore;
+use crate::entities::reservation::descriptor::ReservationDescriptor;
 use crate::entities::stock_item::descriptor::StockItemDescriptor;
-use crate::entities::stock_movement::StockMovementStore;
+use crate::entities::stock_item::{LowStockDetector, StockItemStore};
 use crate::entities::stock_movement::descriptor::StockMovementDescriptor;
+use crate::entities::stock_movement::{StockLedger, StockMovementStore};
 use crate::entities::store::StoreStore;
 use crate::entities::store::descriptor::StoreDescriptor;
 use crate::entities::usage::UsageStore;
 use crate::entities::usage::descriptor::UsageDescriptor;
 use crate::entities::warehouse::WarehouseStore;
 use crate::entities::warehouse::descriptor::WarehouseDescriptor;
+use crate::foreign_keys::{ConsistencyReport, ForeignKeys};
+use crate::services::{Refacturation, RefacturationConfig, StockTransfers};
+
+/// The `links.yaml` of the module
+const LINKS_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
 
 // Combined trait for entity stores
 pub trait EntityStore: EntityFetcher + EntityCreator + Send + Sync {}
@@ -23,6 +33,7 @@ pub trait EntityStore: EntityFetcher + EntityCreator + Send + Sync {}
 // Blanket implementation for any type that implements both traits
 impl<T> EntityStore for T where T: EntityFetcher + EntityCreator + Send + Sync {}
 
+#[derive(Clone)]
 pub struct InventoryStores {
     pub stores_store: Arc<dyn StoreStore>,
     pub stores_entity: Arc<dyn EntityStore>,
@@ -36,15 +47,94 @@ pub struct InventoryStores {
     pub stock_movements_entity: Arc<dyn EntityStore>,
     pub usages_store: Arc<dyn UsageStore>,
     pub usages_entity: Arc<dyn EntityStore>,
+    pub reservations_store: Arc<dyn ReservationStore>,
+    pub reservations_entity: Arc<dyn EntityStore>,
+    /// The ledger under the stock movement store, for writes of several
+    /// movements at once
+    pub stock_ledger: Arc<StockLedger>,
+    /// The stock item store, as the detector publishing low-stock alerts
+    pub low_stock: Arc<LowStockDetector>,
+    /// The foreign keys the warehouse, stock item, stock movement and usage
+    /// stores keep in step with their links
+    pub foreign_keys: Arc<ForeignKeys>,
 }
 
 pub struct InventoryModule {
     pub stores: InventoryStores,
+    pub transfers: Option<Arc<StockTransfers>>,
+    pub refacturation: Option<Arc<Refacturation>>,
 }
 
 impl InventoryModule {
     pub fn new(stores: InventoryStores) -> Self {
-        Self { stores }
+        Self {
+            stores,
+            transfers: None,
+            refacturation: None,
+        }
+    }
+
+    /// Enable `POST /stock_items/{id}/transfer`
+    ///
+    /// `link_service` is the one the host is built with; transfers maintain
+    /// the `contains` and `has_movement` links through it.
+    pub fn with_stock_transfers(mut self, link_service: Arc<dyn LinkService>) -> Self {
+        self.transfers = Some(Arc::new(StockTransfers::new(&self.stores, link_service)));
+        self
+    }
+
+    /// Enable `POST /activities/{id}/refacturation`
+    ///
+    /// Invoices are written to the stores of the billing module; `link_service`
+    /// is the one the host is built with.
+    pub fn with_refacturation(
+        mut self,
+        billing: &BillingStores,
+        link_service: Arc<dyn LinkService>,
+        config: RefacturationConfig,
+    ) -> Self {
+        self.refacturation = Some(Arc::new(Refacturation::new(
+            &self.stores,
+            billing,
+            link_service,
+            config,
+        )));
+        self
+    }
+
+    /// Keep the links mirroring foreign-key fields in step, see
+    /// `crate::foreign_keys`
+    ///
+    /// `link_service` is the one the host is built with.
+    pub fn with_foreign_keys(self, link_service: Arc<dyn LinkService>) -> Self {
+        self.stores.foreign_keys.attach(link_service);
+        self
+    }
+
+    /// Report the entities whose links disagree with their foreign keys, and
+    /// with `repair` fix the links
+    ///
+    /// Needs [`InventoryModule::with_foreign_keys`].
+    pub async fn check_foreign_keys(
+        &self,
+        repair: bool,
+    ) -> Result<ConsistencyReport, anyhow::Error> {
+        self.stores.foreign_keys.check(&self.stores, repair).await
+    }
+
+    /// Publish low-stock alerts on `event_bus`
+    ///
+    /// Pass the bus the host is built with, so that the alerts reach its
+    /// event subscribers such as the WebSocket exposure.
+    pub fn with_event_bus(self, event_bus: EventBus) -> Self {
+        self.stores.low_stock.attach(event_bus);
+        self
+    }
+
+    /// The `cardinality` and `on_delete` rules of the module's links, see
+    /// `common::links`
+    pub fn link_rules(&self) -> Result<LinkRules, anyhow::Error> {
+        LinkRules::from_yaml_file(LINKS_CONFIG)
     }
 }
 
@@ -65,13 +155,13 @@ impl Module for InventoryModule {
             "stock_item",
             "stock_movement",
             "usage",
+            "reservation",
         ]
     }
 
     fn links_config(&self) -> Result<LinksConfig, anyhow::Error> {
         // Load configuration from YAML file
-        let config_path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/links.yaml");
-        LinksConfig::from_yaml_file(config_path)
+        LinksConfig::from_yaml_file(LINKS_CONFIG)
     }
 
     fn get_entity_fetcher(&self, entity_type: &str) -> Option<Arc<dyn EntityFetcher>> {
@@ -82,6 +172,7 @@ impl Module for InventoryModule {
             "stock_item" => Some(self.stores.stock_items_entity.clone()),
             "stock_movement" => Some(self.stores.stock_movements_entity.clone()),
             "usage" => Some(self.stores.usages_entity.clone()),
+            "reservation" => Some(self.stores.reservations_entity.clone()),
             _ => None,
         }
     }
@@ -94,6 +185,7 @@ impl Module for InventoryModule {
             "stock_item" => Some(self.stores.stock_items_entity.clone()),
             "stock_movement" => Some(self.stores.stock_movements_entity.clone()),
             "usage" => Some(self.stores.usages_entity.clone()),
+            "reservation" => Some(self.stores.reservations_entity.clone()),
             _ => None,
         }
     }
@@ -103,18 +195,27 @@ impl Module for InventoryModule {
             self.stores.stores_store.clone(),
             self.stores.stores_entity.clone(),
         )));
-        registry.register(Box::new(ActivityDescriptor::new_with_creator(
-            self.stores.activities_store.clone(),
-            self.stores.activities_entity.clone(),
-        )));
-        registry.register(Box::new(WarehouseDescriptor::new_with_creator(
-            self.stores.warehouses_store.clone(),
-            self.stores.warehouses_entity.clone(),
-        )));
-        registry.register(Box::new(StockItemDescriptor::new_with_creator(
-            self.stores.stock_items_store.clone(),
-            self.stores.stock_items_entity.clone(),
-        )));
+        registry.register(Box::new(
+            ActivityDescriptor::new_with_creator(
+                self.stores.activities_store.clone(),
+                self.stores.activities_entity.clone(),
+            )
+            .with_refacturation(self.refacturation.clone()),
+        ));
+        registry.register(Box::new(
+            WarehouseDescriptor::new_with_creator(
+                self.stores.warehouses_store.clone(),
+                self.stores.warehouses_entity.clone(),
+            )
+            .with_stock_items(self.stores.stock_items_store.clone()),
+        ));
+        registry.register(Box::new(
+            StockItemDescriptor::new_with_creator(
+                self.stores.stock_items_store.clone(),
+                self.stores.stock_items_entity.clone(),
+            )
+            .with_transfers(self.transfers.clone()),
+        ));
         registry.register(Box::new(StockMovementDescriptor::new_with_creator(
             self.stores.stock_movements_store.clone(),
             self.stores.stock_movements_entity.clone(),
@@ -123,5 +224,9 @@ impl Module for InventoryModule {
             self.stores.usages_store.clone(),
             self.stores.usages_entity.clone(),
         )));
+        registry.register(Box::new(ReservationDescriptor::new_with_creator(
+            self.stores.reservations_store.clone(),
+            self.stores.reservations_entity.clone(),
+        )));
     }
 }
@@ -0,0 +1,10 @@
+pub mod refacturation;
+pub mod reservations;
+pub mod transfer;
+
+pub use refacturation::{
+    RateTable, Refacturation, RefacturationConfig, RefacturationError, RefacturationRequest,
+    RefacturedInvoice, UsageRate,
+};
+pub use reservations::ReservationSweeper;
+pub use transfer::{StockTransfer, StockTransferError, StockTransfers, TransferRequest};
@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use billing::BillingStores;
+use billing::entities::invoice::{Invoice, InvoiceStore};
+use billing::entities::invoice_line::{InvoiceLine, InvoiceLineStore};
+use chrono::{Duration, NaiveDate, Utc};
+use common::{ErrorKind, HttpError, Money, MoneyError, Version};
+use serde::{Deserialize, Serialize};
+use this::prelude::{LinkEntity, LinkService, Uuid};
+use tokio::sync::Mutex;
+
+use crate::entities::activity::{Activity, ActivityStore, ActivityStoreError};
+use crate::entities::usage::{Usage, UsageStore};
+use crate::module::InventoryStores;
+
+/// Price of one unit of a usage type
+#[derive(Debug, Clone)]
+pub struct UsageRate {
+    pub usage_type: String,
+    /// Unit the price applies to; `None` matches usages of any unit
+    pub unit: Option<String>,
+    pub unit_price: Money,
+}
+
+/// Prices for refacturated usages, by `usage_type` and `unit`
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: Vec<UsageRate>,
+}
+
+impl RateTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the rate of `usage_type` in `unit`
+    pub fn with_rate(mut self, usage_type: &str, unit: Option<&str>, unit_price: Money) -> Self {
+        let unit = unit.map(String::from);
+        self.rates
+            .retain(|rate| rate.usage_type != usage_type || rate.unit != unit);
+        self.rates.push(UsageRate {
+            usage_type: usage_type.to_string(),
+            unit,
+            unit_price,
+        });
+        self
+    }
+
+    /// Unit price of `usage_type` in `unit`, falling back to the rate of the
+    /// usage type without a unit
+    pub fn price(&self, usage_type: &str, unit: Option<&str>) -> Option<Money> {
+        let find = |unit: Option<&str>| {
+            self.rates
+                .iter()
+                .find(|rate| rate.usage_type == usage_type && rate.unit.as_deref() == unit)
+                .map(|rate| rate.unit_price)
+        };
+        find(unit).or_else(|| unit.and_then(|_| find(None)))
+    }
+}
+
+/// Settings for refacturation invoices
+#[derive(Debug, Clone)]
+pub struct RefacturationConfig {
+    pub rates: RateTable,
+    /// Prefix of the invoice number sequence (`REF` gives `REF-000001`, ...)
+    pub number_prefix: String,
+    /// Days between invoice creation and its `due_date`
+    pub payment_term_days: i64,
+}
+
+impl RefacturationConfig {
+    pub fn new(rates: RateTable) -> Self {
+        Self {
+            rates,
+            number_prefix: "REF".to_string(),
+            payment_term_days: 30,
+        }
+    }
+}
+
+/// Body of `POST /activities/{id}/refacturation`
+///
+/// Both bounds are inclusive `YYYY-MM-DD` dates. Without bounds every recorded
+/// usage is billed; with a bound, usages without a `date` are left out.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RefacturationRequest {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl RefacturationRequest {
+    fn covers(&self, usage: &Usage) -> bool {
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Some(date) = usage
+            .date
+            .as_deref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        else {
+            return false;
+        };
+        self.from.is_none_or(|from| from <= date) && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+/// An invoice created from usages, with its lines and the usages it billed
+#[derive(Debug, Clone, Serialize)]
+pub struct RefacturedInvoice {
+    pub invoice: Invoice,
+    pub lines: Vec<InvoiceLine>,
+    /// The billed usages, now `billed`
+    pub usages: Vec<Usage>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefacturationError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("validation: {0}")]
+    Validation(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for RefacturationError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            RefacturationError::NotFound(_) => ErrorKind::NotFound,
+            RefacturationError::Validation(_) => ErrorKind::Validation,
+            RefacturationError::Other(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<MoneyError> for RefacturationError {
+    fn from(e: MoneyError) -> Self {
+        RefacturationError::Validation(e.to_string())
+    }
+}
+
+/// A write made while invoicing, undone if a later step fails
+enum Undo {
+    Invoice(Uuid),
+    InvoiceLine(Uuid),
+    Link(Uuid),
+    /// The usage as it was before being marked `billed`
+    Usage(Box<Usage>),
+}
+
+/// Bills activities for the usages they caused
+///
+/// A usage recorded by one activity with a `from_activity_id` is a cost of
+/// that other activity. [`invoice_activity`](Self::invoice_activity) collects
+/// the `recorded` usages from an activity over a period, prices them with the
+/// [`RateTable`], and creates a `billing` invoice with one line per
+/// `usage_type`/`unit`. The invoice is linked to the activity with
+/// `activity_invoice`, to its lines with `invoice_line` and to each usage with
+/// `invoiced_in`, and the usages become `billed` so they are never billed
+/// twice. Every step is undone in reverse order if a later one fails.
+pub struct Refacturation {
+    activities: Arc<dyn ActivityStore>,
+    usages: Arc<dyn UsageStore>,
+    invoices: Arc<dyn InvoiceStore>,
+    invoice_lines: Arc<dyn InvoiceLineStore>,
+    link_service: Arc<dyn LinkService>,
+    config: RefacturationConfig,
+    lock: Mutex<()>,
+}
+
+impl Refacturation {
+    pub fn new(
+        stores: &InventoryStores,
+        billing: &BillingStores,
+        link_service: Arc<dyn LinkService>,
+        config: RefacturationConfig,
+    ) -> Self {
+        Self {
+            activities: stores.activities_store.clone(),
+            usages: stores.usages_store.clone(),
+            invoices: billing.invoices_store.clone(),
+            invoice_lines: billing.invoice_lines_store.clone(),
+            link_service,
+            config,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Invoice `activity_id` for its recorded usages in the requested period
+    ///
+    /// Returns `None` when there is nothing to bill. Fails without writing
+    /// anything when a usage type has no rate.
+    pub async fn invoice_activity(
+        &self,
+        activity_id: &Uuid,
+        request: RefacturationRequest,
+    ) -> Result<Option<RefacturedInvoice>, RefacturationError> {
+        let _guard = self.lock.lock().await;
+
+        let activity = match self.activities.get(activity_id).await {
+            Ok(activity) => activity,
+            Err(ActivityStoreError::NotFound(_)) => {
+                return Err(RefacturationError::NotFound(format!(
+                    "activity {}",
+                    activity_id
+                )));
+            }
+            Err(e) => return Err(anyhow::Error::new(e).into()),
+        };
+        if let (Some(from), Some(to)) = (request.from, request.to)
+            && from > to
+        {
+            return Err(RefacturationError::Validation(format!(
+                "period starts on {} after it ends on {}",
+                from, to
+            )));
+        }
+
+        let usages: Vec<Usage> = self
+            .usages
+            .list()
+            .await
+            .map_err(anyhow::Error::new)?
+            .into_iter()
+            .filter(|usage| {
+                usage.from_activity_id == Some(activity.id)
+                    && usage.status == "recorded"
+                    && request.covers(usage)
+            })
+            .collect();
+        if usages.is_empty() {
+            return Ok(None);
+        }
+
+        let (invoice, lines) = self.price(&activity, &request, &usages).await?;
+        let mut undo = Vec::new();
+        match self.run(&activity, invoice, lines, usages, &mut undo).await {
+            Ok(refactured) => Ok(Some(refactured)),
+            Err(e) => {
+                self.rollback(undo).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// The invoice and its lines, one per usage type and unit
+    async fn price(
+        &self,
+        activity: &Activity,
+        request: &RefacturationRequest,
+        usages: &[Usage],
+    ) -> Result<(Invoice, Vec<InvoiceLine>), RefacturationError> {
+        let mut groups: BTreeMap<(&str, Option<&str>), (f64, usize)> = BTreeMap::new();
+        for usage in usages {
+            let group = groups
+                .entry((usage.usage_type.as_str(), usage.unit.as_deref()))
+                .or_default();
+            group.0 += usage.quantity;
+            group.1 += 1;
+        }
+
+        let mut lines = Vec::with_capacity(groups.len());
+        for ((usage_type, unit), (quantity, count)) in groups {
+            let unit_price = self.config.rates.price(usage_type, unit).ok_or_else(|| {
+                RefacturationError::Validation(format!(
+                    "no rate for usage type '{}' in {}",
+                    usage_type,
+                    unit.unwrap_or("any unit")
+                ))
+            })?;
+            lines.push(InvoiceLine::priced(
+                format!(
+                    "{} ({} usage{})",
+                    usage_type,
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ),
+                usage_type.to_string(),
+                quantity,
+                unit.map(String::from),
+                unit_price,
+            )?);
+        }
+
+        let currency = lines[0].line_total.currency();
+        let amount = Money::sum(currency, lines.iter().map(|line| line.line_total))?;
+        let period = match (request.from, request.to) {
+            (Some(from), Some(to)) => format!(" from {} to {}", from, to),
+            (Some(from), None) => format!(" from {}", from),
+            (None, Some(to)) => format!(" until {}", to),
+            (None, None) => String::new(),
+        };
+        let due_date = (Utc::now() + Duration::days(self.config.payment_term_days))
+            .format("%Y-%m-%d")
+            .to_string();
+        let invoice = Invoice::new(
+            format!("Refacturation for {}{}", activity.name, period),
+            "sent".to_string(),
+            self.next_number().await?,
+            amount,
+            Some(due_date),
+            None,
+            activity.owned_by,
+            Version::default(),
+        );
+        Ok((invoice, lines))
+    }
+
+    async fn run(
+        &self,
+        activity: &Activity,
+        invoice: Invoice,
+        lines: Vec<InvoiceLine>,
+        usages: Vec<Usage>,
+        undo: &mut Vec<Undo>,
+    ) -> Result<RefacturedInvoice, RefacturationError> {
+        let invoice = self
+            .invoices
+            .create(invoice)
+            .await
+            .map_err(anyhow::Error::new)?;
+        undo.push(Undo::Invoice(invoice.id));
+        self.link("activity_invoice", activity.id, invoice.id, undo)
+            .await?;
+
+        let mut created_lines = Vec::with_capacity(lines.len());
+        for line in lines {
+            let line = self
+                .invoice_lines
+                .create(line)
+                .await
+                .map_err(anyhow::Error::new)?;
+            undo.push(Undo::InvoiceLine(line.id));
+            self.link("invoice_line", invoice.id, line.id, undo).await?;
+            created_lines.push(line);
+        }
+
+        let mut billed = Vec::with_capacity(usages.len());
+        for usage in usages {
+            self.link("invoiced_in", usage.id, invoice.id, undo).await?;
+            let mut next = usage.clone();
+            next.set_status("billed".to_string());
+            let next = self.usages.update(next).await.map_err(anyhow::Error::new)?;
+            undo.push(Undo::Usage(Box::new(Usage {
+                version: next.version,
+                ..usage
+            })));
+            billed.push(next);
+        }
+
+        Ok(RefacturedInvoice {
+            invoice,
+            lines: created_lines,
+            usages: billed,
+        })
+    }
+
+    async fn link(
+        &self,
+        link_type: &str,
+        source_id: Uuid,
+        target_id: Uuid,
+        undo: &mut Vec<Undo>,
+    ) -> Result<(), RefacturationError> {
+        let link = self
+            .link_service
+            .create(LinkEntity::new(
+                link_type,
+                source_id,
+                target_id,
+                Some(serde_json::json!({ "created_by": "inventory.refacturation" })),
+            ))
+            .await?;
+        undo.push(Undo::Link(link.id));
+        Ok(())
+    }
+
+    /// Next number in the `{prefix}-NNNNNN` sequence
+    async fn next_number(&self) -> Result<String, RefacturationError> {
+        let prefix = format!("{}-", self.config.number_prefix);
+        let last = self
+            .invoices
+            .list()
+            .await
+            .map_err(anyhow::Error::new)?
+            .iter()
+            .filter_map(|invoice| invoice.number.strip_prefix(&prefix)?.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        Ok(format!("{}{:06}", prefix, last + 1))
+    }
+
+    async fn rollback(&self, undo: Vec<Undo>) {
+        for step in undo.into_iter().rev() {
+            let result = match step {
+                Undo::Invoice(id) => self.invoices.delete(&id).await.map_err(Into::into),
+                Undo::InvoiceLine(id) => self.invoice_lines.delete(&id).await.map_err(Into::into),
+                Undo::Link(id) => self.link_service.delete(&id).await,
+                Undo::Usage(usage) => self
+                    .usages
+                    .update(*usage)
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to roll back refacturation step: {:?}", e);
+            }
+        }
+    }
+}
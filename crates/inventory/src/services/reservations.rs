@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use this::prelude::{DateTime, Utc};
+use tokio::task::JoinHandle;
+
+use crate::entities::reservation::{Reservation, ReservationStatus, ReservationStore};
+use crate::module::InventoryStores;
+
+/// Expires reservations whose TTL has passed
+///
+/// Expiring goes through the reservation store, so with the
+/// [`ReservationLedger`](crate::entities::reservation::ReservationLedger) the
+/// held units become available again.
+pub struct ReservationSweeper {
+    reservations: Arc<dyn ReservationStore>,
+}
+
+impl ReservationSweeper {
+    pub fn new(stores: &InventoryStores) -> Self {
+        Self {
+            reservations: stores.reservations_store.clone(),
+        }
+    }
+
+    /// Expire every `active` reservation due at `now`
+    ///
+    /// Returns the expired reservations; one failing does not stop the others.
+    pub async fn sweep(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<Reservation>> {
+        let mut expired = Vec::new();
+        for reservation in self.reservations.list().await? {
+            if reservation.status != ReservationStatus::Active.as_str()
+                || reservation.expires_at > now
+            {
+                continue;
+            }
+            match self
+                .reservations
+                .transition(&reservation.id, ReservationStatus::Expired)
+                .await
+            {
+                Ok(reservation) => expired.push(reservation),
+                Err(e) => eprintln!("Failed to expire reservation {}: {:?}", reservation.id, e),
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Sweep every `period` until the runtime shuts down
+    pub fn spawn(self: Arc<Self>, period: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            loop {
+                ticks.tick().await;
+                match self.sweep(Utc::now()).await {
+                    Ok(expired) if !expired.is_empty() => {
+                        eprintln!("Expired {} stock reservations", expired.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Failed to sweep stock reservations: {:?}", e),
+                }
+            }
+        })
+    }
+}
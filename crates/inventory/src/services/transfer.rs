@@ -0,0 +1,346 @@
+use std::sync::Arc;
+
+use common::{ErrorKind, HttpError, Version};
+use serde::{Deserialize, Serialize};
+use this::prelude::{LinkEntity, LinkService, Uuid};
+use tokio::sync::Mutex;
+
+use crate::entities::stock_item::{StockItem, StockItemStore, StockItemStoreError};
+use crate::entities::stock_movement::{
+    MovementStatus, StockLedger, StockMovement, StockMovementStore, StockMovementStoreError,
+};
+use crate::entities::warehouse::{WarehouseStore, WarehouseStoreError};
+use crate::module::InventoryStores;
+
+/// Body of `POST /stock_items/{id}/transfer`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransferRequest {
+    /// Destination warehouse
+    pub warehouse_id: Uuid,
+    pub quantity: i32,
+    pub reason: Option<String>,
+}
+
+/// Result of a completed transfer
+#[derive(Debug, Clone, Serialize)]
+pub struct StockTransfer {
+    pub source: StockItem,
+    pub destination: StockItem,
+    /// `out` movement on the source item
+    pub outgoing: StockMovement,
+    /// `in` movement on the destination item
+    pub incoming: StockMovement,
+    /// Whether the destination item was created by this transfer
+    pub created_destination: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StockTransferError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("validation: {0}")]
+    Validation(String),
+    #[error(transparent)]
+    Movement(#[from] StockMovementStoreError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl HttpError for StockTransferError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StockTransferError::NotFound(_) => ErrorKind::NotFound,
+            StockTransferError::Validation(_) => ErrorKind::Validation,
+            StockTransferError::Movement(e) => e.kind(),
+            StockTransferError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn extensions(&self) -> serde_json::Map<String, serde_json::Value> {
+        match self {
+            StockTransferError::Movement(e) => e.extensions(),
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+impl From<StockItemStoreError> for StockTransferError {
+    fn from(e: StockItemStoreError) -> Self {
+        match e {
+            StockItemStoreError::NotFound(id) => {
+                StockTransferError::NotFound(format!("stock item {}", id))
+            }
+            e => StockTransferError::Movement(e.into()),
+        }
+    }
+}
+
+/// A write made during a transfer, undone if a later step fails
+enum Undo {
+    StockItem(Uuid),
+    Link(Uuid),
+    Movement(Uuid),
+}
+
+/// Moves stock between warehouses
+///
+/// A transfer takes `quantity` units out of a stock item with an `out`
+/// movement and puts them into the item of the same `product_id` in the
+/// destination warehouse with an `in` movement, both `completed` and linked to
+/// their item with `has_movement`. The destination item is created (and
+/// linked to its warehouse with `contains`) when the warehouse has none yet.
+///
+/// Both movements are first stored `pending` with the same `transfer_id`,
+/// then completed through the [`StockLedger`]: together in one transaction
+/// where the ledger has [`AtomicStockWrites`] (DynamoDB), `out` then `in`
+/// elsewhere. Every step is undone in reverse order if a later one fails: the
+/// movements are cancelled, which gives the stock back, and created items and
+/// links are deleted.
+///
+/// A transfer interrupted by a crash is left with a pending movement, which
+/// [`StockTransfers::recover`] finds and cancels along with the rest of its
+/// transfer. The destination item and the links are not written
+/// transactionally: a crash before the movements are stored can leave an
+/// empty destination item or a link behind, which do not change any stock.
+///
+/// [`AtomicStockWrites`]: crate::entities::stock_movement::AtomicStockWrites
+pub struct StockTransfers {
+    warehouses: Arc<dyn WarehouseStore>,
+    stock_items: Arc<dyn StockItemStore>,
+    stock_movements: Arc<dyn StockMovementStore>,
+    stock_ledger: Arc<StockLedger>,
+    link_service: Arc<dyn LinkService>,
+    lock: Mutex<()>,
+}
+
+impl StockTransfers {
+    pub fn new(stores: &InventoryStores, link_service: Arc<dyn LinkService>) -> Self {
+        Self {
+            warehouses: stores.warehouses_store.clone(),
+            stock_items: stores.stock_items_store.clone(),
+            stock_movements: stores.stock_movements_store.clone(),
+            stock_ledger: stores.stock_ledger.clone(),
+            link_service,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Transfer part of `stock_item_id` to another warehouse
+    pub async fn transfer(
+        &self,
+        stock_item_id: &Uuid,
+        request: TransferRequest,
+    ) -> Result<StockTransfer, StockTransferError> {
+        let _guard = self.lock.lock().await;
+
+        let source = self.stock_items.get(stock_item_id).await?;
+        if request.quantity <= 0 {
+            return Err(StockTransferError::Validation(format!(
+                "transfer quantity must be positive, got {}",
+                request.quantity
+            )));
+        }
+        if request.warehouse_id == source.warehouse_id {
+            return Err(StockTransferError::Validation(format!(
+                "stock item {} is already in warehouse {}",
+                source.id, request.warehouse_id
+            )));
+        }
+        let Some(product_id) = source.product_id else {
+            return Err(StockTransferError::Validation(format!(
+                "stock item {} has no product_id to match in the destination warehouse",
+                source.id
+            )));
+        };
+        match self.warehouses.get(&request.warehouse_id).await {
+            Ok(_) => {}
+            Err(WarehouseStoreError::NotFound(_)) => {
+                return Err(StockTransferError::NotFound(format!(
+                    "warehouse {}",
+                    request.warehouse_id
+                )));
+            }
+            Err(e) => return Err(anyhow::Error::new(e).into()),
+        }
+
+        let mut undo = Vec::new();
+        match self.run(source, product_id, &request, &mut undo).await {
+            Ok(transfer) => Ok(transfer),
+            Err(e) => {
+                self.rollback(undo).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn run(
+        &self,
+        source: StockItem,
+        product_id: Uuid,
+        request: &TransferRequest,
+        undo: &mut Vec<Undo>,
+    ) -> Result<StockTransfer, StockTransferError> {
+        let existing = self.stock_items.list().await?.into_iter().find(|item| {
+            item.warehouse_id == request.warehouse_id && item.product_id == Some(product_id)
+        });
+        let created_destination = existing.is_none();
+        let destination = match existing {
+            Some(item) => item,
+            None => {
+                let item = self
+                    .stock_items
+                    .create(StockItem::new(
+                        source.name.clone(),
+                        "out_of_stock".to_string(),
+                        Some(product_id),
+                        0,
+                        request.warehouse_id,
+                        None,
+                        source.reorder_point,
+                        None,
+                        Version::default(),
+                    ))
+                    .await?;
+                undo.push(Undo::StockItem(item.id));
+                item
+            }
+        };
+        self.ensure_link("contains", destination.warehouse_id, destination.id, undo)
+            .await?;
+
+        let reason = request.reason.clone();
+        let transfer_id = Uuid::new_v4();
+        let outgoing = self
+            .stock_movements
+            .create(StockMovement::new(
+                format!("Transfer to warehouse {}", request.warehouse_id),
+                MovementStatus::Pending.to_string(),
+                source.id,
+                "out".to_string(),
+                request.quantity,
+                reason.clone(),
+                None,
+                None,
+                Some(transfer_id),
+                None,
+                Version::default(),
+            ))
+            .await?;
+        undo.push(Undo::Movement(outgoing.id));
+        self.ensure_link("has_movement", source.id, outgoing.id, undo)
+            .await?;
+
+        let incoming = self
+            .stock_movements
+            .create(StockMovement::new(
+                format!("Transfer from warehouse {}", source.warehouse_id),
+                MovementStatus::Pending.to_string(),
+                destination.id,
+                "in".to_string(),
+                request.quantity,
+                reason,
+                None,
+                None,
+                Some(transfer_id),
+                None,
+                Version::default(),
+            ))
+            .await?;
+        undo.push(Undo::Movement(incoming.id));
+        self.ensure_link("has_movement", destination.id, incoming.id, undo)
+            .await?;
+
+        let mut completed = self
+            .stock_ledger
+            .transition_all(&[outgoing.id, incoming.id], MovementStatus::Completed)
+            .await?
+            .into_iter();
+        let (Some(outgoing), Some(incoming)) = (completed.next(), completed.next()) else {
+            return Err(anyhow::anyhow!("transfer {} completed no movements", transfer_id).into());
+        };
+
+        Ok(StockTransfer {
+            source: self.stock_items.get(&source.id).await?,
+            destination: self.stock_items.get(&destination.id).await?,
+            outgoing,
+            incoming,
+            created_destination,
+        })
+    }
+
+    /// Cancel every transfer that a crash left with a pending movement
+    ///
+    /// Meant for startup, before transfers are served: a transfer running
+    /// meanwhile would be cancelled too. Its completed movements are
+    /// cancelled, giving their stock back, and so are its pending ones.
+    /// Returns the ids of the cancelled transfers.
+    pub async fn recover(&self) -> Result<Vec<Uuid>, StockTransferError> {
+        let _guard = self.lock.lock().await;
+
+        let movements = self.stock_movements.list().await?;
+        let mut interrupted: Vec<Uuid> = movements
+            .iter()
+            .filter(|movement| movement.status == MovementStatus::Pending.as_str())
+            .filter_map(|movement| movement.transfer_id)
+            .collect();
+        interrupted.sort();
+        interrupted.dedup();
+
+        for transfer_id in &interrupted {
+            for movement in movements
+                .iter()
+                .filter(|movement| movement.transfer_id == Some(*transfer_id))
+                .filter(|movement| movement.status != MovementStatus::Cancelled.as_str())
+            {
+                self.stock_movements
+                    .transition(&movement.id, MovementStatus::Cancelled)
+                    .await?;
+            }
+        }
+        Ok(interrupted)
+    }
+
+    /// Link `source_id` to `target_id` unless it already is, e.g. by the
+    /// stores keeping foreign keys and links in step
+    async fn ensure_link(
+        &self,
+        link_type: &str,
+        source_id: Uuid,
+        target_id: Uuid,
+        undo: &mut Vec<Undo>,
+    ) -> Result<(), StockTransferError> {
+        let linked = self
+            .link_service
+            .find_by_target(&target_id, Some(link_type), None)
+            .await?
+            .iter()
+            .any(|link| link.source_id == source_id);
+        if linked {
+            return Ok(());
+        }
+        let link = self
+            .link_service
+            .create(LinkEntity::new(link_type, source_id, target_id, None))
+            .await?;
+        undo.push(Undo::Link(link.id));
+        Ok(())
+    }
+
+    async fn rollback(&self, undo: Vec<Undo>) {
+        for step in undo.into_iter().rev() {
+            let result = match step {
+                Undo::StockItem(id) => self.stock_items.delete(&id).await.map_err(Into::into),
+                Undo::Link(id) => self.link_service.delete(&id).await,
+                Undo::Movement(id) => self
+                    .stock_movements
+                    .transition(&id, MovementStatus::Cancelled)
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to roll back stock transfer step: {:?}", e);
+            }
+        }
+    }
+}
@@ -1,21 +1,30 @@
 use std::sync::Arc;
 
-use crate::module::InventoryStores;
+use crate::module::{EntityStore, InventoryStores};
 
 // Import stores from entity modules
-use crate::entities::activity::InMemoryActivityStore;
-use crate::entities::stock_item::InMemoryStockItemStore;
-use crate::entities::stock_movement::InMemoryStockMovementStore;
-use crate::entities::store::InMemoryStoreStore;
-use crate::entities::usage::InMemoryUsageStore;
-use crate::entities::warehouse::InMemoryWarehouseStore;
+use crate::entities::activity::{ActivityStore, InMemoryActivityStore};
+use crate::entities::reservation::{InMemoryReservationStore, ReservationLedger, ReservationStore};
+use crate::entities::stock_item::{InMemoryStockItemStore, LowStockDetector, StockItemStore};
+use crate::entities::stock_movement::{
+    AtomicStockWrites, InMemoryStockMovementStore, StockLedger, StockMovementStore,
+};
+use crate::entities::store::{InMemoryStoreStore, StoreStore};
+use crate::entities::usage::{InMemoryUsageStore, UsageStore};
+use crate::entities::warehouse::{InMemoryWarehouseStore, WarehouseStore};
+use crate::foreign_keys::{
+    ForeignKeys, LinkedStockItemStore, LinkedStockMovementStore, LinkedUsageStore,
+    LinkedWarehouseStore,
+};
 
 #[cfg(feature = "dynamodb")]
 use crate::entities::activity::ActivityDynamoDBStore;
 #[cfg(feature = "dynamodb")]
+use crate::entities::reservation::ReservationDynamoDBStore;
+#[cfg(feature = "dynamodb")]
 use crate::entities::stock_item::StockItemDynamoDBStore;
 #[cfg(feature = "dynamodb")]
-use crate::entities::stock_movement::StockMovementDynamoDBStore;
+use crate::entities::stock_movement::{DynamoDBStockWrites, StockMovementDynamoDBStore};
 #[cfg(feature = "dynamodb")]
 use crate::entities::store::StoreDynamoDBStore;
 #[cfg(feature = "dynamodb")]
@@ -25,37 +34,164 @@ use crate::entities::warehouse::WarehouseDynamoDBStore;
 #[cfg(feature = "dynamodb")]
 use aws_sdk_dynamodb::Client as DynamoDBClient;
 
+#[cfg(feature = "postgres")]
+use crate::entities::activity::ActivityPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::reservation::ReservationPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::stock_item::StockItemPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::stock_movement::StockMovementPostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::store::StorePostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::usage::UsagePostgresStore;
+#[cfg(feature = "postgres")]
+use crate::entities::warehouse::WarehousePostgresStore;
+#[cfg(feature = "postgres")]
+use sqlx::PgPool;
+
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::activity::ActivityMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::reservation::ReservationMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::stock_item::StockItemMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::stock_movement::StockMovementMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::store::StoreMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::usage::UsageMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use crate::entities::warehouse::WarehouseMongoStore;
+#[cfg(feature = "mongodb_backend")]
+use mongodb::Database as MongoDatabase;
+
+#[cfg(feature = "neo4j")]
+use crate::entities::activity::ActivityNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::reservation::ReservationNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::stock_item::StockItemNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::stock_movement::StockMovementNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::store::StoreNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::usage::UsageNeo4jStore;
+#[cfg(feature = "neo4j")]
+use crate::entities::warehouse::WarehouseNeo4jStore;
+#[cfg(feature = "neo4j")]
+use neo4rs::Graph;
+
+#[cfg(feature = "scylladb")]
+use crate::entities::activity::ActivityScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::reservation::ReservationScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::stock_item::StockItemScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::stock_movement::StockMovementScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::store::StoreScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::usage::UsageScyllaStore;
+#[cfg(feature = "scylladb")]
+use crate::entities::warehouse::WarehouseScyllaStore;
+#[cfg(feature = "scylladb")]
+use scylla::client::session::Session;
+
+#[cfg(feature = "mysql")]
+use crate::entities::activity::ActivityMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::reservation::ReservationMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::stock_item::StockItemMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::stock_movement::StockMovementMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::store::StoreMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::usage::UsageMysqlStore;
+#[cfg(feature = "mysql")]
+use crate::entities::warehouse::WarehouseMysqlStore;
+#[cfg(feature = "mysql")]
+use sqlx::MySqlPool;
+
+#[cfg(feature = "lmdb")]
+use crate::entities::activity::ActivityLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::reservation::ReservationLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::stock_item::StockItemLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::stock_movement::StockMovementLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::store::StoreLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::usage::UsageLmdbStore;
+#[cfg(feature = "lmdb")]
+use crate::entities::warehouse::WarehouseLmdbStore;
+
+#[cfg(feature = "sqlite")]
+use crate::entities::activity::ActivitySqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::reservation::ReservationSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::stock_item::StockItemSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::stock_movement::StockMovementSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::store::StoreSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::usage::UsageSqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::entities::warehouse::WarehouseSqliteStore;
+
 // ============================================================================
 // Store Factories
 // ============================================================================
+//
+// Stock items always go through a `LowStockDetector`, which reports items
+// falling below their reorder point, stock movements through a `StockLedger`,
+// which applies completed movements to the quantity of their stock item, and
+// reservations through a `ReservationLedger`, which holds their units on the
+// stock item. Warehouses, stock items, stock movements and usages are then
+// wrapped to keep their foreign keys and links in step (see
+// `crate::foreign_keys`).
 
 impl InventoryStores {
     /// Create stores with in-memory implementations
     pub fn new_in_memory() -> Self {
-        let stores = Arc::new(InMemoryStoreStore::default());
-        let activities = Arc::new(InMemoryActivityStore::default());
-        let warehouses = Arc::new(InMemoryWarehouseStore::default());
-        let stock_items = Arc::new(InMemoryStockItemStore::default());
-        let stock_movements = Arc::new(InMemoryStockMovementStore::default());
-        let usages = Arc::new(InMemoryUsageStore::default());
+        Self::wrap(
+            Arc::new(InMemoryStoreStore::default()),
+            Arc::new(InMemoryActivityStore::default()),
+            Arc::new(InMemoryWarehouseStore::default()),
+            Arc::new(InMemoryStockItemStore::default()),
+            Arc::new(InMemoryStockMovementStore::default()),
+            Arc::new(InMemoryUsageStore::default()),
+            Arc::new(InMemoryReservationStore::default()),
+        )
+    }
 
-        Self {
-            stores_store: stores.clone(),
-            stores_entity: stores,
-            activities_store: activities.clone(),
-            activities_entity: activities,
-            warehouses_store: warehouses.clone(),
-            warehouses_entity: warehouses,
-            stock_items_store: stock_items.clone(),
-            stock_items_entity: stock_items,
-            stock_movements_store: stock_movements.clone(),
-            stock_movements_entity: stock_movements,
-            usages_store: usages.clone(),
-            usages_entity: usages,
-        }
+    /// Create in-memory stores journaled in `dir`, recovering what they held
+    /// when last stopped (see `common::journal`)
+    pub fn new_journal(dir: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        Ok(Self::wrap(
+            Arc::new(InMemoryStoreStore::open(dir)?),
+            Arc::new(InMemoryActivityStore::open(dir)?),
+            Arc::new(InMemoryWarehouseStore::open(dir)?),
+            Arc::new(InMemoryStockItemStore::open(dir)?),
+            Arc::new(InMemoryStockMovementStore::open(dir)?),
+            Arc::new(InMemoryUsageStore::open(dir)?),
+            Arc::new(InMemoryReservationStore::open(dir)?),
+        ))
     }
 
     #[cfg(feature = "dynamodb")]
+    #[allow(clippy::too_many_arguments)]
     /// Create stores with DynamoDB implementations
     pub fn new_dynamodb(
         client: DynamoDBClient,
@@ -65,13 +201,8 @@ impl InventoryStores {
         stock_items_table: String,
         stock_movements_table: String,
         usages_table: String,
+        reservations_table: String,
     ) -> Self {
-        let stores = Arc::new(StoreDynamoDBStore::new(client.clone(), stores_table));
-        let activities = Arc::new(ActivityDynamoDBStore::new(client.clone(), activities_table));
-        let warehouses = Arc::new(WarehouseDynamoDBStore::new(
-            client.clone(),
-            warehouses_table,
-        ));
         let stock_items = Arc::new(StockItemDynamoDBStore::new(
             client.clone(),
             stock_items_table,
@@ -80,7 +211,200 @@ impl InventoryStores {
             client.clone(),
             stock_movements_table,
         ));
-        let usages = Arc::new(UsageDynamoDBStore::new(client, usages_table));
+        let stock_writes = Arc::new(DynamoDBStockWrites::new(
+            stock_items.clone(),
+            stock_movements.clone(),
+        ));
+        Self::wrap_with(
+            Arc::new(StoreDynamoDBStore::new(client.clone(), stores_table)),
+            Arc::new(ActivityDynamoDBStore::new(client.clone(), activities_table)),
+            Arc::new(WarehouseDynamoDBStore::new(
+                client.clone(),
+                warehouses_table,
+            )),
+            stock_items,
+            stock_movements,
+            Arc::new(UsageDynamoDBStore::new(client.clone(), usages_table)),
+            Arc::new(ReservationDynamoDBStore::new(client, reservations_table)),
+            Some(stock_writes),
+        )
+    }
+
+    #[cfg(feature = "postgres")]
+    /// Create stores with PostgreSQL implementations
+    pub fn new_postgres(pool: PgPool) -> Self {
+        Self::wrap(
+            Arc::new(StorePostgresStore::new(pool.clone())),
+            Arc::new(ActivityPostgresStore::new(pool.clone())),
+            Arc::new(WarehousePostgresStore::new(pool.clone())),
+            Arc::new(StockItemPostgresStore::new(pool.clone())),
+            Arc::new(StockMovementPostgresStore::new(pool.clone())),
+            Arc::new(UsagePostgresStore::new(pool.clone())),
+            Arc::new(ReservationPostgresStore::new(pool)),
+        )
+    }
+
+    #[cfg(feature = "mongodb_backend")]
+    /// Create stores with MongoDB implementations
+    pub fn new_mongodb(database: MongoDatabase) -> Self {
+        Self::wrap(
+            Arc::new(StoreMongoStore::new(database.clone())),
+            Arc::new(ActivityMongoStore::new(database.clone())),
+            Arc::new(WarehouseMongoStore::new(database.clone())),
+            Arc::new(StockItemMongoStore::new(database.clone())),
+            Arc::new(StockMovementMongoStore::new(database.clone())),
+            Arc::new(UsageMongoStore::new(database.clone())),
+            Arc::new(ReservationMongoStore::new(database)),
+        )
+    }
+
+    #[cfg(feature = "neo4j")]
+    /// Create stores with Neo4j implementations
+    pub fn new_neo4j(graph: Graph) -> Self {
+        Self::wrap(
+            Arc::new(StoreNeo4jStore::new(graph.clone())),
+            Arc::new(ActivityNeo4jStore::new(graph.clone())),
+            Arc::new(WarehouseNeo4jStore::new(graph.clone())),
+            Arc::new(StockItemNeo4jStore::new(graph.clone())),
+            Arc::new(StockMovementNeo4jStore::new(graph.clone())),
+            Arc::new(UsageNeo4jStore::new(graph.clone())),
+            Arc::new(ReservationNeo4jStore::new(graph)),
+        )
+    }
+
+    #[cfg(feature = "scylladb")]
+    /// Create stores with ScyllaDB implementations
+    pub fn new_scylladb(session: Arc<Session>, keyspace: impl Into<String>) -> Self {
+        let ks: String = keyspace.into();
+        Self::wrap(
+            Arc::new(StoreScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(ActivityScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(WarehouseScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(StockItemScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(StockMovementScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(UsageScyllaStore::new(session.clone(), ks.clone())),
+            Arc::new(ReservationScyllaStore::new(session, ks)),
+        )
+    }
+
+    #[cfg(feature = "mysql")]
+    /// Create stores with MySQL implementations
+    pub fn new_mysql(pool: MySqlPool) -> Self {
+        Self::wrap(
+            Arc::new(StoreMysqlStore::new(pool.clone())),
+            Arc::new(ActivityMysqlStore::new(pool.clone())),
+            Arc::new(WarehouseMysqlStore::new(pool.clone())),
+            Arc::new(StockItemMysqlStore::new(pool.clone())),
+            Arc::new(StockMovementMysqlStore::new(pool.clone())),
+            Arc::new(UsageMysqlStore::new(pool.clone())),
+            Arc::new(ReservationMysqlStore::new(pool)),
+        )
+    }
+
+    #[cfg(feature = "lmdb")]
+    /// Create stores with LMDB implementations, each entity in its own
+    /// environment under `path`
+    pub fn new_lmdb(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self::wrap(
+            Arc::new(StoreLmdbStore::open(path.join("stores"))?),
+            Arc::new(ActivityLmdbStore::open(path.join("activities"))?),
+            Arc::new(WarehouseLmdbStore::open(path.join("warehouses"))?),
+            Arc::new(StockItemLmdbStore::open(path.join("stock_items"))?),
+            Arc::new(StockMovementLmdbStore::open(path.join("stock_movements"))?),
+            Arc::new(UsageLmdbStore::open(path.join("usages"))?),
+            Arc::new(ReservationLmdbStore::open(path.join("reservations"))?),
+        ))
+    }
+
+    #[cfg(feature = "sqlite")]
+    /// Create stores with SQLite implementations, in the database file at
+    /// `path`
+    pub async fn new_sqlite(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let pool = common::sqlite::connect(path).await?;
+        Ok(Self::wrap(
+            Arc::new(StoreSqliteStore::new(pool.clone())),
+            Arc::new(ActivitySqliteStore::new(pool.clone())),
+            Arc::new(WarehouseSqliteStore::new(pool.clone())),
+            Arc::new(StockItemSqliteStore::new(pool.clone())),
+            Arc::new(StockMovementSqliteStore::new(pool.clone())),
+            Arc::new(UsageSqliteStore::new(pool.clone())),
+            Arc::new(ReservationSqliteStore::new(pool)),
+        ))
+    }
+
+    /// Put the stores of a backend behind the detector, the ledgers and the
+    /// linked stores
+    fn wrap<S, A, W>(
+        stores: Arc<S>,
+        activities: Arc<A>,
+        warehouses: Arc<W>,
+        stock_items: Arc<dyn StockItemStore>,
+        stock_movements: Arc<dyn StockMovementStore>,
+        usages: Arc<dyn UsageStore>,
+        reservations: Arc<dyn ReservationStore>,
+    ) -> Self
+    where
+        S: StoreStore + EntityStore + 'static,
+        A: ActivityStore + EntityStore + 'static,
+        W: WarehouseStore + 'static,
+    {
+        Self::wrap_with(
+            stores,
+            activities,
+            warehouses,
+            stock_items,
+            stock_movements,
+            usages,
+            reservations,
+            None,
+        )
+    }
+
+    /// [`Self::wrap`], with the stock ledger writing through `stock_writes`
+    #[allow(clippy::too_many_arguments)]
+    fn wrap_with<S, A, W>(
+        stores: Arc<S>,
+        activities: Arc<A>,
+        warehouses: Arc<W>,
+        stock_items: Arc<dyn StockItemStore>,
+        stock_movements: Arc<dyn StockMovementStore>,
+        usages: Arc<dyn UsageStore>,
+        reservations: Arc<dyn ReservationStore>,
+        stock_writes: Option<Arc<dyn AtomicStockWrites>>,
+    ) -> Self
+    where
+        S: StoreStore + EntityStore + 'static,
+        A: ActivityStore + EntityStore + 'static,
+        W: WarehouseStore + 'static,
+    {
+        let low_stock = Arc::new(LowStockDetector::new(stock_items));
+        let foreign_keys = Arc::new(ForeignKeys::new(
+            stores.clone(),
+            activities.clone(),
+            warehouses.clone(),
+            low_stock.clone(),
+        ));
+        let warehouses = Arc::new(LinkedWarehouseStore::new(warehouses, foreign_keys.clone()));
+        let stock_items = Arc::new(LinkedStockItemStore::new(
+            low_stock.clone(),
+            foreign_keys.clone(),
+        ));
+        let mut ledger = StockLedger::new(stock_movements, low_stock.clone());
+        if let Some(stock_writes) = stock_writes {
+            ledger = ledger.with_atomic_writes(stock_writes);
+        }
+        let stock_ledger = Arc::new(ledger);
+        let stock_movements = Arc::new(LinkedStockMovementStore::new(
+            stock_ledger.clone(),
+            foreign_keys.clone(),
+        ));
+        let usages = Arc::new(LinkedUsageStore::new(usages, foreign_keys.clone()));
+        let reservations = Arc::new(ReservationLedger::new(
+            reservations,
+            low_stock.clone(),
+            stock_movements.clone(),
+        ));
 
         Self {
             stores_store: stores.clone(),
@@ -91,10 +415,15 @@ impl InventoryStores {
             warehouses_entity: warehouses,
             stock_items_store: stock_items.clone(),
             stock_items_entity: stock_items,
+            low_stock,
             stock_movements_store: stock_movements.clone(),
             stock_movements_entity: stock_movements,
+            stock_ledger,
             usages_store: usages.clone(),
             usages_entity: usages,
+            reservations_store: reservations.clone(),
+            reservations_entity: reservations,
+            foreign_keys,
         }
     }
 }
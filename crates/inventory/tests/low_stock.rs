@@ -0,0 +1,126 @@
+//! Low-stock alerts of the stock item store, on an in-memory store
+
+use std::sync::Arc;
+
+use common::Version;
+use common::store::EntityStore;
+use inventory::entities::stock_item::{
+    InMemoryStockItemStore, LOW_STOCK_ALERT, LowStockDetector, StockItem, StockItemStore,
+    StockItemStoreError,
+};
+use this::core::events::{EntityEvent, EventBus, EventEnvelope, FrameworkEvent};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::TryRecvError;
+use uuid::Uuid;
+
+/// A detector over a new store holding one item of `quantity` units,
+/// reordered at 5, and the receiver of its alerts
+async fn detector(quantity: i32) -> (LowStockDetector, StockItem, Receiver<EventEnvelope>) {
+    let detector = LowStockDetector::new(Arc::new(InMemoryStockItemStore::default()));
+    let event_bus = EventBus::new(16);
+    let events = event_bus.subscribe();
+    detector.attach(event_bus);
+    let item = StockItem::new(
+        "Widgets".into(),
+        "available".into(),
+        None,
+        quantity,
+        Uuid::new_v4(),
+        None,
+        Some(5),
+        None,
+        Version::default(),
+    );
+    let item = detector.create(item).await.unwrap();
+    (detector, item, events)
+}
+
+/// The levels of the alerts published so far
+fn alerts(events: &mut Receiver<EventEnvelope>) -> Vec<String> {
+    let mut levels = Vec::new();
+    loop {
+        match events.try_recv() {
+            Ok(envelope) => {
+                if let FrameworkEvent::Entity(EntityEvent::Created {
+                    entity_type, data, ..
+                }) = envelope.event
+                    && entity_type == LOW_STOCK_ALERT
+                {
+                    levels.push(data["level"].as_str().unwrap_or_default().to_string());
+                }
+            }
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return levels,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn alerts_follow_the_level_down_only() {
+    let (detector, item, mut events) = detector(20).await;
+
+    detector.adjust_quantity(&item.id, -16).await.unwrap();
+    assert_eq!(alerts(&mut events), ["low"]);
+    detector.adjust_quantity(&item.id, -1).await.unwrap();
+    assert!(alerts(&mut events).is_empty(), "still low");
+    detector.adjust_quantity(&item.id, -3).await.unwrap();
+    assert_eq!(alerts(&mut events), ["out"]);
+    // Restocked, but not enough
+    detector.adjust_quantity(&item.id, 2).await.unwrap();
+    assert!(alerts(&mut events).is_empty(), "out to low is better");
+    detector.adjust_quantity(&item.id, 10).await.unwrap();
+    detector.adjust_reserved(&item.id, 9).await.unwrap();
+    assert_eq!(alerts(&mut events), ["low"]);
+}
+
+#[tokio::test]
+async fn creating_an_empty_item_alerts_nothing() {
+    let (_, _, mut events) = detector(0).await;
+
+    assert!(alerts(&mut events).is_empty());
+}
+
+#[tokio::test]
+async fn updates_alert_against_the_item_they_replace() {
+    let (detector, item, mut events) = detector(20).await;
+
+    let mut low = item.clone();
+    low.quantity = 3;
+    let low = detector.update(low).await.unwrap();
+    assert_eq!(alerts(&mut events), ["low"]);
+
+    // Read before the item ran low: the update must not go through, nor
+    // alert from the level it was read at
+    let mut stale = item;
+    stale.quantity = 0;
+    let error = detector.update(stale).await.unwrap_err();
+    assert!(
+        matches!(error, StockItemStoreError::VersionMismatch(_)),
+        "{:?}",
+        error
+    );
+    assert!(alerts(&mut events).is_empty());
+
+    let mut out = low;
+    out.quantity = 0;
+    detector.update(out).await.unwrap();
+    assert_eq!(alerts(&mut events), ["out"]);
+}
+
+#[tokio::test]
+async fn concurrent_adjustments_alert_once() {
+    let (detector, item, mut events) = detector(40).await;
+    let detector = Arc::new(detector);
+
+    let tasks: Vec<_> = (0..40)
+        .map(|_| {
+            let detector = detector.clone();
+            tokio::spawn(async move { detector.adjust_quantity(&item.id, -1).await })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    assert_eq!(alerts(&mut events), ["low", "out"]);
+}
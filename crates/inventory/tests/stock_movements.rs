@@ -0,0 +1,175 @@
+//! Stock movements applied to the quantity of their stock item, on
+//! in-memory stores
+
+use common::Version;
+use inventory::entities::stock_item::{StockItem, StockItemStoreError};
+use inventory::entities::stock_movement::{MovementStatus, StockMovement, StockMovementStoreError};
+use inventory::entities::store::Store;
+use inventory::entities::warehouse::Warehouse;
+use inventory::module::InventoryStores;
+use uuid::Uuid;
+
+/// Stores holding one stock item of `quantity` units
+async fn stock(quantity: i32) -> (InventoryStores, StockItem) {
+    let stores = InventoryStores::new_in_memory();
+    let store = Store::new(
+        "Main store".into(),
+        "active".into(),
+        None,
+        None,
+        Version::default(),
+    );
+    let store = stores.stores_store.create(store).await.unwrap();
+    let warehouse = Warehouse::new(
+        "Main warehouse".into(),
+        "active".into(),
+        None,
+        store.id,
+        None,
+        Version::default(),
+    );
+    let warehouse = stores.warehouses_store.create(warehouse).await.unwrap();
+    let item = StockItem::new(
+        "Widgets".into(),
+        "available".into(),
+        Some(Uuid::new_v4()),
+        quantity,
+        warehouse.id,
+        None,
+        None,
+        None,
+        Version::default(),
+    );
+    let item = stores.stock_items_store.create(item).await.unwrap();
+    (stores, item)
+}
+
+async fn movement(
+    stores: &InventoryStores,
+    item: &StockItem,
+    status: MovementStatus,
+    movement_type: &str,
+    quantity: i32,
+) -> Result<StockMovement, StockMovementStoreError> {
+    let movement = StockMovement::new(
+        format!("{} {}", movement_type, quantity),
+        status.to_string(),
+        item.id,
+        movement_type.into(),
+        quantity,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Version::default(),
+    );
+    stores.stock_movements_store.create(movement).await
+}
+
+async fn quantity(stores: &InventoryStores, item: &StockItem) -> i32 {
+    stores
+        .stock_items_store
+        .get(&item.id)
+        .await
+        .unwrap()
+        .quantity
+}
+
+#[tokio::test]
+async fn completed_movements_change_the_quantity() {
+    let (stores, item) = stock(10).await;
+
+    movement(&stores, &item, MovementStatus::Completed, "in", 5)
+        .await
+        .unwrap();
+    movement(&stores, &item, MovementStatus::Completed, "out", 3)
+        .await
+        .unwrap();
+    movement(&stores, &item, MovementStatus::Completed, "adjustment", -2)
+        .await
+        .unwrap();
+    movement(&stores, &item, MovementStatus::Pending, "out", 7)
+        .await
+        .unwrap();
+
+    assert_eq!(quantity(&stores, &item).await, 10);
+}
+
+#[tokio::test]
+async fn completing_then_cancelling_gives_the_stock_back() {
+    let (stores, item) = stock(10).await;
+    let pending = movement(&stores, &item, MovementStatus::Pending, "out", 4)
+        .await
+        .unwrap();
+
+    stores
+        .stock_movements_store
+        .transition(&pending.id, MovementStatus::Completed)
+        .await
+        .unwrap();
+    let after_completion = quantity(&stores, &item).await;
+    stores
+        .stock_movements_store
+        .transition(&pending.id, MovementStatus::Cancelled)
+        .await
+        .unwrap();
+    let reopened = stores
+        .stock_movements_store
+        .transition(&pending.id, MovementStatus::Completed)
+        .await;
+
+    assert_eq!(after_completion, 6);
+    assert_eq!(quantity(&stores, &item).await, 10);
+    assert!(matches!(
+        reopened,
+        Err(StockMovementStoreError::InvalidTransition(_))
+    ));
+    assert_eq!(quantity(&stores, &item).await, 10);
+}
+
+#[tokio::test]
+async fn movements_cannot_take_more_than_the_stock() {
+    let (stores, item) = stock(3).await;
+
+    let result = movement(&stores, &item, MovementStatus::Completed, "out", 4).await;
+
+    assert!(matches!(
+        result,
+        Err(StockMovementStoreError::Stock(
+            StockItemStoreError::InsufficientStock {
+                available: 3,
+                requested: 4,
+                ..
+            }
+        ))
+    ));
+    assert_eq!(quantity(&stores, &item).await, 3);
+    assert!(
+        stores
+            .stock_movements_store
+            .list()
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn movements_of_a_missing_stock_item_are_rejected() {
+    let (stores, _) = stock(3).await;
+    let mut missing = stock(0).await.1;
+    missing.id = Uuid::new_v4();
+
+    let result = movement(&stores, &missing, MovementStatus::Completed, "in", 1).await;
+
+    assert!(result.is_err());
+    assert!(
+        stores
+            .stock_movements_store
+            .list()
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,313 @@
+//! Stock transfers between warehouses, their rollback and their recovery,
+//! on in-memory stores
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use common::Version;
+use inventory::entities::stock_item::StockItem;
+use inventory::entities::stock_movement::{MovementStatus, StockMovement};
+use inventory::entities::store::Store;
+use inventory::entities::warehouse::Warehouse;
+use inventory::module::InventoryStores;
+use inventory::services::{StockTransferError, StockTransfers, TransferRequest};
+use this::prelude::{LinkEntity, LinkService};
+use this::storage::InMemoryLinkService;
+use uuid::Uuid;
+
+struct Fixture {
+    stores: InventoryStores,
+    links: Arc<FailingLinks>,
+    item: StockItem,
+    destination: Uuid,
+}
+
+impl Fixture {
+    /// An item of 10 units in one warehouse, and an empty second warehouse
+    async fn new() -> Self {
+        let stores = InventoryStores::new_in_memory();
+        let store = Store::new(
+            "Main store".into(),
+            "active".into(),
+            None,
+            None,
+            Version::default(),
+        );
+        let store = stores.stores_store.create(store).await.unwrap();
+        let mut warehouses = Vec::new();
+        for name in ["North", "South"] {
+            let warehouse = Warehouse::new(
+                name.into(),
+                "active".into(),
+                None,
+                store.id,
+                None,
+                Version::default(),
+            );
+            warehouses.push(stores.warehouses_store.create(warehouse).await.unwrap().id);
+        }
+        let item = StockItem::new(
+            "Widgets".into(),
+            "available".into(),
+            Some(Uuid::new_v4()),
+            10,
+            warehouses[0],
+            None,
+            Some(2),
+            None,
+            Version::default(),
+        );
+        let item = stores.stock_items_store.create(item).await.unwrap();
+        Self {
+            stores,
+            links: Arc::new(FailingLinks::default()),
+            item,
+            destination: warehouses[1],
+        }
+    }
+
+    fn transfers(&self) -> StockTransfers {
+        StockTransfers::new(&self.stores, self.links.clone())
+    }
+
+    async fn transfer(&self, quantity: i32) -> Result<(), StockTransferError> {
+        let request = TransferRequest {
+            warehouse_id: self.destination,
+            quantity,
+            reason: None,
+        };
+        self.transfers()
+            .transfer(&self.item.id, request)
+            .await
+            .map(|_| ())
+    }
+
+    async fn quantity(&self) -> i32 {
+        let item = self.stores.stock_items_store.get(&self.item.id).await;
+        item.unwrap().quantity
+    }
+
+    async fn at_destination(&self) -> Vec<StockItem> {
+        let items = self.stores.stock_items_store.list().await.unwrap();
+        items
+            .into_iter()
+            .filter(|item| item.warehouse_id == self.destination)
+            .collect()
+    }
+
+    async fn movements(&self) -> Vec<StockMovement> {
+        self.stores.stock_movements_store.list().await.unwrap()
+    }
+
+    /// A pending movement of `transfer_id` on `stock_item_id`
+    async fn pending(
+        &self,
+        stock_item_id: Uuid,
+        movement_type: &str,
+        transfer_id: Uuid,
+    ) -> StockMovement {
+        let movement = StockMovement::new(
+            "Interrupted transfer".into(),
+            MovementStatus::Pending.to_string(),
+            stock_item_id,
+            movement_type.into(),
+            4,
+            None,
+            None,
+            None,
+            Some(transfer_id),
+            None,
+            Version::default(),
+        );
+        let movements = &self.stores.stock_movements_store;
+        movements.create(movement).await.unwrap()
+    }
+}
+
+/// In-memory links, failing the creation of the `has_movement` link numbered
+/// `fail_at` (from 1; 0 never fails)
+#[derive(Default)]
+struct FailingLinks {
+    inner: InMemoryLinkService,
+    fail_at: AtomicUsize,
+    movement_links: AtomicUsize,
+}
+
+#[async_trait]
+impl LinkService for FailingLinks {
+    async fn create(&self, link: LinkEntity) -> Result<LinkEntity> {
+        if link.link_type == "has_movement" {
+            let count = self.movement_links.fetch_add(1, Ordering::SeqCst) + 1;
+            if count == self.fail_at.load(Ordering::SeqCst) {
+                return Err(anyhow!("link backend unavailable"));
+            }
+        }
+        self.inner.create(link).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<LinkEntity>> {
+        self.inner.get(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<LinkEntity>> {
+        self.inner.list().await
+    }
+
+    async fn find_by_source(
+        &self,
+        source_id: &Uuid,
+        link_type: Option<&str>,
+        target_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_source(source_id, link_type, target_type)
+            .await
+    }
+
+    async fn find_by_target(
+        &self,
+        target_id: &Uuid,
+        link_type: Option<&str>,
+        source_type: Option<&str>,
+    ) -> Result<Vec<LinkEntity>> {
+        self.inner
+            .find_by_target(target_id, link_type, source_type)
+            .await
+    }
+
+    async fn update(&self, id: &Uuid, link: LinkEntity) -> Result<LinkEntity> {
+        self.inner.update(id, link).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<()> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_entity(&self, entity_id: &Uuid) -> Result<()> {
+        self.inner.delete_by_entity(entity_id).await
+    }
+}
+
+#[tokio::test]
+async fn a_transfer_moves_the_stock_into_a_new_destination_item() {
+    let fixture = Fixture::new().await;
+    let request = TransferRequest {
+        warehouse_id: fixture.destination,
+        quantity: 4,
+        reason: Some("rebalancing".into()),
+    };
+
+    let transfer = fixture
+        .transfers()
+        .transfer(&fixture.item.id, request)
+        .await
+        .unwrap();
+
+    assert!(transfer.created_destination);
+    assert_eq!(transfer.source.quantity, 6);
+    assert_eq!(transfer.destination.quantity, 4);
+    assert_eq!(transfer.destination.product_id, fixture.item.product_id);
+    assert_eq!(transfer.outgoing.status, "completed");
+    assert_eq!(transfer.incoming.status, "completed");
+    assert_eq!(transfer.outgoing.transfer_id, transfer.incoming.transfer_id);
+    // `contains` for the new item, `has_movement` for each movement
+    assert_eq!(fixture.links.list().await.unwrap().len(), 3);
+
+    // The next transfer fills the same item
+    fixture.transfer(1).await.unwrap();
+    let destination = fixture.at_destination().await;
+    assert_eq!(destination.len(), 1);
+    assert_eq!(destination[0].quantity, 5);
+}
+
+#[tokio::test]
+async fn a_transfer_beyond_the_stock_leaves_nothing_behind() {
+    let fixture = Fixture::new().await;
+
+    let result = fixture.transfer(11).await;
+
+    assert!(matches!(result, Err(StockTransferError::Movement(_))));
+    assert_eq!(fixture.quantity().await, 10);
+    assert!(fixture.at_destination().await.is_empty());
+    assert!(fixture.links.list().await.unwrap().is_empty());
+    for movement in fixture.movements().await {
+        assert_eq!(movement.status, "cancelled");
+    }
+}
+
+#[tokio::test]
+async fn a_failed_step_rolls_back_the_steps_before_it() {
+    let fixture = Fixture::new().await;
+    // The link of the incoming movement, after the outgoing one is stored
+    fixture.links.fail_at.store(2, Ordering::SeqCst);
+
+    let result = fixture.transfer(4).await;
+
+    assert!(matches!(result, Err(StockTransferError::Other(_))));
+    assert_eq!(fixture.quantity().await, 10);
+    assert!(fixture.at_destination().await.is_empty());
+    assert!(fixture.links.list().await.unwrap().is_empty());
+    let movements = fixture.movements().await;
+    assert_eq!(movements.len(), 2);
+    assert!(movements.iter().all(|m| m.status == "cancelled"));
+}
+
+#[tokio::test]
+async fn invalid_transfers_write_nothing() {
+    let fixture = Fixture::new().await;
+    let transfer = |warehouse_id, quantity| {
+        let request = TransferRequest {
+            warehouse_id,
+            quantity,
+            reason: None,
+        };
+        let transfers = fixture.transfers();
+        let id = fixture.item.id;
+        async move { transfers.transfer(&id, request).await }
+    };
+
+    let zero = transfer(fixture.destination, 0).await;
+    let same = transfer(fixture.item.warehouse_id, 1).await;
+    let nowhere = transfer(Uuid::new_v4(), 1).await;
+
+    assert!(matches!(zero, Err(StockTransferError::Validation(_))));
+    assert!(matches!(same, Err(StockTransferError::Validation(_))));
+    assert!(matches!(nowhere, Err(StockTransferError::NotFound(_))));
+    assert!(fixture.movements().await.is_empty());
+    assert_eq!(fixture.quantity().await, 10);
+}
+
+#[tokio::test]
+async fn recovery_cancels_interrupted_transfers_only() {
+    let fixture = Fixture::new().await;
+    fixture.transfer(2).await.unwrap();
+    let destination = fixture.at_destination().await[0].id;
+    // A crash between completing the outgoing movement and the incoming one
+    let interrupted = Uuid::new_v4();
+    let outgoing = fixture.pending(fixture.item.id, "out", interrupted).await;
+    fixture
+        .stores
+        .stock_movements_store
+        .transition(&outgoing.id, MovementStatus::Completed)
+        .await
+        .unwrap();
+    fixture.pending(destination, "in", interrupted).await;
+    assert_eq!(fixture.quantity().await, 4);
+
+    let recovered = fixture.transfers().recover().await.unwrap();
+
+    assert_eq!(recovered, [interrupted]);
+    assert_eq!(fixture.quantity().await, 8);
+    assert_eq!(fixture.at_destination().await[0].quantity, 2);
+    for movement in fixture.movements().await {
+        let expected = if movement.transfer_id == Some(interrupted) {
+            "cancelled"
+        } else {
+            "completed"
+        };
+        assert_eq!(movement.status, expected);
+    }
+    assert!(fixture.transfers().recover().await.unwrap().is_empty());
+}
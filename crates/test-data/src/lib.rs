@@ -3,31 +3,40 @@ use billing::entities::invoice::Invoice;
 use billing::entities::order::Order;
 use billing::entities::payment::Payment;
 use billing::BillingStores;
+use common::{Currency, Money, Version};
 use std::sync::Arc;
 use this::core::LinkService;
-use this::prelude::{InMemoryLinkService, LinkEntity};
+use this::prelude::LinkEntity;
+
+fn eur(amount: &str) -> Money {
+    Money::parse(amount, Currency::EUR).expect("valid test amount")
+}
 
 /// Populate test data in the billing stores and create links between entities
 pub async fn populate_test_data(
     stores: &BillingStores,
-    link_service: Arc<InMemoryLinkService>,
+    link_service: Arc<dyn LinkService>,
 ) -> Result<()> {
     // Orders
     let order1 = Order::new(
         "Order 1".into(),
         "pending".into(),
         "ORD-001".into(),
-        999.99,
+        eur("999.99"),
         Some("Customer 1".into()),
         Some("Test order 1".into()),
+        None,
+        Version::default(),
     );
     let order2 = Order::new(
         "Order 2".into(),
         "paid".into(),
         "ORD-002".into(),
-        4999.99,
+        eur("4999.99"),
         Some("Customer 2".into()),
         Some("Test order 2".into()),
+        None,
+        Version::default(),
     );
     let order1_result = stores.orders_store.create(order1.clone()).await.ok();
     let order2_result = stores.orders_store.create(order2.clone()).await.ok();
@@ -37,25 +46,31 @@ pub async fn populate_test_data(
         "Invoice 1".into(),
         "draft".into(),
         "INV-001".into(),
-        999.99,
+        eur("999.99"),
         Some("2025-12-31".into()),
         None,
+        None,
+        Version::default(),
     );
     let invoice2 = Invoice::new(
         "Invoice 2".into(),
         "paid".into(),
         "INV-002".into(),
-        999.99,
+        eur("999.99"),
         Some("2025-12-31".into()),
         Some("2025-01-15".into()),
+        None,
+        Version::default(),
     );
     let invoice3 = Invoice::new(
         "Invoice 3".into(),
         "sent".into(),
         "INV-003".into(),
-        4999.99,
+        eur("4999.99"),
         Some("2025-12-31".into()),
         None,
+        None,
+        Version::default(),
     );
     let invoice1_result = stores.invoices_store.create(invoice1.clone()).await.ok();
     let invoice2_result = stores.invoices_store.create(invoice2.clone()).await.ok();
@@ -66,25 +81,31 @@ pub async fn populate_test_data(
         "Payment 1".into(),
         "completed".into(),
         "PAY-001".into(),
-        999.99,
+        eur("999.99"),
         "credit_card".into(),
         Some("txn_001".into()),
+        None,
+        Version::default(),
     );
     let payment2 = Payment::new(
         "Payment 2".into(),
         "completed".into(),
         "PAY-002".into(),
-        999.99,
+        eur("999.99"),
         "bank_transfer".into(),
         Some("txn_002".into()),
+        None,
+        Version::default(),
     );
     let payment3 = Payment::new(
         "Payment 3".into(),
         "pending".into(),
         "PAY-003".into(),
-        4999.99,
+        eur("4999.99"),
         "credit_card".into(),
         Some("txn_003".into()),
+        None,
+        Version::default(),
     );
     let payment1_result = stores.payments_store.create(payment1.clone()).await.ok();
     let payment2_result = stores.payments_store.create(payment2.clone()).await.ok();
@@ -181,7 +202,7 @@ pub async fn populate_test_data(
 /// Populate test data in the catalog stores and create links between entities
 pub async fn populate_catalog_data(
     stores: &catalog::CatalogStores,
-    link_service: Arc<InMemoryLinkService>,
+    link_service: Arc<dyn LinkService>,
 ) -> Result<()> {
     use catalog::entities::category::Category;
     use catalog::entities::product::Product;
@@ -193,18 +214,24 @@ pub async fn populate_catalog_data(
         "active".into(),
         "electronics".into(),
         Some("Electronic products".into()),
+        None,
+        Version::default(),
     );
     let category2 = Category::new(
         "Clothing".into(),
         "active".into(),
         "clothing".into(),
         Some("Clothing items".into()),
+        None,
+        Version::default(),
     );
     let category3 = Category::new(
         "Laptops".into(),
         "active".into(),
         "laptops".into(),
         Some("Laptop computers".into()),
+        None,
+        Version::default(),
     );
     let category1_result = stores.categories_store.create(category1.clone()).await.ok();
     let category2_result = stores.categories_store.create(category2.clone()).await.ok();
@@ -216,18 +243,24 @@ pub async fn populate_catalog_data(
         "active".into(),
         Some("#FF5733".into()),
         Some("Featured products".into()),
+        None,
+        Version::default(),
     );
     let tag2 = Tag::new(
         "new".into(),
         "active".into(),
         Some("#33FF57".into()),
         Some("New arrivals".into()),
+        None,
+        Version::default(),
     );
     let tag3 = Tag::new(
         "sale".into(),
         "active".into(),
         Some("#3357FF".into()),
         Some("On sale".into()),
+        None,
+        Version::default(),
     );
     let tag1_result = stores.tags_store.create(tag1.clone()).await.ok();
     let tag2_result = stores.tags_store.create(tag2.clone()).await.ok();
@@ -238,25 +271,31 @@ pub async fn populate_catalog_data(
         "Laptop Pro".into(),
         "active".into(),
         "LAP-001".into(),
-        1299.99,
+        eur("1299.99"),
         10,
         Some("High-performance laptop".into()),
+        None,
+        Version::default(),
     );
     let product2 = Product::new(
         "T-Shirt Basic".into(),
         "active".into(),
         "TSH-001".into(),
-        19.99,
+        eur("19.99"),
         50,
         Some("Basic cotton t-shirt".into()),
+        None,
+        Version::default(),
     );
     let product3 = Product::new(
         "Smartphone X".into(),
         "active".into(),
         "PHN-001".into(),
-        899.99,
+        eur("899.99"),
         25,
         Some("Latest smartphone model".into()),
+        None,
+        Version::default(),
     );
     let product1_result = stores.products_store.create(product1.clone()).await.ok();
     let product2_result = stores.products_store.create(product2.clone()).await.ok();
@@ -386,7 +425,7 @@ pub async fn populate_catalog_data(
 /// Scenario: Bar with co-working activity - demonstrates multi-activity store and refacturation
 pub async fn populate_inventory_data(
     stores: &inventory::InventoryStores,
-    link_service: Arc<InMemoryLinkService>,
+    link_service: Arc<dyn LinkService>,
 ) -> Result<()> {
     use inventory::entities::activity::Activity;
     use inventory::entities::stock_item::StockItem;
@@ -400,6 +439,8 @@ pub async fn populate_inventory_data(
         "Le Café Central".into(),
         "active".into(),
         Some("123 Main Street, Paris".into()),
+        None,
+        Version::default(),
     );
     let store1_result = stores.stores_store.create(store1.clone()).await.ok();
 
@@ -409,12 +450,16 @@ pub async fn populate_inventory_data(
         "active".into(),
         Some("bar".into()),
         Some("Bar service".into()),
+        None,
+        Version::default(),
     );
     let activity_coworking = Activity::new(
         "Co-working".into(),
         "active".into(),
         Some("coworking".into()),
         Some("Co-working space".into()),
+        None,
+        Version::default(),
     );
     let activity_bar_result = stores
         .activities_store
@@ -461,6 +506,8 @@ pub async fn populate_inventory_data(
             "active".into(),
             Some("Storage room".into()),
             s1.id,
+            None,
+            Version::default(),
         );
         let warehouse1_result = stores
             .warehouses_store
@@ -488,6 +535,9 @@ pub async fn populate_inventory_data(
                 100,
                 w1.id,
                 Some(10),
+                Some(20),
+                None,
+                Version::default(),
             );
             let stock_item1_result = stores
                 .stock_items_store
@@ -517,6 +567,10 @@ pub async fn populate_inventory_data(
                         15,
                         Some("Service to co-working customers".into()),
                         Some(ab.id),
+                        None,
+                        None,
+                        None,
+                        Version::default(),
                     );
                     let movement1_result = stores
                         .stock_movements_store
@@ -568,6 +622,8 @@ pub async fn populate_inventory_data(
             Some("hours".into()),
             Some(ab.id),
             Some("2025-01-15".into()),
+            None,
+            Version::default(),
         );
         let usage1_result = stores.usages_store.create(usage1.clone()).await.ok();
 
@@ -606,6 +662,8 @@ pub async fn populate_inventory_data(
             Some("items".into()),
             Some(ab.id),
             Some("2025-01-15".into()),
+            None,
+            Version::default(),
         );
         let usage2_result = stores.usages_store.create(usage2.clone()).await.ok();
 
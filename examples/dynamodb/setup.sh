@@ -73,8 +73,11 @@ create_table() {
 # Check if AWS CLI is available
 if command -v aws > /dev/null; then
     create_table ${ORDERS_TABLE_NAME:-orders}
+    create_table ${ORDER_LINES_TABLE_NAME:-order_lines}
     create_table ${INVOICES_TABLE_NAME:-invoices}
+    create_table ${INVOICE_LINES_TABLE_NAME:-invoice_lines}
     create_table ${PAYMENTS_TABLE_NAME:-payments}
+    create_table ${SEQUENCES_TABLE_NAME:-sequences}
     create_table ${LINKS_TABLE_NAME:-links}
 else
     echo "⚠️ AWS CLI not found. Tables will be created automatically when the app starts."
@@ -33,21 +33,23 @@ async fn main() -> Result<()> {
     let client = Client::new(&config);
 
     // Create DynamoDB link service
-    let link_service = DynamoDBLinkService::new(
-        client.clone(),
-        env::var("LINKS_TABLE_NAME").unwrap_or_else(|_| "links".to_string()),
-    );
+    let links_table = env::var("LINKS_TABLE_NAME").unwrap_or_else(|_| "links".to_string());
+    let link_service = DynamoDBLinkService::new(client.clone(), links_table.clone());
 
     // Create billing stores with DynamoDB - each entity has its own table
     let stores = BillingStores::new_dynamodb(
-        client,
+        client.clone(),
         env::var("ORDERS_TABLE_NAME").unwrap_or_else(|_| "orders".to_string()),
+        env::var("ORDER_LINES_TABLE_NAME").unwrap_or_else(|_| "order_lines".to_string()),
         env::var("INVOICES_TABLE_NAME").unwrap_or_else(|_| "invoices".to_string()),
+        env::var("INVOICE_LINES_TABLE_NAME").unwrap_or_else(|_| "invoice_lines".to_string()),
         env::var("PAYMENTS_TABLE_NAME").unwrap_or_else(|_| "payments".to_string()),
+        env::var("SEQUENCES_TABLE_NAME").unwrap_or_else(|_| "sequences".to_string()),
     );
 
-    // Create the billing module
-    let billing_module = BillingModule::new(stores);
+    // Create the billing module, settling order amounts from their lines
+    let billing_module = BillingModule::new(stores)
+        .with_order_totals(Arc::new(DynamoDBLinkService::new(client, links_table)));
 
     // Build the server host
     let host = Arc::new(
@@ -77,6 +79,8 @@ async fn main() -> Result<()> {
     println!("    POST   /orders");
     println!("    GET    /orders/{{id}}");
     println!("    DELETE /orders/{{id}}");
+    println!("    GET    /orders/{{id}}/lines");
+    println!("    POST   /orders/{{id}}/lines");
     println!("    GET    /invoices");
     println!("    POST   /invoices");
     println!("    GET    /invoices/{{id}}");
@@ -20,8 +20,8 @@ async fn main() -> Result<()> {
         use this::server::{GraphQLExposure, RestExposure};
         use this::storage::InMemoryLinkService;
 
-        let billing_module = BillingModule::new(stores);
         let link_service_arc = Arc::new(InMemoryLinkService::new());
+        let billing_module = BillingModule::new(stores).with_order_totals(link_service_arc.clone());
 
         // Populate test data BEFORE building the host
         populate_test_data(&billing_module.stores, link_service_arc.clone()).await?;
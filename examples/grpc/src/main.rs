@@ -17,9 +17,8 @@ async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     let stores = BillingStores::new_in_memory();
-    let billing_module = BillingModule::new(stores);
-
     let link_service_arc = Arc::new(InMemoryLinkService::new());
+    let billing_module = BillingModule::new(stores).with_order_totals(link_service_arc.clone());
 
     // Populate test data BEFORE building the host (builder consumes the link service)
     populate_test_data(&billing_module.stores, link_service_arc.clone()).await?;
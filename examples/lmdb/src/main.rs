@@ -36,8 +36,9 @@ async fn main() -> Result<()> {
     std::fs::create_dir_all(&stores_path)?;
     let stores = BillingStores::new_lmdb(&stores_path)?;
 
-    // Create the billing module
-    let billing_module = BillingModule::new(stores);
+    // Create the billing module, settling order amounts from their lines
+    let billing_module =
+        BillingModule::new(stores).with_order_totals(Arc::new(link_service.clone()));
 
     // Build the server host
     let host = Arc::new(
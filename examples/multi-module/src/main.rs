@@ -2,58 +2,211 @@
 // for gRPC support. Install via: brew install protobuf (macOS) or apt install protobuf-compiler (Linux)
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use axum::Router;
 use tower_http::services::ServeDir;
 
+use billing::services::{InvoiceGenerator, InvoicingConfig, OrderTotals, PaymentReconciler};
 use billing::{BillingModule, BillingStores};
 use catalog::{CatalogModule, CatalogStores};
+use common::auth::{AnyOf, Authorization, Claims, JwtAuth, ServiceTokenAuth, ADMIN_ROLE};
+use common::journal::JournaledLinkService;
+use common::links::LinkIntegrity;
+use common::Money;
+use inventory::services::{RateTable, RefacturationConfig, ReservationSweeper};
 use inventory::{InventoryModule, InventoryStores};
 use test_data::{populate_catalog_data, populate_inventory_data, populate_test_data};
 
+use this::core::events::EventBus;
+use this::prelude::EntityFetcher;
 use this::server::builder::ServerBuilder;
 use this::server::{GraphQLExposure, GrpcExposure, RestExposure, WebSocketExposure};
-use this::storage::InMemoryLinkService;
+use uuid::Uuid;
+
+/// Key signing the user tokens, overridden by `AUTH_SECRET`
+const DEV_AUTH_SECRET: &str = "multi-module-dev-secret";
+/// Token of the services, overridden by `SERVICE_TOKEN`
+const DEV_SERVICE_TOKEN: &str = "multi-module-dev-service-token";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let link_service = Arc::new(InMemoryLinkService::new());
-
-    // Billing module
-    let billing_stores = BillingStores::new_in_memory();
-    populate_test_data(&billing_stores, link_service.clone()).await?;
-    let billing_module = BillingModule::new(billing_stores);
+    // With `JOURNAL_DIR`, entities and links are journaled there and survive
+    // restarts (see `common::journal`); the test data is then only loaded
+    // into an empty journal
+    let journal_dir = std::env::var("JOURNAL_DIR").ok();
+    let link_service = Arc::new(match &journal_dir {
+        Some(dir) => JournaledLinkService::open(dir)?,
+        None => JournaledLinkService::new(),
+    });
+    // Shared by the host, the billing REST routes and the inventory low-stock
+    // alerts so that every event reaches WebSocket subscribers and the
+    // invoicing service
+    let event_bus = EventBus::new(1024);
 
     // Catalog module
-    let catalog_stores = CatalogStores::new_in_memory();
-    populate_catalog_data(&catalog_stores, link_service.clone()).await?;
-    let catalog_module = CatalogModule::new(catalog_stores);
+    let catalog_stores = match &journal_dir {
+        Some(dir) => CatalogStores::new_journal(dir)?,
+        None => CatalogStores::new_in_memory(),
+    };
+    if is_empty(&*catalog_stores.products_entity).await? {
+        populate_catalog_data(&catalog_stores, link_service.clone()).await?;
+    }
+
+    // Billing module, pricing order lines from the catalog products
+    let billing_stores = match &journal_dir {
+        Some(dir) => BillingStores::new_journal(dir)?,
+        None => BillingStores::new_in_memory(),
+    };
+    if is_empty(&*billing_stores.orders_entity).await? {
+        populate_test_data(&billing_stores, link_service.clone()).await?;
+    }
+    let billing_module = BillingModule::new(billing_stores.clone())
+        .with_event_bus(event_bus.clone())
+        .with_product_catalog(catalog_stores.products_entity.clone(), link_service.clone())
+        .with_order_totals(link_service.clone());
+    let catalog_module = CatalogModule::new(catalog_stores).with_hierarchy(link_service.clone());
+    let category_hierarchy = catalog_module.hierarchy.clone();
 
     // Inventory module
-    let inventory_stores = InventoryStores::new_in_memory();
-    populate_inventory_data(&inventory_stores, link_service.clone()).await?;
-    let inventory_module = InventoryModule::new(inventory_stores);
+    let inventory_stores = match &journal_dir {
+        Some(dir) => InventoryStores::new_journal(dir)?,
+        None => InventoryStores::new_in_memory(),
+    };
+    if is_empty(&*inventory_stores.stores_entity).await? {
+        populate_inventory_data(&inventory_stores, link_service.clone()).await?;
+    }
+    // Give the stock of expired reservations back every few seconds
+    Arc::new(ReservationSweeper::new(&inventory_stores)).spawn(Duration::from_secs(5));
+    // Rates at which the co-working activity bills the bar for its usages
+    let rates = RateTable::new()
+        .with_rate(
+            "espace_utilise",
+            Some("hours"),
+            "12.50 EUR".parse::<Money>()?,
+        )
+        .with_rate("consommation", None, "2.20 EUR".parse::<Money>()?)
+        .with_rate("service", None, "25.00 EUR".parse::<Money>()?);
+    let inventory_module = InventoryModule::new(inventory_stores)
+        .with_foreign_keys(link_service.clone())
+        .with_stock_transfers(link_service.clone())
+        .with_refacturation(
+            &billing_stores,
+            link_service.clone(),
+            RefacturationConfig::new(rates),
+        )
+        .with_event_bus(event_bus.clone());
+
+    // `check-links [--repair]`: report the inventory entities whose links
+    // disagree with their foreign keys, repair them if asked, and exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("check-links") {
+        let repair = args.iter().any(|arg| arg == "--repair");
+        let report = inventory_module.check_foreign_keys(repair).await?;
+        for mismatch in &report.mismatches {
+            println!("{}", mismatch);
+        }
+        println!(
+            "{} entities checked, {} mismatch(es)",
+            report.checked,
+            report.mismatches.len()
+        );
+        return Ok(());
+    }
+
+    // Cancel the stock transfers a previous run left half done
+    if let Some(transfers) = &inventory_module.transfers {
+        for transfer_id in transfers.recover().await? {
+            println!("↩️  Cancelled interrupted stock transfer {}", transfer_id);
+        }
+    }
+
+    // How many links an entity can have and what deleting it does to them,
+    // as declared by each module
+    let link_rules = billing_module
+        .link_rules()?
+        .merge(catalog_module.link_rules()?)
+        .merge(inventory_module.link_rules()?);
 
     // Build the transport-agnostic host with all three modules
-    // CRITICAL: with_event_bus(1024) is required for WebSocket to broadcast events
-    let host = Arc::new(
-        ServerBuilder::new()
-            .with_link_service((*link_service).clone())
-            .with_event_bus(1024)
-            .register_module(billing_module)?
-            .register_module(catalog_module)?
-            .register_module(inventory_module)?
-            .build_host()?,
-    );
+    // CRITICAL: the event bus is required for WebSocket to broadcast events
+    let mut host = ServerBuilder::new()
+        .with_link_service((*link_service).clone())
+        .register_module(billing_module)?
+        .register_module(catalog_module)?
+        .register_module(inventory_module)?
+        .build_host()?
+        .with_event_bus(event_bus.clone());
+    // Enforce the cardinality of new links and keep the category hierarchy
+    // free of cycles, and cascade, restrict or unlink on every deletion,
+    // whatever the exposure
+    let mut integrity = LinkIntegrity::from_host(&host, link_rules);
+    if let Some(hierarchy) = category_hierarchy {
+        integrity = integrity.with_check(hierarchy);
+    }
+    host.link_service = integrity.checked_link_service();
+    host.entity_creators = integrity.guarded_creators();
+    let host = Arc::new(host);
+
+    // Keep pending order amounts equal to their lines, invoice orders as soon
+    // as they are confirmed, and settle invoices and orders as completed
+    // payments come in
+    Arc::new(OrderTotals::new(&billing_stores, host.link_service.clone())).spawn(event_bus.clone());
+    Arc::new(InvoiceGenerator::new(
+        &billing_stores,
+        host.link_service.clone(),
+        InvoicingConfig::default(),
+    ))
+    .spawn(event_bus.clone());
+    Arc::new(PaymentReconciler::new(
+        &billing_stores,
+        host.link_service.clone(),
+    ))
+    .spawn(event_bus);
+
+    // Users authenticate with a bearer JWT, other services with a token;
+    // every exposure then enforces the `auth:` policies of each module
+    let secret = std::env::var("AUTH_SECRET").unwrap_or_else(|_| DEV_AUTH_SECRET.to_string());
+    let service_token =
+        std::env::var("SERVICE_TOKEN").unwrap_or_else(|_| DEV_SERVICE_TOKEN.to_string());
+    let jwt = JwtAuth::hs256(secret.as_bytes());
+    // Tokens for trying the API out, valid for a day
+    let day = Duration::from_secs(24 * 60 * 60);
+    let user_token = jwt.sign(&Claims::new(Uuid::new_v4(), vec![], day))?;
+    let admin_token = jwt.sign(&Claims::new(
+        Uuid::new_v4(),
+        vec![ADMIN_ROLE.to_string()],
+        day,
+    ))?;
+    let authorization = Authorization::from_host(
+        &host,
+        AnyOf(
+            ServiceTokenAuth::new().with_token("multi-module", service_token.clone()),
+            jwt,
+        ),
+    )?;
 
     // Build all exposure routers
-    let rest_router = RestExposure::build_router(host.clone(), vec![])?;
-    let graphql_router = GraphQLExposure::build_router(host.clone())?;
-    let grpc_router = GrpcExposure::build_router(host.clone())?;
-    let ws_router = WebSocketExposure::build_router(host.clone())?;
+    let rest_router = authorization
+        .clone()
+        .layer(integrity.layer(RestExposure::build_router(host.clone(), vec![])?));
+    // GraphQL, gRPC and WebSocket call the host directly: they get it with
+    // its entities and links checked against the same policies, for the
+    // caller their requests authenticate as
+    let mut host = Arc::try_unwrap(host)
+        .map_err(|_| anyhow::anyhow!("the host is still shared after building the REST routes"))?;
+    authorization.guard(&mut host);
+    let host = Arc::new(host);
+    let graphql_router = authorization
+        .clone()
+        .authenticate(GraphQLExposure::build_router(host.clone())?);
+    let grpc_router = authorization
+        .clone()
+        .authenticate(GrpcExposure::build_router(host.clone())?);
+    let ws_router = authorization.authenticate(WebSocketExposure::build_router(host.clone())?);
 
     // Serve static files (for the HTML WebSocket client)
     let static_files = ServeDir::new("examples/websocket/static");
@@ -75,8 +228,13 @@ async fn main() -> Result<()> {
     println!("    GET    /orders");
     println!("    GET    /invoices");
     println!("    GET    /payments");
+    println!("    POST   /orders/{{id}}/confirm");
+    println!("    POST   /orders/{{id}}/cancel");
     println!("    GET    /orders/{{id}}/invoices");
+    println!("    POST   /orders/{{id}}/lines");
+    println!("    GET    /order_lines/{{id}}/product");
     println!("    GET    /invoices/{{id}}/payments");
+    println!("    GET    /invoices/{{id}}/lines");
     println!("\n  REST API - Catalog:");
     println!("    GET    /products");
     println!("    GET    /categories");
@@ -87,12 +245,23 @@ async fn main() -> Result<()> {
     println!("    GET    /tags/{{id}}/products");
     println!("    GET    /categories/{{id}}/children");
     println!("    GET    /categories/{{id}}/parent");
+    println!("    GET    /categories/{{id}}/ancestors");
+    println!("    GET    /categories/{{id}}/descendants");
+    println!("    GET    /categories/{{id}}/all_products");
     println!("\n  REST API - Inventory:");
     println!("    GET    /stores");
     println!("    GET    /activities");
+    println!("    POST   /activities/{{id}}/refacturation");
     println!("    GET    /warehouses");
+    println!("    GET    /warehouses/{{id}}/low_stock");
     println!("    GET    /stock_items");
     println!("    GET    /stock_movements");
+    println!("    POST   /stock_movements/{{id}}/complete");
+    println!("    POST   /stock_movements/{{id}}/cancel");
+    println!("    POST   /stock_items/{{id}}/transfer");
+    println!("    POST   /stock_items/{{id}}/reservations");
+    println!("    POST   /reservations/{{id}}/commit");
+    println!("    POST   /reservations/{{id}}/release");
     println!("    GET    /usages");
     println!("    GET    /stores/{{id}}/activities");
     println!("    GET    /activities/{{id}}/stores");
@@ -114,10 +283,17 @@ async fn main() -> Result<()> {
     println!("    WS     /ws");
     println!("\n  Client de test WebSocket:");
     println!("    GET    /static/ws-client.html");
+    println!("\n🔑 Authentification (REST, GraphQL, gRPC, WebSocket):");
+    println!("    Authorization: Bearer <jwt>    utilisateur, signé avec AUTH_SECRET");
+    println!("    X-Service-Token: <token>       service, SERVICE_TOKEN");
+    println!("\n  Jetons de développement:");
+    println!("    USER_TOKEN={user_token}");
+    println!("    ADMIN_TOKEN={admin_token}");
+    println!("    SERVICE_TOKEN={service_token}");
     println!("\n💡 Exemples:");
-    println!("   curl http://127.0.0.1:4242/orders");
-    println!("   curl -X POST http://127.0.0.1:4242/graphql -H 'Content-Type: application/json' -d '{{\"query\": \"{{ orders {{ id name }} }}\"}}' ");
-    println!("   curl -s http://127.0.0.1:4242/grpc/proto > /tmp/this.proto && grpcurl -plaintext -proto /tmp/this.proto 127.0.0.1:4242 this_grpc.EntityService/ListEntities");
+    println!("   curl http://127.0.0.1:4242/orders -H \"Authorization: Bearer $USER_TOKEN\"");
+    println!("   curl -X POST http://127.0.0.1:4242/graphql -H \"Authorization: Bearer $USER_TOKEN\" -H 'Content-Type: application/json' -d '{{\"query\": \"{{ orders {{ id name }} }}\"}}' ");
+    println!("   curl -s http://127.0.0.1:4242/grpc/proto -H \"Authorization: Bearer $USER_TOKEN\" > /tmp/this.proto && grpcurl -plaintext -H \"Authorization: Bearer $USER_TOKEN\" -proto /tmp/this.proto 127.0.0.1:4242 this_grpc.EntityService/ListEntities");
     println!();
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:4242").await?;
@@ -125,3 +301,8 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Whether a store holds no entity yet
+async fn is_empty(store: &dyn EntityFetcher) -> Result<bool> {
+    Ok(store.list_as_json(Some(1), None).await?.is_empty())
+}
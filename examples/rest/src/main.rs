@@ -1,18 +1,33 @@
 use billing::{BillingModule, BillingStores};
+use common::journal::JournaledLinkService;
 use std::sync::Arc;
 use test_data::populate_test_data;
 use this::server::builder::ServerBuilder;
-use this::storage::InMemoryLinkService;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let link_service = Arc::new(InMemoryLinkService::new());
+    // With `JOURNAL_DIR`, entities and links are journaled there and survive
+    // restarts (see `common::journal`)
+    let (stores, link_service) = match std::env::var("JOURNAL_DIR") {
+        Ok(dir) => (
+            BillingStores::new_journal(&dir)?,
+            JournaledLinkService::open(&dir)?,
+        ),
+        Err(_) => (BillingStores::new_in_memory(), JournaledLinkService::new()),
+    };
+    let link_service = Arc::new(link_service);
 
-    // Create stores and populate with test data
-    let stores = BillingStores::new_in_memory();
-    populate_test_data(&stores, link_service.clone()).await?;
+    // Populate with test data, unless the journal already has some
+    if stores
+        .orders_entity
+        .list_as_json(Some(1), None)
+        .await?
+        .is_empty()
+    {
+        populate_test_data(&stores, link_service.clone()).await?;
+    }
 
-    let billing_module = BillingModule::new(stores);
+    let billing_module = BillingModule::new(stores).with_order_totals(link_service.clone());
 
     let app = ServerBuilder::new()
         .with_link_service((*link_service).clone())
@@ -47,8 +47,9 @@ async fn main() -> Result<()> {
     // Create billing stores with ScyllaDB
     let stores = BillingStores::new_scylladb(session, keyspace);
 
-    // Create the billing module
-    let billing_module = BillingModule::new(stores);
+    // Create the billing module, settling order amounts from their lines
+    let billing_module =
+        BillingModule::new(stores).with_order_totals(Arc::new(link_service.clone()));
 
     // Build the server host
     let host = Arc::new(
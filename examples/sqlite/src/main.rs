@@ -0,0 +1,102 @@
+//! Billing, Catalog and Inventory APIs - SQLite Example with This-RS
+//!
+//! SQLite is an embedded database — no external service needed. Every
+//! entity and link is stored in a single local file, whose tables are
+//! created on first use.
+
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::Router;
+
+use billing::{BillingModule, BillingStores};
+use catalog::{CatalogModule, CatalogStores};
+use common::links::LinkIntegrity;
+use common::sqlite::SqliteLinkService;
+use inventory::{InventoryModule, InventoryStores};
+use this::core::LinkService;
+use this::server::builder::ServerBuilder;
+use this::server::RestExposure;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize tracing
+    tracing_subscriber::fmt::init();
+
+    // Configure the SQLite database file
+    let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "./data/app.db".to_string());
+
+    // Create its directory if it doesn't exist
+    if let Some(dir) = Path::new(&path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    println!("Using SQLite database: {}", path);
+
+    // Links live in the same file as the entities
+    let pool = common::sqlite::connect(&path).await?;
+    let link_service: Arc<dyn LinkService> = Arc::new(SqliteLinkService::new(pool.clone()));
+
+    // Create the stores of the three modules with SQLite
+    let catalog_module = CatalogModule::new(CatalogStores::new_sqlite(&path).await?)
+        .with_hierarchy(link_service.clone());
+    let category_hierarchy = catalog_module.hierarchy.clone();
+    let billing_module = BillingModule::new(BillingStores::new_sqlite(&path).await?)
+        .with_order_totals(link_service.clone());
+    let inventory_module = InventoryModule::new(InventoryStores::new_sqlite(&path).await?)
+        .with_foreign_keys(link_service.clone())
+        .with_stock_transfers(link_service);
+    // Cancel the stock transfers a previous run left half done
+    if let Some(transfers) = &inventory_module.transfers {
+        for transfer_id in transfers.recover().await? {
+            println!("↩️  Cancelled interrupted stock transfer {}", transfer_id);
+        }
+    }
+
+    let link_rules = billing_module
+        .link_rules()?
+        .merge(catalog_module.link_rules()?)
+        .merge(inventory_module.link_rules()?);
+
+    // Build the server host
+    let mut host = ServerBuilder::new()
+        .with_link_service(SqliteLinkService::new(pool))
+        .register_module(billing_module)?
+        .register_module(catalog_module)?
+        .register_module(inventory_module)?
+        .build_host()?;
+    // Enforce link cardinality and what deleting an entity does to its links
+    let mut integrity = LinkIntegrity::from_host(&host, link_rules);
+    if let Some(hierarchy) = category_hierarchy {
+        integrity = integrity.with_check(hierarchy);
+    }
+    host.link_service = integrity.checked_link_service();
+    host.entity_creators = integrity.guarded_creators();
+    let host = Arc::new(host);
+
+    // Build router
+    let app = Router::new().merge(integrity.layer(RestExposure::build_router(host, vec![])?));
+
+    println!("\n🌐 Server running on http://0.0.0.0:4242");
+    println!("\n📚 Available endpoints:");
+    println!("    GET    /orders");
+    println!("    POST   /orders");
+    println!("    GET    /invoices");
+    println!("    GET    /payments");
+    println!("    GET    /products");
+    println!("    POST   /products");
+    println!("    GET    /categories");
+    println!("    GET    /tags");
+    println!("    GET    /stores");
+    println!("    GET    /warehouses");
+    println!("    GET    /stock_items");
+    println!("    POST   /stock_movements");
+    println!("    GET    /reservations");
+    println!("    GET    /{{entity}}/{{id}}/{{route}}  (links)");
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:4242").await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
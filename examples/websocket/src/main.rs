@@ -4,9 +4,11 @@ use anyhow::Result;
 use axum::Router;
 use tower_http::services::ServeDir;
 
+use billing::services::{InvoiceGenerator, InvoicingConfig, OrderTotals, PaymentReconciler};
 use billing::{BillingModule, BillingStores};
 use test_data::populate_test_data;
 
+use this::core::events::EventBus;
 use this::server::builder::ServerBuilder;
 use this::server::{RestExposure, WebSocketExposure};
 use this::storage::InMemoryLinkService;
@@ -15,24 +17,41 @@ use this::storage::InMemoryLinkService;
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let stores = BillingStores::new_in_memory();
-    let billing_module = BillingModule::new(stores);
+    // Shared by the host and the billing REST routes so that REST mutations
+    // are broadcast as well
+    let event_bus = EventBus::new(1024);
 
+    let stores = BillingStores::new_in_memory();
     let link_service_arc = Arc::new(InMemoryLinkService::new());
+    let billing_module = BillingModule::new(stores.clone())
+        .with_event_bus(event_bus.clone())
+        .with_order_totals(link_service_arc.clone());
 
     // Populate test data BEFORE building the host (builder consumes the link service)
     populate_test_data(&billing_module.stores, link_service_arc.clone()).await?;
 
     // Build the transport-agnostic host
-    // CRITICAL: with_event_bus(1024) is required for WebSocket to broadcast events
+    // CRITICAL: the event bus is required for WebSocket to broadcast events
     let host = Arc::new(
         ServerBuilder::new()
             .with_link_service((*link_service_arc).clone())
-            .with_event_bus(1024)
             .register_module(billing_module)?
-            .build_host()?,
+            .build_host()?
+            .with_event_bus(event_bus.clone()),
     );
 
+    // Keep pending order amounts equal to their lines, invoice orders as soon
+    // as they are confirmed, and settle invoices and orders as completed
+    // payments come in
+    Arc::new(OrderTotals::new(&stores, host.link_service.clone())).spawn(event_bus.clone());
+    Arc::new(InvoiceGenerator::new(
+        &stores,
+        host.link_service.clone(),
+        InvoicingConfig::default(),
+    ))
+    .spawn(event_bus.clone());
+    Arc::new(PaymentReconciler::new(&stores, host.link_service.clone())).spawn(event_bus);
+
     // Build REST + WebSocket routers
     let rest_router = RestExposure::build_router(host.clone(), vec![])?;
     let ws_router = WebSocketExposure::build_router(host.clone())?;